@@ -22,7 +22,12 @@ const decoderRoutes = require('./routes/decoder');
 const app = express();
 
 // Middleware
-app.use(cors());
+// LNURL endpoints must answer any origin (LUD-01) for web wallets, but only
+// with the methods and header the routes take, and never with credentials
+app.use(cors({
+  methods: ['GET', 'HEAD', 'POST'],
+  allowedHeaders: ['Content-Type']
+}));
 app.use(express.json());
 app.use(httpLogger); // Request logging middleware (must stay before routes)
 
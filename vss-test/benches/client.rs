@@ -0,0 +1,109 @@
+//! Client-side micro-benchmarks
+//!
+//! What a client does before a request leaves it, measured without the
+//! compose stack: encoding VSS requests and decoding listings with prost,
+//! signing JWTs the way `vss_test::vss` does, and signing an LNURL-auth k1
+//! with a linking key the way the wallet does before it has a JWT at all.
+//! A slower release of prost, jsonwebtoken or secp256k1, or a change to the
+//! helpers here, shows as a regression against criterion's saved baseline:
+//!
+//!   cargo bench --bench client
+//!
+//! The JWT benchmarks read the lnurl-server key, so they run from `vss-test`
+//! like the binaries.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use prost::Message;
+use secp256k1::{Secp256k1, SecretKey};
+use vss_client::types::{KeyValue, ListKeyVersionsResponse, PutObjectRequest};
+use vss_test::vss::{sign_token, sign_tokens, VSS_SIGNING_KEY_PATH};
+
+const STORE_ID: &str = "bench-store";
+// Value sizes encoded, from a small record to a channel monitor
+const VALUE_SIZES: [usize; 3] = [64, 4 * 1024, 256 * 1024];
+// Keys in the listing decoded, a full page of listKeyVersions
+const LISTING_KEYS: usize = 100;
+// Tokens signed per iteration of the batch benchmark
+const BATCH_TOKENS: usize = 100;
+const BATCH_SAMPLES: usize = 10;
+// A fixed linking key and k1, so every run signs the same message
+const LINKING_KEY: [u8; 32] = [0x42; 32];
+const K1: [u8; 32] = [0x17; 32];
+
+fn put_request(value_size: usize) -> PutObjectRequest {
+    PutObjectRequest {
+        store_id: STORE_ID.to_string(),
+        global_version: None,
+        transaction_items: vec![KeyValue {
+            key: "bench-key".to_string(),
+            version: 1,
+            value: vec![0xab; value_size],
+        }],
+        delete_items: vec![],
+    }
+}
+
+fn encoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_put_object");
+    for size in VALUE_SIZES {
+        let request = put_request(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &request, |b, request| {
+            b.iter(|| black_box(request).encode_to_vec())
+        });
+    }
+    group.finish();
+
+    let listing = ListKeyVersionsResponse {
+        key_versions: (0..LISTING_KEYS)
+            .map(|i| KeyValue {
+                key: format!("bench-key-{:08}", i),
+                version: i as i64,
+                value: vec![],
+            })
+            .collect(),
+        next_page_token: Some("bench-key-00000099".to_string()),
+        global_version: None,
+    };
+    let body = listing.encode_to_vec();
+    c.bench_function("decode_list_key_versions", |b| {
+        b.iter(|| ListKeyVersionsResponse::decode(black_box(body.as_slice())).expect("decodes"))
+    });
+}
+
+fn jwt(c: &mut Criterion) {
+    c.bench_function("sign_token", |b| {
+        b.iter(|| sign_token(VSS_SIGNING_KEY_PATH, black_box("bench-subject")).expect("signs"))
+    });
+    let subjects: Vec<String> = (0..BATCH_TOKENS)
+        .map(|i| format!("bench-subject-{}", i))
+        .collect();
+    let mut group = c.benchmark_group("sign_tokens");
+    // A batch takes around 100 ms; the default 100 samples would take minutes
+    group.sample_size(BATCH_SAMPLES);
+    group.throughput(Throughput::Elements(BATCH_TOKENS as u64));
+    group.bench_function(BenchmarkId::from_parameter(BATCH_TOKENS), |b| {
+        b.iter(|| sign_tokens(VSS_SIGNING_KEY_PATH, black_box(&subjects)).expect("signs"))
+    });
+    group.finish();
+}
+
+fn linking_key(c: &mut Criterion) {
+    let secp = Secp256k1::new();
+    let key = SecretKey::from_slice(&LINKING_KEY).expect("valid linking key");
+    let k1 = secp256k1::Message::from_digest_slice(&K1).expect("32-byte k1");
+    // What the wallet sends: the DER signature of k1 and the compressed public
+    // key, both in hex
+    c.bench_function("sign_k1", |b| {
+        b.iter(|| {
+            let signature = secp.sign_ecdsa(black_box(&k1), &key);
+            (
+                hex::encode(signature.serialize_der()),
+                hex::encode(key.public_key(&secp).serialize()),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, encoding, jwt, linking_key);
+criterion_main!(benches);
@@ -0,0 +1,693 @@
+//! Fault injection against running compose services
+//!
+//! Network partitions, tc/netem traffic shaping, DNS outages, clock skew,
+//! full disks, memory limits and a monkey that kills random containers.
+//! Every fault is a guard: healing it is explicit so tests can assert on
+//! recovery, and a guard dropped unhealed (failed assertion, panic) still
+//! puts the stack back.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use bollard::system::EventsOptions;
+use futures_util::StreamExt;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::DockerEnv;
+
+/// Network only `postgres` and `vss-server` share, see docker-compose.yml.
+pub const VSS_DB_NETWORK: &str = "vss-db";
+
+// Image providing `tc`, `iptables` and `dig`, overridable via NETEM_IMAGE
+pub const DEFAULT_NETEM_IMAGE: &str = "nicolaka/netshoot:latest";
+// Burst the ingress policer lets through above its rate
+const POLICE_BURST_KB: u64 = 32;
+// Docker's DNS server inside every container on a user-defined network
+const EMBEDDED_DNS: &str = "127.0.0.11";
+
+// glibc image libfaketime is installed from, overridable via FAKETIME_IMAGE;
+// it must match the libc of the skewed service's image
+pub const DEFAULT_FAKETIME_IMAGE: &str = "debian:bookworm-slim";
+// Volume, prefixed with the project name, holding libfaketime for preloading
+const FAKETIME_VOLUME: &str = "faketime";
+const FAKETIME_LIB: &str = "/faketime/libfaketime.so.1";
+// How far the clock seen in the container may be off the requested skew
+const CLOCK_SKEW_SLACK_SECS: i64 = 30;
+
+// Postgres data directory and init script mount, as in docker-compose.yml
+const POSTGRES_DATA_DIR: &str = "/var/lib/postgresql/data";
+const POSTGRES_INIT_SCRIPT: &str =
+    "./sql/v0_create_vss_db.sql:/docker-entrypoint-initdb.d/init.sql";
+const FILLER_FILE: &str = "disk-full-filler";
+
+/// A service cut off from a compose network until `heal` is called.
+pub struct Partition {
+    env: DockerEnv,
+    service: String,
+    network: String,
+    healed: bool,
+}
+
+impl Partition {
+    /// Disconnect `service` from `network`.
+    pub async fn start(env: &DockerEnv, service: &str, network: &str) -> Result<Self, String> {
+        env.disconnect(service, network).await?;
+        Ok(Self {
+            env: env.clone(),
+            service: service.to_string(),
+            network: network.to_string(),
+            healed: false,
+        })
+    }
+
+    /// Cut `vss-server` off from Postgres while it stays reachable from the host.
+    pub async fn vss_from_db(env: &DockerEnv) -> Result<Self, String> {
+        Self::start(env, "vss-server", VSS_DB_NETWORK).await
+    }
+
+    /// Reconnect the service.
+    pub async fn heal(mut self) -> Result<(), String> {
+        self.healed = true;
+        self.env.connect(&self.service, &self.network).await
+    }
+}
+
+impl Drop for Partition {
+    fn drop(&mut self) {
+        if self.healed {
+            return;
+        }
+        let (service, network) = (self.service.clone(), self.network.clone());
+        crate::run_detached(self.env.project().to_string(), move |env| async move {
+            if let Err(e) = env.connect(&service, &network).await {
+                tracing::warn!(%service, %network, "Failed to heal partition: {}", e);
+            }
+        });
+    }
+}
+
+/// tc/netem settings for a service's traffic.
+#[derive(Debug, Clone, Default)]
+pub struct Netem {
+    pub delay: Duration,
+    /// Random variation around `delay`.
+    pub jitter: Duration,
+    /// Throughput cap in kbit/s, applied to traffic in both directions.
+    pub rate_kbit: Option<u64>,
+    /// Share of outgoing packets dropped at random, in percent.
+    pub loss_percent: Option<f64>,
+}
+
+impl Netem {
+    pub fn delay(delay: Duration) -> Self {
+        Self {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn rate_kbit(mut self, rate_kbit: u64) -> Self {
+        self.rate_kbit = Some(rate_kbit);
+        self
+    }
+
+    pub fn loss(mut self, percent: f64) -> Self {
+        self.loss_percent = Some(percent);
+        self
+    }
+
+    /// Arguments after `tc qdisc replace dev <dev> root netem`.
+    fn args(&self) -> String {
+        let mut args = format!(
+            "delay {}us {}us",
+            self.delay.as_micros(),
+            self.jitter.as_micros()
+        );
+        if let Some(rate) = self.rate_kbit {
+            args.push_str(&format!(" rate {}kbit", rate));
+        }
+        if let Some(loss) = self.loss_percent {
+            args.push_str(&format!(" loss {}%", loss));
+        }
+        args
+    }
+
+    /// Commands for `$dev` capping incoming traffic, which netem cannot
+    /// shape. Excess packets are dropped, so TCP settles near the rate.
+    fn ingress(&self) -> Option<String> {
+        let rate = self.rate_kbit?;
+        Some(format!(
+            "tc qdisc add dev \"$dev\" handle ffff: ingress && \
+             tc filter add dev \"$dev\" parent ffff: protocol all u32 match u32 0 0 \
+             police rate {}kbit burst {}k drop flowid :1",
+            rate, POLICE_BURST_KB
+        ))
+    }
+}
+
+/// Traffic shaping on every non-loopback interface of a service, in both the
+/// host and in-stack directions, until `heal` is called. Delay applies to
+/// what the service sends; a rate caps what it receives as well. The rules
+/// are set from a sidecar sharing the service's network namespace, so the
+/// service image needs no tooling.
+pub struct Shaping {
+    env: DockerEnv,
+    service: String,
+    healed: bool,
+}
+
+impl Shaping {
+    pub async fn start(env: &DockerEnv, service: &str, netem: &Netem) -> Result<Self, String> {
+        let mut shape = format!("tc qdisc replace dev \"$dev\" root netem {}", netem.args());
+        if let Some(ingress) = netem.ingress() {
+            shape = format!(
+                "{} && {{ tc qdisc del dev \"$dev\" ingress 2>/dev/null; {}; }}",
+                shape, ingress
+            );
+        }
+        let script = format!(
+            "for dev in $(ls /sys/class/net); do [ \"$dev\" = lo ] || {{ {}; }} || exit 1; done",
+            shape
+        );
+        env.run_sidecar(service, &netem_image(), &script, &["NET_ADMIN"])
+            .await?;
+        Ok(Self {
+            env: env.clone(),
+            service: service.to_string(),
+            healed: false,
+        })
+    }
+
+    /// Remove the shaping rules.
+    pub async fn heal(mut self) -> Result<(), String> {
+        self.healed = true;
+        clear_shaping(&self.env, &self.service).await
+    }
+}
+
+impl Drop for Shaping {
+    fn drop(&mut self) {
+        if self.healed {
+            return;
+        }
+        let service = self.service.clone();
+        crate::run_detached(self.env.project().to_string(), move |env| async move {
+            if let Err(e) = clear_shaping(&env, &service).await {
+                tracing::warn!(%service, "Failed to clear traffic shaping: {}", e);
+            }
+        });
+    }
+}
+
+fn netem_image() -> String {
+    std::env::var("NETEM_IMAGE").unwrap_or_else(|_| DEFAULT_NETEM_IMAGE.to_string())
+}
+
+async fn clear_shaping(env: &DockerEnv, service: &str) -> Result<(), String> {
+    // Deleting a missing qdisc fails, which is fine here
+    let script = "for dev in $(ls /sys/class/net); do tc qdisc del dev \"$dev\" root 2>/dev/null; tc qdisc del dev \"$dev\" ingress 2>/dev/null; done; true";
+    env.run_sidecar(service, &netem_image(), script, &["NET_ADMIN"])
+        .await
+        .map(|_| ())
+}
+
+/// A service unable to resolve names through Docker's embedded DNS server
+/// until `heal` is called. Queries are dropped rather than refused, so
+/// lookups time out like they do when compose DNS hiccups; connections that
+/// are already open keep working. Set up from a sidecar, like `Shaping`.
+pub struct DnsOutage {
+    env: DockerEnv,
+    service: String,
+    healed: bool,
+}
+
+impl DnsOutage {
+    pub async fn start(env: &DockerEnv, service: &str) -> Result<Self, String> {
+        // mangle, because the nat table rewrites the port before filter sees it
+        let script = format!(
+            "for proto in udp tcp; do iptables -t mangle -I OUTPUT -d {dns} -p $proto --dport 53 -j DROP || exit 1; done; \
+             if dig +time=1 +tries=1 @{dns} {service} >/dev/null; then echo 'DNS still answers'; exit 1; fi",
+            dns = EMBEDDED_DNS,
+            service = service
+        );
+        // Guard first, so rules from a failed check are removed too
+        let outage = Self {
+            env: env.clone(),
+            service: service.to_string(),
+            healed: false,
+        };
+        env.run_sidecar(service, &netem_image(), &script, &["NET_ADMIN"])
+            .await?;
+        Ok(outage)
+    }
+
+    /// Let DNS queries through again.
+    pub async fn heal(mut self) -> Result<(), String> {
+        self.healed = true;
+        clear_dns_outage(&self.env, &self.service).await
+    }
+}
+
+impl Drop for DnsOutage {
+    fn drop(&mut self) {
+        if self.healed {
+            return;
+        }
+        let service = self.service.clone();
+        crate::run_detached(self.env.project().to_string(), move |env| async move {
+            if let Err(e) = clear_dns_outage(&env, &service).await {
+                tracing::warn!(%service, "Failed to restore DNS: {}", e);
+            }
+        });
+    }
+}
+
+async fn clear_dns_outage(env: &DockerEnv, service: &str) -> Result<(), String> {
+    let script = format!(
+        "for proto in udp tcp; do \
+         while iptables -t mangle -D OUTPUT -d {} -p $proto --dport 53 -j DROP 2>/dev/null; do :; done; \
+         done; true",
+        EMBEDDED_DNS
+    );
+    env.run_sidecar(service, &netem_image(), &script, &["NET_ADMIN"])
+        .await
+        .map(|_| ())
+}
+
+/// A service recreated with an extra compose file layered over
+/// docker-compose.yml. Dropping it without `restore` recreates the service
+/// from docker-compose.yml alone.
+struct Overridden {
+    env: DockerEnv,
+    service: String,
+    file: PathBuf,
+    restored: bool,
+}
+
+impl Overridden {
+    /// `kind` names the override file, e.g. `faketime`.
+    async fn start(
+        env: &DockerEnv,
+        service: &str,
+        kind: &str,
+        compose_override: &str,
+    ) -> Result<Self, String> {
+        let file = std::env::temp_dir().join(format!("{}-{}-{}.yml", kind, env.project(), service));
+        std::fs::write(&file, compose_override)
+            .map_err(|e| format!("Failed to write {}: {:?}", file.display(), e))?;
+
+        // Guard first, so a failed recreate still puts the service back
+        let overridden = Self {
+            env: env.clone(),
+            service: service.to_string(),
+            file,
+            restored: false,
+        };
+        let file = overridden.file.display().to_string();
+        crate::compose(
+            env.project(),
+            &[
+                "--file",
+                "docker-compose.yml",
+                "--file",
+                &file,
+                "up",
+                "--detach",
+                "--no-deps",
+                "--force-recreate",
+                service,
+            ],
+        )
+        .await?;
+        Ok(overridden)
+    }
+
+    async fn restore(mut self) -> Result<(), String> {
+        self.restored = true;
+        restore_service(&self.env, &self.service, &self.file).await
+    }
+}
+
+impl Drop for Overridden {
+    fn drop(&mut self) {
+        if self.restored {
+            return;
+        }
+        let (service, file) = (self.service.clone(), self.file.clone());
+        crate::run_detached(self.env.project().to_string(), move |env| async move {
+            if let Err(e) = restore_service(&env, &service, &file).await {
+                tracing::warn!(%service, "Failed to restore service: {}", e);
+            }
+        });
+    }
+}
+
+async fn restore_service(env: &DockerEnv, service: &str, file: &Path) -> Result<(), String> {
+    let _ = std::fs::remove_file(file);
+    crate::compose(
+        env.project(),
+        &["up", "--detach", "--no-deps", "--force-recreate", service],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// A service recreated with libfaketime preloaded so its wall clock runs
+/// `offset_secs` ahead (or behind, if negative) until `heal` is called.
+/// Monotonic clocks are left alone so timers keep working.
+pub struct ClockSkew {
+    overridden: Overridden,
+}
+
+impl ClockSkew {
+    pub async fn start(env: &DockerEnv, service: &str, offset_secs: i64) -> Result<Self, String> {
+        let volume = env.volume_name(FAKETIME_VOLUME);
+        let install = format!(
+            "test -f {lib} || (apt-get update -qq && apt-get install -y -qq libfaketime >/dev/null \
+             && cp \"$(dpkg -L libfaketime | grep 'libfaketime.so.1$' | head -n 1)\" {lib})",
+            lib = FAKETIME_LIB
+        );
+        env.run_helper(
+            &faketime_image(),
+            &install,
+            &[format!("{}:/faketime", volume)],
+        )
+        .await
+        .map_err(|e| format!("Failed to provide libfaketime: {}", e))?;
+
+        let compose_override = format!(
+            r#"services:
+  {service}:
+    environment:
+      LD_PRELOAD: {lib}
+      FAKETIME: "{offset:+}"
+      FAKETIME_DONT_FAKE_MONOTONIC: "1"
+    volumes:
+      - {FAKETIME_VOLUME}:/faketime:ro
+volumes:
+  {FAKETIME_VOLUME}:
+    external: true
+    name: {volume}
+"#,
+            service = service,
+            lib = FAKETIME_LIB,
+            offset = offset_secs,
+            volume = volume,
+        );
+        let overridden = Overridden::start(env, service, "faketime", &compose_override).await?;
+
+        let clock = env.exec(service, &["date", "+%s"]).await?;
+        let seen: i64 = clock.stdout.trim().parse().map_err(|_| {
+            format!(
+                "Cannot read the clock of {} to verify the skew: {}",
+                service,
+                clock.stderr.trim()
+            )
+        })?;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        if (seen - now - offset_secs).abs() > CLOCK_SKEW_SLACK_SECS {
+            return Err(format!(
+                "{} sees a clock {}s off instead of {}s; libfaketime did not load",
+                service,
+                seen - now,
+                offset_secs
+            ));
+        }
+        Ok(Self { overridden })
+    }
+
+    /// Recreate the service with its real clock.
+    pub async fn heal(self) -> Result<(), String> {
+        self.overridden.restore().await
+    }
+}
+
+fn faketime_image() -> String {
+    std::env::var("FAKETIME_IMAGE").unwrap_or_else(|_| DEFAULT_FAKETIME_IMAGE.to_string())
+}
+
+/// A service recreated with its data directory on a size-limited tmpfs, so
+/// the disk can be filled on purpose, until `heal` is called. The data starts
+/// out empty and is discarded on heal; the service's volume is not touched.
+pub struct DiskLimit {
+    overridden: Overridden,
+    path: String,
+}
+
+impl DiskLimit {
+    /// Limit `path` in `service` to `size_mb`. `keep_volumes` are the
+    /// service's other volume entries from docker-compose.yml, which the
+    /// override has to repeat.
+    pub async fn start(
+        env: &DockerEnv,
+        service: &str,
+        path: &str,
+        size_mb: u64,
+        keep_volumes: &[&str],
+    ) -> Result<Self, String> {
+        let mut compose_override = format!("services:\n  {}:\n    volumes: !override\n", service);
+        for volume in keep_volumes {
+            compose_override.push_str(&format!("      - {}\n", volume));
+        }
+        compose_override.push_str(&format!("    tmpfs:\n      - {}:size={}m\n", path, size_mb));
+        let overridden = Overridden::start(env, service, "disk-limit", &compose_override).await?;
+        Ok(Self {
+            overridden,
+            path: path.to_string(),
+        })
+    }
+
+    /// Postgres with `size_mb` for its data, initialised afresh on start.
+    pub async fn postgres(env: &DockerEnv, size_mb: u64) -> Result<Self, String> {
+        Self::start(
+            env,
+            "postgres",
+            POSTGRES_DATA_DIR,
+            size_mb,
+            &[POSTGRES_INIT_SCRIPT],
+        )
+        .await
+    }
+
+    /// Write a filler file until the filesystem is full.
+    pub async fn fill(&self) -> Result<(), String> {
+        let filler = format!("{}/{}", self.path, FILLER_FILE);
+        // dd stops with ENOSPC, which is the point
+        let output = self
+            .overridden
+            .env
+            .exec(
+                &self.overridden.service,
+                &[
+                    "sh",
+                    "-c",
+                    &format!(
+                        "dd if=/dev/zero of={} bs=1M 2>&1; df -k {}",
+                        filler, self.path
+                    ),
+                ],
+            )
+            .await?;
+        if !output.stdout.contains("No space left") {
+            return Err(format!(
+                "Filling {} did not run out of space: {}",
+                self.path,
+                output.stdout.trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Delete the filler file again.
+    pub async fn free(&self) -> Result<(), String> {
+        let filler = format!("{}/{}", self.path, FILLER_FILE);
+        let output = self
+            .overridden
+            .env
+            .exec(&self.overridden.service, &["rm", "-f", &filler])
+            .await?;
+        if !output.success() {
+            return Err(format!(
+                "Failed to delete {}: {}",
+                filler,
+                output.stderr.trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Recreate the service on its usual volume.
+    pub async fn heal(self) -> Result<(), String> {
+        self.overridden.restore().await
+    }
+}
+
+/// A service recreated under a hard memory limit, with swap disabled so the
+/// kernel OOM-kills it instead of paging, until `heal` is called. OOM kills
+/// of the new container are counted from the Docker event stream, since its
+/// `OOMKilled` flag is cleared as soon as the restart policy starts it again.
+pub struct MemoryLimit {
+    overridden: Overridden,
+    oom_kills: Arc<AtomicUsize>,
+    _watcher: AbortOnDrop,
+}
+
+impl MemoryLimit {
+    pub async fn start(env: &DockerEnv, service: &str, limit_mb: u64) -> Result<Self, String> {
+        let compose_override = format!(
+            "services:\n  {}:\n    mem_limit: {limit}m\n    memswap_limit: {limit}m\n",
+            service,
+            limit = limit_mb
+        );
+        let overridden = Overridden::start(env, service, "memory-limit", &compose_override).await?;
+
+        let id = env.container_id(service).await?;
+        let options = EventsOptions::<String> {
+            filters: HashMap::from([
+                ("container".to_string(), vec![id]),
+                ("event".to_string(), vec!["oom".to_string()]),
+            ]),
+            ..Default::default()
+        };
+        let mut events = env.docker().events(Some(options));
+        let oom_kills = Arc::new(AtomicUsize::new(0));
+        let counter = oom_kills.clone();
+        let watcher = tokio::spawn(async move {
+            while let Some(Ok(_)) = events.next().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        Ok(Self {
+            overridden,
+            oom_kills,
+            _watcher: AbortOnDrop(watcher),
+        })
+    }
+
+    /// How often the kernel has OOM-killed the service since the limit was applied.
+    pub fn oom_kills(&self) -> usize {
+        self.oom_kills.load(Ordering::SeqCst)
+    }
+
+    /// Recreate the service without the limit.
+    pub async fn heal(self) -> Result<(), String> {
+        self.overridden.restore().await
+    }
+}
+
+/// Background task that ends with its owner.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A container the monkey killed, and when relative to its start.
+#[derive(Debug, Clone)]
+pub struct Kill {
+    pub service: String,
+    pub at: Duration,
+}
+
+/// Background task that SIGKILLs a random service every `interval` and starts
+/// it again after `downtime`, until stopped.
+pub struct ChaosMonkey {
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<Result<Vec<Kill>, String>>>,
+    env: DockerEnv,
+    services: Vec<String>,
+}
+
+impl ChaosMonkey {
+    /// Start killing; the same `seed` replays the same choice of victims.
+    pub fn start(
+        env: &DockerEnv,
+        services: &[&str],
+        interval: Duration,
+        downtime: Duration,
+        seed: u64,
+    ) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let services: Vec<String> = services.iter().map(|s| s.to_string()).collect();
+        let task_env = env.clone();
+        let victims = services.clone();
+        let task = tokio::spawn(async move {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let start = Instant::now();
+            let mut kills = Vec::new();
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => return Ok(kills),
+                    _ = tokio::time::sleep(interval) => {}
+                }
+                let Some(service) = victims.choose(&mut rng) else {
+                    return Ok(kills);
+                };
+                task_env.kill(service, "SIGKILL").await?;
+                kills.push(Kill {
+                    service: service.clone(),
+                    at: start.elapsed(),
+                });
+                tokio::time::sleep(downtime).await;
+                task_env.start(service).await?;
+            }
+        });
+        Self {
+            stop: Some(stop),
+            task: Some(task),
+            env: env.clone(),
+            services,
+        }
+    }
+
+    /// Stop killing, make sure every victim is running again and return the kills.
+    pub async fn stop(mut self) -> Result<Vec<Kill>, String> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        let kills = match self.task.take() {
+            Some(task) => task
+                .await
+                .map_err(|e| format!("Chaos monkey task failed: {:?}", e))?,
+            None => Ok(Vec::new()),
+        };
+        for service in &self.services {
+            self.env.start(service).await?;
+        }
+        kills
+    }
+}
+
+impl Drop for ChaosMonkey {
+    fn drop(&mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+        task.abort();
+        let services = self.services.clone();
+        crate::run_detached(self.env.project().to_string(), move |env| async move {
+            for service in &services {
+                if let Err(e) = env.start(service).await {
+                    tracing::warn!(%service, "Failed to restart after chaos: {}", e);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,51 @@
+//! The compose `.env` file, shared with the harness
+//!
+//! docker compose reads `.env` next to docker-compose.yml (or the files listed
+//! in `COMPOSE_ENV_FILES`) for the variables it interpolates. `load` puts the
+//! same files into this process's environment, so the harness sees the project
+//! name, image tags and any of its own settings the containers were started
+//! with instead of repeating them. As with compose, variables already set in
+//! the environment win, and a missing default `.env` is not an error.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::COMPOSE_DIR;
+
+pub const ENV_FILE: &str = ".env";
+// Comma-separated, like docker compose's own variable
+pub const ENV_FILES_VAR: &str = "COMPOSE_ENV_FILES";
+
+static LOADED: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Load the env files once per process; later calls return the first outcome.
+pub fn load() -> Result<(), String> {
+    LOADED.get_or_init(load_files).clone()
+}
+
+fn load_files() -> Result<(), String> {
+    let compose_dir = Path::new(COMPOSE_DIR);
+    let listed = std::env::var(ENV_FILES_VAR).unwrap_or_default();
+    let listed: Vec<PathBuf> = listed
+        .split(',')
+        .map(str::trim)
+        .filter(|file| !file.is_empty())
+        .map(|file| compose_dir.join(file))
+        .collect();
+    if listed.is_empty() {
+        let default = compose_dir.join(ENV_FILE);
+        if !default.exists() {
+            return Ok(());
+        }
+        return load_file(&default);
+    }
+    for file in listed {
+        load_file(&file)?;
+    }
+    Ok(())
+}
+
+fn load_file(path: &Path) -> Result<(), String> {
+    dotenvy::from_path(path)
+        .map_err(|e| format!("Failed to load env file {}: {}", path.display(), e))
+}
@@ -0,0 +1,143 @@
+//! Private copies of the stack for parallel runs
+//!
+//! Every `IsolatedEnv` is a compose project of its own, with a unique name, so
+//! its containers, networks and named volumes are separate from the shared
+//! stack and from each other. docker-compose.isolated.yml drops the fixed
+//! container names and publishes ports on ephemeral host ports, which callers
+//! find through `DockerEnv::host_port`. Services keeping state in bind mounts
+//! (the LND nodes, lnurl-server) would still share it, so only profiles made
+//! of `ISOLATABLE_SERVICES` can be isolated.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_util::future::join_all;
+
+use crate::profile::Profile;
+use crate::teardown::keep_alive_requested;
+use crate::{default_project_name, DockerEnv, COMPOSE_DIR};
+
+const ISOLATED_ENV: &str = "HARNESS_ISOLATED";
+/// Compose file layered over docker-compose.yml for isolated projects.
+pub const ISOLATED_OVERRIDE: &str = "docker-compose.isolated.yml";
+
+/// Services whose state lives only in named volumes, and which the override
+/// file covers.
+pub const ISOLATABLE_SERVICES: [&str; 3] = ["postgres", "lnurl-auth-server", "vss-server"];
+
+// Distinguishes environments spawned by the same process
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the run was asked to use a private environment.
+pub fn isolated_requested() -> bool {
+    crate::args().isolated
+        || matches!(std::env::var(ISOLATED_ENV).as_deref(), Ok("1") | Ok("true"))
+}
+
+/// A running isolated project; `down` (or dropping it) removes it with its volumes.
+pub struct IsolatedEnv {
+    env: DockerEnv,
+    keep_alive: bool,
+    down: bool,
+}
+
+impl IsolatedEnv {
+    /// Bring `profile` up under a fresh project name.
+    pub async fn spawn(profile: &Profile) -> Result<Self, String> {
+        if profile.services.is_empty() {
+            return Err(format!(
+                "Profile {} starts every service and cannot be isolated",
+                profile.name
+            ));
+        }
+        if let Some(service) = profile
+            .services
+            .iter()
+            .find(|s| !ISOLATABLE_SERVICES.contains(s))
+        {
+            return Err(format!(
+                "Profile {} cannot be isolated: {} keeps state outside named volumes",
+                profile.name, service
+            ));
+        }
+
+        let project = format!(
+            "{}-{}-{}",
+            default_project_name(std::path::Path::new(COMPOSE_DIR))?,
+            std::process::id(),
+            SPAWNED.fetch_add(1, Ordering::SeqCst)
+        );
+        // Guard first, so a half-started project is cleaned up too
+        let environment = Self {
+            env: DockerEnv::new(&project)?,
+            keep_alive: keep_alive_requested(),
+            down: false,
+        };
+        tracing::info!(profile = profile.name, %project, "Bringing up isolated project");
+        let mut args = compose_files().to_vec();
+        args.extend(profile.up_args());
+        crate::compose(&project, &args).await?;
+        Ok(environment)
+    }
+
+    /// Bring up `count` isolated copies of `profile` concurrently.
+    pub async fn spawn_many(profile: &Profile, count: usize) -> Result<Vec<Self>, String> {
+        // If one fails, the others are dropped here and thereby removed
+        let spawned = join_all((0..count).map(|_| Self::spawn(profile))).await;
+        spawned.into_iter().collect()
+    }
+
+    pub fn env(&self) -> &DockerEnv {
+        &self.env
+    }
+
+    pub fn project(&self) -> &str {
+        self.env.project()
+    }
+
+    /// Point `DockerEnv::local` (and with it everything else in the process)
+    /// at this environment.
+    pub fn activate(&self) {
+        std::env::set_var("COMPOSE_PROJECT_NAME", self.project());
+    }
+
+    /// Remove the project's containers, networks and volumes.
+    pub async fn down(mut self) -> Result<(), String> {
+        self.down = true;
+        if self.keep_alive {
+            tracing::info!(project = self.project(), "Keeping isolated project alive");
+            return Ok(());
+        }
+        down(self.project()).await
+    }
+}
+
+impl Drop for IsolatedEnv {
+    fn drop(&mut self) {
+        if self.down {
+            return;
+        }
+        if self.keep_alive {
+            tracing::info!(project = self.project(), "Keeping isolated project alive");
+            return;
+        }
+        crate::run_detached(self.project().to_string(), move |env| async move {
+            if let Err(e) = down(env.project()).await {
+                tracing::warn!(
+                    project = env.project(),
+                    "Failed to remove isolated project: {}",
+                    e
+                );
+            }
+        });
+    }
+}
+
+fn compose_files() -> [&'static str; 4] {
+    ["--file", "docker-compose.yml", "--file", ISOLATED_OVERRIDE]
+}
+
+async fn down(project: &str) -> Result<(), String> {
+    let mut args = compose_files().to_vec();
+    args.extend(["down", "--volumes", "--remove-orphans"]);
+    crate::compose(project, &args).await.map(|_| ())
+}
@@ -0,0 +1,721 @@
+//! Programmatic control of the docker-compose.yml services over the Docker API
+//!
+//! Containers are found through the labels docker compose puts on them, so
+//! every call is keyed by compose service name. Containers must have been
+//! created (`docker compose create` or `up`) at least once; from there tests
+//! can start, stop, restart and inspect them without shelling out. `readiness`
+//! waits for the stack to be healthy before tests run, `teardown` puts back
+//! whatever a run started, `logs` attaches container output to failures,
+//! `chaos` injects faults, `snapshot` saves and restores service data,
+//! `profile` brings up only what a suite needs, `isolated` spawns private
+//! copies of the stack for parallel runs, `matrix` reruns a suite across image
+//! versions, `stats` records the containers' resource usage per test, `memory`
+//! watches a service's memory for growth over a long run, `startup` restarts
+//! the stack in a random order, `env_file` shares compose's `.env` with the
+//! harness, `summary` ends a run with `summary.json` and an exit code for CI
+//! and `notify` posts failed runs to a webhook.
+//!
+//! A binary hands the flags it parsed into `HarnessArgs` to `init` first
+//! thing; the modules read them from there, each alongside its environment
+//! variable.
+
+pub mod chaos;
+pub mod env_file;
+pub mod isolated;
+pub mod logs;
+pub mod matrix;
+pub mod memory;
+pub mod notify;
+pub mod profile;
+pub mod readiness;
+pub mod snapshot;
+pub mod startup;
+pub mod stats;
+pub mod summary;
+pub mod teardown;
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use bollard::container::{
+    Config, CreateContainerOptions, InspectContainerOptions, KillContainerOptions,
+    ListContainersOptions, LogOutput, LogsOptions, RemoveContainerOptions, RestartContainerOptions,
+    StartContainerOptions, StopContainerOptions, WaitContainerOptions,
+};
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::image::CreateImageOptions;
+use bollard::models::{EndpointSettings, HostConfig};
+use bollard::network::{ConnectNetworkOptions, DisconnectNetworkOptions};
+use bollard::Docker;
+use futures_util::StreamExt;
+use test_harness::cli::HarnessArgs;
+
+const PROJECT_LABEL: &str = "com.docker.compose.project";
+const SERVICE_LABEL: &str = "com.docker.compose.service";
+
+// Directory holding docker-compose.yml, relative to the vss-test crate
+pub const COMPOSE_DIR: &str = "..";
+// Seconds a container gets to exit cleanly before it is killed
+pub const DEFAULT_STOP_TIMEOUT_SECS: i64 = 10;
+
+static ARGS: OnceLock<HarnessArgs> = OnceLock::new();
+
+/// Take the harness flags of this run, as the binary parsed them. Call it
+/// before anything else here; without it only the environment variables
+/// count.
+pub fn init(args: &HarnessArgs) {
+    if ARGS.set(args.clone()).is_err() {
+        tracing::warn!("harness_docker::init called twice; keeping the first flags");
+    }
+}
+
+/// The flags `init` took, or none given.
+pub(crate) fn args() -> &'static HarnessArgs {
+    ARGS.get_or_init(HarnessArgs::default)
+}
+
+/// Runtime state of a service's container.
+#[derive(Debug, Clone)]
+pub struct ServiceState {
+    pub service: String,
+    pub container_id: String,
+    pub container_name: String,
+    /// Docker status, e.g. `running`, `exited`, `restarting`.
+    pub status: String,
+    pub running: bool,
+    /// Healthcheck status, `None` when the service defines no healthcheck.
+    pub health: Option<String>,
+    /// Healthcheck command as configured, e.g. `["CMD", "pg_isready"]`.
+    pub healthcheck: Option<Vec<String>>,
+    pub exit_code: Option<i64>,
+    /// Whether the kernel OOM-killed the last run; cleared when it starts again.
+    pub oom_killed: bool,
+    pub restart_count: i64,
+    pub started_at: Option<String>,
+}
+
+/// The image a service's container runs.
+#[derive(Debug, Clone)]
+pub struct ServiceImage {
+    /// As given in docker-compose.yml, e.g. `postgres:15-alpine`.
+    pub image: String,
+    /// Content digest of the image, `sha256:...`.
+    pub image_id: String,
+}
+
+/// What a command run inside a container printed, and how it exited.
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i64,
+}
+
+impl ExecOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+#[derive(Clone)]
+pub struct DockerEnv {
+    docker: Docker,
+    project: String,
+}
+
+impl DockerEnv {
+    /// Connect to the local Docker daemon for the given compose project.
+    pub fn new(project: &str) -> Result<Self, String> {
+        let docker = Docker::connect_with_local_defaults()
+            .map_err(|e| format!("Failed to connect to Docker: {:?}", e))?;
+        Ok(Self {
+            docker,
+            project: project.to_string(),
+        })
+    }
+
+    /// Connect for the project named by `COMPOSE_PROJECT_NAME` (which may come
+    /// from compose's `.env`), or derived from the compose directory name the
+    /// way docker compose does.
+    pub fn local() -> Result<Self, String> {
+        env_file::load()?;
+        let project = match std::env::var("COMPOSE_PROJECT_NAME") {
+            Ok(name) if !name.is_empty() => name,
+            _ => default_project_name(Path::new(COMPOSE_DIR))?,
+        };
+        Self::new(&project)
+    }
+
+    pub fn project(&self) -> &str {
+        &self.project
+    }
+
+    /// The underlying client, for calls this crate does not wrap.
+    pub fn docker(&self) -> &Docker {
+        &self.docker
+    }
+
+    /// Names of all services with a container in the project, running or not.
+    pub async fn services(&self) -> Result<Vec<String>, String> {
+        let mut services: Vec<String> = self
+            .list(None)
+            .await?
+            .into_iter()
+            .filter_map(|c| c.labels.and_then(|mut l| l.remove(SERVICE_LABEL)))
+            .collect();
+        services.sort();
+        services.dedup();
+        Ok(services)
+    }
+
+    /// The image of every service with a container in the project, by service.
+    pub async fn images(&self) -> Result<BTreeMap<String, ServiceImage>, String> {
+        Ok(self
+            .list(None)
+            .await?
+            .into_iter()
+            .filter_map(|c| {
+                let service = c.labels?.remove(SERVICE_LABEL)?;
+                let image = ServiceImage {
+                    image: c.image.unwrap_or_default(),
+                    image_id: c.image_id.unwrap_or_default(),
+                };
+                Some((service, image))
+            })
+            .collect())
+    }
+
+    /// ID of the service's container.
+    pub async fn container_id(&self, service: &str) -> Result<String, String> {
+        let containers = self.list(Some(service)).await?;
+        match containers.as_slice() {
+            [] => Err(format!(
+                "No container for service {} in project {}; run `docker compose create {}` first",
+                service, self.project, service
+            )),
+            [container] => container
+                .id
+                .clone()
+                .ok_or_else(|| format!("Container for service {} has no ID", service)),
+            _ => Err(format!(
+                "Service {} has {} containers; scaled services are not supported",
+                service,
+                containers.len()
+            )),
+        }
+    }
+
+    /// Start the service's container; a no-op when it is already running.
+    pub async fn start(&self, service: &str) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        match self
+            .docker
+            .start_container(&id, None::<StartContainerOptions<String>>)
+            .await
+        {
+            // 304: already started
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 304, ..
+            }) => Ok(()),
+            Err(e) => Err(format!("Failed to start {}: {:?}", service, e)),
+        }
+    }
+
+    /// Stop the service's container, killing it after `timeout_secs`.
+    pub async fn stop(&self, service: &str, timeout_secs: i64) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        match self
+            .docker
+            .stop_container(&id, Some(StopContainerOptions { t: timeout_secs }))
+            .await
+        {
+            // 304: already stopped
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 304, ..
+            }) => Ok(()),
+            Err(e) => Err(format!("Failed to stop {}: {:?}", service, e)),
+        }
+    }
+
+    /// Restart the service's container, killing it after `timeout_secs` if it does not exit.
+    pub async fn restart(&self, service: &str, timeout_secs: i64) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        self.docker
+            .restart_container(
+                &id,
+                Some(RestartContainerOptions {
+                    t: timeout_secs as isize,
+                }),
+            )
+            .await
+            .map_err(|e| format!("Failed to restart {}: {:?}", service, e))
+    }
+
+    /// Kill and remove the service's container with its anonymous volumes.
+    pub async fn remove(&self, service: &str) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        self.docker
+            .remove_container(
+                &id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    v: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .map_err(|e| format!("Failed to remove {}: {:?}", service, e))
+    }
+
+    /// Send `signal` (e.g. `SIGKILL`) to the service's main process.
+    pub async fn kill(&self, service: &str, signal: &str) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        self.docker
+            .kill_container(&id, Some(KillContainerOptions { signal }))
+            .await
+            .map_err(|e| format!("Failed to send {} to {}: {:?}", signal, service, e))
+    }
+
+    /// Current state of the service's container.
+    pub async fn inspect(&self, service: &str) -> Result<ServiceState, String> {
+        let id = self.container_id(service).await?;
+        let details = self
+            .docker
+            .inspect_container(&id, None::<InspectContainerOptions>)
+            .await
+            .map_err(|e| format!("Failed to inspect {}: {:?}", service, e))?;
+        let state = details.state.unwrap_or_default();
+        Ok(ServiceState {
+            service: service.to_string(),
+            container_id: id,
+            container_name: details
+                .name
+                .map(|n| n.trim_start_matches('/').to_string())
+                .unwrap_or_default(),
+            status: state.status.map(|s| s.to_string()).unwrap_or_default(),
+            running: state.running.unwrap_or(false),
+            health: state.health.and_then(|h| h.status).map(|s| s.to_string()),
+            // ["NONE"] disables a healthcheck inherited from the image
+            healthcheck: details
+                .config
+                .and_then(|c| c.healthcheck)
+                .and_then(|h| h.test)
+                .filter(|test| test.first().is_some_and(|t| t != "NONE")),
+            exit_code: state.exit_code,
+            oom_killed: state.oom_killed.unwrap_or(false),
+            restart_count: details.restart_count.unwrap_or(0),
+            started_at: state.started_at,
+        })
+    }
+
+    /// The last `lines` lines of the service's stdout and stderr, interleaved.
+    pub async fn logs(&self, service: &str, lines: usize) -> Result<String, String> {
+        let id = self.container_id(service).await?;
+        self.read_logs(&id, &lines.to_string())
+            .await
+            .map_err(|e| format!("Failed to read logs of {}: {}", service, e))
+    }
+
+    /// Run `cmd` inside the service's running container and collect its
+    /// output. A non-zero exit is not an error; check `ExecOutput::success`.
+    pub async fn exec(&self, service: &str, cmd: &[&str]) -> Result<ExecOutput, String> {
+        let id = self.container_id(service).await?;
+        let what = format!("{} in {}", cmd.join(" "), service);
+        let exec = self
+            .docker
+            .create_exec(
+                &id,
+                CreateExecOptions {
+                    cmd: Some(cmd.to_vec()),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to create exec of {}: {:?}", what, e))?;
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        match self.docker.start_exec(&exec.id, None).await {
+            Ok(StartExecResults::Attached { mut output, .. }) => {
+                while let Some(chunk) = output.next().await {
+                    match chunk
+                        .map_err(|e| format!("Failed to read output of {}: {:?}", what, e))?
+                    {
+                        LogOutput::StdOut { message } => {
+                            stdout.push_str(&String::from_utf8_lossy(&message))
+                        }
+                        LogOutput::StdErr { message } => {
+                            stderr.push_str(&String::from_utf8_lossy(&message))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(StartExecResults::Detached) => {
+                return Err(format!("Exec of {} detached unexpectedly", what))
+            }
+            Err(e) => return Err(format!("Failed to start {}: {:?}", what, e)),
+        }
+
+        let exit_code = self
+            .docker
+            .inspect_exec(&exec.id)
+            .await
+            .map_err(|e| format!("Failed to inspect exec of {}: {:?}", what, e))?
+            .exit_code
+            .ok_or_else(|| format!("{} has no exit code", what))?;
+        Ok(ExecOutput {
+            stdout,
+            stderr,
+            exit_code,
+        })
+    }
+
+    /// Environment variables the service's container was created with.
+    pub async fn container_env(&self, service: &str) -> Result<HashMap<String, String>, String> {
+        let id = self.container_id(service).await?;
+        let details = self
+            .docker
+            .inspect_container(&id, None::<InspectContainerOptions>)
+            .await
+            .map_err(|e| format!("Failed to inspect {}: {:?}", service, e))?;
+        Ok(details
+            .config
+            .and_then(|c| c.env)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|var| {
+                var.split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+            })
+            .collect())
+    }
+
+    /// Detach the service's container from a compose network, e.g. `vss-db`.
+    pub async fn disconnect(&self, service: &str, network: &str) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        self.docker
+            .disconnect_network(
+                &self.network_name(network),
+                DisconnectNetworkOptions {
+                    container: id,
+                    force: true,
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to disconnect {} from {}: {:?}", service, network, e))
+    }
+
+    /// Attach the service's container to a compose network under its service
+    /// name, as compose does.
+    pub async fn connect(&self, service: &str, network: &str) -> Result<(), String> {
+        let id = self.container_id(service).await?;
+        self.docker
+            .connect_network(
+                &self.network_name(network),
+                ConnectNetworkOptions {
+                    container: id,
+                    endpoint_config: EndpointSettings {
+                        aliases: Some(vec![service.to_string()]),
+                        ..Default::default()
+                    },
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to connect {} to {}: {:?}", service, network, e))
+    }
+
+    /// Run `script` with `sh -c` in a throwaway `image` container that shares
+    /// the service's network namespace, returning its output. `cap_add` grants
+    /// capabilities such as `NET_ADMIN` for traffic shaping.
+    pub async fn run_sidecar(
+        &self,
+        service: &str,
+        image: &str,
+        script: &str,
+        cap_add: &[&str],
+    ) -> Result<String, String> {
+        let id = self.container_id(service).await?;
+        let host_config = HostConfig {
+            network_mode: Some(format!("container:{}", id)),
+            cap_add: Some(cap_add.iter().map(|c| c.to_string()).collect()),
+            ..Default::default()
+        };
+        let what = format!("sidecar for {}", service);
+        self.run_once(&what, image, script, host_config).await
+    }
+
+    /// Run `script` with `sh -c` in a throwaway `image` container with `binds`
+    /// (`source:target` as in `docker run -v`) mounted, returning its output.
+    pub async fn run_helper(
+        &self,
+        image: &str,
+        script: &str,
+        binds: &[String],
+    ) -> Result<String, String> {
+        let host_config = HostConfig {
+            binds: Some(binds.to_vec()),
+            ..Default::default()
+        };
+        self.run_once("helper container", image, script, host_config)
+            .await
+    }
+
+    async fn run_once(
+        &self,
+        what: &str,
+        image: &str,
+        script: &str,
+        host_config: HostConfig,
+    ) -> Result<String, String> {
+        self.ensure_image(image).await?;
+        let container = self
+            .docker
+            .create_container(
+                None::<CreateContainerOptions<String>>,
+                Config {
+                    image: Some(image.to_string()),
+                    entrypoint: Some(vec!["sh".to_string(), "-c".to_string()]),
+                    cmd: Some(vec![script.to_string()]),
+                    host_config: Some(host_config),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to create {}: {:?}", what, e))?
+            .id;
+
+        let result = async {
+            self.docker
+                .start_container(&container, None::<StartContainerOptions<String>>)
+                .await
+                .map_err(|e| format!("Failed to start {}: {:?}", what, e))?;
+            let exit = self
+                .docker
+                .wait_container(&container, None::<WaitContainerOptions<String>>)
+                .next()
+                .await;
+            let output = self
+                .read_logs(&container, "all")
+                .await
+                .map_err(|e| format!("Failed to read output of {}: {}", what, e))?;
+            match exit {
+                // Non-zero exits surface as wait errors
+                Some(Ok(_)) => Ok(output),
+                Some(Err(e)) => Err(format!("{} failed: {:?}: {}", what, e, output.trim())),
+                None => Err(format!("{} vanished", what)),
+            }
+        }
+        .await;
+
+        let _ = self
+            .docker
+            .remove_container(
+                &container,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await;
+        result
+    }
+
+    /// Pull `image` unless it is already present.
+    pub async fn ensure_image(&self, image: &str) -> Result<(), String> {
+        if self.docker.inspect_image(image).await.is_ok() {
+            return Ok(());
+        }
+        let mut pull = self.docker.create_image(
+            Some(CreateImageOptions {
+                from_image: image,
+                ..Default::default()
+            }),
+            None,
+            None,
+        );
+        while let Some(progress) = pull.next().await {
+            progress.map_err(|e| format!("Failed to pull {}: {:?}", image, e))?;
+        }
+        Ok(())
+    }
+
+    /// Docker name of a volume declared in docker-compose.yml.
+    pub fn volume_name(&self, volume: &str) -> String {
+        format!("{}_{}", self.project, volume)
+    }
+
+    /// Docker name of a network declared in docker-compose.yml.
+    pub fn network_name(&self, network: &str) -> String {
+        format!("{}_{}", self.project, network)
+    }
+
+    /// Host port Docker mapped the service's `container_port` (TCP) to, so
+    /// tests do not depend on the ports fixed in docker-compose.yml.
+    pub async fn host_port(&self, service: &str, container_port: u16) -> Result<u16, String> {
+        let id = self.container_id(service).await?;
+        let details = self
+            .docker
+            .inspect_container(&id, None::<InspectContainerOptions>)
+            .await
+            .map_err(|e| format!("Failed to inspect {}: {:?}", service, e))?;
+        let bindings = details
+            .network_settings
+            .and_then(|n| n.ports)
+            .and_then(|mut ports| ports.remove(&format!("{}/tcp", container_port)))
+            .flatten()
+            .unwrap_or_default();
+        // Prefer the IPv4 binding; the IPv6 one normally carries the same port
+        bindings
+            .iter()
+            .filter(|b| !b.host_ip.as_deref().unwrap_or_default().contains(':'))
+            .chain(bindings.iter())
+            .find_map(|b| b.host_port.as_deref().and_then(|p| p.parse().ok()))
+            .ok_or_else(|| {
+                format!(
+                    "{} does not publish port {} (is it running?)",
+                    service, container_port
+                )
+            })
+    }
+
+    /// `scheme://localhost:<port>` for the service's published `container_port`.
+    pub async fn service_url(
+        &self,
+        service: &str,
+        container_port: u16,
+        scheme: &str,
+    ) -> Result<String, String> {
+        let port = self.host_port(service, container_port).await?;
+        Ok(format!("{}://localhost:{}", scheme, port))
+    }
+
+    /// Whether the service's container is running.
+    pub async fn is_running(&self, service: &str) -> Result<bool, String> {
+        Ok(self.inspect(service).await?.running)
+    }
+
+    async fn read_logs(&self, id: &str, tail: &str) -> Result<String, String> {
+        let mut stream = self.docker.logs(
+            id,
+            Some(LogsOptions {
+                stdout: true,
+                stderr: true,
+                tail: tail.to_string(),
+                ..Default::default()
+            }),
+        );
+        let mut output = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| format!("{:?}", e))?;
+            output.push_str(&chunk.to_string());
+        }
+        Ok(output)
+    }
+
+    async fn list(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<bollard::models::ContainerSummary>, String> {
+        let mut labels = vec![format!("{}={}", PROJECT_LABEL, self.project)];
+        if let Some(service) = service {
+            labels.push(format!("{}={}", SERVICE_LABEL, service));
+        }
+        let filters = HashMap::from([("label".to_string(), labels)]);
+        self.docker
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters,
+                ..Default::default()
+            }))
+            .await
+            .map_err(|e| format!("Failed to list containers of {}: {:?}", self.project, e))
+    }
+}
+
+/// Compose's default project name: the directory name, lowercased, keeping
+/// only characters compose allows.
+pub fn default_project_name(compose_dir: &Path) -> Result<String, String> {
+    let dir = compose_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {:?}", compose_dir.display(), e))?;
+    let name: String = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect();
+    let name = name.trim_start_matches(['_', '-']).to_string();
+    if name.is_empty() {
+        return Err(format!(
+            "Cannot derive a compose project name from {}; set COMPOSE_PROJECT_NAME",
+            dir.display()
+        ));
+    }
+    Ok(name)
+}
+
+/// Run `docker compose --project-name <project> <args>` in the compose
+/// directory, returning stdout.
+pub(crate) async fn compose(project: &str, args: &[&str]) -> Result<String, String> {
+    compose_with_env(project, &[], args).await
+}
+
+/// `compose` with extra environment variables, e.g. for interpolating
+/// docker-compose.yml.
+pub(crate) async fn compose_with_env(
+    project: &str,
+    vars: &[(&str, &str)],
+    args: &[&str],
+) -> Result<String, String> {
+    let output = tokio::process::Command::new("docker")
+        .args(["compose", "--project-name", project])
+        .args(args)
+        .envs(vars.iter().copied())
+        .current_dir(COMPOSE_DIR)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run docker compose {}: {:?}", args.join(" "), e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "docker compose {} exited with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run `task` to completion on its own thread, runtime and Docker connection.
+/// For cleanup from `Drop`, which may run inside or after the caller's runtime.
+pub(crate) fn run_detached<F, Fut>(project: String, task: F)
+where
+    F: FnOnce(DockerEnv) -> Fut + Send + 'static,
+    Fut: Future<Output = ()>,
+{
+    let handle = std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                tracing::warn!("Cleanup failed to start a runtime: {:?}", e);
+                return;
+            }
+        };
+        match DockerEnv::new(&project) {
+            Ok(env) => runtime.block_on(task(env)),
+            Err(e) => tracing::warn!("Cleanup failed: {}", e),
+        }
+    });
+    let _ = handle.join();
+}
@@ -0,0 +1,48 @@
+//! Container logs attached to test failures
+//!
+//! A failed check prints the tail of the relevant services' logs right under
+//! its FAILED line, so a 500 can be diagnosed without rerunning by hand.
+
+use crate::DockerEnv;
+
+// Overridable via FAILURE_LOG_LINES
+pub const DEFAULT_FAILURE_LOG_LINES: usize = 50;
+
+/// Services behind the VSS endpoints.
+pub const VSS_SERVICES: [&str; 3] = ["vss-server", "lnurl-server", "postgres"];
+
+/// Tail of each service's logs, one indented block per service. Errors
+/// fetching a service's logs are reported in its block instead.
+pub async fn failure_logs(services: &[&str], lines: usize) -> String {
+    let env = match DockerEnv::local() {
+        Ok(env) => env,
+        Err(e) => return format!("    (container logs unavailable: {})\n", e),
+    };
+    let mut report = String::new();
+    for service in services {
+        report.push_str(&format!("    --- {} (last {} lines) ---\n", service, lines));
+        match env.logs(service, lines).await {
+            Ok(logs) => {
+                for line in logs.lines() {
+                    report.push_str(&format!("    | {}\n", line));
+                }
+            }
+            Err(e) => report.push_str(&format!("    (unavailable: {})\n", e)),
+        }
+    }
+    report
+}
+
+/// Lines per service from FAILURE_LOG_LINES, or the default.
+pub fn failure_log_lines() -> usize {
+    std::env::var("FAILURE_LOG_LINES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_FAILURE_LOG_LINES)
+}
+
+/// Print `failure_logs` for `services`, with the line count from the
+/// environment.
+pub async fn print_failure_logs(services: &[&str]) {
+    print!("{}", failure_logs(services, failure_log_lines()).await);
+}
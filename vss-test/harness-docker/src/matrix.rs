@@ -0,0 +1,309 @@
+//! Reruns of a suite across image versions
+//!
+//! docker-compose.yml takes the VSS, lnurl-server, bitcoind and LND image tags
+//! from `*_IMAGE_TAG` variables. With `--matrix` a test binary does not run its
+//! tests itself: for every combination of the tags listed in the matrix file
+//! it recreates the services with those tags, reruns itself with the same
+//! arguments, told by `MATRIX_CELL_ENV` to run the tests this time, and
+//! records whether it passed. The outcome is printed as a
+//! compatibility grid, and the stack is put back on the default tags.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
+
+use tokio::process::Command;
+
+use crate::readiness::Readiness;
+use crate::summary;
+use crate::DockerEnv;
+
+// Matrix file relative to vss-test; `--matrix=<path>` or MATRIX_FILE override it
+pub const DEFAULT_MATRIX_FILE: &str = "matrix.json";
+// Set for the reruns, which run one combination's tests despite `--matrix`
+pub const MATRIX_CELL_ENV: &str = "HARNESS_MATRIX_CELL";
+
+/// An image whose tag the matrix can vary.
+#[derive(Debug, Clone, Copy)]
+pub struct Dimension {
+    /// Key in the matrix file.
+    pub name: &'static str,
+    /// Variable docker-compose.yml reads the tag from.
+    pub env_var: &'static str,
+}
+
+pub const DIMENSIONS: [Dimension; 4] = [
+    Dimension {
+        name: "vss-server",
+        env_var: "VSS_IMAGE_TAG",
+    },
+    Dimension {
+        name: "lnurl-server",
+        env_var: "LNURL_SERVER_IMAGE_TAG",
+    },
+    Dimension {
+        name: "bitcoind",
+        env_var: "BITCOIND_IMAGE_TAG",
+    },
+    Dimension {
+        name: "lnd",
+        env_var: "LND_IMAGE_TAG",
+    },
+];
+
+/// Tags to try per dimension, in `DIMENSIONS` order. Dimensions left out of
+/// the file keep the compose default.
+#[derive(Debug, Clone)]
+pub struct Matrix {
+    pub axes: Vec<(Dimension, Vec<String>)>,
+}
+
+/// One combination of tags and how the suite did with it.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub tags: Vec<(Dimension, String)>,
+    pub passed: bool,
+    /// Why the suite could not run at all, e.g. an image failed to pull.
+    pub error: Option<String>,
+    pub elapsed: Duration,
+}
+
+impl Matrix {
+    /// Parse a JSON object mapping dimension names to lists of tags, e.g.
+    /// `{"lnd": ["0.17.4-beta", "0.18.0-beta"]}`.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let raw: BTreeMap<String, Vec<String>> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid matrix: {}", e))?;
+        if let Some(unknown) = raw
+            .keys()
+            .find(|k| !DIMENSIONS.iter().any(|d| d.name == k.as_str()))
+        {
+            let names: Vec<&str> = DIMENSIONS.iter().map(|d| d.name).collect();
+            return Err(format!(
+                "Unknown matrix dimension {}, expected one of: {}",
+                unknown,
+                names.join(", ")
+            ));
+        }
+        let mut axes = Vec::new();
+        for dimension in DIMENSIONS {
+            match raw.get(dimension.name) {
+                Some(tags) if tags.is_empty() => {
+                    return Err(format!("Matrix lists no tags for {}", dimension.name))
+                }
+                Some(tags) => axes.push((dimension, tags.clone())),
+                None => {}
+            }
+        }
+        Ok(Self { axes })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read matrix file {}: {:?}", path, e))?;
+        Self::parse(&json)
+    }
+
+    /// Every combination of tags, the last dimension varying fastest.
+    pub fn combinations(&self) -> Vec<Vec<(Dimension, String)>> {
+        let mut combinations = vec![Vec::new()];
+        for (dimension, tags) in &self.axes {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|prefix: Vec<(Dimension, String)>| {
+                    tags.iter().map(move |tag| {
+                        let mut combination = prefix.clone();
+                        combination.push((*dimension, tag.clone()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+}
+
+/// The matrix file named by `--matrix=<path>`, MATRIX_FILE or the default,
+/// if `--matrix` was given at all and this is not one of its reruns.
+pub fn requested() -> Option<String> {
+    if std::env::var_os(MATRIX_CELL_ENV).is_some() {
+        return None;
+    }
+    let path = crate::args().matrix.clone()?;
+    Some(path.unwrap_or_else(|| {
+        std::env::var("MATRIX_FILE").unwrap_or_else(|_| DEFAULT_MATRIX_FILE.to_string())
+    }))
+}
+
+/// Run the matrix if `--matrix` was given and return the exit status the
+/// binary should end with (see `summary`); `None` means run the tests
+/// normally. `required` are the services the suite needs, which are
+/// recreated per combination.
+pub async fn run_if_requested(required: &[&str]) -> Option<i32> {
+    let path = requested()?;
+    let matrix = match Matrix::load(&path) {
+        Ok(matrix) => matrix,
+        Err(e) => {
+            tracing::error!("Matrix run failed: {}", e);
+            return Some(summary::HARNESS_ERROR);
+        }
+    };
+    let result = async {
+        let env = DockerEnv::local()?;
+        run(&env, &matrix, required).await
+    }
+    .await;
+    match result {
+        Ok(cells) => {
+            println!();
+            print!("{}", grid(&cells));
+            Some(if cells.iter().all(|c| c.passed) {
+                0
+            } else {
+                summary::TESTS_FAILED
+            })
+        }
+        Err(e) => {
+            tracing::error!("Matrix run failed: {}", e);
+            Some(summary::ENVIRONMENT_UNAVAILABLE)
+        }
+    }
+}
+
+/// Rerun the current binary for every combination of `matrix`, then put the
+/// services back on their default tags. Each rerun writes its own summary,
+/// numbered after the combination.
+pub async fn run(env: &DockerEnv, matrix: &Matrix, required: &[&str]) -> Result<Vec<Cell>, String> {
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the test binary: {:?}", e))?;
+    // Passed on as given; the reruns leave `--matrix` to MATRIX_CELL_ENV
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+
+    let combinations = matrix.combinations();
+    let mut cells = Vec::new();
+    for (i, tags) in combinations.iter().enumerate() {
+        println!();
+        println!(
+            "=== Matrix {}/{}: {}",
+            i + 1,
+            combinations.len(),
+            describe(tags)
+        );
+        let start = Instant::now();
+        let vars: Vec<(&str, &str)> = tags
+            .iter()
+            .map(|(d, tag)| (d.env_var, tag.as_str()))
+            .collect();
+
+        let outcome = async {
+            recreate(env, &vars, required).await?;
+            Command::new(&exe)
+                .args(&args)
+                .envs(vars.iter().copied())
+                .env(MATRIX_CELL_ENV, "1")
+                .env(summary::SUMMARY_ENV, summary::numbered_path(i + 1))
+                .status()
+                .await
+                .map_err(|e| format!("Failed to run {}: {:?}", exe.display(), e))
+        }
+        .await;
+        cells.push(match outcome {
+            Ok(status) => Cell {
+                tags: tags.clone(),
+                passed: status.success(),
+                error: None,
+                elapsed: start.elapsed(),
+            },
+            Err(e) => {
+                tracing::warn!(tags = %describe(tags), "{}", e);
+                Cell {
+                    tags: tags.clone(),
+                    passed: false,
+                    error: Some(e),
+                    elapsed: start.elapsed(),
+                }
+            }
+        });
+    }
+
+    tracing::info!("Restoring default image tags");
+    recreate(env, &[], required).await?;
+    Ok(cells)
+}
+
+/// Bring `required` up with `vars` set, which recreates every service whose
+/// image tag changed, and wait for the ones readiness knows how to probe.
+/// Images are never built here: a locally built service would otherwise be
+/// tagged with whatever tag was asked for.
+async fn recreate(env: &DockerEnv, vars: &[(&str, &str)], required: &[&str]) -> Result<(), String> {
+    let mut args = vec!["up", "--detach", "--no-build"];
+    args.extend(required);
+    crate::compose_with_env(env.project(), vars, &args).await?;
+
+    let readiness = Readiness::stack_for(env).await?;
+    let targets: Vec<&str> = required
+        .iter()
+        .copied()
+        .filter(|s| readiness.declares(s))
+        .collect();
+    if !targets.is_empty() {
+        readiness.wait(&targets).await?;
+    }
+    Ok(())
+}
+
+fn describe(tags: &[(Dimension, String)]) -> String {
+    if tags.is_empty() {
+        return "default tags".to_string();
+    }
+    tags.iter()
+        .map(|(d, tag)| format!("{}={}", d.name, tag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// One row per combination and one column per varied image.
+pub fn grid(cells: &[Cell]) -> String {
+    let Some(first) = cells.first() else {
+        return "Compatibility matrix: no combinations\n".to_string();
+    };
+    let mut header: Vec<String> = first.tags.iter().map(|(d, _)| d.name.to_string()).collect();
+    header.push("result".to_string());
+    let rows: Vec<Vec<String>> = cells
+        .iter()
+        .map(|cell| {
+            let mut row: Vec<String> = cell.tags.iter().map(|(_, tag)| tag.clone()).collect();
+            row.push(match (&cell.error, cell.passed) {
+                (Some(_), _) => format!("error ({:?})", cell.elapsed),
+                (None, true) => format!("ok ({:?})", cell.elapsed),
+                (None, false) => format!("FAILED ({:?})", cell.elapsed),
+            });
+            row
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|r| r[i].len())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<w$}", c, w = w))
+            .collect();
+        format!("  {}\n", padded.join("  ").trim_end())
+    };
+
+    let mut grid = String::from("Compatibility matrix:\n");
+    grid.push_str(&line(&header));
+    for row in &rows {
+        grid.push_str(&line(row));
+    }
+    grid
+}
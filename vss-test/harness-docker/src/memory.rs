@@ -0,0 +1,152 @@
+//! Memory growth of one service over a long run
+//!
+//! A `MemoryWatch` samples the memory of one container, as `docker stats`
+//! counts it, every second for as long as it lives. At the end a least-squares
+//! line through the samples gives how fast the memory grew, in MiB per hour.
+//! The first fifth of the run is left out: connection pools and caches fill
+//! while the load starts, and a server that settles afterwards does not leak.
+//! Runs compare the slope with a limit, so "the server seems to leak" becomes
+//! a failure with a number instead of a hunch from a dashboard.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+
+use crate::stats::sample;
+use crate::DockerEnv;
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+// Share of the run, from its start, left out of the slope
+const WARMUP_FRACTION: f64 = 0.2;
+// Fewer samples after the warm-up than this give no slope
+const MIN_SAMPLES: usize = 5;
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Background sampler of one service's memory.
+pub struct MemoryWatch {
+    service: String,
+    samples: Arc<Mutex<Vec<(Instant, u64)>>>,
+    task: JoinHandle<()>,
+}
+
+impl MemoryWatch {
+    /// Start sampling `service` every `interval`. Readings that fail, as
+    /// while the container restarts, are skipped.
+    pub fn start(env: &DockerEnv, service: &str, interval: Duration) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let env = env.clone();
+        let sink = samples.clone();
+        let watched = service.to_string();
+        let task = tokio::spawn(async move {
+            loop {
+                if let Some(reading) = sample(&env, &watched).await {
+                    sink.lock()
+                        .unwrap()
+                        .push((reading.at, reading.memory_bytes));
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+        Self {
+            service: service.to_string(),
+            samples,
+            task,
+        }
+    }
+
+    /// What the samples so far show.
+    pub fn growth(&self) -> Growth {
+        let samples = self.samples.lock().unwrap().clone();
+        let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+            return Growth {
+                service: self.service.clone(),
+                ..Default::default()
+            };
+        };
+        let span = last.0.duration_since(first.0);
+        let warmed_up = first.0 + span.mul_f64(WARMUP_FRACTION);
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .filter(|(at, _)| *at >= warmed_up)
+            .map(|(at, bytes)| {
+                (
+                    at.duration_since(first.0).as_secs_f64() / 3600.0,
+                    *bytes as f64 / MIB,
+                )
+            })
+            .collect();
+        Growth {
+            service: self.service.clone(),
+            samples: samples.len(),
+            span,
+            first_bytes: first.1,
+            last_bytes: last.1,
+            peak_bytes: samples.iter().map(|(_, bytes)| *bytes).max().unwrap_or(0),
+            mib_per_hour: slope(&points),
+        }
+    }
+}
+
+impl Drop for MemoryWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// How a service's memory changed over a run.
+#[derive(Debug, Clone, Default)]
+pub struct Growth {
+    pub service: String,
+    pub samples: usize,
+    /// From the first sample to the last.
+    pub span: Duration,
+    pub first_bytes: u64,
+    pub last_bytes: u64,
+    pub peak_bytes: u64,
+    /// Slope after the warm-up, or `None` with too few samples for one.
+    pub mib_per_hour: Option<f64>,
+}
+
+impl Growth {
+    /// Whether the memory grew faster than `max_mib_per_hour`. A run too
+    /// short for a slope has not shown growth.
+    pub fn exceeds(&self, max_mib_per_hour: f64) -> bool {
+        self.mib_per_hour
+            .is_some_and(|slope| slope > max_mib_per_hour)
+    }
+
+    /// One line for people, e.g. `vss-server memory 120.3 MiB -> 180.0 MiB
+    /// (peak 181.2 MiB) over 4h: +12.50 MiB/h`.
+    pub fn describe(&self) -> String {
+        let slope = match self.mib_per_hour {
+            Some(slope) => format!("{:+.2} MiB/h", slope),
+            None => format!("too few samples ({}) for a slope", self.samples),
+        };
+        format!(
+            "{} memory {:.1} MiB -> {:.1} MiB (peak {:.1} MiB) over {}: {}",
+            self.service,
+            self.first_bytes as f64 / MIB,
+            self.last_bytes as f64 / MIB,
+            self.peak_bytes as f64 / MIB,
+            humantime::format_duration(Duration::from_secs(self.span.as_secs())),
+            slope
+        )
+    }
+}
+
+/// Least-squares slope of `points`, as (x, y).
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    if points.len() < MIN_SAMPLES {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance)
+}
@@ -0,0 +1,156 @@
+//! Posting failed runs to a chat webhook
+//!
+//! Nobody watches a nightly or chaos job go red at 3 a.m., so a `Run` given a
+//! `Notifier` posts a short message to a webhook whenever it ends with a
+//! non-zero code: the binary, the outcome, the counts, the failed tests by
+//! name (or the error that ended the run early), the seed to replay it with,
+//! and a link to the run's artifacts. Passing runs post nothing.
+//!
+//! The body takes the shape the receiving end expects:
+//!
+//! - `slack`: an incoming webhook's `{"text": ..}`, links in Slack's syntax
+//! - `discord`: `{"content": ..}`, cut to Discord's 2000 characters
+//! - `generic`: `{"text": ..}` next to the whole `summary.json` and the
+//!   artifacts link, for anything else to take apart
+//!
+//! The artifacts link is the configured one, else the Actions run page when
+//! `GITHUB_RUN_ID` and its siblings are set. A webhook that cannot be reached
+//! is logged and does not change the run's exit code.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Names listed before the rest are only counted
+const MAX_LISTED_TESTS: usize = 20;
+const DISCORD_MAX_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookKind {
+    Slack,
+    Discord,
+    Generic,
+}
+
+impl FromStr for WebhookKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "slack" => Ok(Self::Slack),
+            "discord" => Ok(Self::Discord),
+            "generic" => Ok(Self::Generic),
+            _ => Err(format!(
+                "Unknown webhook kind {:?}; expected slack, discord or generic",
+                s
+            )),
+        }
+    }
+}
+
+/// Where and how a failed run is posted.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    pub url: String,
+    pub kind: WebhookKind,
+    /// Link to the run's artifacts; unset falls back to `github_run_url`.
+    pub artifacts_url: Option<String>,
+}
+
+impl Notifier {
+    /// Post the run `summary` describes, as `Run` wrote it to `summary.json`.
+    pub async fn post(&self, summary: &Value) -> Result<(), String> {
+        let artifacts = self.artifacts_url.clone().or_else(github_run_url);
+        let body = payload(self.kind, summary, artifacts.as_deref());
+        let response = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {:?}", e))?
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to post to the webhook: {:?}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("Webhook answered {}: {}", status, text.trim()));
+        }
+        Ok(())
+    }
+}
+
+/// The GitHub Actions page of the current run, which lists its artifacts.
+pub fn github_run_url() -> Option<String> {
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    Some(format!(
+        "{}/{}/actions/runs/{}",
+        var("GITHUB_SERVER_URL")?,
+        var("GITHUB_REPOSITORY")?,
+        var("GITHUB_RUN_ID")?
+    ))
+}
+
+/// The request body posting `summary` to a `kind` webhook.
+fn payload(kind: WebhookKind, summary: &Value, artifacts: Option<&str>) -> Value {
+    let mut text = message(summary);
+    match kind {
+        WebhookKind::Slack => {
+            if let Some(url) = artifacts {
+                text.push_str(&format!("\n<{}|Artifacts>", url));
+            }
+            json!({ "text": text })
+        }
+        WebhookKind::Discord => {
+            let link = artifacts
+                .map(|url| format!("\n[Artifacts](<{}>)", url))
+                .unwrap_or_default();
+            let room = DISCORD_MAX_CHARS.saturating_sub(link.chars().count());
+            if text.chars().count() > room {
+                text = text.chars().take(room.saturating_sub(1)).collect();
+                text.push('…');
+            }
+            json!({ "content": text + &link })
+        }
+        WebhookKind::Generic => {
+            if let Some(url) = artifacts {
+                text.push_str(&format!("\nArtifacts: {}", url));
+            }
+            json!({ "text": text, "summary": summary, "artifacts_url": artifacts })
+        }
+    }
+}
+
+/// What went wrong in the run `summary` describes, in a few lines.
+fn message(summary: &Value) -> String {
+    let binary = summary["binary"].as_str().unwrap_or("harness");
+    let outcome = summary["outcome"].as_str().unwrap_or("failed");
+    let mut text = match summary["error"].as_str() {
+        Some(error) => format!("{} failed ({}): {}", binary, outcome, error),
+        None => format!(
+            "{} failed ({}): {} passed, {} failed",
+            binary, outcome, summary["passed"], summary["failed"]
+        ),
+    };
+    if let Some(tests) = summary["failed_tests"].as_array() {
+        let names: Vec<&str> = tests.iter().filter_map(Value::as_str).collect();
+        if !names.is_empty() {
+            text.push_str("\nFailed tests:");
+            for name in names.iter().take(MAX_LISTED_TESTS) {
+                text.push_str(&format!("\n- {}", name));
+            }
+            if names.len() > MAX_LISTED_TESTS {
+                text.push_str(&format!("\n- and {} more", names.len() - MAX_LISTED_TESTS));
+            }
+        }
+    }
+    if let Some(seed) = summary["seed"].as_u64() {
+        text.push_str(&format!("\nSeed: {}", seed));
+    }
+    if let Some(shard) = summary["shard"].as_str() {
+        text.push_str(&format!("\nShard: {}", shard));
+    }
+    text
+}
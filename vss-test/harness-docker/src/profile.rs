@@ -0,0 +1,236 @@
+//! Bringing up only the part of the stack a suite needs
+//!
+//! `--profile <name>` (or `HARNESS_PROFILE`) makes a test binary start the
+//! services of that profile with `docker compose up` before it runs, instead
+//! of expecting the whole stack to be up already. Each binary names the
+//! services it needs; a profile lacking one of them is rejected before
+//! anything is started. Add `--isolated` to bring the profile up as a private
+//! copy of the stack instead, see `isolated`. `--startup-race` is handled
+//! here too, once the stack is up, see `startup`.
+
+use crate::isolated::{isolated_requested, IsolatedEnv};
+use crate::startup::race_if_requested;
+use crate::DockerEnv;
+
+const PROFILE_ENV: &str = "HARNESS_PROFILE";
+
+/// A named subset of the docker-compose.yml services.
+#[derive(Debug, Clone, Copy)]
+pub struct Profile {
+    pub name: &'static str,
+    /// Services to start; compose adds their `depends_on`. Empty means all.
+    pub services: &'static [&'static str],
+    /// Compose profiles to enable, for services that are off by default.
+    pub compose_profiles: &'static [&'static str],
+}
+
+/// VSS, its database and the auth server; no chain, no Lightning nodes.
+pub const VSS_ONLY: Profile = Profile {
+    name: "vss-only",
+    services: &["postgres", "lnurl-auth-server", "vss-server"],
+    compose_profiles: &[],
+};
+
+/// Chain, electrs, `lnd` and `lnd2`, which is behind the `lightning` compose
+/// profile.
+pub const LIGHTNING: Profile = Profile {
+    name: "lightning",
+    services: &[
+        "bitcoind",
+        "bitcoinsetup",
+        "electrs",
+        "darkhttpd",
+        "lnd",
+        "lnd2",
+        "lnurl-server",
+    ],
+    compose_profiles: &["lightning"],
+};
+
+/// `lightning` plus the extra graph nodes behind the `graph` compose profile,
+/// which also enables `lnd3` for the scenarios that start it.
+pub const GRAPH: Profile = Profile {
+    name: "graph",
+    services: &[
+        "bitcoind",
+        "bitcoinsetup",
+        "electrs",
+        "darkhttpd",
+        "lnd",
+        "lnd2",
+        "lnd4",
+        "lnd5",
+        "lnurl-server",
+    ],
+    compose_profiles: &["lightning", "graph"],
+};
+
+/// Every service, including the optional ones.
+pub const FULL: Profile = Profile {
+    name: "full",
+    services: &[],
+    compose_profiles: &["lightning", "graph"],
+};
+
+pub const PROFILES: [Profile; 4] = [VSS_ONLY, LIGHTNING, GRAPH, FULL];
+
+impl Profile {
+    pub fn named(name: &str) -> Result<Profile, String> {
+        PROFILES
+            .iter()
+            .find(|p| p.name == name)
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+                format!(
+                    "Unknown profile {}, expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// The profile from `--profile <name>` or `HARNESS_PROFILE`, if any.
+    pub fn requested() -> Result<Option<Profile>, String> {
+        if let Some(name) = &crate::args().profile {
+            return Self::named(name).map(Some);
+        }
+        match std::env::var(PROFILE_ENV) {
+            Ok(name) if !name.is_empty() => Self::named(&name).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn includes(&self, service: &str) -> bool {
+        self.services.is_empty() || self.services.contains(&service)
+    }
+
+    /// Fail if any of `required` is not part of the profile.
+    pub fn check(&self, required: &[&str]) -> Result<(), String> {
+        let missing: Vec<&str> = required
+            .iter()
+            .copied()
+            .filter(|s| !self.includes(s))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Profile {} does not include {}, which this suite needs",
+            self.name,
+            missing.join(", ")
+        ))
+    }
+
+    /// `docker compose --profile ... up --detach <services>` for `env`'s project.
+    pub async fn up(&self, env: &DockerEnv) -> Result<(), String> {
+        crate::compose(env.project(), &self.up_args())
+            .await
+            .map(|_| ())
+    }
+
+    /// Arguments after `docker compose` that bring the profile up.
+    pub(crate) fn up_args(&self) -> Vec<&'static str> {
+        self.up_args_for(self.services)
+    }
+
+    /// Like `up_args`, but for `services` only.
+    fn up_args_for<'a>(&self, services: &[&'a str]) -> Vec<&'a str> {
+        let mut args = Vec::new();
+        for profile in self.compose_profiles {
+            args.extend(["--profile", *profile]);
+        }
+        args.extend(["up", "--detach"]);
+        args.extend(services);
+        args
+    }
+}
+
+/// Bring up the requested profile, if any, and make sure every service in
+/// `required` is running afterwards. Without a profile this does nothing and
+/// the stack is expected to be up already.
+///
+/// With `--isolated` the profile (by default the smallest one covering
+/// `required`) comes up as a private environment instead, which the rest of
+/// the run targets until the returned guard is dropped. With
+/// `--startup-race` the services are then restarted in a random order.
+pub async fn select(required: &[&str]) -> Result<Option<IsolatedEnv>, String> {
+    select_with(required, None).await
+}
+
+/// `select` for suites that need services a plain `docker compose up` leaves
+/// out, such as `lnd2`. Without a profile, the services of `required` that
+/// `default` covers and that are not running yet are started with its compose
+/// profiles enabled; everything else is still expected to be up already.
+pub async fn select_or(
+    required: &[&str],
+    default: &Profile,
+) -> Result<Option<IsolatedEnv>, String> {
+    select_with(required, Some(default)).await
+}
+
+async fn select_with(
+    required: &[&str],
+    default: Option<&Profile>,
+) -> Result<Option<IsolatedEnv>, String> {
+    let isolated = isolated_requested();
+    let profile = match Profile::requested()? {
+        Some(profile) => profile,
+        None if isolated => smallest_covering(required)?,
+        None => {
+            if let Some(default) = default {
+                start_missing(default, required).await?;
+            }
+            race_if_requested().await?;
+            return Ok(None);
+        }
+    };
+    profile.check(required)?;
+
+    let (env, environment) = if isolated {
+        let environment = IsolatedEnv::spawn(&profile).await?;
+        environment.activate();
+        (environment.env().clone(), Some(environment))
+    } else {
+        let env = DockerEnv::local()?;
+        tracing::info!(profile = profile.name, "Bringing up profile");
+        profile.up(&env).await?;
+        (env, None)
+    };
+    for service in required {
+        if !env.is_running(service).await? {
+            return Err(format!(
+                "{} is not running after bringing up profile {}",
+                service, profile.name
+            ));
+        }
+    }
+    race_if_requested().await?;
+    Ok(environment)
+}
+
+async fn start_missing(profile: &Profile, required: &[&str]) -> Result<(), String> {
+    let env = DockerEnv::local()?;
+    let mut missing = Vec::new();
+    for service in required.iter().copied() {
+        if profile.includes(service) && !env.is_running(service).await? {
+            missing.push(service);
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(profile = profile.name, services = ?missing, "Starting services");
+    crate::compose(env.project(), &profile.up_args_for(&missing))
+        .await
+        .map(|_| ())
+}
+
+fn smallest_covering(required: &[&str]) -> Result<Profile, String> {
+    PROFILES
+        .iter()
+        .filter(|p| !p.services.is_empty() && p.check(required).is_ok())
+        .min_by_key(|p| p.services.len())
+        .copied()
+        .ok_or_else(|| format!("No profile short of full covers {}", required.join(", ")))
+}
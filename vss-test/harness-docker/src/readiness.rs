@@ -0,0 +1,368 @@
+//! Dependency-aware readiness checks for the compose stack
+//!
+//! Each service declares how to tell it is up and which services it needs.
+//! `Readiness::wait` probes services in dependency order, all services whose
+//! dependencies are ready at once, each against its own timeout.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use serde_json::{json, Value};
+
+use crate::DockerEnv;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_SERVICE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How to tell a service is accepting work.
+#[derive(Debug, Clone)]
+pub enum Probe {
+    /// Any response below 500. TLS certificates are not verified.
+    Http { url: String },
+    /// A TCP connection is accepted.
+    Tcp { addr: String },
+    /// A JSON-RPC call with basic auth returns a result without an error.
+    JsonRpc {
+        url: String,
+        user: String,
+        password: String,
+        method: String,
+    },
+}
+
+impl Probe {
+    pub fn http(url: &str) -> Self {
+        Probe::Http {
+            url: url.to_string(),
+        }
+    }
+
+    pub fn tcp(addr: &str) -> Self {
+        Probe::Tcp {
+            addr: addr.to_string(),
+        }
+    }
+
+    pub fn json_rpc(url: &str, user: &str, password: &str, method: &str) -> Self {
+        Probe::JsonRpc {
+            url: url.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+            method: method.to_string(),
+        }
+    }
+
+    async fn check(&self, client: &reqwest::Client) -> Result<(), String> {
+        match self {
+            Probe::Http { url } => {
+                let resp = client
+                    .get(url)
+                    .send()
+                    .await
+                    .map_err(|e| format!("GET {} failed: {}", url, e))?;
+                if resp.status().is_server_error() {
+                    return Err(format!("GET {} returned {}", url, resp.status()));
+                }
+                Ok(())
+            }
+            Probe::Tcp { addr } => tokio::net::TcpStream::connect(addr)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Connect to {} failed: {}", addr, e)),
+            Probe::JsonRpc {
+                url,
+                user,
+                password,
+                method,
+            } => {
+                let resp: Value = client
+                    .post(url)
+                    .basic_auth(user, Some(password))
+                    .json(&json!({"jsonrpc": "1.0", "id": "readiness", "method": method, "params": []}))
+                    .send()
+                    .await
+                    .map_err(|e| format!("{} on {} failed: {}", method, url, e))?
+                    .json()
+                    .await
+                    .map_err(|e| format!("{} on {} returned no JSON: {}", method, url, e))?;
+                if !resp["error"].is_null() {
+                    return Err(format!("{} on {} returned {}", method, url, resp["error"]));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A service's probe, dependencies and time budget.
+#[derive(Debug, Clone)]
+pub struct ServiceProbe {
+    pub service: String,
+    pub probe: Probe,
+    pub depends_on: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl ServiceProbe {
+    pub fn new(service: &str, probe: Probe) -> Self {
+        Self {
+            service: service.to_string(),
+            probe,
+            depends_on: Vec::new(),
+            timeout: DEFAULT_SERVICE_TIMEOUT,
+        }
+    }
+
+    pub fn depends_on(mut self, services: &[&str]) -> Self {
+        self.depends_on = services.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn wait(&self, client: &reqwest::Client) -> Result<Ready, String> {
+        let start = Instant::now();
+        loop {
+            let last_error = match self.probe.check(client).await {
+                Ok(()) => {
+                    return Ok(Ready {
+                        service: self.service.clone(),
+                        elapsed: start.elapsed(),
+                    })
+                }
+                Err(e) => e,
+            };
+            if start.elapsed() >= self.timeout {
+                return Err(format!(
+                    "{} not ready after {:?}: {}",
+                    self.service, self.timeout, last_error
+                ));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// A service that passed its probe, and how long that took once its
+/// dependencies were ready.
+#[derive(Debug, Clone)]
+pub struct Ready {
+    pub service: String,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    services: Vec<ServiceProbe>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn service(mut self, probe: ServiceProbe) -> Self {
+        self.services.retain(|s| s.service != probe.service);
+        self.services.push(probe);
+        self
+    }
+
+    /// Probes for the services in docker-compose.yml at the host ports it
+    /// publishes them on.
+    pub fn stack() -> Self {
+        STACK.iter().fold(Self::new(), |readiness, entry| {
+            readiness.service(entry.probe(entry.default_host_port))
+        })
+    }
+
+    /// Probes for the services of `env`'s compose project at the host ports
+    /// Docker actually mapped. Services without a container are left out.
+    pub async fn stack_for(env: &DockerEnv) -> Result<Self, String> {
+        let mut readiness = Self::new();
+        for entry in &STACK {
+            match env.host_port(entry.service, entry.container_port).await {
+                Ok(port) => readiness = readiness.service(entry.probe(port)),
+                Err(e) if e.starts_with("No container") => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(readiness)
+    }
+
+    /// Whether a probe is declared for `service`.
+    pub fn declares(&self, service: &str) -> bool {
+        self.services.iter().any(|s| s.service == service)
+    }
+
+    /// Wait until `targets` and everything they depend on are ready; all
+    /// declared services when `targets` is empty. Fails on the first service
+    /// that exhausts its timeout, or on unknown or cyclic dependencies.
+    pub async fn wait(&self, targets: &[&str]) -> Result<Vec<Ready>, String> {
+        let wanted = self.closure(targets)?;
+        let client = probe_client()?;
+
+        let mut ready: Vec<Ready> = Vec::new();
+        let mut done: HashSet<&str> = HashSet::new();
+        while done.len() < wanted.len() {
+            let wave: Vec<&ServiceProbe> = wanted
+                .iter()
+                .filter(|s| !done.contains(s.service.as_str()))
+                .filter(|s| s.depends_on.iter().all(|d| done.contains(d.as_str())))
+                .copied()
+                .collect();
+            if wave.is_empty() {
+                let stuck: Vec<&str> = wanted
+                    .iter()
+                    .map(|s| s.service.as_str())
+                    .filter(|s| !done.contains(s))
+                    .collect();
+                return Err(format!("Dependency cycle among {}", stuck.join(", ")));
+            }
+            for result in join_all(wave.iter().map(|s| s.wait(&client))).await {
+                ready.push(result?);
+            }
+            done.extend(wave.iter().map(|s| s.service.as_str()));
+        }
+        Ok(ready)
+    }
+
+    /// Probe `service` once, without waiting for it or its dependencies.
+    pub async fn check(&self, service: &str) -> Result<(), String> {
+        let probe = self
+            .services
+            .iter()
+            .find(|s| s.service == service)
+            .ok_or_else(|| format!("No readiness probe declared for {}", service))?;
+        probe.probe.check(&probe_client()?).await
+    }
+
+    /// The targets plus their transitive dependencies.
+    fn closure(&self, targets: &[&str]) -> Result<Vec<&ServiceProbe>, String> {
+        let mut pending: Vec<String> = if targets.is_empty() {
+            self.services.iter().map(|s| s.service.clone()).collect()
+        } else {
+            targets.iter().map(|s| s.to_string()).collect()
+        };
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut probes = Vec::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let probe = self
+                .services
+                .iter()
+                .find(|s| s.service == name)
+                .ok_or_else(|| format!("No readiness probe declared for {}", name))?;
+            pending.extend(probe.depends_on.iter().cloned());
+            probes.push(probe);
+        }
+        Ok(probes)
+    }
+}
+
+fn probe_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {:?}", e))
+}
+
+enum StackProbe {
+    /// bitcoind's JSON-RPC with the regtest credentials.
+    BitcoinRpc,
+    Tcp,
+    Http(&'static str),
+    Https(&'static str),
+}
+
+struct StackService {
+    service: &'static str,
+    container_port: u16,
+    /// Host port docker-compose.yml publishes `container_port` on.
+    default_host_port: u16,
+    probe: StackProbe,
+    depends_on: &'static [&'static str],
+}
+
+impl StackService {
+    fn probe(&self, port: u16) -> ServiceProbe {
+        let probe = match self.probe {
+            StackProbe::BitcoinRpc => Probe::json_rpc(
+                &format!("http://localhost:{}", port),
+                "polaruser",
+                "polarpass",
+                "getblockchaininfo",
+            ),
+            StackProbe::Tcp => Probe::tcp(&format!("localhost:{}", port)),
+            StackProbe::Http(path) => Probe::http(&format!("http://localhost:{}{}", port, path)),
+            StackProbe::Https(path) => Probe::http(&format!("https://localhost:{}{}", port, path)),
+        };
+        ServiceProbe::new(self.service, probe).depends_on(self.depends_on)
+    }
+}
+
+const STACK: [StackService; 8] = [
+    StackService {
+        service: "bitcoind",
+        container_port: 43782,
+        default_host_port: 43782,
+        probe: StackProbe::BitcoinRpc,
+        depends_on: &[],
+    },
+    StackService {
+        service: "electrs",
+        container_port: 60001,
+        default_host_port: 60001,
+        probe: StackProbe::Tcp,
+        depends_on: &["bitcoind"],
+    },
+    StackService {
+        service: "lnd",
+        container_port: 8080,
+        default_host_port: 8080,
+        probe: StackProbe::Https("/v1/state"),
+        depends_on: &["bitcoind"],
+    },
+    StackService {
+        service: "lnd2",
+        container_port: 8080,
+        default_host_port: 8081,
+        probe: StackProbe::Https("/v1/state"),
+        depends_on: &["bitcoind"],
+    },
+    StackService {
+        service: "lnurl-server",
+        container_port: 3000,
+        default_host_port: 3000,
+        probe: StackProbe::Http(""),
+        depends_on: &["lnd"],
+    },
+    StackService {
+        service: "postgres",
+        container_port: 5432,
+        default_host_port: 5432,
+        probe: StackProbe::Tcp,
+        depends_on: &[],
+    },
+    StackService {
+        service: "lnurl-auth-server",
+        container_port: 5005,
+        default_host_port: 5005,
+        probe: StackProbe::Http("/health"),
+        depends_on: &[],
+    },
+    StackService {
+        service: "vss-server",
+        container_port: 5050,
+        default_host_port: 5050,
+        probe: StackProbe::Http(""),
+        depends_on: &["postgres", "lnurl-auth-server"],
+    },
+];
@@ -0,0 +1,392 @@
+//! Snapshots of service data for fast resets
+//!
+//! Seeding a channel graph or a large key set is slow. Once a run has paid for
+//! it, the data of the services involved is archived, and later runs or test
+//! groups restore the archive instead of seeding again. A snapshot can also be
+//! exported as a single file and imported on another machine.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde_json::{json, Value};
+
+use crate::{DockerEnv, COMPOSE_DIR};
+
+// Image whose tar unpacks the archives; overridable via SNAPSHOT_HELPER_IMAGE
+pub const DEFAULT_HELPER_IMAGE: &str = "alpine:3.20";
+// Host directory holding snapshots, relative to vss-test; overridable via SNAPSHOT_DIR
+pub const DEFAULT_SNAPSHOT_DIR: &str = "../snapshots";
+
+/// Where a service keeps its data.
+#[derive(Debug, Clone, Copy)]
+pub enum DataMount {
+    /// A named volume from docker-compose.yml.
+    Volume(&'static str),
+    /// A bind-mounted directory, relative to the compose directory.
+    Bind(&'static str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceData {
+    pub service: &'static str,
+    pub mount: DataMount,
+}
+
+pub const POSTGRES_DATA: ServiceData = ServiceData {
+    service: "postgres",
+    mount: DataMount::Volume("postgres_data"),
+};
+
+pub const BITCOIND_DATA: ServiceData = ServiceData {
+    service: "bitcoind",
+    mount: DataMount::Volume("bitcoin_home"),
+};
+
+/// Chain plus the graph-capable LND nodes; restore them together so the
+/// nodes' view of the chain matches bitcoind's.
+pub const GRAPH_DATA: [ServiceData; 5] = [
+    BITCOIND_DATA,
+    ServiceData {
+        service: "lnd",
+        mount: DataMount::Bind("lnd"),
+    },
+    ServiceData {
+        service: "lnd2",
+        mount: DataMount::Bind("lnd2"),
+    },
+    ServiceData {
+        service: "lnd4",
+        mount: DataMount::Bind("lnd4"),
+    },
+    ServiceData {
+        service: "lnd5",
+        mount: DataMount::Bind("lnd5"),
+    },
+];
+
+/// What a freshly seeded default stack is made of: the chain, the funded
+/// wallets and channels of the two default LND nodes, lnurl-server's data and
+/// the VSS database with its auth server. Order as in `GRAPH_DATA`, chain first.
+pub const GOLDEN_DATA: [ServiceData; 6] = [
+    BITCOIND_DATA,
+    ServiceData {
+        service: "lnd",
+        mount: DataMount::Bind("lnd"),
+    },
+    ServiceData {
+        service: "lnd2",
+        mount: DataMount::Bind("lnd2"),
+    },
+    ServiceData {
+        service: "lnurl-server",
+        mount: DataMount::Bind("lnurl-server/data"),
+    },
+    POSTGRES_DATA,
+    ServiceData {
+        service: "lnurl-auth-server",
+        mount: DataMount::Volume("lnurl_auth_data"),
+    },
+];
+
+// Describes an exported snapshot, next to the per-service archives
+const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_VERSION: u64 = 1;
+
+pub struct Snapshots {
+    env: DockerEnv,
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub fn new(env: &DockerEnv, dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {:?}", dir.display(), e))?;
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {:?}", dir.display(), e))?;
+        Ok(Self {
+            env: env.clone(),
+            dir,
+        })
+    }
+
+    /// Snapshots under `SNAPSHOT_DIR`, or `../snapshots` by default.
+    pub fn local(env: &DockerEnv) -> Result<Self, String> {
+        let dir =
+            std::env::var("SNAPSHOT_DIR").unwrap_or_else(|_| DEFAULT_SNAPSHOT_DIR.to_string());
+        Self::new(env, Path::new(&dir))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.dir.join(name).is_dir()
+    }
+
+    /// Archive the data of `data`'s services as snapshot `name`, replacing any
+    /// previous one. The services are stopped meanwhile so the copy is consistent.
+    pub async fn take(&self, name: &str, data: &[ServiceData]) -> Result<(), String> {
+        let partial = self.dir.join(format!("{}.partial", name));
+        remove_dir(&partial)?;
+        std::fs::create_dir_all(&partial)
+            .map_err(|e| format!("Failed to create {}: {:?}", partial.display(), e))?;
+
+        let archived = self
+            .while_stopped(data, || async {
+                for entry in data {
+                    let script = format!("tar -C /data -cf /snapshot/{}.tar .", entry.service);
+                    let binds = vec![
+                        format!("{}:/data:ro", self.source(entry)?),
+                        format!("{}:/snapshot", partial.display()),
+                    ];
+                    self.env
+                        .run_helper(&helper_image(), &script, &binds)
+                        .await
+                        .map_err(|e| format!("Failed to archive {}: {}", entry.service, e))?;
+                }
+                Ok(())
+            })
+            .await;
+        if let Err(e) = archived {
+            let _ = remove_dir(&partial);
+            return Err(e);
+        }
+
+        let target = self.dir.join(name);
+        remove_dir(&target)?;
+        std::fs::rename(&partial, &target)
+            .map_err(|e| format!("Failed to finish snapshot {}: {:?}", name, e))
+    }
+
+    /// Replace the data of `data`'s services with snapshot `name`.
+    pub async fn restore(&self, name: &str, data: &[ServiceData]) -> Result<(), String> {
+        let snapshot = self.dir.join(name);
+        self.check_complete(name, data)?;
+
+        self.while_stopped(data, || async {
+            for entry in data {
+                let script = format!(
+                    "find /data -mindepth 1 -delete && tar -C /data -xpf /snapshot/{}.tar",
+                    entry.service
+                );
+                let binds = vec![
+                    format!("{}:/data", self.source(entry)?),
+                    format!("{}:/snapshot:ro", snapshot.display()),
+                ];
+                self.env
+                    .run_helper(&helper_image(), &script, &binds)
+                    .await
+                    .map_err(|e| format!("Failed to restore {}: {}", entry.service, e))?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Restore snapshot `name` if it exists; otherwise run `seed` and take it.
+    /// Returns whether the snapshot was restored.
+    pub async fn restore_or_take<F, Fut>(
+        &self,
+        name: &str,
+        data: &[ServiceData],
+        seed: F,
+    ) -> Result<bool, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        if self.exists(name) {
+            self.restore(name, data).await?;
+            return Ok(true);
+        }
+        seed().await?;
+        self.take(name, data).await?;
+        Ok(false)
+    }
+
+    /// Pack snapshot `name` with a manifest into the gzipped tarball `archive`,
+    /// which `import` unpacks on any machine running the same compose file.
+    pub async fn export(
+        &self,
+        name: &str,
+        data: &[ServiceData],
+        archive: &Path,
+    ) -> Result<(), String> {
+        let snapshot = self.dir.join(name);
+        self.check_complete(name, data)?;
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let manifest = json!({
+            "version": MANIFEST_VERSION,
+            "name": name,
+            "services": data.iter().map(|entry| entry.service).collect::<Vec<_>>(),
+            "created": created,
+        });
+        // An imported manifest belongs to root; the directory does not
+        let manifest_path = snapshot.join(MANIFEST_FILE);
+        let _ = std::fs::remove_file(&manifest_path);
+        std::fs::write(&manifest_path, manifest.to_string())
+            .map_err(|e| format!("Failed to write {}: {:?}", manifest_path.display(), e))?;
+
+        let (out_dir, file) = split_archive_path(archive)?;
+        std::fs::create_dir_all(&out_dir)
+            .map_err(|e| format!("Failed to create {}: {:?}", out_dir.display(), e))?;
+        let out_dir = out_dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {:?}", out_dir.display(), e))?;
+        // Members only, no `.` entry, so unpacking leaves the directory ours
+        let script = format!("cd /snapshot && tar -czf '/out/{}' *", file);
+        let binds = vec![
+            format!("{}:/snapshot:ro", snapshot.display()),
+            format!("{}:/out", out_dir.display()),
+        ];
+        self.env
+            .run_helper(&helper_image(), &script, &binds)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to export snapshot {}: {}", name, e))
+    }
+
+    /// Unpack an archive written by `export` as a local snapshot, replacing
+    /// one of the same name, and return that name. Fails unless the archive
+    /// covers every service of `data`.
+    pub async fn import(&self, archive: &Path, data: &[ServiceData]) -> Result<String, String> {
+        let (in_dir, file) = split_archive_path(archive)?;
+        let in_dir = in_dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {:?}", archive.display(), e))?;
+        if !in_dir.join(&file).is_file() {
+            return Err(format!("No archive at {}", archive.display()));
+        }
+
+        let partial = self.dir.join("import.partial");
+        remove_dir(&partial)?;
+        std::fs::create_dir_all(&partial)
+            .map_err(|e| format!("Failed to create {}: {:?}", partial.display(), e))?;
+        let script = format!("tar -C /snapshot -xzf '/in/{}'", file);
+        let binds = vec![
+            format!("{}:/in:ro", in_dir.display()),
+            format!("{}:/snapshot", partial.display()),
+        ];
+        let unpacked = async {
+            self.env
+                .run_helper(&helper_image(), &script, &binds)
+                .await
+                .map_err(|e| format!("Failed to unpack {}: {}", archive.display(), e))?;
+            read_manifest(&partial)
+        }
+        .await;
+        let name = match unpacked {
+            Ok(name) => name,
+            Err(e) => {
+                let _ = remove_dir(&partial);
+                return Err(e);
+            }
+        };
+
+        let target = self.dir.join(&name);
+        remove_dir(&target)?;
+        std::fs::rename(&partial, &target)
+            .map_err(|e| format!("Failed to finish snapshot {}: {:?}", name, e))?;
+        self.check_complete(&name, data)?;
+        Ok(name)
+    }
+
+    /// Fail unless snapshot `name` has an archive for every service of `data`.
+    fn check_complete(&self, name: &str, data: &[ServiceData]) -> Result<(), String> {
+        let snapshot = self.dir.join(name);
+        if let Some(missing) = data
+            .iter()
+            .find(|entry| !snapshot.join(format!("{}.tar", entry.service)).is_file())
+        {
+            return Err(format!(
+                "Snapshot {} has no archive for {}",
+                name, missing.service
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run `task` with the services stopped, starting them again (in order)
+    /// whether or not it succeeded.
+    async fn while_stopped<F, Fut>(&self, data: &[ServiceData], task: F) -> Result<(), String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        // Dependents first, so nodes do not lose bitcoind while still running
+        for entry in data.iter().rev() {
+            self.env
+                .stop(entry.service, crate::DEFAULT_STOP_TIMEOUT_SECS)
+                .await?;
+        }
+        let result = task().await;
+        for entry in data {
+            self.env.start(entry.service).await?;
+        }
+        result
+    }
+
+    /// Mount source of a service's data, as the Docker daemon sees it.
+    fn source(&self, entry: &ServiceData) -> Result<String, String> {
+        match entry.mount {
+            DataMount::Volume(volume) => Ok(self.env.volume_name(volume)),
+            DataMount::Bind(dir) => {
+                let path = Path::new(COMPOSE_DIR).join(dir);
+                path.canonicalize()
+                    .map(|p| p.display().to_string())
+                    .map_err(|e| format!("Failed to resolve {}: {:?}", path.display(), e))
+            }
+        }
+    }
+}
+
+fn helper_image() -> String {
+    std::env::var("SNAPSHOT_HELPER_IMAGE").unwrap_or_else(|_| DEFAULT_HELPER_IMAGE.to_string())
+}
+
+/// The directory holding `archive` and its file name, which has to be safe to
+/// quote in the helper's shell script.
+fn split_archive_path(archive: &Path) -> Result<(PathBuf, String), String> {
+    let file = archive
+        .file_name()
+        .and_then(|f| f.to_str())
+        .filter(|f| !f.contains('\''))
+        .ok_or_else(|| format!("Unusable archive path {}", archive.display()))?;
+    let dir = match archive.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Ok((dir, file.to_string()))
+}
+
+/// The snapshot name recorded in an unpacked export.
+fn read_manifest(dir: &Path) -> Result<String, String> {
+    let path = dir.join(MANIFEST_FILE);
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Archive has no readable {}: {:?}", MANIFEST_FILE, e))?;
+    let manifest: Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid {}: {}", MANIFEST_FILE, e))?;
+    let version = manifest["version"].as_u64().unwrap_or_default();
+    if version != MANIFEST_VERSION {
+        return Err(format!(
+            "Archive has manifest version {}, expected {}",
+            version, MANIFEST_VERSION
+        ));
+    }
+    manifest["name"]
+        .as_str()
+        .filter(|name| !name.is_empty() && !name.contains(['/', '.']))
+        .map(str::to_string)
+        .ok_or_else(|| format!("{} names no usable snapshot", MANIFEST_FILE))
+}
+
+fn remove_dir(dir: &Path) -> Result<(), String> {
+    match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {:?}", dir.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
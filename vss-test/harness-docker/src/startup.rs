@@ -0,0 +1,176 @@
+//! Starting the stack's services in a random order
+//!
+//! Compose starts a service only after its `depends_on`, so an image that
+//! gives up when a dependency comes up late never shows it. With
+//! `--startup-race` (or `HARNESS_STARTUP_RACE=1`) a test binary first stops
+//! every running service and starts them again one by one in a shuffled
+//! order with `docker start`, which ignores `depends_on`. Each of them then
+//! has to retry its way to ready and healthy before the suite runs.
+//! The order follows the run's seed (`--seed`, see `test_harness::rng`), and
+//! `STARTUP_SEED` overrides it for the shuffle alone.
+
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use test_harness::rng;
+
+use crate::readiness::Readiness;
+use crate::DockerEnv;
+
+const STARTUP_RACE_ENV: &str = "HARNESS_STARTUP_RACE";
+// Pause between two starts, overridable via STARTUP_GAP_SECS; long enough
+// for the earlier service to run into its missing dependency
+pub const DEFAULT_STARTUP_GAP_SECS: u64 = 3;
+// Grace period for `docker stop` before the container is killed
+const STOP_TIMEOUT_SECS: i64 = 10;
+// How long a service may take to turn healthy once everything is started
+const HEALTHY_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether the run was asked to start services in a random order first.
+pub fn startup_race_requested() -> bool {
+    crate::args().startup_race
+        || matches!(
+            std::env::var(STARTUP_RACE_ENV).as_deref(),
+            Ok("1") | Ok("true")
+        )
+}
+
+/// How a shuffled start went.
+#[derive(Debug, Clone)]
+pub struct Race {
+    pub seed: u64,
+    pub order: Vec<String>,
+    /// Services the restart policy had to bring back, with how often.
+    pub restarts: Vec<(String, i64)>,
+    /// From the first start until every service was ready and healthy.
+    pub elapsed: Duration,
+}
+
+impl Race {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Started in order {} (seed {}); all ready after {:?}\n",
+            self.order.join(", "),
+            self.seed,
+            self.elapsed
+        );
+        for (service, restarts) in &self.restarts {
+            summary.push_str(&format!(
+                "  {} was restarted {} time(s) on the way\n",
+                service, restarts
+            ));
+        }
+        summary
+    }
+}
+
+/// Run the race on the local project if `--startup-race` was given.
+pub async fn race_if_requested() -> Result<(), String> {
+    if !startup_race_requested() {
+        return Ok(());
+    }
+    let seed = match std::env::var("STARTUP_SEED") {
+        Ok(seed) => seed
+            .parse()
+            .map_err(|e| format!("Invalid STARTUP_SEED {:?}: {}", seed, e))?,
+        Err(_) => rng::seed(),
+    };
+    let gap = std::env::var("STARTUP_GAP_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_STARTUP_GAP_SECS);
+    let env = DockerEnv::local()?;
+    tracing::info!(seed, "Restarting services in a random order");
+    let race = race(&env, seed, Duration::from_secs(gap))
+        .await
+        .map_err(|e| format!("Startup race with seed {} failed: {}", seed, e))?;
+    print!("{}", race.summary());
+    println!();
+    Ok(())
+}
+
+/// Stop every running service of `env`, start them again `gap` apart in an
+/// order shuffled by `seed`, and wait until all of them are ready and
+/// healthy. One-shot services that already exited are left alone.
+pub async fn race(env: &DockerEnv, seed: u64, gap: Duration) -> Result<Race, String> {
+    let mut order = Vec::new();
+    for service in env.services().await? {
+        if env.is_running(&service).await? {
+            order.push(service);
+        }
+    }
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+    let mut restarts_before = Vec::new();
+    for service in &order {
+        restarts_before.push(env.inspect(service).await?.restart_count);
+    }
+
+    let stopped = join_all(order.iter().map(|s| env.stop(s, STOP_TIMEOUT_SECS))).await;
+    stopped.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let start = Instant::now();
+    for (i, service) in order.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(gap).await;
+        }
+        env.start(service).await?;
+    }
+
+    // Healthchecks first: a service that exited for good fails fast here
+    for service in &order {
+        wait_healthy(env, service).await?;
+    }
+    let readiness = Readiness::stack_for(env).await?;
+    let probed: Vec<&str> = order
+        .iter()
+        .map(String::as_str)
+        .filter(|s| readiness.declares(s))
+        .collect();
+    if !probed.is_empty() {
+        readiness.wait(&probed).await?;
+    }
+    let elapsed = start.elapsed();
+
+    let mut restarts = Vec::new();
+    for (service, before) in order.iter().zip(restarts_before) {
+        let after = env.inspect(service).await?.restart_count;
+        if after > before {
+            restarts.push((service.clone(), after - before));
+        }
+    }
+    Ok(Race {
+        seed,
+        order,
+        restarts,
+        elapsed,
+    })
+}
+
+/// Wait until `service` runs and, if it has a healthcheck, passes it. A
+/// container that exited for good (no restart pending) fails right away.
+async fn wait_healthy(env: &DockerEnv, service: &str) -> Result<(), String> {
+    let deadline = Instant::now() + HEALTHY_TIMEOUT;
+    loop {
+        let state = env.inspect(service).await?;
+        if state.running && state.health.as_deref().is_none_or(|h| h == "healthy") {
+            return Ok(());
+        }
+        if state.status == "exited" {
+            return Err(format!(
+                "{} exited with {:?} instead of retrying",
+                service, state.exit_code
+            ));
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "{} still {} (health {:?}) after {:?}",
+                service, state.status, state.health, HEALTHY_TIMEOUT
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
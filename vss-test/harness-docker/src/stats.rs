@@ -0,0 +1,294 @@
+//! CPU, memory and I/O of the stack's containers while tests run
+//!
+//! A `ResourceMonitor` samples every container of the project through the
+//! Docker stats API in the background. Tests are bracketed with `begin` and
+//! `end`, and the report lists each test's peaks per service, so a VSS or
+//! lnurl image that suddenly needs twice the memory shows up in the same run
+//! that tests it. Sampling is off unless `--stats` (or `HARNESS_STATS=1`) is
+//! given.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bollard::container::{MemoryStatsStats, Stats, StatsOptions};
+use futures_util::future::join_all;
+use futures_util::StreamExt;
+use tokio::task::JoinHandle;
+
+use crate::DockerEnv;
+
+const STATS_ENV: &str = "HARNESS_STATS";
+// Pause between sampling rounds, overridable via STATS_INTERVAL_MS
+pub const DEFAULT_STATS_INTERVAL_MS: u64 = 1_000;
+
+/// Whether the run was asked to monitor resource usage.
+pub fn stats_requested() -> bool {
+    crate::args().stats
+        || matches!(std::env::var(STATS_ENV).as_deref(), Ok("1") | Ok("true"))
+}
+
+/// One reading of one container. I/O counters are cumulative since it started.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub service: String,
+    pub at: Instant,
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    pub block_read_bytes: u64,
+    pub block_write_bytes: u64,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+}
+
+/// Highest CPU and memory of a service over a window, and the I/O it did in it.
+#[derive(Debug, Clone, Default)]
+pub struct Peak {
+    pub service: String,
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    pub block_read_bytes: u64,
+    pub block_write_bytes: u64,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+}
+
+/// Background sampler of the project's containers; does nothing when disabled.
+pub struct ResourceMonitor {
+    samples: Arc<Mutex<Vec<Sample>>>,
+    task: Option<JoinHandle<()>>,
+    current: Option<(String, Instant)>,
+    sections: Vec<(String, Vec<Peak>)>,
+}
+
+impl ResourceMonitor {
+    /// Start sampling every `interval`.
+    pub fn start(env: &DockerEnv, interval: Duration) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let env = env.clone();
+        let sink = samples.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let round = sample_round(&env).await;
+                sink.lock().unwrap().extend(round);
+                tokio::time::sleep(interval).await;
+            }
+        });
+        Self {
+            samples,
+            task: Some(task),
+            current: None,
+            sections: Vec::new(),
+        }
+    }
+
+    /// A monitor that records nothing.
+    pub fn disabled() -> Self {
+        Self {
+            samples: Arc::new(Mutex::new(Vec::new())),
+            task: None,
+            current: None,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Monitor the local compose project if `--stats` was given.
+    pub fn from_args() -> Result<Self, String> {
+        if !stats_requested() {
+            return Ok(Self::disabled());
+        }
+        let interval = std::env::var("STATS_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_STATS_INTERVAL_MS);
+        Ok(Self::start(
+            &DockerEnv::local()?,
+            Duration::from_millis(interval),
+        ))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Start attributing samples to `test`, ending the previous test if any.
+    pub fn begin(&mut self, test: &str) {
+        if !self.enabled() {
+            return;
+        }
+        self.end();
+        self.current = Some((test.to_string(), Instant::now()));
+    }
+
+    /// Close the current test and return its peaks.
+    pub fn end(&mut self) -> Vec<Peak> {
+        let Some((test, from)) = self.current.take() else {
+            return Vec::new();
+        };
+        let peaks = self.peaks_between(from, Instant::now());
+        self.sections.push((test, peaks.clone()));
+        peaks
+    }
+
+    /// Peaks per service over samples taken between `from` and `to`.
+    pub fn peaks_between(&self, from: Instant, to: Instant) -> Vec<Peak> {
+        let samples = self.samples.lock().unwrap();
+        let mut peaks: Vec<(Peak, &Sample)> = Vec::new();
+        for sample in samples.iter().filter(|s| s.at >= from && s.at <= to) {
+            let index = match peaks.iter().position(|(p, _)| p.service == sample.service) {
+                Some(index) => index,
+                None => {
+                    let peak = Peak {
+                        service: sample.service.clone(),
+                        ..Default::default()
+                    };
+                    peaks.push((peak, sample));
+                    peaks.len() - 1
+                }
+            };
+            let (peak, first) = &mut peaks[index];
+            peak.cpu_percent = peak.cpu_percent.max(sample.cpu_percent);
+            peak.memory_bytes = peak.memory_bytes.max(sample.memory_bytes);
+            // Counters restart with the container; a kill makes the delta unknowable
+            peak.block_read_bytes = sample
+                .block_read_bytes
+                .saturating_sub(first.block_read_bytes);
+            peak.block_write_bytes = sample
+                .block_write_bytes
+                .saturating_sub(first.block_write_bytes);
+            peak.net_rx_bytes = sample.net_rx_bytes.saturating_sub(first.net_rx_bytes);
+            peak.net_tx_bytes = sample.net_tx_bytes.saturating_sub(first.net_tx_bytes);
+        }
+        let mut peaks: Vec<Peak> = peaks.into_iter().map(|(p, _)| p).collect();
+        peaks.sort_by(|a, b| a.service.cmp(&b.service));
+        peaks
+    }
+
+    /// Per-test peaks of the run so far, or `None` if monitoring is off.
+    pub fn report(&mut self) -> Option<String> {
+        if !self.enabled() {
+            return None;
+        }
+        self.end();
+        let mut report =
+            String::from("Resource peaks (cpu, memory, disk read/write, net rx/tx):\n");
+        for (test, peaks) in &self.sections {
+            report.push_str(&format!("  {}\n", test));
+            if peaks.is_empty() {
+                report.push_str("    no samples\n");
+            }
+            for peak in peaks {
+                report.push_str(&format!(
+                    "    {:<20} {:>6.1}% {:>10}  {:>10} / {:<10} {:>10} / {}\n",
+                    peak.service,
+                    peak.cpu_percent,
+                    format_bytes(peak.memory_bytes),
+                    format_bytes(peak.block_read_bytes),
+                    format_bytes(peak.block_write_bytes),
+                    format_bytes(peak.net_rx_bytes),
+                    format_bytes(peak.net_tx_bytes),
+                ));
+            }
+        }
+        Some(report)
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+async fn sample_round(env: &DockerEnv) -> Vec<Sample> {
+    // Services come and go (chaos, recreated nodes), so list them every round
+    let Ok(services) = env.services().await else {
+        return Vec::new();
+    };
+    let readings = join_all(services.iter().map(|service| sample(env, service))).await;
+    readings.into_iter().flatten().collect()
+}
+
+/// One reading of `service`, or `None` if it has no container or no stats.
+pub(crate) async fn sample(env: &DockerEnv, service: &str) -> Option<Sample> {
+    let id = env.container_id(service).await.ok()?;
+    let options = StatsOptions {
+        stream: false,
+        one_shot: false,
+    };
+    let stats = env.docker().stats(&id, Some(options)).next().await?.ok()?;
+    Some(to_sample(service, &stats))
+}
+
+fn to_sample(service: &str, stats: &Stats) -> Sample {
+    let cpu_delta = stats
+        .cpu_stats
+        .cpu_usage
+        .total_usage
+        .saturating_sub(stats.precpu_stats.cpu_usage.total_usage);
+    let system_delta = stats
+        .cpu_stats
+        .system_cpu_usage
+        .unwrap_or_default()
+        .saturating_sub(stats.precpu_stats.system_cpu_usage.unwrap_or_default());
+    let cpus = stats.cpu_stats.online_cpus.unwrap_or(1);
+    let cpu_percent = if system_delta == 0 {
+        0.0
+    } else {
+        cpu_delta as f64 / system_delta as f64 * cpus as f64 * 100.0
+    };
+
+    // Like `docker stats`, leave out page cache the kernel can drop
+    let inactive_file = match &stats.memory_stats.stats {
+        Some(MemoryStatsStats::V1(v1)) => v1.total_inactive_file,
+        Some(MemoryStatsStats::V2(v2)) => v2.inactive_file,
+        None => 0,
+    };
+    let memory_bytes = stats
+        .memory_stats
+        .usage
+        .unwrap_or_default()
+        .saturating_sub(inactive_file);
+
+    let io = stats
+        .blkio_stats
+        .io_service_bytes_recursive
+        .as_deref()
+        .unwrap_or_default();
+    let block = |op: &str| {
+        io.iter()
+            .filter(|e| e.op.eq_ignore_ascii_case(op))
+            .map(|e| e.value)
+            .sum::<u64>()
+    };
+    let networks = stats.networks.iter().flat_map(|n| n.values());
+    let (net_rx_bytes, net_tx_bytes) =
+        networks.fold((0, 0), |(rx, tx), n| (rx + n.rx_bytes, tx + n.tx_bytes));
+
+    Sample {
+        service: service.to_string(),
+        at: Instant::now(),
+        cpu_percent,
+        memory_bytes,
+        block_read_bytes: block("read"),
+        block_write_bytes: block("write"),
+        net_rx_bytes,
+        net_tx_bytes,
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
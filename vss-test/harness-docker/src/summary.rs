@@ -0,0 +1,176 @@
+//! How a run ends: `summary.json` and an exit code CI can branch on
+//!
+//! Each binary starts a `Run` first thing and ends through it, either with
+//! `finish` once its tests have run or with `abort` when it cannot get that
+//! far. Both write `summary.json` (or the file `SUMMARY_PATH` names) and
+//! return the code to exit with:
+//!
+//!   0   every test passed
+//!   1   `TESTS_FAILED`: tests ran and at least one failed
+//!   2   `HARNESS_ERROR`: bad flags or configuration, or a harness bug; clap
+//!       uses 2 for usage errors as well
+//!   3   `ENVIRONMENT_UNAVAILABLE`: the stack could not be brought up or
+//!       reached, so the run says nothing about the tests
+//!   130 interrupted (see `teardown`)
+//!
+//! The summary holds the counts, the duration, the failed tests by name (`null`
+//! when the run was aborted before its tests ran), the error that ended the
+//! run early, the seed, the shard, and the environment: the image and
+//! image id of every service's container, so results can be matched to the
+//! builds they ran against. Take the summary before an isolated environment is
+//! removed, or its containers are gone from it. Numbers a run measured, like
+//! the capacity `vss_load` found, go under `metrics` for tracking across
+//! builds.
+//!
+//! A run given a webhook with `notify` also posts the summary there when it
+//! ends with a non-zero code (see `notify`).
+
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Map, Value};
+use test_harness::report::binary_name;
+use test_harness::{rng, shard};
+
+use crate::notify::Notifier;
+use crate::DockerEnv;
+
+pub const TESTS_FAILED: i32 = 1;
+pub const HARNESS_ERROR: i32 = 2;
+pub const ENVIRONMENT_UNAVAILABLE: i32 = 3;
+
+pub const SUMMARY_ENV: &str = "SUMMARY_PATH";
+const DEFAULT_SUMMARY_PATH: &str = "summary.json";
+
+pub struct Run {
+    started: Instant,
+    notifier: Option<Notifier>,
+    metrics: Map<String, Value>,
+}
+
+impl Run {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            notifier: None,
+            metrics: Map::new(),
+        }
+    }
+
+    /// Post the summary to `notifier`'s webhook if the run fails.
+    pub fn notify(&mut self, notifier: Option<Notifier>) {
+        self.notifier = notifier;
+    }
+
+    /// Keep `value` under `metrics` in the summary as `name`.
+    pub fn metric(&mut self, name: &str, value: f64) {
+        self.metrics.insert(name.to_string(), json!(value));
+    }
+
+    /// Write the summary of a run whose tests ran; returns 0 or `TESTS_FAILED`.
+    pub async fn finish(
+        &self,
+        passed: usize,
+        failed: usize,
+        failed_tests: Option<Vec<String>>,
+    ) -> i32 {
+        let code = if failed > 0 { TESTS_FAILED } else { 0 };
+        let summary = json!({
+            "passed": passed,
+            "failed": failed,
+            "failed_tests": failed_tests,
+            "error": null,
+        });
+        self.write(code, summary).await;
+        code
+    }
+
+    /// Write the summary of a run `error` ended before its tests could;
+    /// returns `code`. Printing the error is left to the caller.
+    pub async fn abort(&self, code: i32, error: &str) -> i32 {
+        let summary = json!({
+            "passed": 0,
+            "failed": 0,
+            "failed_tests": null,
+            "error": error,
+        });
+        self.write(code, summary).await;
+        code
+    }
+
+    async fn write(&self, code: i32, mut summary: Value) {
+        summary["binary"] = json!(binary_name());
+        summary["exit_code"] = json!(code);
+        summary["outcome"] = json!(outcome(code));
+        summary["duration_ms"] = json!(millis(self.started.elapsed()));
+        summary["seed"] = json!(rng::used_seed());
+        summary["shard"] = json!(shard::current().map(|shard| shard.to_string()));
+        if !self.metrics.is_empty() {
+            summary["metrics"] = Value::Object(self.metrics.clone());
+        }
+        summary["environment"] = match environment().await {
+            Ok(environment) => environment,
+            Err(e) => {
+                tracing::warn!("Summary has no environment: {}", e);
+                Value::Null
+            }
+        };
+        let path = path();
+        let text = format!("{:#}\n", summary);
+        match std::fs::write(&path, text) {
+            Ok(()) => tracing::info!("Summary written to {}", path),
+            Err(e) => tracing::error!("Failed to write {}: {:?}", path, e),
+        }
+        if let Some(notifier) = self.notifier.as_ref().filter(|_| code != 0) {
+            match notifier.post(&summary).await {
+                Ok(()) => tracing::info!("Failure posted to the webhook"),
+                Err(e) => tracing::warn!("Failed to notify: {}", e),
+            }
+        }
+    }
+}
+
+/// Where the summary goes: SUMMARY_PATH, else `summary.json`.
+pub fn path() -> String {
+    std::env::var(SUMMARY_ENV)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| DEFAULT_SUMMARY_PATH.to_string())
+}
+
+/// `path` with `-<n>` before its extension, e.g. `summary-2.json`.
+pub fn numbered_path(n: usize) -> String {
+    let path = path();
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => {
+            format!("{}-{}.{}", stem, n, extension)
+        }
+        _ => format!("{}-{}", path, n),
+    }
+}
+
+/// What `code` means, as named in the summary.
+fn outcome(code: i32) -> &'static str {
+    match code {
+        0 => "passed",
+        TESTS_FAILED => "tests_failed",
+        HARNESS_ERROR => "harness_error",
+        ENVIRONMENT_UNAVAILABLE => "environment_unavailable",
+        _ => "other",
+    }
+}
+
+/// The images the project's services run, by service.
+async fn environment() -> Result<Value, String> {
+    let images = DockerEnv::local()?.images().await?;
+    Ok(images
+        .into_iter()
+        .map(|(service, image)| {
+            let fingerprint = json!({ "image": image.image, "image_id": image.image_id });
+            (service, fingerprint)
+        })
+        .collect())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
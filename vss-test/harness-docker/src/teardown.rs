@@ -0,0 +1,226 @@
+//! Cleanup of everything a test run brought up
+//!
+//! A `Teardown` collects what the run started or created and undoes it in
+//! reverse order, whether the run finishes, panics or is interrupted with
+//! Ctrl-C. Pass `--keep-alive` (or set `HARNESS_KEEP_ALIVE=1`) to leave the
+//! environment running for debugging.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use bollard::volume::RemoveVolumeOptions;
+
+use crate::DockerEnv;
+
+const KEEP_ALIVE_ENV: &str = "HARNESS_KEEP_ALIVE";
+// Exit status of a process interrupted by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Whether the run was asked to leave its environment up.
+pub fn keep_alive_requested() -> bool {
+    crate::args().keep_alive
+        || matches!(
+            std::env::var(KEEP_ALIVE_ENV).as_deref(),
+            Ok("1") | Ok("true")
+        )
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    StopService(String),
+    RemoveService(String),
+    RemoveNetwork(String),
+    RemoveVolume(String),
+    RemovePath(PathBuf),
+}
+
+impl Action {
+    fn describe(&self) -> String {
+        match self {
+            Action::StopService(service) => format!("stop service {}", service),
+            Action::RemoveService(service) => format!("remove service {}", service),
+            Action::RemoveNetwork(network) => format!("remove network {}", network),
+            Action::RemoveVolume(volume) => format!("remove volume {}", volume),
+            Action::RemovePath(path) => format!("remove {}", path.display()),
+        }
+    }
+
+    async fn run(&self, env: &DockerEnv) -> Result<(), String> {
+        match self {
+            Action::StopService(service) => {
+                env.stop(service, crate::DEFAULT_STOP_TIMEOUT_SECS).await
+            }
+            Action::RemoveService(service) => match env.remove(service).await {
+                // Already gone, e.g. the test destroyed it itself
+                Err(e) if e.starts_with("No container") => Ok(()),
+                result => result,
+            },
+            Action::RemoveNetwork(network) => env
+                .docker()
+                .remove_network(network)
+                .await
+                .map_err(|e| format!("Failed to remove network {}: {:?}", network, e)),
+            Action::RemoveVolume(volume) => env
+                .docker()
+                .remove_volume(volume, Some(RemoveVolumeOptions { force: true }))
+                .await
+                .map_err(|e| format!("Failed to remove volume {}: {:?}", volume, e)),
+            Action::RemovePath(path) => {
+                let removed = if path.is_dir() {
+                    std::fs::remove_dir_all(path)
+                } else {
+                    std::fs::remove_file(path)
+                };
+                match removed {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        Err(format!("Failed to remove {}: {:?}", path.display(), e))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+struct Registry {
+    actions: Vec<Action>,
+    done: bool,
+}
+
+/// Guard undoing a run's environment changes; cloning shares the same registry.
+#[derive(Clone)]
+pub struct Teardown {
+    env: DockerEnv,
+    keep_alive: bool,
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Teardown {
+    pub fn new(env: DockerEnv, keep_alive: bool) -> Self {
+        Self {
+            env,
+            keep_alive,
+            registry: Arc::new(Mutex::new(Registry {
+                actions: Vec::new(),
+                done: false,
+            })),
+        }
+    }
+
+    /// Guard for the local compose project, honouring `--keep-alive`.
+    pub fn local() -> Result<Self, String> {
+        Ok(Self::new(DockerEnv::local()?, keep_alive_requested()))
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// Stop `service` on teardown unless it was already running now.
+    pub async fn stop_on_exit(&self, service: &str) -> Result<(), String> {
+        let running = match self.env.is_running(service).await {
+            Ok(running) => running,
+            Err(e) if e.starts_with("No container") => false,
+            Err(e) => return Err(e),
+        };
+        if !running {
+            self.push(Action::StopService(service.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Remove `service`'s container on teardown; for throwaway services.
+    pub fn remove_on_exit(&self, service: &str) {
+        self.push(Action::RemoveService(service.to_string()));
+    }
+
+    pub fn remove_network_on_exit(&self, network: &str) {
+        self.push(Action::RemoveNetwork(network.to_string()));
+    }
+
+    pub fn remove_volume_on_exit(&self, volume: &str) {
+        self.push(Action::RemoveVolume(volume.to_string()));
+    }
+
+    /// Delete a host file or directory on teardown.
+    pub fn remove_path_on_exit(&self, path: impl Into<PathBuf>) {
+        self.push(Action::RemovePath(path.into()));
+    }
+
+    /// Tear down and exit with status 130 when the process gets Ctrl-C.
+    pub fn handle_ctrl_c(&self) {
+        // Hold the registry weakly so the listener does not keep the guard alive
+        let env = self.env.clone();
+        let keep_alive = self.keep_alive;
+        let registry = Arc::downgrade(&self.registry);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                tracing::info!("Interrupted, tearing down");
+                if let Some(registry) = registry.upgrade() {
+                    Teardown {
+                        env,
+                        keep_alive,
+                        registry,
+                    }
+                    .run()
+                    .await;
+                }
+                std::process::exit(INTERRUPTED_EXIT_CODE);
+            }
+        });
+    }
+
+    /// Undo everything registered, most recent first. Runs once; failures are
+    /// reported and do not stop the remaining steps.
+    pub async fn run(&self) {
+        let Some(actions) = self.take() else {
+            return;
+        };
+        if self.keep_alive {
+            for action in actions.iter().rev() {
+                tracing::info!(action = %action.describe(), "Keeping environment alive, skipped");
+            }
+            return;
+        }
+        run_actions(&self.env, &actions).await;
+    }
+
+    fn push(&self, action: Action) {
+        self.registry.lock().unwrap().actions.push(action);
+    }
+
+    fn take(&self) -> Option<Vec<Action>> {
+        let mut registry = self.registry.lock().unwrap();
+        if registry.done {
+            return None;
+        }
+        registry.done = true;
+        Some(std::mem::take(&mut registry.actions))
+    }
+}
+
+impl Drop for Teardown {
+    fn drop(&mut self) {
+        // Clones share the registry; only the last one cleans up
+        if Arc::strong_count(&self.registry) > 1 || self.keep_alive {
+            return;
+        }
+        let Some(actions) = self.take() else {
+            return;
+        };
+        if actions.is_empty() {
+            return;
+        }
+        crate::run_detached(self.env.project().to_string(), move |env| async move {
+            run_actions(&env, &actions).await;
+        });
+    }
+}
+
+async fn run_actions(env: &DockerEnv, actions: &[Action]) {
+    for action in actions.iter().rev() {
+        if let Err(e) = action.run(env).await {
+            tracing::warn!("Teardown could not {}: {}", action.describe(), e);
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! Minimal JSON-RPC client for the regtest bitcoind container
+
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config;
+
+pub const BITCOIND_RPC_URL: &str = "http://localhost:43782";
+pub const BITCOIND_RPC_USER: &str = "polaruser";
+pub const BITCOIND_RPC_PASS: &str = "polarpass";
+
+pub const SATS_PER_BTC: u64 = 100_000_000;
+
+/// Outcome of [`Bitcoind::reorg`].
+#[derive(Debug)]
+pub struct Reorg {
+    /// Height of the first replaced block.
+    pub fork_height: u64,
+    /// Hashes of the blocks that were replaced, lowest first.
+    pub orphaned: Vec<String>,
+    pub new_tip: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockHeader {
+    pub hash: String,
+    pub height: u64,
+    /// -1 when the block is not on the active chain.
+    pub confirmations: i64,
+}
+
+/// A confirmed transaction spending outputs of another one.
+#[derive(Debug)]
+pub struct Spender {
+    pub txid: String,
+    pub height: u64,
+    /// Output indexes of the spent transaction that it consumes.
+    pub vouts: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+pub struct Bitcoind {
+    client: Client,
+    url: String,
+    user: String,
+    pass: String,
+}
+
+impl Bitcoind {
+    pub fn new(url: &str, user: &str, pass: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.to_string(),
+            user: user.to_string(),
+            pass: pass.to_string(),
+        }
+    }
+
+    /// Client for the compose bitcoind, with the credentials from docker-compose.yml
+    /// unless the settings name others.
+    pub fn local() -> Self {
+        let settings = &config::get().bitcoind;
+        Self::new(&settings.rpc_url, &settings.rpc_user, &settings.rpc_password)
+    }
+
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        let body = json!({
+            "jsonrpc": "1.0",
+            "id": "vss-test",
+            "method": method,
+            "params": params,
+        });
+
+        let resp = self
+            .client
+            .post(&self.url)
+            .basic_auth(&self.user, Some(&self.pass))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("bitcoind {} request failed: {:?}", method, e))?;
+
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| format!("bitcoind {} body read failed: {:?}", method, e))?;
+
+        // bitcoind answers RPC errors with a non-2xx status and a JSON error body
+        let parsed: RpcResponse<T> = serde_json::from_str(&text)
+            .map_err(|e| format!("bitcoind {} returned {}: {} ({})", method, status, text, e))?;
+
+        if let Some(err) = parsed.error {
+            return Err(format!("bitcoind {} error {}: {}", method, err.code, err.message));
+        }
+        parsed
+            .result
+            .ok_or_else(|| format!("bitcoind {} returned no result", method))
+    }
+
+    pub async fn get_block_count(&self) -> Result<u64, String> {
+        self.call("getblockcount", json!([])).await
+    }
+
+    pub async fn get_best_block_hash(&self) -> Result<String, String> {
+        self.call("getbestblockhash", json!([])).await
+    }
+
+    pub async fn get_block_hash(&self, height: u64) -> Result<String, String> {
+        self.call("getblockhash", json!([height])).await
+    }
+
+    pub async fn get_block_header(&self, hash: &str) -> Result<BlockHeader, String> {
+        self.call("getblockheader", json!([hash])).await
+    }
+
+    /// Median time past of the tip, the clock time-based locks are checked against.
+    pub async fn get_median_time(&self) -> Result<u64, String> {
+        let info: Value = self.call("getblockchaininfo", json!([])).await?;
+        info["mediantime"]
+            .as_u64()
+            .ok_or_else(|| format!("getblockchaininfo returned no mediantime: {}", info))
+    }
+
+    /// Pin bitcoind's clock to `timestamp` (unix seconds); 0 returns to wall-clock time.
+    pub async fn set_mock_time(&self, timestamp: u64) -> Result<(), String> {
+        self.call::<Value>("setmocktime", json!([timestamp])).await?;
+        Ok(())
+    }
+
+    /// Confirmation height of a transaction, or `None` while it is unconfirmed.
+    pub async fn get_tx_height(&self, txid: &str) -> Result<Option<u64>, String> {
+        let tx: Value = self.call("getrawtransaction", json!([txid, true])).await?;
+        match tx["blockhash"].as_str() {
+            Some(hash) => Ok(Some(self.get_block_header(hash).await?.height)),
+            None => Ok(None),
+        }
+    }
+
+    /// Output script of any address, as raw bytes.
+    pub async fn get_script_pubkey(&self, address: &str) -> Result<Vec<u8>, String> {
+        let info: Value = self.call("getaddressinfo", json!([address])).await?;
+        info["scriptPubKey"]
+            .as_str()
+            .and_then(|s| hex::decode(s).ok())
+            .ok_or_else(|| format!("getaddressinfo returned no scriptPubKey: {}", info))
+    }
+
+    pub async fn get_balance_sat(&self) -> Result<u64, String> {
+        let btc: f64 = self.call("getbalance", json!([])).await?;
+        Ok(btc_to_sat(btc))
+    }
+
+    pub async fn get_new_address(&self) -> Result<String, String> {
+        self.call("getnewaddress", json!(["", "bech32"])).await
+    }
+
+    /// New P2TR (bech32m) address from the bitcoind wallet.
+    pub async fn get_new_taproot_address(&self) -> Result<String, String> {
+        self.call("getnewaddress", json!(["", "bech32m"])).await
+    }
+
+    /// Mine `blocks` blocks paying the coinbase to the bitcoind wallet.
+    pub async fn mine(&self, blocks: u64) -> Result<Vec<String>, String> {
+        let address = self.get_new_address().await?;
+        self.call("generatetoaddress", json!([blocks, address])).await
+    }
+
+    /// Mine one block containing only the coinbase, leaving the mempool untouched.
+    pub async fn mine_empty_block(&self) -> Result<String, String> {
+        let address = self.get_new_address().await?;
+        let block: Value = self.call("generateblock", json!([address, []])).await?;
+        block["hash"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("generateblock returned no hash: {}", block))
+    }
+
+    /// Replace the last `depth` blocks with a longer competing chain of empty
+    /// blocks. Transactions from the orphaned blocks fall back to the mempool.
+    pub async fn reorg(&self, depth: u64) -> Result<Reorg, String> {
+        let height = self.get_block_count().await?;
+        if depth == 0 || depth >= height {
+            return Err(format!("Cannot reorg {} blocks at height {}", depth, height));
+        }
+
+        let fork_height = height - depth + 1;
+        let mut orphaned = Vec::new();
+        for h in fork_height..=height {
+            orphaned.push(self.get_block_hash(h).await?);
+        }
+
+        self.call::<Value>("invalidateblock", json!([orphaned[0]]))
+            .await?;
+        let mut new_tip = String::new();
+        for _ in 0..=depth {
+            new_tip = self.mine_empty_block().await?;
+        }
+
+        Ok(Reorg {
+            fork_height,
+            orphaned,
+            new_tip,
+        })
+    }
+
+    pub async fn send_to_address(&self, address: &str, amount_sat: u64) -> Result<String, String> {
+        self.call("sendtoaddress", json!([address, sat_to_btc(amount_sat)]))
+            .await
+    }
+
+    /// Send `amount_sat` paying an explicit fee rate, signalling RBF.
+    pub async fn send_with_fee_rate(
+        &self,
+        address: &str,
+        amount_sat: u64,
+        sat_per_vbyte: f64,
+    ) -> Result<String, String> {
+        // sendtoaddress positional args: address amount comment comment_to
+        // subtractfeefromamount replaceable conf_target estimate_mode avoid_reuse fee_rate
+        self.call(
+            "sendtoaddress",
+            json!([address, sat_to_btc(amount_sat), "", "", false, true, null, "unset", null, sat_per_vbyte]),
+        )
+        .await
+    }
+
+    /// Fill the mempool with `count` wallet-to-wallet transactions whose fee
+    /// rates are spread evenly over `min_fee_rate..=max_fee_rate` sat/vB.
+    pub async fn spam_mempool(
+        &self,
+        count: u64,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+    ) -> Result<Vec<String>, String> {
+        const SPAM_AMOUNT_SAT: u64 = 10_000;
+        let step = if count > 1 {
+            (max_fee_rate - min_fee_rate) / (count - 1) as f64
+        } else {
+            0.0
+        };
+        let mut txids = Vec::new();
+        for i in 0..count {
+            let address = self.get_new_address().await?;
+            let fee_rate = ((min_fee_rate + step * i as f64) * 1000.0).round() / 1000.0;
+            txids.push(self.send_with_fee_rate(&address, SPAM_AMOUNT_SAT, fee_rate).await?);
+        }
+        Ok(txids)
+    }
+
+    /// Replace a wallet transaction via RBF at `sat_per_vbyte`, returning the new txid.
+    pub async fn bump_fee(&self, txid: &str, sat_per_vbyte: f64) -> Result<String, String> {
+        let resp: Value = self
+            .call("bumpfee", json!([txid, { "fee_rate": sat_per_vbyte }]))
+            .await?;
+        resp["txid"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("bumpfee returned no txid: {}", resp))
+    }
+
+    /// Spend exactly the wallet output `txid:vout` to `address`, returning the txid.
+    pub async fn sweep_outpoint(
+        &self,
+        txid: &str,
+        vout: u32,
+        address: &str,
+        sat_per_vbyte: f64,
+    ) -> Result<String, String> {
+        let utxo: Value = self.call("gettxout", json!([txid, vout])).await?;
+        let value_btc = utxo["value"]
+            .as_f64()
+            .ok_or_else(|| format!("Output {}:{} is spent or unknown", txid, vout))?;
+        let resp: Value = self
+            .call(
+                "send",
+                json!({
+                    "outputs": [{ address: value_btc }],
+                    "fee_rate": sat_per_vbyte,
+                    "options": {
+                        "inputs": [{ "txid": txid, "vout": vout }],
+                        "add_inputs": false,
+                        "subtract_fee_from_outputs": [0],
+                    },
+                }),
+            )
+            .await?;
+        resp["txid"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("send returned no txid: {}", resp))
+    }
+
+    /// Mempool entry for `txid`, or `None` if it is not in the mempool.
+    pub async fn mempool_entry(&self, txid: &str) -> Result<Option<Value>, String> {
+        match self.call("getmempoolentry", json!([txid])).await {
+            Ok(entry) => Ok(Some(entry)),
+            Err(e) if e.contains("not in mempool") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Values in sat of every output of `txid`, by index.
+    pub async fn output_values(&self, txid: &str) -> Result<Vec<u64>, String> {
+        let tx: Value = self.call("getrawtransaction", json!([txid, true])).await?;
+        tx["vout"]
+            .as_array()
+            .map(|outputs| {
+                outputs
+                    .iter()
+                    .map(|o| btc_to_sat(o["value"].as_f64().unwrap_or(0.0)))
+                    .collect()
+            })
+            .ok_or_else(|| format!("Transaction {} has no outputs: {}", txid, tx))
+    }
+
+    /// Confirmed transactions in blocks `from_height..=tip` spending outputs of `txid`.
+    pub async fn find_spenders(&self, txid: &str, from_height: u64) -> Result<Vec<Spender>, String> {
+        let tip = self.get_block_count().await?;
+        let mut spenders = Vec::new();
+        for height in from_height..=tip {
+            let hash = self.get_block_hash(height).await?;
+            let block: Value = self.call("getblock", json!([hash, 2])).await?;
+            for tx in block["tx"].as_array().into_iter().flatten() {
+                let vouts: Vec<u32> = tx["vin"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|input| input["txid"].as_str() == Some(txid))
+                    .filter_map(|input| input["vout"].as_u64().map(|v| v as u32))
+                    .collect();
+                if !vouts.is_empty() {
+                    spenders.push(Spender {
+                        txid: tx["txid"].as_str().unwrap_or_default().to_string(),
+                        height,
+                        vouts,
+                    });
+                }
+            }
+        }
+        Ok(spenders)
+    }
+
+    /// Index of the output of `txid` paying to `address`.
+    pub async fn find_vout(&self, txid: &str, address: &str) -> Result<u32, String> {
+        let tx: Value = self.call("getrawtransaction", json!([txid, true])).await?;
+        tx["vout"]
+            .as_array()
+            .and_then(|outputs| {
+                outputs
+                    .iter()
+                    .find(|o| o["scriptPubKey"]["address"].as_str() == Some(address))
+            })
+            .and_then(|o| o["n"].as_u64())
+            .map(|n| n as u32)
+            .ok_or_else(|| format!("Transaction {} has no output to {}", txid, address))
+    }
+
+    pub async fn mempool_size(&self) -> Result<u64, String> {
+        let info: Value = self.call("getmempoolinfo", json!([])).await?;
+        info["size"]
+            .as_u64()
+            .ok_or_else(|| format!("getmempoolinfo returned no size: {}", info))
+    }
+
+    /// Smart fee estimate in sat/vB, or `None` when bitcoind lacks data for `conf_target`.
+    pub async fn estimate_smart_fee(&self, conf_target: u32) -> Result<Option<f64>, String> {
+        let estimate: Value = self.call("estimatesmartfee", json!([conf_target])).await?;
+        // feerate is reported in BTC/kvB
+        Ok(estimate["feerate"]
+            .as_f64()
+            .map(|btc_per_kvb| btc_per_kvb * SATS_PER_BTC as f64 / 1000.0))
+    }
+
+    /// Make sure the wallet holds at least `amount_sat` of mature coins, mining if needed.
+    pub async fn ensure_funds(&self, amount_sat: u64) -> Result<(), String> {
+        // Coinbase outputs need 100 confirmations, so the first top-up mines 101 blocks
+        for blocks in [101, 101, 101] {
+            if self.get_balance_sat().await? >= amount_sat {
+                return Ok(());
+            }
+            self.mine(blocks).await?;
+        }
+        let balance = self.get_balance_sat().await?;
+        if balance >= amount_sat {
+            Ok(())
+        } else {
+            Err(format!(
+                "bitcoind wallet holds {} sat, need {} sat",
+                balance, amount_sat
+            ))
+        }
+    }
+}
+
+pub fn sat_to_btc(amount_sat: u64) -> f64 {
+    amount_sat as f64 / SATS_PER_BTC as f64
+}
+
+pub fn btc_to_sat(amount_btc: f64) -> u64 {
+    (amount_btc * SATS_PER_BTC as f64).round() as u64
+}
@@ -0,0 +1,239 @@
+//! Minimal client for the Blocktank (LSP) v2 HTTP API
+//!
+//! Blocktank is not part of docker-compose.yml; point `BLOCKTANK_URL` at an
+//! instance wired to this stack's bitcoind so its invoices are payable from lnd.
+
+use reqwest::{Client, Method};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const BLOCKTANK_URL_ENV: &str = "BLOCKTANK_URL";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspNode {
+    pub alias: String,
+    pub pubkey: String,
+    #[serde(default)]
+    pub connection_strings: Vec<String>,
+}
+
+impl LspNode {
+    /// Split the first `pubkey@host:port` connection string into pubkey and host.
+    pub fn peer_address(&self) -> Result<(String, String), String> {
+        self.connection_strings
+            .iter()
+            .find_map(|c| c.split_once('@'))
+            .map(|(pubkey, host)| (pubkey.to_string(), host.to_string()))
+            .ok_or_else(|| format!("LSP node {} advertises no connection string", self.alias))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoOptions {
+    pub min_channel_size_sat: u64,
+    pub max_channel_size_sat: u64,
+    pub min_expiry_weeks: u32,
+    pub max_expiry_weeks: u32,
+    #[serde(default)]
+    pub max_client_balance_sat: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Info {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub nodes: Vec<LspNode>,
+    pub options: InfoOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bolt11Invoice {
+    pub request: String,
+    /// `pending`, `holding`, `paid` or `canceled`
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub expires_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnchainPayment {
+    pub address: String,
+    #[serde(default)]
+    pub confirmed_sat: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    /// `created`, `paid`, `refunded`, `refundAvailable` or `canceled`
+    #[serde(default)]
+    pub state2: String,
+    #[serde(default)]
+    pub paid_sat: u64,
+    pub bolt11_invoice: Bolt11Invoice,
+    pub onchain: Option<OnchainPayment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspChannel {
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub lsp_node_pubkey: String,
+    #[serde(default)]
+    pub client_node_pubkey: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Order {
+    pub id: String,
+    /// `created`, `paid`, `executed` or `expired`
+    pub state2: String,
+    pub fee_sat: u64,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    #[serde(default)]
+    pub zero_conf: bool,
+    #[serde(default)]
+    pub order_expires_at: String,
+    pub payment: Payment,
+    pub channel: Option<LspChannel>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOrder {
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub channel_expiry_weeks: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zero_conf: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCjit {
+    pub channel_size_sat: u64,
+    pub invoice_sat: u64,
+    pub invoice_description: String,
+    pub node_id: String,
+    pub channel_expiry_weeks: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CjitEntry {
+    pub id: String,
+    /// `created`, `completed`, `expired` or `failed`
+    pub state: String,
+    pub fee_sat: u64,
+    pub channel_size_sat: u64,
+    pub invoice: Bolt11Invoice,
+    pub channel: Option<LspChannel>,
+    #[serde(default)]
+    pub channel_open_error: Option<String>,
+}
+
+pub struct Blocktank {
+    client: Client,
+    url: String,
+}
+
+impl Blocktank {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Client for the instance named by `BLOCKTANK_URL`.
+    pub fn from_env() -> Result<Self, String> {
+        std::env::var(BLOCKTANK_URL_ENV)
+            .map(|url| Self::new(&url))
+            .map_err(|_| {
+                format!(
+                    "{} is not set; Blocktank is not part of the compose stack, \
+                     point it at an LSP instance connected to this regtest chain",
+                    BLOCKTANK_URL_ENV
+                )
+            })
+    }
+
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<T, String> {
+        let mut req = self
+            .client
+            .request(method.clone(), format!("{}{}", self.url, path));
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| format!("Blocktank {} {} request failed: {:?}", method, path, e))?;
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| format!("Blocktank {} {} body read failed: {:?}", method, path, e))?;
+
+        if !status.is_success() {
+            return Err(format!("Blocktank {} {} returned {}: {}", method, path, status, text));
+        }
+        serde_json::from_str(&text).map_err(|e| {
+            format!("Blocktank {} {} returned unparsable body {}: {}", method, path, text, e)
+        })
+    }
+
+    pub async fn info(&self) -> Result<Info, String> {
+        self.request(Method::GET, "/info", None).await
+    }
+
+    pub async fn create_order(&self, order: &CreateOrder) -> Result<Order, String> {
+        let body = serde_json::to_value(order).map_err(|e| format!("{:?}", e))?;
+        self.request(Method::POST, "/channels", Some(body)).await
+    }
+
+    pub async fn get_order(&self, id: &str) -> Result<Order, String> {
+        self.request(Method::GET, &format!("/channels/{}", id), None)
+            .await
+    }
+
+    /// Request a just-in-time channel invoice: paying it makes the LSP open a
+    /// channel to `node_id` and forward `invoice_sat` minus fees over it.
+    pub async fn create_cjit(&self, entry: &CreateCjit) -> Result<CjitEntry, String> {
+        let body = serde_json::to_value(entry).map_err(|e| format!("{:?}", e))?;
+        self.request(Method::POST, "/cjit", Some(body)).await
+    }
+
+    pub async fn get_cjit(&self, id: &str) -> Result<CjitEntry, String> {
+        self.request(Method::GET, &format!("/cjit/{}", id), None)
+            .await
+    }
+
+    /// Ask the LSP to open the paid order's channel to `connection_string` (`pubkey@host:port`).
+    pub async fn open_channel(&self, id: &str, connection_string: &str) -> Result<Order, String> {
+        let body = json!({
+            "connectionStringOrPubkey": connection_string,
+            "announceChannel": false,
+        });
+        self.request(Method::POST, &format!("/channels/{}/open", id), Some(body))
+            .await
+    }
+}
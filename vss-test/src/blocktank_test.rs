@@ -0,0 +1,436 @@
+//! Blocktank Order Integration Test Binary
+//!
+//! Tests the Blocktank (LSP) channel order and CJIT flows against the regtest
+//! stack: `lnd` pays the LSP invoices and channels are opened to `lnd2`.
+//! Unpaid and underpaid orders must expire and expose their refund state
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use std::time::Duration;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::test_cases;
+use vss_test::bitcoind::Bitcoind;
+use vss_test::blocktank::{Blocktank, CreateCjit, CreateOrder, Info, Order};
+use vss_test::faucet::Faucet;
+use vss_test::lnd::{Lnd, LND_B_P2P_HOST};
+use vss_test::{env_u64, wait_for};
+
+// Local services; Blocktank itself is external. Checked against `--profile`
+const REQUIRED_SERVICES: [&str; 3] = ["bitcoind", "lnd", "lnd2"];
+
+// Capacity of the lnd -> LSP channel used to pay order invoices
+const PAYER_CHANNEL_CAPACITY_SAT: u64 = 2_000_000;
+const DEFAULT_LSP_BALANCE_SAT: u64 = 100_000;
+
+// How far a CJIT invoice exceeds the client's current inbound capacity
+const CJIT_EXCESS_SAT: u64 = 20_000;
+
+// Order expiry is wall-clock on the LSP and cannot be fast-forwarded, so the
+// expiry test waits it out for up to BLOCKTANK_EXPIRY_TIMEOUT_SECS
+const DEFAULT_EXPIRY_TIMEOUT_SECS: u64 = 600;
+const EXPIRY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const ORDER_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MINE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Blocktank Order Integration Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The LSP and the nodes every case works with.
+struct Lsp {
+    blocktank: Blocktank,
+    bitcoind: Bitcoind,
+    /// Pays the LSP's invoices
+    payer: Lnd,
+    /// Gets the channels the LSP opens
+    client_node: Lnd,
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    // The paid cases spend from the payer and mine blocks the others would
+    // confirm or count on
+    let cases = test_cases!(Lsp;
+        test_info,
+        test_create_order,
+        #[exclusive] test_order_paid_and_opened,
+        #[exclusive] test_cjit_channel_opened_mid_payment,
+        // Waits out the LSP's wall-clock order expiry
+        #[exclusive] #[tags(Slow)] test_order_expiry_and_refund,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("Blocktank Order Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select_or(&REQUIRED_SERVICES, &profile::LIGHTNING).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let blocktank = match Blocktank::from_env() {
+        Ok(blocktank) => blocktank,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+    };
+    let bitcoind = Bitcoind::local();
+    let (payer, client_node) = match (Lnd::node_a(), Lnd::node_b()) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            let e = format!("Failed to set up LND clients: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let lsp = Lsp {
+        blocktank,
+        bitcoind,
+        payer,
+        client_node,
+    };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &lsp, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// Order request sized within the LSP's advertised limits.
+fn order_request(info: &Info) -> CreateOrder {
+    let options = &info.options;
+    CreateOrder {
+        lsp_balance_sat: DEFAULT_LSP_BALANCE_SAT
+            .max(options.min_channel_size_sat)
+            .min(options.max_channel_size_sat),
+        client_balance_sat: 0,
+        channel_expiry_weeks: options.min_expiry_weeks,
+        zero_conf: None,
+    }
+}
+
+/// Poll an order until `done` accepts it.
+async fn wait_order<F>(blocktank: &Blocktank, id: &str, what: &str, done: F) -> Result<Order, String>
+where
+    F: Fn(&Order) -> bool,
+{
+    wait_for(what, ORDER_TIMEOUT, POLL_INTERVAL, || async {
+        let order = blocktank.get_order(id).await?;
+        Ok(done(&order).then_some(order))
+    })
+    .await
+}
+
+async fn test_info(lsp: &Lsp) -> Outcome {
+    let info = lsp.blocktank.info().await?;
+    let options = &info.options;
+    if info.nodes.is_empty() {
+        return Err("LSP advertises no nodes".to_string());
+    }
+    if options.min_channel_size_sat > options.max_channel_size_sat
+        || options.min_expiry_weeks > options.max_expiry_weeks
+    {
+        return Err(format!("Inconsistent limits: {:?}", options));
+    }
+    info.nodes[0].peer_address()?;
+    Ok(format!(
+        "{} node(s), channel size {}..={} sat",
+        info.nodes.len(),
+        options.min_channel_size_sat,
+        options.max_channel_size_sat
+    ))
+}
+
+async fn test_create_order(lsp: &Lsp) -> Outcome {
+    let blocktank = &lsp.blocktank;
+
+    let request = order_request(&blocktank.info().await?);
+    let order = blocktank.create_order(&request).await?;
+
+    if order.state2 != "created" {
+        return Err(format!("Order state is {}, expected created", order.state2));
+    }
+    if order.lsp_balance_sat != request.lsp_balance_sat {
+        return Err(format!(
+            "Order LSP balance is {} sat, requested {} sat",
+            order.lsp_balance_sat, request.lsp_balance_sat
+        ));
+    }
+    if order.fee_sat == 0 || !order.payment.bolt11_invoice.request.starts_with("lnbcrt") {
+        return Err(format!(
+            "Order is missing a regtest invoice or fee: fee {} sat, invoice {:?}",
+            order.fee_sat, order.payment.bolt11_invoice.request
+        ));
+    }
+
+    // The order must be retrievable by id with the same terms
+    let fetched = blocktank.get_order(&order.id).await?;
+    if fetched.fee_sat != order.fee_sat || fetched.state2 != order.state2 {
+        return Err(format!("Fetched order differs from created one: {:?}", fetched));
+    }
+    Ok(format!("Order {} fee {} sat", order.id, order.fee_sat))
+}
+
+async fn test_order_paid_and_opened(Lsp { blocktank, bitcoind, payer, client_node }: &Lsp) -> Outcome {
+    let info = blocktank.info().await?;
+    let lsp = info
+        .nodes
+        .first()
+        .ok_or_else(|| "LSP advertises no nodes".to_string())?;
+    let (lsp_pubkey, lsp_host) = lsp.peer_address()?;
+
+    let order = blocktank.create_order(&order_request(&info)).await?;
+
+    // Pay the order invoice from lnd over a direct channel to the LSP
+    payer
+        .ensure_channel(bitcoind, &lsp_pubkey, &lsp_host, PAYER_CHANNEL_CAPACITY_SAT, order.fee_sat)
+        .await?;
+    let payment = payer.pay_invoice(&order.payment.bolt11_invoice.request).await?;
+    if !payment.payment_error.is_empty() {
+        return Err(format!("Paying order invoice failed: {}", payment.payment_error));
+    }
+
+    let paid = wait_order(blocktank, &order.id, "order to be paid", |o| o.state2 == "paid").await?;
+    if paid.payment.state2 != "paid" {
+        return Err(format!("Payment state is {}, expected paid", paid.payment.state2));
+    }
+
+    // Have the LSP open the channel to the client node
+    let client_pubkey = client_node.get_info().await?.identity_pubkey;
+    blocktank
+        .open_channel(&order.id, &format!("{}@{}", client_pubkey, LND_B_P2P_HOST))
+        .await?;
+    bitcoind.mine(6).await?;
+
+    let opened = wait_order(blocktank, &order.id, "order channel to open", |o| {
+        o.state2 == "executed" && o.channel.as_ref().is_some_and(|c| c.state == "open")
+    })
+    .await?;
+    let channel = opened.channel.as_ref().map(|c| c.client_node_pubkey.as_str());
+    if channel != Some(client_pubkey.as_str()) {
+        return Err(format!("Order channel opened to {:?}, expected {}", channel, client_pubkey));
+    }
+
+    client_node.wait_synced(bitcoind).await?;
+    let visible = client_node.wait_channel_active(&lsp_pubkey).await?;
+    if visible.capacity < order.lsp_balance_sat as i64 {
+        return Err(format!(
+            "Client channel capacity {} sat is below ordered {} sat",
+            visible.capacity, order.lsp_balance_sat
+        ));
+    }
+    Ok(format!("Order {} paid and opened", order.id))
+}
+
+async fn test_cjit_channel_opened_mid_payment(Lsp { blocktank, bitcoind, payer, client_node }: &Lsp) -> Outcome {
+    let info = blocktank.info().await?;
+    let lsp = info
+        .nodes
+        .first()
+        .ok_or_else(|| "LSP advertises no nodes".to_string())?;
+    let (lsp_pubkey, lsp_host) = lsp.peer_address()?;
+    let client_pubkey = client_node.get_info().await?.identity_pubkey;
+
+    // Size the invoice so it cannot be received over existing channels
+    let inbound_sat = client_node.channel_balance().await?.remote_balance.sat as u64;
+    let invoice_sat = inbound_sat + CJIT_EXCESS_SAT;
+    let channel_size_sat = (invoice_sat * 2).max(info.options.min_channel_size_sat);
+    if channel_size_sat > info.options.max_channel_size_sat {
+        return Err(format!(
+            "Client inbound {} sat leaves no room for a CJIT channel (max {} sat)",
+            inbound_sat, info.options.max_channel_size_sat
+        ));
+    }
+
+    let channels_before: Vec<String> = client_node
+        .list_channels()
+        .await?
+        .into_iter()
+        .map(|c| c.channel_point)
+        .collect();
+    let local_before = client_node.channel_balance().await?.local_balance.sat;
+
+    let entry = blocktank
+        .create_cjit(&CreateCjit {
+            channel_size_sat,
+            invoice_sat,
+            invoice_description: "vss-test cjit".to_string(),
+            node_id: client_pubkey.clone(),
+            channel_expiry_weeks: info.options.min_expiry_weeks,
+        })
+        .await?;
+    if entry.state != "created" {
+        return Err(format!("CJIT entry state is {}, expected created", entry.state));
+    }
+
+    payer
+        .ensure_channel(bitcoind, &lsp_pubkey, &lsp_host, PAYER_CHANNEL_CAPACITY_SAT, invoice_sat)
+        .await?;
+
+    // The LSP holds the HTLC while it opens the channel; keep mining in
+    // case it waits for confirmations instead of opening zero-conf
+    let pay = payer.pay_invoice(&entry.invoice.request);
+    let mine = async {
+        loop {
+            tokio::time::sleep(MINE_INTERVAL).await;
+            if let Err(e) = bitcoind.mine(1).await {
+                break e;
+            }
+        }
+    };
+    let payment = tokio::select! {
+        payment = pay => payment?,
+        e = mine => return Err(format!("Mining during payment failed: {}", e)),
+    };
+    if !payment.payment_error.is_empty() {
+        return Err(format!("CJIT payment failed: {}", payment.payment_error));
+    }
+
+    let completed = wait_for("CJIT entry to complete", ORDER_TIMEOUT, POLL_INTERVAL, || async {
+        let entry = blocktank.get_cjit(&entry.id).await?;
+        if entry.state == "failed" {
+            return Err(format!("CJIT failed: {:?}", entry.channel_open_error));
+        }
+        Ok((entry.state == "completed").then_some(entry))
+    })
+    .await?;
+
+    // A new channel from the LSP must exist on the client node
+    let opened = client_node
+        .list_channels()
+        .await?
+        .into_iter()
+        .find(|c| c.remote_pubkey == lsp_pubkey && !channels_before.contains(&c.channel_point))
+        .ok_or_else(|| "No new LSP channel on the client node".to_string())?;
+    if opened.capacity as u64 != completed.channel_size_sat {
+        return Err(format!(
+            "CJIT channel capacity {} sat, expected {} sat",
+            opened.capacity, completed.channel_size_sat
+        ));
+    }
+
+    // The client receives the invoice amount minus the LSP fee
+    let received_sat = client_node.channel_balance().await?.local_balance.sat - local_before;
+    let expected_sat = (invoice_sat - completed.fee_sat) as i64;
+    if received_sat != expected_sat {
+        return Err(format!(
+            "Client received {} sat, expected {} sat",
+            received_sat, expected_sat
+        ));
+    }
+    Ok(format!(
+        "CJIT {} opened {} sat channel, fee {} sat",
+        completed.id, completed.channel_size_sat, completed.fee_sat
+    ))
+}
+
+async fn test_order_expiry_and_refund(lsp: &Lsp) -> Outcome {
+    let (blocktank, bitcoind) = (&lsp.blocktank, &lsp.bitcoind);
+
+    let expiry_timeout = Duration::from_secs(env_u64(
+        "BLOCKTANK_EXPIRY_TIMEOUT_SECS",
+        DEFAULT_EXPIRY_TIMEOUT_SECS,
+    )?);
+    let request = order_request(&blocktank.info().await?);
+
+    // One order is left unpaid, the other gets half its fee on-chain
+    let unpaid = blocktank.create_order(&request).await?;
+    let underpaid = blocktank.create_order(&request).await?;
+    let address = underpaid
+        .payment
+        .onchain
+        .as_ref()
+        .map(|o| o.address.clone())
+        .ok_or_else(|| format!("Order {} offers no on-chain payment", underpaid.id))?;
+    let underpay_sat = underpaid.fee_sat / 2;
+    Faucet::from_env(bitcoind)?.fund(&address, underpay_sat, 6).await?;
+
+    let seen = wait_order(blocktank, &underpaid.id, "underpayment to be seen", |o| {
+        o.payment.onchain.as_ref().is_some_and(|p| p.confirmed_sat > 0)
+    })
+    .await?;
+    if seen.state2 != "created" || seen.payment.state2 == "paid" {
+        return Err(format!(
+            "Underpaid order is {} with payment {}, expected it to stay unpaid",
+            seen.state2, seen.payment.state2
+        ));
+    }
+
+    let wait_expired = |id: String, expires_at: String| async move {
+        let what = format!("order {} to expire (expires at {})", id, expires_at);
+        wait_for(&what, expiry_timeout, EXPIRY_POLL_INTERVAL, || async {
+            let order = blocktank.get_order(&id).await?;
+            Ok((order.state2 == "expired").then_some(order))
+        })
+        .await
+    };
+    let unpaid = wait_expired(unpaid.id, unpaid.order_expires_at).await?;
+    let underpaid = wait_expired(underpaid.id, underpaid.order_expires_at).await?;
+
+    // Nothing was received for the unpaid order, so there is nothing to refund
+    if unpaid.payment.paid_sat != 0 || unpaid.payment.state2.starts_with("refund") {
+        return Err(format!(
+            "Unpaid order reports {} sat paid, payment {}",
+            unpaid.payment.paid_sat, unpaid.payment.state2
+        ));
+    }
+    // The held invoices must never settle once the order is expired
+    for order in [&unpaid, &underpaid] {
+        let invoice_state = &order.payment.bolt11_invoice.state;
+        if invoice_state == "paid" || invoice_state == "holding" {
+            return Err(format!("Expired order {} invoice is {}", order.id, invoice_state));
+        }
+    }
+    // The on-chain underpayment is kept for refund
+    let refundable = matches!(underpaid.payment.state2.as_str(), "refundAvailable" | "refunded");
+    if !refundable {
+        return Err(format!(
+            "Underpaid order payment is {}, expected refundAvailable or refunded",
+            underpaid.payment.state2
+        ));
+    }
+    Ok(format!(
+        "Orders {} and {} expired, underpayment {}",
+        unpaid.id, underpaid.id, underpaid.payment.state2
+    ))
+}
@@ -0,0 +1,697 @@
+//! On-chain Integration Test Binary
+//!
+//! Tests how bitcoind, electrs and the LND nodes behave around chain events
+//! and what fee rates the stack hands out under different mempool conditions,
+//! including RBF replacements, CPFP fee bumps, taproot outputs and fast-forwarding
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use reqwest::Client;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::test_cases;
+use vss_test::bitcoind::{Bitcoind, SATS_PER_BTC};
+use vss_test::clock::fast_forward;
+use vss_test::electrum::Electrum;
+use vss_test::faucet::Faucet;
+use vss_test::lnd::Lnd;
+use vss_test::wait_for;
+
+// darkhttpd serves the fee estimates; checked against `--profile`
+const REQUIRED_SERVICES: [&str; 5] = ["bitcoind", "electrs", "darkhttpd", "lnd", "lnd2"];
+
+// Blocks replaced by the competing chain; the probe transaction is in the last one
+const REORG_DEPTH: u64 = 2;
+const PROBE_AMOUNT_SAT: u64 = 50_000;
+
+// Static estimates served by darkhttpd and consumed by LND's --feeurl
+const FEE_ESTIMATES_URL: &str = "http://localhost:80/btc-fee-estimates.json";
+const CONF_TARGETS: [u32; 6] = [1, 2, 3, 6, 12, 144];
+const MIN_RELAY_FEE_RATE: f64 = 1.0;
+const MAX_SANE_FEE_RATE: f64 = 10_000.0;
+// Mempool congestion generated by the spam helper, in sat/vB
+const SPAM_TX_COUNT: u64 = 60;
+const SPAM_ROUNDS: u64 = 6;
+const SPAM_MIN_FEE_RATE: f64 = 1.0;
+const SPAM_MAX_FEE_RATE: f64 = 50.0;
+// Fee bumping: a low-fee parent and the rate it is bumped to, in sat/vB
+const BUMP_AMOUNT_SAT: u64 = 100_000;
+const LOW_FEE_RATE: f64 = 1.0;
+const BUMPED_FEE_RATE: u64 = 20;
+// Taproot round trips: funded from bitcoind, then swept by the owning wallet
+const TAPROOT_AMOUNT_SAT: u64 = 75_000;
+const TAPROOT_SPEND_FEE_RATE: u64 = 2;
+const REGTEST_TAPROOT_PREFIX: &str = "bcrt1p";
+
+// A day of blocks carrying an hour of chain time
+const FAST_FORWARD_BLOCKS: u64 = 144;
+const FAST_FORWARD_TIME: Duration = Duration::from_secs(60 * 60);
+
+// LND never goes below the 253 sat/kw floor
+const LND_FEE_FLOOR_SAT_PER_KW: i64 = 253;
+
+const CONVERGE_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// On-chain Integration Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The chain backends and nodes every case works with.
+struct Chain {
+    bitcoind: Bitcoind,
+    /// `lnd` and `lnd2`; the first takes the probes, deposits and fee bumps
+    nodes: Vec<Lnd>,
+    client: Client,
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    // Every case but the fee file check mines or fills the mempool others read
+    let cases = test_cases!(Chain;
+        #[exclusive] test_reorg_convergence,
+        test_fee_estimates_endpoint,
+        #[exclusive] test_fee_tiers_empty_mempool,
+        #[exclusive] test_fee_tiers_congested_mempool,
+        #[exclusive] test_rbf_replacement,
+        #[exclusive] test_cpfp_acceleration,
+        #[exclusive] test_taproot_bitcoind_wallet,
+        #[exclusive] test_taproot_lnd_wallet,
+        #[exclusive] test_fast_forward,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("On-chain Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select_or(&REQUIRED_SERVICES, &profile::LIGHTNING).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let bitcoind = Bitcoind::local();
+    let nodes = match (Lnd::node_a(), Lnd::node_b()) {
+        (Ok(a), Ok(b)) => vec![a, b],
+        (Err(e), _) | (_, Err(e)) => {
+            let e = format!("Failed to set up LND clients: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let chain = Chain {
+        bitcoind,
+        nodes,
+        client: Client::new(),
+    };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &chain, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// Wait until electrs reports the same tip as bitcoind.
+async fn wait_electrs_tip(bitcoind: &Bitcoind, electrum: &Electrum) -> Result<(), String> {
+    let best = bitcoind.get_best_block_hash().await?;
+    wait_for("electrs to reach bitcoind tip", CONVERGE_TIMEOUT, POLL_INTERVAL, || async {
+        let (height, hash) = electrum.tip().await?;
+        if hash == best {
+            Ok(Some(()))
+        } else {
+            Err(format!("electrs at {} ({}), bitcoind at {}", height, hash, best))
+        }
+    })
+    .await
+}
+
+/// Wait until electrs reports `txid` at `height` (`None` meaning unconfirmed) for `script`.
+async fn wait_electrs_tx_height(
+    electrum: &Electrum,
+    script: &[u8],
+    txid: &str,
+    height: Option<u64>,
+) -> Result<(), String> {
+    wait_for("electrs history to match bitcoind", CONVERGE_TIMEOUT, POLL_INTERVAL, || async {
+        let history = electrum.script_history(script).await?;
+        let reported = history
+            .iter()
+            .find(|h| h.tx_hash == txid)
+            .map(|h| if h.height > 0 { Some(h.height as u64) } else { None });
+        if reported == Some(height) {
+            Ok(Some(()))
+        } else {
+            Err(format!("electrs reports {:?} for {}, expected {:?}", reported, txid, height))
+        }
+    })
+    .await
+}
+
+/// Wait until an LND node follows bitcoind's tip and sees `txid` with the expected confirmations.
+async fn wait_lnd_converged(
+    bitcoind: &Bitcoind,
+    node: &Lnd,
+    txid: &str,
+    confirmed: bool,
+) -> Result<(), String> {
+    let best = bitcoind.get_best_block_hash().await?;
+    wait_for("LND to follow the reorg", CONVERGE_TIMEOUT, POLL_INTERVAL, || async {
+        let info = node.get_info().await?;
+        if info.block_hash != best {
+            return Err(format!("{} at {}, bitcoind at {}", info.alias, info.block_hash, best));
+        }
+        let tx = node.get_transactions().await?.into_iter().find(|t| t.tx_hash == txid);
+        match tx {
+            Some(tx) if (tx.num_confirmations > 0) == confirmed => Ok(Some(())),
+            Some(tx) => Err(format!(
+                "{} reports {} confirmations for {}",
+                info.alias, tx.num_confirmations, txid
+            )),
+            None => Err(format!("{} does not know {}", info.alias, txid)),
+        }
+    })
+    .await
+}
+
+async fn test_reorg_convergence(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let electrum = Electrum::local().await?;
+    let receiver = &nodes[0];
+
+    // Channels that must survive the reorg untouched
+    let mut active_before = Vec::new();
+    for node in nodes {
+        let channels = node.list_channels().await?;
+        active_before.push(
+            channels
+                .into_iter()
+                .filter(|c| c.active)
+                .map(|c| c.channel_point)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // Confirm a probe transaction in the tip block
+    let address = receiver.new_address().await?;
+    let script = bitcoind.get_script_pubkey(&address).await?;
+    let drip = Faucet::from_env(bitcoind)?
+        .fund(&address, PROBE_AMOUNT_SAT, 1)
+        .await?;
+    let txid = drip.txid;
+    let confirmed_height = drip
+        .height
+        .ok_or_else(|| format!("Probe {} did not confirm", txid))?;
+    wait_electrs_tip(bitcoind, &electrum).await?;
+    wait_electrs_tx_height(&electrum, &script, &txid, Some(confirmed_height)).await?;
+    wait_lnd_converged(bitcoind, receiver, &txid, true).await?;
+
+    // Replace the tip with empty blocks: the probe must drop back to unconfirmed
+    let reorg = bitcoind.reorg(REORG_DEPTH).await?;
+    for hash in &reorg.orphaned {
+        if bitcoind.get_block_header(hash).await?.confirmations != -1 {
+            return Err(format!("Orphaned block {} is still on the active chain", hash));
+        }
+    }
+    if bitcoind.get_tx_height(&txid).await?.is_some() {
+        return Err(format!("Probe {} is still confirmed after the reorg", txid));
+    }
+    wait_electrs_tip(bitcoind, &electrum).await?;
+    wait_electrs_tx_height(&electrum, &script, &txid, None).await?;
+    wait_lnd_converged(bitcoind, receiver, &txid, false).await?;
+
+    // Re-confirming on the new chain must be reflected at the new height
+    bitcoind.mine(1).await?;
+    let reconfirmed_height = bitcoind
+        .get_tx_height(&txid)
+        .await?
+        .ok_or_else(|| format!("Probe {} did not reconfirm", txid))?;
+    wait_electrs_tip(bitcoind, &electrum).await?;
+    wait_electrs_tx_height(&electrum, &script, &txid, Some(reconfirmed_height)).await?;
+    wait_lnd_converged(bitcoind, receiver, &txid, true).await?;
+
+    // No channel may be stuck inactive or pending after the reorg
+    for (node, before) in nodes.iter().zip(&active_before) {
+        node.wait_synced(bitcoind).await?;
+        let alias = node.get_info().await?.alias;
+        wait_for("channels to stay active", CONVERGE_TIMEOUT, POLL_INTERVAL, || async {
+            let active: Vec<String> = node
+                .list_channels()
+                .await?
+                .into_iter()
+                .filter(|c| c.active)
+                .map(|c| c.channel_point)
+                .collect();
+            match before.iter().find(|point| !active.contains(point)) {
+                None => Ok(Some(())),
+                Some(point) => Err(format!("{} channel {} is not active", alias, point)),
+            }
+        })
+        .await?;
+        let pending = node.pending_channels().await?;
+        if !pending.pending_open_channels.is_empty() {
+            return Err(format!(
+                "{} has {} channel(s) stuck pending open",
+                alias,
+                pending.pending_open_channels.len()
+            ));
+        }
+    }
+
+    Ok(format!(
+        "Reorg at {}: probe moved from block {} to {}",
+        reorg.fork_height, confirmed_height, reconfirmed_height
+    ))
+}
+
+#[derive(serde::Deserialize)]
+struct FeeEstimates {
+    /// Block target -> fee rate in sat/kvB
+    fee_by_block_target: BTreeMap<u32, u64>,
+}
+
+/// Check fee tiers, ordered by increasing confirmation target, are sane and never increase.
+fn check_fee_tiers(source: &str, tiers: &[(u32, f64)]) -> Result<(), String> {
+    if tiers.is_empty() {
+        return Err(format!("{} returned no fee tiers", source));
+    }
+    for (target, rate) in tiers {
+        if !(MIN_RELAY_FEE_RATE..=MAX_SANE_FEE_RATE).contains(rate) {
+            return Err(format!(
+                "{} fee rate {} sat/vB for target {} is outside {}..={}",
+                source, rate, target, MIN_RELAY_FEE_RATE, MAX_SANE_FEE_RATE
+            ));
+        }
+    }
+    for pair in tiers.windows(2) {
+        let ((fast_target, fast), (slow_target, slow)) = (pair[0], pair[1]);
+        if slow > fast {
+            return Err(format!(
+                "{} is not monotonic: target {} pays {} sat/vB but target {} pays {} sat/vB",
+                source, slow_target, slow, fast_target, fast
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn fetch_fee_estimates(client: &Client) -> Result<Vec<(u32, f64)>, String> {
+    let resp = client
+        .get(FEE_ESTIMATES_URL)
+        .send()
+        .await
+        .map_err(|e| format!("Fee estimates request failed: {:?}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Fee estimates returned {}", resp.status()));
+    }
+    let estimates: FeeEstimates = resp
+        .json()
+        .await
+        .map_err(|e| format!("Fee estimates are not valid JSON: {:?}", e))?;
+    Ok(estimates
+        .fee_by_block_target
+        .into_iter()
+        .map(|(target, sat_per_kvb)| (target, sat_per_kvb as f64 / 1000.0))
+        .collect())
+}
+
+/// bitcoind smart fee tiers for the targets it has data for.
+async fn bitcoind_fee_tiers(bitcoind: &Bitcoind) -> Result<Vec<(u32, f64)>, String> {
+    let mut tiers = Vec::new();
+    for target in CONF_TARGETS {
+        if let Some(rate) = bitcoind.estimate_smart_fee(target).await? {
+            tiers.push((target, rate));
+        }
+    }
+    Ok(tiers)
+}
+
+/// LND wallet fee tiers, converted from sat/kw to sat/vB.
+async fn lnd_fee_tiers(node: &Lnd) -> Result<Vec<(u32, f64)>, String> {
+    let mut tiers = Vec::new();
+    for target in CONF_TARGETS {
+        let sat_per_kw = node.estimate_fee_sat_per_kw(target).await?;
+        if sat_per_kw < LND_FEE_FLOOR_SAT_PER_KW {
+            return Err(format!(
+                "LND fee rate {} sat/kw for target {} is below the relay floor",
+                sat_per_kw, target
+            ));
+        }
+        tiers.push((target, (sat_per_kw as f64 * 4.0 / 1000.0).max(MIN_RELAY_FEE_RATE)));
+    }
+    Ok(tiers)
+}
+
+async fn test_fee_estimates_endpoint(Chain { client, nodes, .. }: &Chain) -> Outcome {
+    let node = &nodes[0];
+    let file_tiers = fetch_fee_estimates(client).await?;
+    check_fee_tiers("fee estimates file", &file_tiers)?;
+
+    // LND reads the same file through --feeurl
+    let lnd_tiers = lnd_fee_tiers(node).await?;
+    check_fee_tiers("LND estimatefee", &lnd_tiers)?;
+    for (target, file_rate) in &file_tiers {
+        let lnd_rate = lnd_tiers.iter().find(|(t, _)| t == target).map(|(_, r)| *r);
+        if let Some(lnd_rate) = lnd_rate {
+            if (lnd_rate - file_rate).abs() > 1.0 {
+                return Err(format!(
+                    "LND uses {} sat/vB for target {}, fee file says {} sat/vB",
+                    lnd_rate, target, file_rate
+                ));
+            }
+        }
+    }
+    Ok(format!("Tiers {:?}", file_tiers))
+}
+
+async fn test_fee_tiers_empty_mempool(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let node = &nodes[0];
+    bitcoind.mine(1).await?;
+    let mempool = bitcoind.mempool_size().await?;
+    if mempool != 0 {
+        return Err(format!("Mempool still holds {} transactions after mining", mempool));
+    }
+
+    // bitcoind may lack estimator data on a fresh regtest chain; whatever it has must be sane
+    let tiers = bitcoind_fee_tiers(bitcoind).await?;
+    if !tiers.is_empty() {
+        check_fee_tiers("bitcoind estimatesmartfee", &tiers)?;
+    }
+    check_fee_tiers("LND estimatefee", &lnd_fee_tiers(node).await?)?;
+    Ok(format!("bitcoind tiers {:?}", tiers))
+}
+
+async fn test_fee_tiers_congested_mempool(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let node = &nodes[0];
+    let result = async {
+        bitcoind.ensure_funds(100_000_000).await?;
+
+        // Feed the estimator with spam that confirms, then leave one round pending
+        for _ in 0..SPAM_ROUNDS {
+            bitcoind
+                .spam_mempool(SPAM_TX_COUNT, SPAM_MIN_FEE_RATE, SPAM_MAX_FEE_RATE)
+                .await?;
+            bitcoind.mine(1).await?;
+        }
+        bitcoind
+            .spam_mempool(SPAM_TX_COUNT, SPAM_MIN_FEE_RATE, SPAM_MAX_FEE_RATE)
+            .await?;
+        let mempool = bitcoind.mempool_size().await?;
+        if mempool < SPAM_TX_COUNT {
+            return Err(format!(
+                "Mempool holds {} transactions, expected at least {}",
+                mempool, SPAM_TX_COUNT
+            ));
+        }
+
+        let tiers = bitcoind_fee_tiers(bitcoind).await?;
+        check_fee_tiers("bitcoind estimatesmartfee", &tiers)?;
+        check_fee_tiers("LND estimatefee", &lnd_fee_tiers(node).await?)?;
+        Ok(format!("{} pending txs, bitcoind tiers {:?}", mempool, tiers))
+    }
+    .await;
+
+    // Leave an empty mempool for whatever runs next
+    let cleanup = bitcoind.mine(1).await;
+    let detail = result?;
+    cleanup?;
+    Ok(detail)
+}
+
+/// Wait until electrs lists exactly the expected members of `present`/`absent` in `script`'s history.
+async fn wait_electrs_history(
+    electrum: &Electrum,
+    script: &[u8],
+    present: &[&str],
+    absent: &[&str],
+) -> Result<(), String> {
+    wait_for("electrs to reflect the mempool", CONVERGE_TIMEOUT, POLL_INTERVAL, || async {
+        let history = electrum.script_history(script).await?;
+        let listed = |txid: &&str| history.iter().any(|h| h.tx_hash == *txid);
+        if let Some(missing) = present.iter().find(|t| !listed(t)) {
+            return Err(format!("electrs does not list {}", missing));
+        }
+        if let Some(stale) = absent.iter().find(|t| listed(t)) {
+            return Err(format!("electrs still lists {}", stale));
+        }
+        Ok(Some(()))
+    })
+    .await
+}
+
+async fn test_rbf_replacement(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let node = &nodes[0];
+    let electrum = Electrum::local().await?;
+    bitcoind.ensure_funds(BUMP_AMOUNT_SAT * 2).await?;
+    bitcoind.mine(1).await?;
+
+    let address = node.new_address().await?;
+    let script = bitcoind.get_script_pubkey(&address).await?;
+    let original = bitcoind
+        .send_with_fee_rate(&address, BUMP_AMOUNT_SAT, LOW_FEE_RATE)
+        .await?;
+    wait_electrs_history(&electrum, &script, &[&original], &[]).await?;
+
+    let replacement = bitcoind.bump_fee(&original, BUMPED_FEE_RATE as f64).await?;
+    if bitcoind.mempool_entry(&original).await?.is_some() {
+        return Err(format!("Replaced transaction {} is still in the mempool", original));
+    }
+    if bitcoind.mempool_entry(&replacement).await?.is_none() {
+        return Err(format!("Replacement {} is not in the mempool", replacement));
+    }
+    wait_electrs_history(&electrum, &script, &[&replacement], &[&original]).await?;
+
+    // Only the replacement may ever confirm
+    bitcoind.mine(1).await?;
+    if bitcoind.get_tx_height(&replacement).await?.is_none() {
+        return Err(format!("Replacement {} did not confirm", replacement));
+    }
+    wait_electrs_tip(bitcoind, &electrum).await?;
+    let height = bitcoind.get_tx_height(&replacement).await?;
+    wait_electrs_tx_height(&electrum, &script, &replacement, height).await?;
+    wait_electrs_history(&electrum, &script, &[&replacement], &[&original]).await?;
+    wait_lnd_converged(bitcoind, node, &replacement, true).await?;
+    let phantom = node
+        .get_transactions()
+        .await?
+        .into_iter()
+        .any(|t| t.tx_hash == original && t.num_confirmations > 0);
+    if phantom {
+        return Err(format!("LND reports replaced transaction {} as confirmed", original));
+    }
+    Ok(format!("{} replaced by {}", original, replacement))
+}
+
+async fn test_cpfp_acceleration(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let node = &nodes[0];
+    let electrum = Electrum::local().await?;
+    bitcoind.ensure_funds(BUMP_AMOUNT_SAT * 2).await?;
+    bitcoind.mine(1).await?;
+
+    // A low-fee payment into the LND wallet, which the receiver accelerates
+    let address = node.new_address().await?;
+    let script = bitcoind.get_script_pubkey(&address).await?;
+    let parent = bitcoind
+        .send_with_fee_rate(&address, BUMP_AMOUNT_SAT, LOW_FEE_RATE)
+        .await?;
+    let vout = bitcoind.find_vout(&parent, &address).await?;
+    wait_lnd_converged(bitcoind, node, &parent, false).await?;
+
+    node.bump_fee(&parent, vout, BUMPED_FEE_RATE).await?;
+
+    // The child shows up as a descendant that lifts the package fee rate
+    let entry = wait_for("CPFP child in the mempool", CONVERGE_TIMEOUT, POLL_INTERVAL, || async {
+        let entry = bitcoind
+            .mempool_entry(&parent)
+            .await?
+            .ok_or_else(|| format!("Parent {} left the mempool", parent))?;
+        let has_child = entry["spentby"].as_array().is_some_and(|c| !c.is_empty());
+        Ok(has_child.then_some(entry))
+    })
+    .await?;
+    let child = entry["spentby"][0]
+        .as_str()
+        .ok_or_else(|| format!("Unexpected mempool entry: {}", entry))?
+        .to_string();
+    let fee_sat = |v: &serde_json::Value| v.as_f64().map(|btc| btc * SATS_PER_BTC as f64).unwrap_or(0.0);
+    let parent_rate = fee_sat(&entry["fees"]["base"]) / entry["vsize"].as_f64().unwrap_or(1.0);
+    let package_rate =
+        fee_sat(&entry["fees"]["descendant"]) / entry["descendantsize"].as_f64().unwrap_or(1.0);
+    if package_rate <= parent_rate {
+        return Err(format!(
+            "Package fee rate {:.2} sat/vB does not exceed parent rate {:.2} sat/vB",
+            package_rate, parent_rate
+        ));
+    }
+
+    // The child spends the parent's output, so electrs lists both for the script
+    wait_electrs_history(&electrum, &script, &[&parent, &child], &[]).await?;
+
+    bitcoind.mine(1).await?;
+    let parent_height = bitcoind.get_tx_height(&parent).await?;
+    let child_height = bitcoind.get_tx_height(&child).await?;
+    if parent_height.is_none() || parent_height != child_height {
+        return Err(format!(
+            "Parent confirmed at {:?} and child at {:?}, expected the same block",
+            parent_height, child_height
+        ));
+    }
+    wait_electrs_tip(bitcoind, &electrum).await?;
+    wait_electrs_tx_height(&electrum, &script, &parent, parent_height).await?;
+    wait_lnd_converged(bitcoind, node, &parent, true).await?;
+    Ok(format!("Package rate {:.2} sat/vB over parent {:.2} sat/vB", package_rate, parent_rate))
+}
+
+/// Fund a P2TR `address` from the bitcoind wallet, checking its shape on the
+/// way, and return the funding outpoint and output script.
+async fn fund_taproot_address(bitcoind: &Bitcoind, address: &str) -> Result<(String, u32, Vec<u8>), String> {
+    if !address.starts_with(REGTEST_TAPROOT_PREFIX) {
+        return Err(format!("{} is not a regtest bech32m address", address));
+    }
+    let script = bitcoind
+        .get_script_pubkey(address)
+        .await
+        .map_err(|e| format!("bitcoind cannot decode taproot address: {}", e))?;
+    // OP_1 followed by a 32-byte x-only key
+    if script.len() != 34 || script[0] != 0x51 || script[1] != 0x20 {
+        return Err(format!("{} has non-P2TR script {}", address, hex::encode(&script)));
+    }
+
+    let drip = Faucet::from_env(bitcoind)?
+        .fund(address, TAPROOT_AMOUNT_SAT, 1)
+        .await
+        .map_err(|e| format!("bitcoind failed to pay taproot address: {}", e))?;
+    Ok((drip.txid, drip.vout, script))
+}
+
+/// Confirm `spend` and check electrs indexes both sides of the taproot output.
+async fn verify_taproot_indexed(
+    bitcoind: &Bitcoind,
+    script: &[u8],
+    funding: &str,
+    spend: &str,
+) -> Result<(), String> {
+    bitcoind.mine(1).await?;
+    let funding_height = bitcoind.get_tx_height(funding).await?;
+    let spend_height = bitcoind.get_tx_height(spend).await?;
+    if funding_height.is_none() || spend_height.is_none() {
+        return Err(format!(
+            "Taproot funding confirmed at {:?} and spend at {:?}",
+            funding_height, spend_height
+        ));
+    }
+
+    let electrum = Electrum::local().await?;
+    let indexed = async {
+        wait_electrs_tip(bitcoind, &electrum).await?;
+        wait_electrs_history(&electrum, script, &[funding, spend], &[]).await?;
+        wait_electrs_tx_height(&electrum, script, funding, funding_height).await?;
+        wait_electrs_tx_height(&electrum, script, spend, spend_height).await
+    }
+    .await;
+    indexed.map_err(|e| format!("electrs does not track the taproot script: {}", e))
+}
+
+async fn test_taproot_bitcoind_wallet(Chain { bitcoind, .. }: &Chain) -> Outcome {
+    let address = bitcoind
+        .get_new_taproot_address()
+        .await
+        .map_err(|e| format!("bitcoind cannot generate a taproot address: {}", e))?;
+    let (funding, vout, script) = fund_taproot_address(bitcoind, &address).await?;
+
+    let destination = bitcoind.get_new_address().await?;
+    let spend = bitcoind
+        .sweep_outpoint(&funding, vout, &destination, TAPROOT_SPEND_FEE_RATE as f64)
+        .await
+        .map_err(|e| format!("bitcoind cannot spend its taproot output: {}", e))?;
+    verify_taproot_indexed(bitcoind, &script, &funding, &spend).await?;
+    Ok(format!("{} funded and spent in {}", address, spend))
+}
+
+async fn test_taproot_lnd_wallet(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let node = &nodes[0];
+    let address = node
+        .new_taproot_address()
+        .await
+        .map_err(|e| format!("LND cannot generate a taproot address: {}", e))?;
+    let (funding, vout, script) = fund_taproot_address(bitcoind, &address).await?;
+    wait_lnd_converged(bitcoind, node, &funding, true)
+        .await
+        .map_err(|e| format!("LND does not see its taproot deposit: {}", e))?;
+
+    let destination = bitcoind.get_new_address().await?;
+    let spend = node
+        .sweep_outpoint(&funding, vout, &destination, TAPROOT_SPEND_FEE_RATE)
+        .await
+        .map_err(|e| format!("LND cannot spend its taproot output: {}", e))?;
+    verify_taproot_indexed(bitcoind, &script, &funding, &spend).await?;
+    wait_lnd_converged(bitcoind, node, &spend, true)
+        .await
+        .map_err(|e| format!("LND does not see its taproot spend confirm: {}", e))?;
+    Ok(format!("{} funded and spent in {}", address, spend))
+}
+
+async fn test_fast_forward(Chain { bitcoind, nodes, .. }: &Chain) -> Outcome {
+    let node_refs: Vec<&Lnd> = nodes.iter().collect();
+    let jump = fast_forward(bitcoind, FAST_FORWARD_BLOCKS, Some(FAST_FORWARD_TIME), &node_refs).await?;
+    if jump.to_height != jump.from_height + FAST_FORWARD_BLOCKS {
+        return Err(format!(
+            "Fast-forward went from {} to {}, expected {} blocks",
+            jump.from_height, jump.to_height, FAST_FORWARD_BLOCKS
+        ));
+    }
+    // Median time past lags the tip by a few blocks, so allow half the jump
+    let moved = jump.to_median_time.saturating_sub(jump.from_median_time);
+    if moved < FAST_FORWARD_TIME.as_secs() / 2 {
+        return Err(format!(
+            "Chain time moved {}s, expected about {}s",
+            moved,
+            FAST_FORWARD_TIME.as_secs()
+        ));
+    }
+
+    // Mining must keep working once bitcoind is back on the wall clock
+    bitcoind.mine(1).await?;
+    for node in nodes {
+        let info = node.get_info().await?;
+        if (info.block_height as u64) < jump.to_height {
+            return Err(format!("{} stuck at {} after fast-forward", info.alias, info.block_height));
+        }
+    }
+    Ok(format!("Height {} -> {}, chain time +{}s", jump.from_height, jump.to_height, moved))
+}
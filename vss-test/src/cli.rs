@@ -0,0 +1,52 @@
+//! Command line of the test binaries, beyond what `test_harness::cli` declares
+//!
+//! `ConfigArgs` picks the settings file, overrides single settings and aims
+//! the run at a remote deployment or at the TLS front of the compose stack.
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::config::{self, Config};
+use crate::tls;
+
+/// Where the settings of `config` come from.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigArgs {
+    /// Read settings from this file instead of vss-test.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Override one setting, e.g. --set vss.url=http://localhost:5050
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub set: Vec<String>,
+    /// Run against the remote vss-server at this URL instead of compose; the
+    /// rest of its settings are the [target] ones
+    #[arg(long, value_name = "URL")]
+    pub target_url: Option<String>,
+    /// Send VSS requests over HTTPS, through the vss-tls front of the compose
+    /// stack, trusting the CA generated for it
+    #[arg(long, conflicts_with = "target_url")]
+    pub tls: bool,
+}
+
+impl ConfigArgs {
+    /// Load the settings and make them the ones `config::get` returns.
+    pub fn apply(&self) -> Result<(), String> {
+        let mut overrides = self.set.clone();
+        if let Some(url) = &self.target_url {
+            overrides.push(format!("target.url={}", url));
+        }
+        if self.tls {
+            // Before anything else, so --set still wins over these
+            tls::prepare(false)?;
+            overrides.splice(
+                0..0,
+                [
+                    format!("vss.url={}", tls::VALID_URL),
+                    format!("vss.ca_cert_path={}", tls::CA_CERT_PATH),
+                ],
+            );
+        }
+        config::init(Config::load(self.config.as_deref(), &overrides)?)
+    }
+}
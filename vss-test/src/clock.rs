@@ -0,0 +1,93 @@
+//! Fast-forwarding chain height and chain time for expiry scenarios
+//!
+//! Block-height expiries (CLTV/CSV, HTLC timeouts) are reached by mining. Chain
+//! time can only be moved through bitcoind's mock clock: every container shares
+//! the host kernel clock, so LND invoice expiry and Blocktank order expiry keep
+//! following wall-clock time and need short expiries instead.
+
+use std::time::{Duration, SystemTime};
+
+use crate::bitcoind::Bitcoind;
+use crate::lnd::Lnd;
+
+// generatetoaddress batch size, keeping each RPC call short
+const MINE_BATCH: u64 = 100;
+
+/// bitcoind rejects blocks more than two hours ahead of its clock; chain time
+/// may not run further ahead than this, or mining breaks once the mock clock
+/// is released.
+pub const MAX_CHAIN_TIME_AHEAD: Duration = Duration::from_secs(90 * 60);
+
+#[derive(Debug)]
+pub struct FastForward {
+    pub from_height: u64,
+    pub to_height: u64,
+    /// Median time past of the tip before and after, in unix seconds.
+    pub from_median_time: u64,
+    pub to_median_time: u64,
+}
+
+/// Mine `blocks` blocks, optionally spreading `advance` of chain time across
+/// them, then wait for `nodes` to catch up.
+pub async fn fast_forward(
+    bitcoind: &Bitcoind,
+    blocks: u64,
+    advance: Option<Duration>,
+    nodes: &[&Lnd],
+) -> Result<FastForward, String> {
+    let from_height = bitcoind.get_block_count().await?;
+    let from_median_time = bitcoind.get_median_time().await?;
+
+    match advance {
+        None => {
+            let mut remaining = blocks;
+            while remaining > 0 {
+                let batch = remaining.min(MINE_BATCH);
+                bitcoind.mine(batch).await?;
+                remaining -= batch;
+            }
+        }
+        Some(advance) => {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_err(|e| format!("System clock before epoch: {:?}", e))?
+                .as_secs();
+            let start = now.max(from_median_time);
+            let target = start + advance.as_secs();
+            if target > now + MAX_CHAIN_TIME_AHEAD.as_secs() {
+                return Err(format!(
+                    "Cannot move chain time {:?} ahead of the wall clock (limit {:?})",
+                    Duration::from_secs(target - now),
+                    MAX_CHAIN_TIME_AHEAD
+                ));
+            }
+            if blocks == 0 {
+                return Err("Advancing chain time needs at least one block".to_string());
+            }
+
+            // One mock-clock step per block so timestamps rise evenly
+            let step = advance.as_secs() / blocks;
+            let mined: Result<(), String> = async {
+                for i in 1..=blocks {
+                    bitcoind.set_mock_time(start + step * i).await?;
+                    bitcoind.mine(1).await?;
+                }
+                Ok(())
+            }
+            .await;
+            // Always hand bitcoind its wall clock back
+            bitcoind.set_mock_time(0).await?;
+            mined?;
+        }
+    }
+
+    for node in nodes {
+        node.wait_synced(bitcoind).await?;
+    }
+    Ok(FastForward {
+        from_height,
+        to_height: bitcoind.get_block_count().await?,
+        from_median_time,
+        to_median_time: bitcoind.get_median_time().await?,
+    })
+}
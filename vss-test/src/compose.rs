@@ -0,0 +1,51 @@
+//! Lifecycle control of docker-compose.yml services through the docker CLI
+//!
+//! Test binaries run from `vss-test`, so the compose project is the parent directory.
+
+use std::process::Command;
+
+pub const COMPOSE_DIR: &str = "..";
+
+/// Run `docker compose <args>` in the project directory, returning stdout.
+pub fn compose(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("docker")
+        .arg("compose")
+        .args(args)
+        .current_dir(COMPOSE_DIR)
+        .output()
+        .map_err(|e| format!("Failed to run docker compose {}: {:?}", args.join(" "), e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "docker compose {} exited with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Stop and remove a service's container, discarding everything not on a volume.
+pub fn destroy_service(service: &str) -> Result<(), String> {
+    compose(&["rm", "--stop", "--force", service]).map(|_| ())
+}
+
+/// Create (if needed) and start a service.
+pub fn start_service(service: &str) -> Result<(), String> {
+    compose(&["up", "--detach", service]).map(|_| ())
+}
+
+/// Stop a service's container, keeping it and its state around.
+pub fn stop_service(service: &str) -> Result<(), String> {
+    compose(&["stop", service]).map(|_| ())
+}
+
+/// Copy `path` out of a service's container (running or stopped) to `dest` on the host.
+pub fn copy_from_service(service: &str, path: &str, dest: &str) -> Result<(), String> {
+    compose(&["cp", &format!("{}:{}", service, path), dest]).map(|_| ())
+}
+
+/// Copy the host file `src` into a service's container (running or stopped) at `path`.
+pub fn copy_to_service(src: &str, service: &str, path: &str) -> Result<(), String> {
+    compose(&["cp", src, &format!("{}:{}", service, path)]).map(|_| ())
+}
@@ -0,0 +1,388 @@
+//! Settings shared by the test binaries
+//!
+//! Service URLs, key paths, credentials and timeouts come from `vss-test.toml`
+//! in the working directory (or the file `VSS_TEST_CONFIG` names), falling back
+//! to the compose defaults for anything it leaves out. A missing file is fine;
+//! a malformed one or an unknown key is an error, so typos do not go unnoticed.
+//!
+//! Each setting can also come from the environment variable `ENV_OVERRIDES`
+//! names, so one binary runs unchanged in compose, CI and against a remote
+//! stack. Compose's `.env` is loaded first (see `harness_docker::env_file`), so
+//! the variables can live there next to the ones compose reads. Binaries taking `ConfigArgs` accept `--config <path>` and
+//! `--set <key>=<value>`. The first source that has a setting wins:
+//!
+//!   --set  >  environment  >  config file  >  default
+//!
+//! Setting `target.url` (or `--target-url`) aims the whole run at a remote
+//! deployment such as staging: its VSS and LNURL URLs and signing key replace
+//! the `vss` and `lnurl` ones, so the compose credentials are never sent
+//! there, and clients also trust the CA in `target.ca_cert_path` for its TLS.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use harness_docker::env_file;
+use harness_docker::notify::Notifier;
+use reqwest::{Certificate, Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::bitcoind::{BITCOIND_RPC_PASS, BITCOIND_RPC_URL, BITCOIND_RPC_USER};
+use crate::electrum::ELECTRUM_ADDR;
+use crate::lnd::{LND_A_MACAROON_PATH, LND_A_REST_URL, LND_B_MACAROON_PATH, LND_B_REST_URL};
+use crate::vss::{VSS_PUBLIC_KEY_PATH, VSS_SIGNING_KEY_PATH, VSS_STORE_ID};
+
+pub const CONFIG_FILE: &str = "vss-test.toml";
+pub const CONFIG_FILE_ENV: &str = "VSS_TEST_CONFIG";
+
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_WEBHOOK_KIND: &str = "generic";
+
+/// Environment variable for each setting, as `(variable, <section>.<key>)`.
+pub const ENV_OVERRIDES: [(&str, &str); 22] = [
+    ("VSS_URL", "vss.url"),
+    ("VSS_CA_CERT_PATH", "vss.ca_cert_path"),
+    ("JWT_PRIVATE_KEY_PATH", "vss.signing_key_path"),
+    ("JWT_PUBLIC_KEY_PATH", "vss.public_key_path"),
+    ("VSS_STORE_ID", "vss.store_id"),
+    ("LNURL_URL", "lnurl.url"),
+    ("BITCOIND_RPC_URL", "bitcoind.rpc_url"),
+    ("BITCOIND_RPC_USER", "bitcoind.rpc_user"),
+    ("BITCOIND_RPC_PASSWORD", "bitcoind.rpc_password"),
+    ("LND_A_REST_URL", "lnd.node_a_rest_url"),
+    ("LND_A_MACAROON_PATH", "lnd.node_a_macaroon_path"),
+    ("LND_B_REST_URL", "lnd.node_b_rest_url"),
+    ("LND_B_MACAROON_PATH", "lnd.node_b_macaroon_path"),
+    ("ELECTRUM_ADDR", "electrum.addr"),
+    ("REQUEST_TIMEOUT_SECS", "timeouts.request_secs"),
+    ("NOTIFY_WEBHOOK_URL", "notify.webhook_url"),
+    ("NOTIFY_WEBHOOK_KIND", "notify.kind"),
+    ("NOTIFY_ARTIFACTS_URL", "notify.artifacts_url"),
+    ("TARGET_URL", "target.url"),
+    ("TARGET_LNURL_URL", "target.lnurl_url"),
+    ("TARGET_SIGNING_KEY_PATH", "target.signing_key_path"),
+    ("TARGET_CA_CERT_PATH", "target.ca_cert_path"),
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub vss: VssConfig,
+    pub lnurl: LnurlConfig,
+    pub bitcoind: BitcoindConfig,
+    pub lnd: LndConfig,
+    pub electrum: ElectrumConfig,
+    pub timeouts: Timeouts,
+    pub notify: NotifyConfig,
+    pub target: TargetConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VssConfig {
+    /// Base URL of vss-server; unset means the host port of the compose service.
+    pub url: Option<String>,
+    /// Private key JWTs are signed with, as lnurl-server does.
+    pub signing_key_path: String,
+    /// Public half of the signing key, which VSS verifies tokens with.
+    pub public_key_path: String,
+    /// Store the token checks list keys of.
+    pub store_id: String,
+    /// PEM CA vss-server's TLS certificate is checked against, besides the
+    /// system roots, as the one `--tls` generates.
+    pub ca_cert_path: Option<String>,
+}
+
+impl Default for VssConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            signing_key_path: VSS_SIGNING_KEY_PATH.to_string(),
+            public_key_path: VSS_PUBLIC_KEY_PATH.to_string(),
+            store_id: VSS_STORE_ID.to_string(),
+            ca_cert_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LnurlConfig {
+    /// Base URL of lnurl-auth-server; unset means the host port of the compose service.
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BitcoindConfig {
+    pub rpc_url: String,
+    pub rpc_user: String,
+    pub rpc_password: String,
+}
+
+impl Default for BitcoindConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: BITCOIND_RPC_URL.to_string(),
+            rpc_user: BITCOIND_RPC_USER.to_string(),
+            rpc_password: BITCOIND_RPC_PASS.to_string(),
+        }
+    }
+}
+
+/// REST endpoints and admin macaroons of the two default nodes.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LndConfig {
+    pub node_a_rest_url: String,
+    pub node_a_macaroon_path: String,
+    pub node_b_rest_url: String,
+    pub node_b_macaroon_path: String,
+}
+
+impl Default for LndConfig {
+    fn default() -> Self {
+        Self {
+            node_a_rest_url: LND_A_REST_URL.to_string(),
+            node_a_macaroon_path: LND_A_MACAROON_PATH.to_string(),
+            node_b_rest_url: LND_B_REST_URL.to_string(),
+            node_b_macaroon_path: LND_B_MACAROON_PATH.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ElectrumConfig {
+    pub addr: String,
+}
+
+impl Default for ElectrumConfig {
+    fn default() -> Self {
+        Self {
+            addr: ELECTRUM_ADDR.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// Give up on a single HTTP request to VSS or the auth server after this long.
+    pub request_secs: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            request_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// Where failed runs are posted (see `harness_docker::notify`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Webhook failed runs are posted to; unset posts nothing.
+    pub webhook_url: Option<String>,
+    /// `slack`, `discord` or `generic`, the body the webhook expects.
+    pub kind: String,
+    /// Link to the run's artifacts; unset means the GitHub Actions run, if any.
+    pub artifacts_url: Option<String>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            kind: DEFAULT_WEBHOOK_KIND.to_string(),
+            artifacts_url: None,
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// The notifier to give `Run`, if a webhook is set.
+    pub fn notifier(&self) -> Result<Option<Notifier>, String> {
+        let Some(url) = self.webhook_url.as_ref().filter(|url| !url.is_empty()) else {
+            return Ok(None);
+        };
+        Ok(Some(Notifier {
+            url: url.clone(),
+            kind: self
+                .kind
+                .parse()
+                .map_err(|e| format!("notify.kind: {}", e))?,
+            artifacts_url: self.artifacts_url.clone(),
+        }))
+    }
+}
+
+/// A remote deployment to run against instead of the compose stack.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetConfig {
+    /// Base URL of the remote vss-server; unset runs against compose.
+    pub url: Option<String>,
+    /// Base URL of the remote lnurl-auth-server; unset skips the LNURL checks.
+    pub lnurl_url: Option<String>,
+    /// Private key the remote VSS trusts tokens signed with.
+    pub signing_key_path: Option<String>,
+    /// PEM CA the remote's TLS certificates are checked against, besides the
+    /// system roots.
+    pub ca_cert_path: Option<String>,
+}
+
+impl Config {
+    /// Whether the run targets a remote deployment rather than compose.
+    pub fn remote(&self) -> bool {
+        self.target.url.is_some()
+    }
+
+    /// Settings from `path`, or from the default file if there is one, with the
+    /// `ENV_OVERRIDES` variables and then `overrides` (`<section>.<key>=<value>`)
+    /// applied on top.
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Self, String> {
+        env_file::load()?;
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var(CONFIG_FILE_ENV).ok().map(PathBuf::from));
+        let mut table = match path {
+            Some(path) => read_table(&path)?,
+            None if Path::new(CONFIG_FILE).exists() => read_table(Path::new(CONFIG_FILE))?,
+            None => Table::new(),
+        };
+        for (var, key) in ENV_OVERRIDES {
+            if let Ok(value) = std::env::var(var) {
+                set(&mut table, key, typed_value(key, &value))?;
+            }
+        }
+        for entry in overrides {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Expected <key>=<value>, got {:?}", entry))?;
+            let key = key.trim();
+            set(&mut table, key, typed_value(key, value))?;
+        }
+        let mut config: Self = Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("Invalid settings: {}", e))?;
+        config.aim_at_target()?;
+        Ok(config)
+    }
+
+    /// Replace the compose URLs and signing key with the target's, if set.
+    fn aim_at_target(&mut self) -> Result<(), String> {
+        let Some(url) = self.target.url.clone() else {
+            return Ok(());
+        };
+        // The compose key must not be taken for the target's by accident
+        let signing_key_path = self.target.signing_key_path.clone().ok_or_else(|| {
+            "target.signing_key_path must be set along with target.url: \
+             a remote VSS does not trust the compose signing key"
+                .to_string()
+        })?;
+        self.root_certificates()?;
+        self.vss.url = Some(url);
+        self.vss.signing_key_path = signing_key_path;
+        self.lnurl.url = self.target.lnurl_url.clone();
+        Ok(())
+    }
+
+    /// The files of `vss.ca_cert_path` and `target.ca_cert_path`, for clients
+    /// that do not go through `http_client`.
+    pub fn ca_cert_paths(&self) -> impl Iterator<Item = &String> {
+        [&self.vss.ca_cert_path, &self.target.ca_cert_path]
+            .into_iter()
+            .flatten()
+    }
+
+    /// The CAs of `vss.ca_cert_path` and `target.ca_cert_path`.
+    fn root_certificates(&self) -> Result<Vec<Certificate>, String> {
+        self.ca_cert_paths()
+            .map(|path| {
+                let pem =
+                    fs::read(path).map_err(|e| format!("Failed to read {}: {:?}", path, e))?;
+                Certificate::from_pem(&pem)
+                    .map_err(|e| format!("Failed to parse CA certificate {}: {:?}", path, e))
+            })
+            .collect()
+    }
+}
+
+/// HTTP client for the services under test, giving up on a request after
+/// `timeout`; it trusts `vss.ca_cert_path` and `target.ca_cert_path` as well
+/// as the system roots.
+pub fn http_client(timeout: Duration) -> Result<Client, String> {
+    http_client_builder(timeout)?
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {:?}", e))
+}
+
+/// The builder of `http_client`, for clients that need more settings.
+pub fn http_client_builder(timeout: Duration) -> Result<ClientBuilder, String> {
+    let mut builder = Client::builder().timeout(timeout);
+    for certificate in get().root_certificates()? {
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder)
+}
+
+/// Make `config` the settings `get` returns; fails once they are in use.
+pub fn init(config: Config) -> Result<(), String> {
+    CONFIG
+        .set(config)
+        .map_err(|_| "Settings were already loaded".to_string())
+}
+
+/// The settings of this run, loaded from the default file on first use unless
+/// `init` came first.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::load(None, &[]).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+fn read_table(path: &Path) -> Result<Table, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {:?}", path.display(), e))?;
+    text.parse()
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Set the dotted `key` in `table`, creating the sections on the way.
+fn set(table: &mut Table, key: &str, value: Value) -> Result<(), String> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().filter(|last| !last.is_empty());
+    let last = last.ok_or_else(|| format!("Invalid setting name {:?}", key))?;
+    let mut section = table;
+    for part in parts {
+        let entry = section
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        section = entry
+            .as_table_mut()
+            .ok_or_else(|| format!("Setting {:?}: {} is not a section", key, part))?;
+    }
+    section.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// `raw` typed like the default of `key`: parsed where that is a number or a
+/// boolean, kept as a string otherwise, so URLs, paths and passwords made of
+/// digits need no quoting.
+fn typed_value(key: &str, raw: &str) -> Value {
+    let mut default = Value::try_from(Config::default()).ok();
+    for part in key.split('.') {
+        default = default.and_then(|section| section.get(part).cloned());
+    }
+    let parsed = match default {
+        Some(Value::Integer(_)) => raw.parse().ok().map(Value::Integer),
+        Some(Value::Boolean(_)) => raw.parse().ok().map(Value::Boolean),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::String(raw.to_string()))
+}
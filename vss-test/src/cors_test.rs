@@ -0,0 +1,442 @@
+//! CORS Integration Test Binary
+//!
+//! Checks the CORS policy of VSS and lnurl-server is what browser-based
+//! tooling needs and nothing more. Each check runs for a spread of `Origin`
+//! values, from a page of lnurl-server itself to an unknown site and the
+//! `null` origin of a sandboxed page:
+//!
+//! - VSS: only the Bitkit apps and server-side tooling call it, never a
+//!   browser page, so neither a preflight nor a cross-origin POST may get any
+//!   `Access-Control-Allow-*` header back
+//! - lnurl-server: LNURL endpoints must answer any origin (LUD-01), so web
+//!   wallets and decoders get `Access-Control-Allow-Origin: *` on GETs and on
+//!   JSON POSTs, and a preflight for either. Never the origin reflected, never
+//!   credentials, and a preflight for another method or for headers other
+//!   than Content-Type is not allowed either
+//!
+//!   cargo run --bin cors_test
+//!
+//! Against a remote target the lnurl-server checks are left out, unless
+//! `--lnurl-server-url` names one.
+
+use std::time::Duration;
+
+use clap::Parser;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::DockerEnv;
+use prost::Message;
+use reqwest::{Client, Method, RequestBuilder, Response};
+use serde_json::json;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, SuiteArgs};
+use test_harness::runner::Tag;
+use test_harness::test_cases;
+use vss_client::types::GetObjectRequest;
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::vss::{local_url, sign_token};
+
+const SUBJECT: &str = "cors-test";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const LNURL_SERVER_SERVICE: &str = "lnurl-server";
+const LNURL_SERVER_PORT: u16 = 3000;
+
+// A page of lnurl-server itself, a site Bitkit runs, an unknown site, and a
+// sandboxed page or local file
+const ORIGINS: [&str; 4] = [
+    "http://localhost:3000",
+    "https://bitkit.to",
+    "https://evil.example",
+    "null",
+];
+// What lnurl-server's routes take, and so all a preflight may allow
+const LNURL_METHODS: [&str; 3] = ["GET", "HEAD", "POST"];
+const LNURL_HEADERS: [&str; 1] = ["content-type"];
+// Asked for in preflights, none of which lnurl-server may allow
+const UNNEEDED_METHODS: [&str; 3] = ["PUT", "PATCH", "DELETE"];
+const UNNEEDED_HEADERS: [&str; 2] = ["authorization", "x-requested-with"];
+
+#[derive(Parser)]
+#[command(about = "Check the CORS policy of VSS and lnurl-server")]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    /// lnurl-server to check instead of the compose one
+    #[arg(long)]
+    lnurl_server_url: Option<String>,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// The services under test and the client that calls them.
+struct Targets {
+    client: Client,
+    vss_url: String,
+    lnurl_server_url: Option<String>,
+}
+
+/// The CORS headers of a response.
+struct Cors {
+    status: u16,
+    allow_origin: Option<String>,
+    allow_credentials: Option<String>,
+    allow_methods: Option<String>,
+    allow_headers: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let mut run = Run::start();
+    let mut cli = Cli::parse();
+    if let Err(e) = setup(&cli, &mut run) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let cases = test_cases!(Targets;
+        #[tags(Vss)] test_vss_preflight_refused,
+        #[tags(Vss)] test_vss_cross_origin_post_not_shared,
+        #[tags(Lnurl)] test_lnurl_server_preflight,
+        #[tags(Lnurl)] test_lnurl_server_preflight_limited,
+        #[tags(Lnurl)] test_lnurl_server_cross_origin_get,
+        #[tags(Lnurl)] test_lnurl_server_cross_origin_post,
+    );
+    // A remote target has no lnurl-server of its own to find
+    let lnurl_left_out = cli.lnurl_server_url.is_none() && config::get().remote();
+    if lnurl_left_out {
+        cli.filter.exclude.push(Tag::Lnurl);
+    }
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("CORS Integration Test");
+    report.say("");
+
+    let urls = async {
+        let vss = local_url().await?;
+        let lnurl_server = match &cli.lnurl_server_url {
+            Some(url) => Some(url.clone()),
+            None if lnurl_left_out => None,
+            None => Some(
+                DockerEnv::local()?
+                    .service_url(LNURL_SERVER_SERVICE, LNURL_SERVER_PORT, "http")
+                    .await?,
+            ),
+        };
+        Ok::<_, String>((vss, lnurl_server))
+    }
+    .await;
+    let (vss_url, lnurl_server_url) = match urls {
+        Ok(urls) => urls,
+        Err(e) => {
+            let e = format!("Services not found: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let client = match config::http_client(REQUEST_TIMEOUT) {
+        Ok(client) => client,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+    };
+    report.say(&format!("VSS:          {}", vss_url));
+    match &lnurl_server_url {
+        Some(url) => report.say(&format!("lnurl-server: {}", url)),
+        None => report.say("lnurl-server: left out, as the target has none"),
+    }
+    report.say("");
+
+    let targets = Targets {
+        client,
+        vss_url,
+        lnurl_server_url,
+    };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &targets, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    let code = run
+        .finish(passed, failed, Some(report.failed_cases()))
+        .await;
+    std::process::exit(code);
+}
+
+/// Settle the settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    cli.suite.init(&cli.filter)?;
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// The lnurl-server to check; only cases tagged `Lnurl` ask, and those are
+/// left out when there is none.
+fn lnurl_url(targets: &Targets) -> Result<&str, String> {
+    targets
+        .lnurl_server_url
+        .as_deref()
+        .ok_or_else(|| "No lnurl-server to check".to_string())
+}
+
+/// A preflight for a POST with the headers a VSS client sends gets nothing
+/// allowed, from any origin.
+async fn test_vss_preflight_refused(targets: &Targets) -> Outcome {
+    let Targets {
+        client, vss_url, ..
+    } = targets;
+    let url = format!("{}/vss/getObject", vss_url);
+    let mut statuses = Vec::new();
+    for origin in ORIGINS {
+        let cors = preflight(client, &url, origin, "POST", "authorization, content-type").await?;
+        if let Some(granted) = cors.granted() {
+            return Err(format!(
+                "the preflight from {} got {} back",
+                origin, granted
+            ));
+        }
+        statuses.push(cors.status);
+    }
+    Ok(format!("answered {}", statuses_text(&statuses)))
+}
+
+/// A POST sent cross-origin anyway, with a valid token, is not shared with
+/// the page, from any origin.
+async fn test_vss_cross_origin_post_not_shared(targets: &Targets) -> Outcome {
+    let Targets {
+        client, vss_url, ..
+    } = targets;
+    let url = format!("{}/vss/getObject", vss_url);
+    let token = sign_token(&config::get().vss.signing_key_path, SUBJECT)?;
+    let body = GetObjectRequest {
+        store_id: unique_id("cors"),
+        key: "cors".to_string(),
+    }
+    .encode_to_vec();
+    let mut statuses = Vec::new();
+    for origin in ORIGINS {
+        let request = client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/x-protobuf")
+            .body(body.clone());
+        let cors = cross_origin(request, origin).await?;
+        if let Some(granted) = cors.granted() {
+            return Err(format!("the POST from {} got {} back", origin, granted));
+        }
+        statuses.push(cors.status);
+    }
+    Ok(format!("answered {}", statuses_text(&statuses)))
+}
+
+/// A preflight for a JSON POST is allowed for any origin, as `*`.
+async fn test_lnurl_server_preflight(targets: &Targets) -> Outcome {
+    let client = &targets.client;
+    let url = format!("{}/decode/lnurl/encode", lnurl_url(targets)?);
+    for origin in ORIGINS {
+        let cors = preflight(client, &url, origin, "POST", "content-type").await?;
+        let fail = |e: String| format!("the preflight from {}: {}", origin, e);
+        if !(200..300).contains(&cors.status) {
+            return Err(fail(format!("answered {}", cors.status)));
+        }
+        cors.allows_any_origin().map_err(fail)?;
+        let methods = cors.allow_methods.clone().unwrap_or_default();
+        if !listed(&methods, "POST") {
+            return Err(fail(format!("POST is not allowed, only {:?}", methods)));
+        }
+        let headers = cors.allow_headers.clone().unwrap_or_default();
+        if !listed(&headers, "content-type") {
+            return Err(fail(format!(
+                "Content-Type is not allowed, only {:?}",
+                headers
+            )));
+        }
+    }
+    Ok(format!(
+        "{} origin(s) allowed POST with Content-Type",
+        ORIGINS.len()
+    ))
+}
+
+/// Preflights for methods and headers no route takes get none of them
+/// allowed, nor anything beyond what the routes take.
+async fn test_lnurl_server_preflight_limited(targets: &Targets) -> Outcome {
+    let client = &targets.client;
+    let url = format!("{}/decode/lnurl/encode", lnurl_url(targets)?);
+    for origin in ORIGINS {
+        for method in UNNEEDED_METHODS {
+            let cors = preflight(client, &url, origin, method, "content-type").await?;
+            let methods = cors.allow_methods.clone().unwrap_or_default();
+            if let Some(extra) = beyond(&methods, &LNURL_METHODS) {
+                return Err(format!(
+                    "the {} preflight from {} got {} allowed ({:?})",
+                    method, origin, extra, methods
+                ));
+            }
+        }
+        for header in UNNEEDED_HEADERS {
+            let cors = preflight(client, &url, origin, "POST", header).await?;
+            let headers = cors.allow_headers.clone().unwrap_or_default();
+            if let Some(extra) = beyond(&headers, &LNURL_HEADERS) {
+                return Err(format!(
+                    "the preflight for {} from {} got {} allowed ({:?})",
+                    header, origin, extra, headers
+                ));
+            }
+        }
+    }
+    Ok(format!(
+        "{} and {} refused for {} origin(s)",
+        UNNEEDED_METHODS.join(", "),
+        UNNEEDED_HEADERS.join(", "),
+        ORIGINS.len()
+    ))
+}
+
+/// A cross-origin GET is shared with any origin, as `*`.
+async fn test_lnurl_server_cross_origin_get(targets: &Targets) -> Outcome {
+    lnurl_cross_origin(targets, Method::GET, "/health").await
+}
+
+/// A cross-origin JSON POST is shared with any origin, as `*`.
+async fn test_lnurl_server_cross_origin_post(targets: &Targets) -> Outcome {
+    lnurl_cross_origin(targets, Method::POST, "/decode/lnurl/encode").await
+}
+
+/// A cross-origin request to `path` is shared with any origin, as `*`.
+async fn lnurl_cross_origin(targets: &Targets, method: Method, path: &str) -> Outcome {
+    let client = &targets.client;
+    let url = format!("{}{}", lnurl_url(targets)?, path);
+    let mut statuses = Vec::new();
+    for origin in ORIGINS {
+        let mut request = client.request(method.clone(), &url);
+        if method == Method::POST {
+            request = request.json(&json!({ "url": "https://example.com/lnurl" }));
+        }
+        let cors = cross_origin(request, origin).await?;
+        if cors.status >= 500 {
+            return Err(format!(
+                "the {} from {} got {}",
+                method, origin, cors.status
+            ));
+        }
+        cors.allows_any_origin()
+            .map_err(|e| format!("the {} from {}: {}", method, origin, e))?;
+        statuses.push(cors.status);
+    }
+    Ok(format!(
+        "answered {} with Access-Control-Allow-Origin: *",
+        statuses_text(&statuses)
+    ))
+}
+
+/// Send a CORS preflight for `method` with `headers` from `origin`.
+async fn preflight(
+    client: &Client,
+    url: &str,
+    origin: &str,
+    method: &str,
+    headers: &str,
+) -> Result<Cors, String> {
+    let request = client
+        .request(Method::OPTIONS, url)
+        .header("Access-Control-Request-Method", method)
+        .header("Access-Control-Request-Headers", headers);
+    cross_origin(request, origin).await
+}
+
+/// Send `request` as a page of `origin` would.
+async fn cross_origin(request: RequestBuilder, origin: &str) -> Result<Cors, String> {
+    let response = request
+        .header("Origin", origin)
+        .send()
+        .await
+        .map_err(|e| format!("Request from {} failed: {:?}", origin, e))?;
+    Ok(Cors::from(&response))
+}
+
+impl Cors {
+    fn from(response: &Response) -> Self {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+        };
+        Self {
+            status: response.status().as_u16(),
+            allow_origin: header("Access-Control-Allow-Origin"),
+            allow_credentials: header("Access-Control-Allow-Credentials"),
+            allow_methods: header("Access-Control-Allow-Methods"),
+            allow_headers: header("Access-Control-Allow-Headers"),
+        }
+    }
+
+    /// The first `Access-Control-Allow-*` header there is, as `name: value`.
+    fn granted(&self) -> Option<String> {
+        [
+            ("Access-Control-Allow-Origin", &self.allow_origin),
+            ("Access-Control-Allow-Credentials", &self.allow_credentials),
+            ("Access-Control-Allow-Methods", &self.allow_methods),
+            ("Access-Control-Allow-Headers", &self.allow_headers),
+        ]
+        .into_iter()
+        .find_map(|(name, value)| Some(format!("{}: {}", name, value.as_ref()?)))
+    }
+
+    /// Any origin is allowed as `*`, which browsers never send cookies for,
+    /// rather than by echoing it back, and credentials are not.
+    fn allows_any_origin(&self) -> Result<(), String> {
+        match self.allow_origin.as_deref() {
+            Some("*") => {}
+            Some(origin) => {
+                return Err(format!(
+                    "Access-Control-Allow-Origin is {:?}, not *",
+                    origin
+                ))
+            }
+            None => return Err("no Access-Control-Allow-Origin".to_string()),
+        }
+        if let Some(credentials) = &self.allow_credentials {
+            return Err(format!(
+                "Access-Control-Allow-Credentials is {:?}",
+                credentials
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether the comma-separated `list` holds `item`, whatever its case.
+fn listed(list: &str, item: &str) -> bool {
+    list.split(',')
+        .any(|entry| entry.trim().eq_ignore_ascii_case(item))
+}
+
+/// The first entry of the comma-separated `list` not in `allowed`.
+fn beyond(list: &str, allowed: &[&str]) -> Option<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .find(|entry| !allowed.iter().any(|item| item.eq_ignore_ascii_case(entry)))
+        .map(str::to_string)
+}
+
+/// `statuses`, or the one status they all share.
+fn statuses_text(statuses: &[u16]) -> String {
+    match statuses.first() {
+        Some(first) if statuses.iter().all(|status| status == first) => first.to_string(),
+        _ => format!("{:?}", statuses),
+    }
+}
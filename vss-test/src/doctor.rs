@@ -0,0 +1,338 @@
+//! Telling environment problems from test failures
+//!
+//! Most failing runs on a new machine fail for reasons outside the suites:
+//! Docker is not running, a container exited, a port is taken, a macaroon is
+//! only readable by root, or the Docker VM's clock drifted while the laptop
+//! slept and every token is "not yet valid". `examine` looks for each of these
+//! and says how to fix what it finds:
+//!
+//! - Docker: the daemon answers
+//! - containers: every service of the profile has a running, healthy container
+//! - ports: the services answer their readiness probe at the host port Docker
+//!   mapped
+//! - key files: the signing key, its public half and, for profiles with LND,
+//!   the macaroons the settings name exist and are readable, and the private
+//!   key is not readable by everyone
+//! - clock: the containers' clock agrees with the host's
+//!
+//! Checks needing Docker are skipped when it is not reachable.
+
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use harness_docker::profile::{Profile, PROFILES};
+use harness_docker::readiness::Readiness;
+use harness_docker::DockerEnv;
+
+use crate::config::Config;
+
+// Worth knowing about, though no token check fails yet
+const CLOCK_WARN_SKEW: Duration = Duration::from_secs(5);
+// jsonwebtoken's default leeway; further off, fresh tokens are rejected
+const CLOCK_FAIL_SKEW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Failed,
+}
+
+/// The outcome of one check.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub check: String,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    pub fn ok(check: &str, detail: impl Into<String>) -> Self {
+        Self {
+            check: check.to_string(),
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    pub fn warning(check: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warning,
+            fix: Some(fix.into()),
+            ..Self::ok(check, detail)
+        }
+    }
+
+    pub fn failed(check: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Failed,
+            fix: Some(fix.into()),
+            ..Self::ok(check, detail)
+        }
+    }
+
+    /// The finding as printed: a status column, the check and its detail,
+    /// then the fix indented below.
+    pub fn render(&self) -> String {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Failed => "FAIL",
+        };
+        let mut text = format!("{:<5} {:<28} {}\n", status, self.check, self.detail);
+        if let Some(fix) = &self.fix {
+            text.push_str(&format!("{:<5} fix: {}\n", "", fix));
+        }
+        text
+    }
+}
+
+/// Every check against the services of `profile` and the key files `config`
+/// names, in the order above.
+pub async fn examine(profile: &Profile, config: &Config) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let services = required_services(profile);
+    match docker().await {
+        Ok((env, version)) => {
+            findings.push(Finding::ok(
+                "docker",
+                format!("Docker {} (project {})", version, env.project()),
+            ));
+            findings.extend(containers(&env, profile, &services).await);
+            findings.extend(ports(&env, &services).await);
+            findings.extend(keys(profile, config));
+            findings.push(clock(&env, &services).await);
+        }
+        Err(e) => {
+            findings.push(Finding::failed(
+                "docker",
+                e,
+                "start Docker (Docker Desktop, or `sudo systemctl start docker`); if it runs, \
+                 add your user to the docker group or point DOCKER_HOST at its socket",
+            ));
+            findings.extend(keys(profile, config));
+        }
+    }
+    findings
+}
+
+/// The services `profile` starts; for one starting everything, those of the
+/// profiles that need no compose profile enabled.
+fn required_services(profile: &Profile) -> Vec<&'static str> {
+    if !profile.services.is_empty() {
+        return profile.services.to_vec();
+    }
+    let mut services = Vec::new();
+    let defaults = PROFILES
+        .iter()
+        .filter(|p| !p.services.is_empty() && p.compose_profiles.is_empty());
+    for service in defaults.flat_map(|p| p.services.iter().copied()) {
+        if !services.contains(&service) {
+            services.push(service);
+        }
+    }
+    services
+}
+
+async fn docker() -> Result<(DockerEnv, String), String> {
+    let env = DockerEnv::local()?;
+    let version = env
+        .docker()
+        .version()
+        .await
+        .map_err(|e| format!("Docker does not answer: {:?}", e))?;
+    Ok((env, version.version.unwrap_or_else(|| "?".to_string())))
+}
+
+/// `docker compose up` for `services`, with the compose profiles `profile`
+/// enables.
+fn up_command(profile: &Profile, services: &[&str]) -> String {
+    let mut command = "docker compose".to_string();
+    for compose_profile in profile.compose_profiles {
+        command.push_str(&format!(" --profile {}", compose_profile));
+    }
+    format!("{} up --detach {}", command, services.join(" "))
+}
+
+async fn containers(env: &DockerEnv, profile: &Profile, services: &[&str]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for service in services {
+        let check = format!("container {}", service);
+        let up = up_command(profile, &[service]);
+        let state = match env.inspect(service).await {
+            Ok(state) => state,
+            Err(e) if e.starts_with("No container") => {
+                findings.push(Finding::failed(&check, "not created", up));
+                continue;
+            }
+            Err(e) => {
+                findings.push(Finding::failed(&check, e, up));
+                continue;
+            }
+        };
+        let logs = format!("see why with `docker compose logs {}`", service);
+        let finding = if !state.running {
+            let mut detail = state.status.clone();
+            if let Some(code) = state.exit_code {
+                detail.push_str(&format!(", exit code {}", code));
+            }
+            if state.oom_killed {
+                detail.push_str(", killed for running out of memory");
+            }
+            Finding::failed(&check, detail, format!("{}; {}", up, logs))
+        } else {
+            match state.health.as_deref() {
+                Some("unhealthy") => Finding::failed(&check, "running but unhealthy", logs),
+                Some("starting") => Finding::warning(
+                    &check,
+                    "running, healthcheck still starting",
+                    "wait for it to turn healthy, then run again",
+                ),
+                _ if state.restart_count > 0 => Finding::warning(
+                    &check,
+                    format!("running, restarted {} times", state.restart_count),
+                    logs,
+                ),
+                Some(health) => Finding::ok(&check, format!("running, {}", health)),
+                None => Finding::ok(&check, "running"),
+            }
+        };
+        findings.push(finding);
+    }
+    findings
+}
+
+async fn ports(env: &DockerEnv, services: &[&str]) -> Vec<Finding> {
+    let readiness = match Readiness::stack_for(env).await {
+        Ok(readiness) => readiness,
+        Err(e) => return vec![Finding::failed("ports", e, "check the containers above")],
+    };
+    let mut findings = Vec::new();
+    // Services without a container were reported above
+    for service in services.iter().filter(|s| readiness.declares(s)) {
+        let check = format!("port {}", service);
+        findings.push(match readiness.check(service).await {
+            Ok(()) => Finding::ok(&check, "answers at its published port"),
+            Err(e) => Finding::failed(
+                &check,
+                e,
+                format!(
+                    "compare `docker compose port {}` with what listens there \
+                     (`lsof -i` or `ss -ltnp`); another process or a firewall may hold the port",
+                    service
+                ),
+            ),
+        });
+    }
+    findings
+}
+
+fn keys(profile: &Profile, config: &Config) -> Vec<Finding> {
+    let generate =
+        "generate the pair as the README describes (`openssl genrsa`) into lnurl-server/keys";
+    let mut findings = vec![
+        key_file("signing key", &config.vss.signing_key_path, true, generate),
+        key_file("public key", &config.vss.public_key_path, false, generate),
+    ];
+    let macaroons = [
+        ("lnd", &config.lnd.node_a_macaroon_path),
+        ("lnd2", &config.lnd.node_b_macaroon_path),
+    ];
+    for (service, path) in macaroons {
+        if profile.includes(service) {
+            let start = format!(
+                "start {} once so it writes it: `{}`",
+                service,
+                up_command(profile, &[service])
+            );
+            findings.push(key_file(
+                &format!("macaroon {}", service),
+                path,
+                false,
+                &start,
+            ));
+        }
+    }
+    findings
+}
+
+/// Whether the key file at `path` can be read; `missing` says how to create
+/// it. A `private` key should be readable by its owner alone.
+fn key_file(check: &str, path: &str, private: bool, missing: &str) -> Finding {
+    if let Err(e) = fs::metadata(path) {
+        return Finding::failed(check, format!("{}: {}", path, e), missing);
+    }
+    if let Err(e) = fs::read(path) {
+        // Files the containers write belong to root
+        let fix = if private {
+            format!("`sudo chown $USER {}`", path)
+        } else {
+            format!("`sudo chmod a+r {}`", path)
+        };
+        return Finding::failed(check, format!("{} is not readable: {}", path, e), fix);
+    }
+    if private && readable_by_others(path) {
+        return Finding::warning(
+            check,
+            format!("{} is readable by every user", path),
+            format!("`chmod 600 {}`", path),
+        );
+    }
+    Finding::ok(check, path)
+}
+
+#[cfg(unix)]
+fn readable_by_others(path: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o004 != 0)
+}
+
+#[cfg(not(unix))]
+fn readable_by_others(_path: &str) -> bool {
+    false
+}
+
+/// The host's clock against the first running container's, read with `date`
+/// and taken as of the middle of the exec.
+async fn clock(env: &DockerEnv, services: &[&str]) -> Finding {
+    let check = "clock";
+    for service in services {
+        let before = SystemTime::now();
+        let Ok(output) = env.exec(service, &["date", "+%s"]).await else {
+            continue;
+        };
+        let Ok(container_secs) = output.stdout.trim().parse::<f64>() else {
+            continue;
+        };
+        let after = SystemTime::now();
+        let host_secs = unix_secs(before) + (unix_secs(after) - unix_secs(before)) / 2.0;
+        // `date` truncates to the second
+        let skew = container_secs + 0.5 - host_secs;
+        let detail = format!("{} is {:+.1}s off the host", service, skew);
+        let fix = "Docker Desktop's VM clock drifts after sleep: restart Docker Desktop, or \
+                   `docker run --rm --privileged alpine hwclock -s`; keep the host on NTP \
+                   (`sudo timedatectl set-ntp true`)";
+        let off = Duration::from_secs_f64(skew.abs());
+        return if off > CLOCK_FAIL_SKEW {
+            Finding::failed(check, detail, fix)
+        } else if off > CLOCK_WARN_SKEW {
+            Finding::warning(check, detail, fix)
+        } else {
+            Finding::ok(check, detail)
+        };
+    }
+    Finding::warning(
+        check,
+        "no running container could tell its time",
+        "start the stack and run again",
+    )
+}
+
+fn unix_secs(time: SystemTime) -> f64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
@@ -0,0 +1,119 @@
+//! Minimal Electrum protocol client for the electrs container
+//!
+//! Electrum speaks newline-delimited JSON-RPC over plain TCP on port 60001.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::config;
+
+pub const ELECTRUM_ADDR: &str = "localhost:60001";
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryItem {
+    pub tx_hash: String,
+    /// Confirmation height; 0 or -1 while the transaction is in the mempool.
+    pub height: i64,
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+    next_id: u64,
+}
+
+pub struct Electrum {
+    conn: Mutex<Connection>,
+}
+
+impl Electrum {
+    pub async fn connect(addr: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| format!("Failed to connect to electrum at {}: {:?}", addr, e))?;
+        Ok(Self {
+            conn: Mutex::new(Connection {
+                stream: BufReader::new(stream),
+                next_id: 0,
+            }),
+        })
+    }
+
+    /// Client for the compose electrs.
+    pub async fn local() -> Result<Self, String> {
+        Self::connect(&config::get().electrum.addr).await
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let mut conn = self.conn.lock().await;
+        conn.next_id += 1;
+        let id = conn.next_id;
+        let mut line = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+            .to_string();
+        line.push('\n');
+        conn.stream
+            .get_mut()
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("electrum {} write failed: {:?}", method, e))?;
+
+        // Skip subscription notifications until our response arrives
+        loop {
+            let mut response = String::new();
+            let read = conn
+                .stream
+                .read_line(&mut response)
+                .await
+                .map_err(|e| format!("electrum {} read failed: {:?}", method, e))?;
+            if read == 0 {
+                return Err(format!("electrum closed the connection during {}", method));
+            }
+            let mut value: Value = serde_json::from_str(&response)
+                .map_err(|e| format!("electrum {} returned {}: {}", method, response, e))?;
+            if value["id"].as_u64() != Some(id) {
+                continue;
+            }
+            if !value["error"].is_null() {
+                return Err(format!("electrum {} error: {}", method, value["error"]));
+            }
+            return Ok(value["result"].take());
+        }
+    }
+
+    /// Current tip as (height, block hash).
+    pub async fn tip(&self) -> Result<(u64, String), String> {
+        let header = self.call("blockchain.headers.subscribe", json!([])).await?;
+        let height = header["height"]
+            .as_u64()
+            .ok_or_else(|| format!("electrum tip has no height: {}", header))?;
+        let raw = header["hex"]
+            .as_str()
+            .and_then(|h| hex::decode(h).ok())
+            .ok_or_else(|| format!("electrum tip has no header hex: {}", header))?;
+        Ok((height, block_hash(&raw)))
+    }
+
+    pub async fn script_history(&self, script_pubkey: &[u8]) -> Result<Vec<HistoryItem>, String> {
+        let history = self
+            .call("blockchain.scripthash.get_history", json!([script_hash(script_pubkey)]))
+            .await?;
+        serde_json::from_value(history).map_err(|e| format!("Unexpected electrum history: {}", e))
+    }
+}
+
+/// Electrum script hash: reversed sha256 of the output script, hex encoded.
+pub fn script_hash(script_pubkey: &[u8]) -> String {
+    let mut hash = Sha256::digest(script_pubkey).to_vec();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+/// Block hash (display byte order) of a serialized 80-byte header.
+pub fn block_hash(header: &[u8]) -> String {
+    let mut hash = Sha256::digest(Sha256::digest(header)).to_vec();
+    hash.reverse();
+    hex::encode(hash)
+}
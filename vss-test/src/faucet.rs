@@ -0,0 +1,122 @@
+//! Regtest faucet paying arbitrary addresses from the bitcoind wallet
+//!
+//! Every payout is capped per call and the total per test run, so a runaway
+//! scenario fails loudly instead of quietly draining the miner wallet.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bitcoind::Bitcoind;
+use crate::env_u64;
+
+// Overridable via FAUCET_MAX_CALL_SAT / FAUCET_MAX_RUN_SAT
+pub const DEFAULT_MAX_CALL_SAT: u64 = 500_000_000;
+pub const DEFAULT_MAX_RUN_SAT: u64 = 5_000_000_000;
+
+/// Total paid out by all faucets in this process.
+static DISPENSED_SAT: AtomicU64 = AtomicU64::new(0);
+
+/// A confirmed (or, at depth 0, broadcast) faucet payment.
+#[derive(Debug, Clone)]
+pub struct Drip {
+    pub txid: String,
+    pub vout: u32,
+    pub amount_sat: u64,
+    /// Confirmation height, `None` when funded at depth 0.
+    pub height: Option<u64>,
+}
+
+pub struct Faucet<'a> {
+    bitcoind: &'a Bitcoind,
+    max_call_sat: u64,
+    max_run_sat: u64,
+}
+
+impl<'a> Faucet<'a> {
+    pub fn new(bitcoind: &'a Bitcoind, max_call_sat: u64, max_run_sat: u64) -> Self {
+        Self {
+            bitcoind,
+            max_call_sat,
+            max_run_sat,
+        }
+    }
+
+    /// Faucet with limits from the environment, falling back to the defaults.
+    pub fn from_env(bitcoind: &'a Bitcoind) -> Result<Self, String> {
+        Ok(Self::new(
+            bitcoind,
+            env_u64("FAUCET_MAX_CALL_SAT", DEFAULT_MAX_CALL_SAT)?,
+            env_u64("FAUCET_MAX_RUN_SAT", DEFAULT_MAX_RUN_SAT)?,
+        ))
+    }
+
+    /// Amount paid out so far in this run.
+    pub fn dispensed_sat(&self) -> u64 {
+        DISPENSED_SAT.load(Ordering::SeqCst)
+    }
+
+    /// Pay `amount_sat` to `address` and mine until it has `confirmations` confirmations.
+    pub async fn fund(&self, address: &str, amount_sat: u64, confirmations: u64) -> Result<Drip, String> {
+        if amount_sat > self.max_call_sat {
+            return Err(format!(
+                "Faucet request of {} sat exceeds the {} sat per-call limit",
+                amount_sat, self.max_call_sat
+            ));
+        }
+        let max_run_sat = self.max_run_sat;
+        DISPENSED_SAT
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |total| {
+                total.checked_add(amount_sat).filter(|t| *t <= max_run_sat)
+            })
+            .map_err(|total| {
+                format!(
+                    "Faucet request of {} sat exceeds the {} sat per-run limit ({} sat already paid)",
+                    amount_sat, max_run_sat, total
+                )
+            })?;
+
+        // Keep headroom for fees and the change output
+        let sent = async {
+            self.bitcoind.ensure_funds(amount_sat * 2).await?;
+            self.bitcoind.send_to_address(address, amount_sat).await
+        }
+        .await;
+        let txid = match sent {
+            Ok(txid) => txid,
+            Err(e) => {
+                // Nothing left the wallet, so give the allowance back
+                DISPENSED_SAT.fetch_sub(amount_sat, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        let vout = self.bitcoind.find_vout(&txid, address).await?;
+        if confirmations == 0 {
+            return Ok(Drip {
+                txid,
+                vout,
+                amount_sat,
+                height: None,
+            });
+        }
+
+        self.bitcoind.mine(confirmations).await?;
+        let height = self
+            .bitcoind
+            .get_tx_height(&txid)
+            .await?
+            .ok_or_else(|| format!("Faucet payment {} did not confirm", txid))?;
+        let depth = self.bitcoind.get_block_count().await? + 1 - height;
+        if depth < confirmations {
+            return Err(format!(
+                "Faucet payment {} has {} confirmations, expected {}",
+                txid, depth, confirmations
+            ));
+        }
+        Ok(Drip {
+            txid,
+            vout,
+            amount_sat,
+            height: Some(height),
+        })
+    }
+}
@@ -0,0 +1,240 @@
+//! Named, shared data sets for test cases
+//!
+//! A case asks for a fixture by type and name with `acquire`. The first
+//! caller sets it up, later and concurrent callers wait for that setup and
+//! share the result, and a setup that fails is retried by the next caller.
+//! Each holder counts as one user; when the last one drops its `Shared`, the
+//! data set is forgotten and the next `acquire` builds a fresh one. Cases
+//! that must not see each other's data ask for different names.
+//!
+//! Data sets never share keys with anything else: stores get names unique to
+//! the run from `unique_id`, so leftovers of earlier runs and other cases
+//! cannot collide with them. Subjects are drawn from the run's seed (see
+//! `test_harness::rng`), so a replayed run authenticates as the same ones.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use rand::RngCore;
+use test_harness::rng;
+use tokio::sync::OnceCell;
+
+use crate::bitcoind::Bitcoind;
+use crate::config;
+use crate::lnd::Lnd;
+use crate::vss::{local_url, sign_token, Vss};
+
+// Objects in a `PopulatedStore`
+pub const STORE_OBJECTS: usize = 20;
+// Identities in an `Identities` set
+pub const IDENTITY_COUNT: usize = 5;
+// Confirmed balance a `FundedWallet` has at least
+pub const FUNDED_WALLET_SAT: u64 = 10_000_000;
+
+pub type SetupFuture<T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send>>;
+
+/// A data set `acquire` can build.
+pub trait Fixture: Sized + Send + Sync + 'static {
+    /// Build the data set called `name`.
+    fn setup(name: &str) -> SetupFuture<Self>;
+}
+
+type Key = (TypeId, String);
+type Value = Arc<dyn Any + Send + Sync>;
+
+struct Entry {
+    value: Arc<OnceCell<Value>>,
+    users: usize,
+}
+
+static FIXTURES: OnceLock<Mutex<HashMap<Key, Entry>>> = OnceLock::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn fixtures() -> &'static Mutex<HashMap<Key, Entry>> {
+    FIXTURES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// A fixture in use; the data set lives at least as long as this does.
+pub struct Shared<T> {
+    value: Arc<T>,
+    _user: User,
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// One user of the fixture `key`, counted from `acquire` until dropped.
+struct User {
+    key: Key,
+}
+
+impl Drop for User {
+    fn drop(&mut self) {
+        let mut fixtures = fixtures().lock().unwrap();
+        if let Some(entry) = fixtures.get_mut(&self.key) {
+            entry.users -= 1;
+            if entry.users == 0 {
+                fixtures.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// The `T` called `name`, set up now unless another user already holds it.
+pub async fn acquire<T: Fixture>(name: &str) -> Result<Shared<T>, String> {
+    let key = (TypeId::of::<T>(), name.to_string());
+    let value = {
+        let mut fixtures = fixtures().lock().unwrap();
+        let entry = fixtures.entry(key.clone()).or_insert_with(|| Entry {
+            value: Arc::new(OnceCell::new()),
+            users: 0,
+        });
+        entry.users += 1;
+        entry.value.clone()
+    };
+    // Counted before setup, so a failed or abandoned setup is not leaked
+    let user = User { key };
+    let value = value
+        .get_or_try_init(|| async { T::setup(name).await.map(|set| Arc::new(set) as Value) })
+        .await?;
+    let value = value
+        .clone()
+        .downcast::<T>()
+        .map_err(|_| format!("Fixture {} has an unexpected type", name))?;
+    Ok(Shared { value, _user: user })
+}
+
+/// `prefix` followed by a suffix no other call in any run returns; only
+/// digits and dashes are added, so the id is safe to inline into SQL if the
+/// prefix is.
+pub fn unique_id(prefix: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{}-{}-{}",
+        prefix,
+        nanos,
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// A compressed-pubkey-shaped subject (`02` and 32 random bytes of hex), for
+/// JWTs of identities no real node has.
+pub fn subject(rng: &mut impl RngCore) -> String {
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    format!("02{}", hex::encode(bytes))
+}
+
+/// A VSS store of its own holding `STORE_OBJECTS` objects, `key-00` and on,
+/// each with its key as value.
+pub struct PopulatedStore {
+    pub vss: Vss,
+    /// Who `vss` is authenticated as.
+    pub subject: String,
+    pub store_id: String,
+    pub objects: Vec<(String, Vec<u8>)>,
+}
+
+impl Fixture for PopulatedStore {
+    fn setup(name: &str) -> SetupFuture<Self> {
+        let name = name.to_string();
+        Box::pin(async move {
+            let subject = subject(&mut rng::rng(&format!("store:{}", name)));
+            let vss = Vss::local(&subject).await?;
+            let store_id = unique_id(&format!("fixture-{}", name));
+            let objects: Vec<(String, Vec<u8>)> = (0..STORE_OBJECTS)
+                .map(|i| {
+                    let key = format!("key-{:02}", i);
+                    let value = key.clone().into_bytes();
+                    (key, value)
+                })
+                .collect();
+            for (key, value) in &objects {
+                vss.put_object(&store_id, key, value.clone()).await?;
+            }
+            Ok(Self {
+                vss,
+                subject,
+                store_id,
+                objects,
+            })
+        })
+    }
+}
+
+/// A subject and a valid JWT for it.
+pub struct Identity {
+    pub subject: String,
+    pub token: String,
+}
+
+impl Identity {
+    /// Client for the compose vss-server authenticated as this identity.
+    pub async fn vss(&self) -> Result<Vss, String> {
+        Vss::with_token(&local_url().await?, &self.token)
+    }
+}
+
+/// `IDENTITY_COUNT` distinct identities, with tokens signed by the lnurl-server key.
+pub struct Identities {
+    pub identities: Vec<Identity>,
+}
+
+impl Fixture for Identities {
+    fn setup(name: &str) -> SetupFuture<Self> {
+        let name = name.to_string();
+        Box::pin(async move {
+            let signing_key_path = &config::get().vss.signing_key_path;
+            let mut rng = rng::rng(&format!("identities:{}", name));
+            let identities = (0..IDENTITY_COUNT)
+                .map(|_| {
+                    let subject = subject(&mut rng);
+                    let token = sign_token(signing_key_path, &subject)?;
+                    Ok(Identity { subject, token })
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(Self { identities })
+        })
+    }
+}
+
+/// The on-chain wallet of an LND node with at least `FUNDED_WALLET_SAT`
+/// confirmed; named after the node, `node_a` or `node_b`.
+pub struct FundedWallet {
+    pub node: Lnd,
+    pub confirmed_sat: u64,
+}
+
+impl Fixture for FundedWallet {
+    fn setup(name: &str) -> SetupFuture<Self> {
+        let name = name.to_string();
+        Box::pin(async move {
+            let node = match name.as_str() {
+                "node_a" => Lnd::node_a()?,
+                "node_b" => Lnd::node_b()?,
+                other => return Err(format!("No LND node called {}", other)),
+            };
+            node.ensure_wallet_funds(&Bitcoind::local(), FUNDED_WALLET_SAT)
+                .await?;
+            let confirmed_sat = node.wallet_balance().await?.confirmed_balance as u64;
+            Ok(Self {
+                node,
+                confirmed_sat,
+            })
+        })
+    }
+}
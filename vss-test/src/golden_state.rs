@@ -0,0 +1,141 @@
+//! Golden-State Export/Import Binary
+//!
+//! Seeding the default stack (mining, funding the LND wallets, opening the
+//! channel, filling VSS) takes a new contributor tens of minutes. `export`
+//! archives the data of a seeded stack into one portable file; `import` puts
+//! that file's state into another machine's stack in seconds:
+//!
+//!   cargo run --bin golden_state -- export [<archive>]
+//!   cargo run --bin golden_state -- import [<archive>]
+//!
+//! The archive defaults to GOLDEN_ARCHIVE or `../golden-state.tar.gz`. Both
+//! machines need the same docker-compose.yml; the snapshot kept locally is
+//! named `golden`, under SNAPSHOT_DIR like every other snapshot.
+
+use clap::{Parser, Subcommand};
+use harness_docker::readiness::Readiness;
+use harness_docker::snapshot::{Snapshots, GOLDEN_DATA};
+use harness_docker::DockerEnv;
+use std::path::{Path, PathBuf};
+use test_harness::cli::HarnessArgs;
+use test_harness::log::{self, LogFormat};
+use vss_test::bitcoind::Bitcoind;
+use vss_test::lnd::Lnd;
+
+const GOLDEN_SNAPSHOT: &str = "golden";
+// Relative to vss-test; overridable via GOLDEN_ARCHIVE or the command line
+const DEFAULT_GOLDEN_ARCHIVE: &str = "../golden-state.tar.gz";
+
+#[derive(Parser)]
+#[command(about = "Move the state of a seeded stack between machines")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Snapshot the running stack and pack it into an archive
+    Export {
+        /// Archive to write; GOLDEN_ARCHIVE or ../golden-state.tar.gz by default
+        archive: Option<PathBuf>,
+    },
+    /// Restore an archive over the running stack
+    Import {
+        /// Archive to read; GOLDEN_ARCHIVE or ../golden-state.tar.gz by default
+        archive: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    log::init(LogFormat::from_env());
+    println!("===");
+    println!("Golden-State Export/Import");
+    println!();
+
+    let result = match cli.command {
+        Command::Export { archive } => export(&archive_or_default(archive)).await,
+        Command::Import { archive } => import(&archive_or_default(archive)).await,
+    };
+    if let Err(e) = result {
+        println!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// `archive`, or GOLDEN_ARCHIVE, or the default next to the compose file.
+fn archive_or_default(archive: Option<PathBuf>) -> PathBuf {
+    archive.unwrap_or_else(|| {
+        std::env::var("GOLDEN_ARCHIVE")
+            .unwrap_or_else(|_| DEFAULT_GOLDEN_ARCHIVE.to_string())
+            .into()
+    })
+}
+
+/// Snapshot the running stack and pack it into `archive`.
+async fn export(archive: &Path) -> Result<(), String> {
+    let env = DockerEnv::local()?;
+    print!("{}", summary().await?);
+    let snapshots = Snapshots::local(&env)?;
+    println!("Stopping the seeded services to snapshot them");
+    snapshots.take(GOLDEN_SNAPSHOT, &GOLDEN_DATA).await?;
+    snapshots
+        .export(GOLDEN_SNAPSHOT, &GOLDEN_DATA, archive)
+        .await?;
+    wait_ready(&env).await?;
+    println!("Exported golden state to {}", archive.display());
+    Ok(())
+}
+
+/// Unpack `archive` and restore it over the running stack.
+async fn import(archive: &Path) -> Result<(), String> {
+    let env = DockerEnv::local()?;
+    let snapshots = Snapshots::local(&env)?;
+    let name = snapshots.import(archive, &GOLDEN_DATA).await?;
+    println!("Restoring snapshot {} from {}", name, archive.display());
+    snapshots.restore(&name, &GOLDEN_DATA).await?;
+    wait_ready(&env).await?;
+
+    // Macaroons came with the archive, so the clients are built only now
+    let bitcoind = Bitcoind::local();
+    Lnd::node_a()?.wait_synced(&bitcoind).await?;
+    Lnd::node_b()?.wait_synced(&bitcoind).await?;
+    print!("{}", summary().await?);
+    println!("Imported golden state");
+    Ok(())
+}
+
+/// Wait for the restarted services that readiness knows how to probe.
+async fn wait_ready(env: &DockerEnv) -> Result<(), String> {
+    let readiness = Readiness::stack_for(env).await?;
+    let targets: Vec<&str> = GOLDEN_DATA
+        .iter()
+        .map(|entry| entry.service)
+        .filter(|s| readiness.declares(s))
+        .collect();
+    readiness.wait(&targets).await.map(|_| ())
+}
+
+/// Chain height plus the wallet and channel state of both default nodes.
+async fn summary() -> Result<String, String> {
+    let height = Bitcoind::local().get_block_count().await?;
+    let mut summary = format!("Chain height {}\n", height);
+    for (name, node) in [("lnd", Lnd::node_a()?), ("lnd2", Lnd::node_b()?)] {
+        let wallet = node.wallet_balance().await?;
+        let channels = node.list_channels().await?;
+        let active = channels.iter().filter(|c| c.active).count();
+        summary.push_str(&format!(
+            "  {}: {} sat on chain, {} channel(s), {} active\n",
+            name,
+            wallet.confirmed_balance,
+            channels.len(),
+            active
+        ));
+    }
+    Ok(summary)
+}
@@ -0,0 +1,255 @@
+//! Multi-node Lightning graph bootstrap for routing scenarios
+//!
+//! Starts the LND services the graph needs, opens channels between them in a
+//! chosen topology and waits until every node has learned the whole graph
+//! through gossip.
+
+use std::time::Duration;
+
+use crate::bitcoind::Bitcoind;
+use crate::compose::{destroy_service, start_service};
+use crate::lnd::{Channel, Lnd, LND_C_REST_URL, LND_C_SERVICE};
+use crate::wait_for;
+
+/// An LND compose service that can take part in a graph.
+#[derive(Debug, Clone, Copy)]
+pub struct NodeSpec {
+    pub service: &'static str,
+    pub rest_url: &'static str,
+    pub macaroon_path: &'static str,
+    pub p2p_host: &'static str,
+}
+
+/// Graph-capable nodes in the order they are used; `lnd4` and `lnd5` live in
+/// the `graph` compose profile and are only started when a graph needs them.
+pub const GRAPH_NODES: [NodeSpec; 4] = [
+    NodeSpec {
+        service: "lnd",
+        rest_url: crate::lnd::LND_A_REST_URL,
+        macaroon_path: crate::lnd::LND_A_MACAROON_PATH,
+        p2p_host: crate::lnd::LND_A_P2P_HOST,
+    },
+    NodeSpec {
+        service: "lnd2",
+        rest_url: crate::lnd::LND_B_REST_URL,
+        macaroon_path: crate::lnd::LND_B_MACAROON_PATH,
+        p2p_host: crate::lnd::LND_B_P2P_HOST,
+    },
+    NodeSpec {
+        service: "lnd4",
+        rest_url: "https://localhost:8083",
+        macaroon_path: "../lnd4/data/chain/bitcoin/regtest/admin.macaroon",
+        p2p_host: "lnd4:9735",
+    },
+    NodeSpec {
+        service: "lnd5",
+        rest_url: "https://localhost:8084",
+        macaroon_path: "../lnd5/data/chain/bitcoin/regtest/admin.macaroon",
+        p2p_host: "lnd5:9735",
+    },
+];
+
+// Password for wallets created on the seeded node
+const BLANK_NODE_PASSWORD: &str = "vss-test-password";
+
+const NODE_START_TIMEOUT: Duration = Duration::from_secs(60);
+const CHANNEL_REACTIVATE_TIMEOUT: Duration = Duration::from_secs(90);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Channel layout between nodes, by index. Each edge is opened by its first
+/// node, which therefore holds the outbound liquidity.
+#[derive(Debug, Clone)]
+pub enum Topology {
+    /// 0 -> 1 -> ... -> n-1
+    Line,
+    /// A line closed back from n-1 to 0.
+    Ring,
+    /// 0 opens a channel to every other node.
+    Star,
+    /// Explicit (opener, peer) pairs.
+    Edges(Vec<(usize, usize)>),
+}
+
+impl Topology {
+    pub fn edges(&self, nodes: usize) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Line => (1..nodes).map(|i| (i - 1, i)).collect(),
+            Topology::Ring => {
+                let mut edges = Topology::Line.edges(nodes);
+                if nodes > 2 {
+                    edges.push((nodes - 1, 0));
+                }
+                edges
+            }
+            Topology::Star => (1..nodes).map(|i| (0, i)).collect(),
+            Topology::Edges(edges) => edges.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphConfig {
+    pub nodes: usize,
+    pub topology: Topology,
+    pub channel_capacity_sat: u64,
+    pub gossip_timeout: Duration,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            nodes: 3,
+            topology: Topology::Line,
+            channel_capacity_sat: 1_000_000,
+            gossip_timeout: Duration::from_secs(180),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GraphChannel {
+    pub from: usize,
+    pub to: usize,
+    pub channel: Channel,
+}
+
+pub struct Graph {
+    pub nodes: Vec<Lnd>,
+    pub specs: Vec<NodeSpec>,
+    pub pubkeys: Vec<String>,
+    pub channels: Vec<GraphChannel>,
+}
+
+impl Graph {
+    /// Start `config.nodes` nodes, open the topology's channels (reusing active
+    /// ones) and wait until every node's graph holds every node and channel.
+    pub async fn bootstrap(bitcoind: &Bitcoind, config: &GraphConfig) -> Result<Self, String> {
+        if config.nodes < 2 || config.nodes > GRAPH_NODES.len() {
+            return Err(format!(
+                "A graph needs 2 to {} nodes, got {}",
+                GRAPH_NODES.len(),
+                config.nodes
+            ));
+        }
+        let edges = config.topology.edges(config.nodes);
+        if let Some(edge) = edges
+            .iter()
+            .find(|(from, to)| from == to || *from >= config.nodes || *to >= config.nodes)
+        {
+            return Err(format!("Invalid edge {:?} for {} nodes", edge, config.nodes));
+        }
+
+        let specs = GRAPH_NODES[..config.nodes].to_vec();
+        let mut nodes = Vec::new();
+        let mut pubkeys = Vec::new();
+        for spec in &specs {
+            let node = start_node(spec).await?;
+            node.wait_synced(bitcoind).await?;
+            pubkeys.push(node.get_info().await?.identity_pubkey);
+            nodes.push(node);
+        }
+
+        let mut channels = Vec::new();
+        for (from, to) in edges {
+            let channel = nodes[from]
+                .ensure_channel(
+                    bitcoind,
+                    &pubkeys[to],
+                    specs[to].p2p_host,
+                    config.channel_capacity_sat,
+                    config.channel_capacity_sat / 2,
+                )
+                .await?;
+            channels.push(GraphChannel { from, to, channel });
+        }
+
+        let graph = Self {
+            nodes,
+            specs,
+            pubkeys,
+            channels,
+        };
+        for node in &graph.nodes {
+            node.wait_synced(bitcoind).await?;
+        }
+        graph.wait_gossip(config.gossip_timeout).await?;
+        Ok(graph)
+    }
+
+    /// Wait until every node knows every graph node and both policies of every channel.
+    pub async fn wait_gossip(&self, timeout: Duration) -> Result<(), String> {
+        for node in &self.nodes {
+            wait_for("graph gossip to propagate", timeout, POLL_INTERVAL, || async {
+                let view = node.describe_graph().await?;
+                if let Some(missing) = self
+                    .pubkeys
+                    .iter()
+                    .find(|pubkey| !view.nodes.iter().any(|n| &n.pub_key == *pubkey))
+                {
+                    return Err(format!("node {} not announced yet", missing));
+                }
+                for graph_channel in &self.channels {
+                    let point = &graph_channel.channel.channel_point;
+                    let edge = view
+                        .edges
+                        .iter()
+                        .find(|e| &e.chan_point == point)
+                        .ok_or_else(|| format!("channel {} not announced yet", point))?;
+                    if edge.node1_policy.is_none() || edge.node2_policy.is_none() {
+                        return Err(format!("channel {} is missing a policy update", point));
+                    }
+                }
+                Ok(Some(()))
+            })
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Start a node's service and wait until its REST API answers.
+pub async fn start_node(spec: &NodeSpec) -> Result<Lnd, String> {
+    start_service(spec.service)?;
+    // The macaroon only appears once LND has created its wallet
+    let what = format!("{} to start", spec.service);
+    wait_for(&what, NODE_START_TIMEOUT, POLL_INTERVAL, || async {
+        let node = Lnd::new(spec.rest_url, spec.macaroon_path)?;
+        node.get_info().await?;
+        Ok(Some(node))
+    })
+    .await
+}
+
+/// Wait until all of `node`'s channels with `peers` are active again, e.g.
+/// after the nodes restarted.
+pub async fn wait_channels_active(node: &Lnd, peers: &[String]) -> Result<(), String> {
+    wait_for("channels to reactivate", CHANNEL_REACTIVATE_TIMEOUT, POLL_INTERVAL, || async {
+        let channels = node.list_channels().await?;
+        Ok(channels
+            .iter()
+            .filter(|c| peers.contains(&c.remote_pubkey))
+            .all(|c| c.active)
+            .then_some(()))
+    })
+    .await
+}
+
+/// Recreate the seeded `lnd3` service from scratch with a new wallet, giving a
+/// node that has never seen the graph.
+pub async fn start_blank_node() -> Result<Lnd, String> {
+    destroy_service(LND_C_SERVICE)?;
+    start_service(LND_C_SERVICE)?;
+    let unlocker = Lnd::uninitialized(LND_C_REST_URL)?;
+    let mnemonic = wait_for("lnd3 wallet unlocker", NODE_START_TIMEOUT, POLL_INTERVAL, || async {
+        unlocker.gen_seed().await.map(Some)
+    })
+    .await?;
+    let node = unlocker
+        .init_wallet(BLANK_NODE_PASSWORD, &mnemonic, None, 0)
+        .await?;
+    wait_for("lnd3 RPC after wallet init", NODE_START_TIMEOUT, POLL_INTERVAL, || async {
+        node.get_info().await.map(|_| Some(()))
+    })
+    .await?;
+    Ok(node)
+}
@@ -0,0 +1,129 @@
+//! Report Tools Binary
+//!
+//! Works on the JSON reports the suites write with `--format json`, and on
+//! the reasons behind them. `merge` combines the reports of a suite run in shards (`--shard K/N`, one CI job
+//! each) into one report on stdout, as if a single job had run every case:
+//!
+//!   cargo run --bin harness_report -- merge shard-1.jsonl shard-2.jsonl shard-3.jsonl
+//!
+//! It exits 1 if any case failed, and 2 without writing anything if a report
+//! is unreadable or a shard's report is missing or given twice.
+//!
+//! `doctor` checks the environment the suites need before blaming a failure
+//! on them: Docker, the profile's containers and ports, the key files and the
+//! containers' clock (see `vss_test::doctor`). It prints a fix for each
+//! problem and exits 3 if anything failed:
+//!
+//!   cargo run --bin harness_report -- doctor --profile vss-only
+
+use clap::{Parser, Subcommand};
+use harness_docker::profile::{Profile, FULL};
+use harness_docker::summary::{ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR, TESTS_FAILED};
+use std::path::PathBuf;
+use test_harness::shard;
+use vss_test::config::Config;
+use vss_test::doctor::{self, Status};
+
+#[derive(Parser)]
+#[command(about = "Work on the JSON reports of the test suites and check their environment")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Combine the reports of every shard of one suite into one report
+    Merge {
+        /// The report of each shard, in any order
+        #[arg(required = true, value_name = "REPORT")]
+        reports: Vec<PathBuf>,
+    },
+    /// Check Docker, the containers, ports, key files and clock the suites need
+    Doctor {
+        /// Check the services of this profile instead of the whole default stack
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+        /// Read settings from this file instead of vss-test.toml
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let code = match cli.command {
+        Command::Merge { reports } => merge(&reports),
+        Command::Doctor { profile, config } => doctor(profile.as_deref(), config).await,
+    };
+    std::process::exit(code);
+}
+
+/// Print what is wrong with the environment and return the code to exit with.
+async fn doctor(profile: Option<&str>, config: Option<PathBuf>) -> i32 {
+    let profile = match profile.map(Profile::named).transpose() {
+        Ok(profile) => profile.unwrap_or(FULL),
+        Err(e) => {
+            eprintln!("{}", e);
+            return HARNESS_ERROR;
+        }
+    };
+    let config = match Config::load(config.as_deref(), &[]) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return HARNESS_ERROR;
+        }
+    };
+    println!("Checking the environment for profile {}", profile.name);
+    println!();
+    let findings = doctor::examine(&profile, &config).await;
+    for finding in &findings {
+        print!("{}", finding.render());
+    }
+    let count = |status| findings.iter().filter(|f| f.status == status).count();
+    let (warnings, failures) = (count(Status::Warning), count(Status::Failed));
+    println!();
+    println!("{} failed, {} warned", failures, warnings);
+    if failures > 0 {
+        ENVIRONMENT_UNAVAILABLE
+    } else {
+        0
+    }
+}
+
+/// Print the merged report of `paths` and return the code to exit with.
+fn merge(paths: &[PathBuf]) -> i32 {
+    let mut reports = Vec::new();
+    for path in paths {
+        match std::fs::read_to_string(path) {
+            Ok(text) => reports.push((path.display().to_string(), text)),
+            Err(e) => {
+                eprintln!("Failed to read {}: {:?}", path.display(), e);
+                return HARNESS_ERROR;
+            }
+        }
+    }
+    let merged = match shard::merge(&reports) {
+        Ok(merged) => merged,
+        Err(e) => {
+            eprintln!("{}", e);
+            return HARNESS_ERROR;
+        }
+    };
+    for record in &merged.records {
+        println!("{}", record);
+    }
+    eprintln!(
+        "Merged {} reports: {} passed, {} failed",
+        reports.len(),
+        merged.passed,
+        merged.failed
+    );
+    if merged.failed > 0 {
+        TESTS_FAILED
+    } else {
+        0
+    }
+}
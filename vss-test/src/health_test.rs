@@ -0,0 +1,275 @@
+//! Docker Healthcheck Conformance Test Binary
+//!
+//! Checks that every healthcheck in docker-compose.yml means what it says: a
+//! service Docker reports healthy must also pass the harness readiness probe
+//! (accept connections, answer requests), both in steady state and from the
+//! first moment it turns healthy after a restart. Catches healthchecks that
+//! only prove the process is alive while the app is not listening yet.
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::readiness::Readiness;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::DockerEnv;
+use std::time::Duration;
+use test_harness::case::{self, CaseFuture, Outcome, TestCase};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::runner::Tag;
+use test_harness::test_cases;
+use vss_test::wait_for;
+
+// Checks whatever is running; `--profile` decides what that is
+const REQUIRED_SERVICES: [&str; 0] = [];
+
+// Longest healthcheck interval in docker-compose.yml plus its start period
+const HEALTH_SETTLE_TIMEOUT: Duration = Duration::from_secs(120);
+// How long after turning healthy the readiness probe may still fail
+const PROBE_GRACE: Duration = Duration::from_secs(2);
+const RESTART_TIMEOUT_SECS: i64 = 10;
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Docker Healthcheck Conformance Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The running stack and the readiness probes for its services.
+struct Stack {
+    env: DockerEnv,
+    readiness: Readiness,
+    /// Running services with a readiness probe
+    probed: Vec<String>,
+}
+
+/// A check of one service with both a healthcheck and a readiness probe, in
+/// steady state or across a restart.
+struct HealthCase {
+    service: String,
+    restart: bool,
+}
+
+impl TestCase<Stack> for HealthCase {
+    fn name(&self) -> String {
+        if self.restart {
+            format!("test_healthy_means_ready_after_restart ({})", self.service)
+        } else {
+            format!("test_healthy_means_ready ({})", self.service)
+        }
+    }
+
+    // A restart takes dependants down with it for a while
+    fn exclusive(&self) -> bool {
+        self.restart
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        if self.restart {
+            vec![Tag::Destructive, Tag::Docker]
+        } else {
+            vec![Tag::Docker]
+        }
+    }
+
+    fn run<'a>(&'a self, stack: &'a Stack) -> CaseFuture<'a> {
+        if self.restart {
+            Box::pin(healthy_means_ready_after_restart(stack, &self.service))
+        } else {
+            Box::pin(healthy_means_ready(stack, &self.service))
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    // The cases follow the services running now
+    if cli.filter.list {
+        match inspect().await {
+            Ok((_, checked, _)) => cli.filter.print_list(&case::names(&cases(&checked))),
+            Err(e) => {
+                let e = format!("Failed to inspect the stack: {}", e);
+                eprintln!("{}", e);
+                std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+            }
+        }
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("Docker Healthcheck Conformance Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select(&REQUIRED_SERVICES).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let (stack, checked, unchecked) = match inspect().await {
+        Ok(inspected) => inspected,
+        Err(e) => {
+            let e = format!("Failed to inspect the stack: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    if !unchecked.is_empty() {
+        report.say(&format!("Not checked: {}", unchecked.join(", ")));
+    }
+    report.say("");
+
+    let cases = cases(&checked);
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &stack, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// The running stack, the services with both a healthcheck and a readiness
+/// probe, and why the others are not checked.
+async fn inspect() -> Result<(Stack, Vec<String>, Vec<String>), String> {
+    let env = DockerEnv::local()?;
+    let readiness = Readiness::stack_for(&env).await?;
+    let mut probed = Vec::new();
+    let mut checked = Vec::new();
+    let mut unchecked = Vec::new();
+    for service in env.services().await? {
+        let state = env.inspect(&service).await?;
+        if !state.running {
+            continue;
+        }
+        if readiness.declares(&service) {
+            probed.push(service.clone());
+        }
+        match (&state.healthcheck, readiness.declares(&service)) {
+            (Some(_), true) => checked.push(service),
+            (Some(_), false) => unchecked.push(format!("{} (no readiness probe)", service)),
+            (None, _) => unchecked.push(format!("{} (no healthcheck)", service)),
+        }
+    }
+    let stack = Stack {
+        env,
+        readiness,
+        probed,
+    };
+    Ok((stack, checked, unchecked))
+}
+
+/// Both checks of every service in `checked`, then the stack check that
+/// must come after the restarts.
+fn cases(checked: &[String]) -> Vec<Box<dyn TestCase<Stack>>> {
+    let mut cases: Vec<Box<dyn TestCase<Stack>>> = Vec::new();
+    for service in checked {
+        for restart in [false, true] {
+            cases.push(Box::new(HealthCase {
+                service: service.clone(),
+                restart,
+            }));
+        }
+    }
+    cases.extend(test_cases!(Stack;
+        #[exclusive] #[tags(Docker)] test_stack_ready_after_restarts,
+    ));
+    cases
+}
+
+/// Wait for Docker to settle on a health status other than `starting`.
+async fn settled_health(env: &DockerEnv, service: &str) -> Result<String, String> {
+    wait_for(
+        &format!("{} to finish starting", service),
+        HEALTH_SETTLE_TIMEOUT,
+        POLL_INTERVAL,
+        || async {
+            let health = env.inspect(service).await?.health;
+            Ok(health.filter(|h| h != "starting"))
+        },
+    )
+    .await
+}
+
+/// Probe `service`, allowing `PROBE_GRACE` for it to come up.
+async fn probe_within_grace(readiness: &Readiness, service: &str) -> Result<(), String> {
+    wait_for(
+        &format!("{} to pass its readiness probe", service),
+        PROBE_GRACE,
+        POLL_INTERVAL,
+        || async { readiness.check(service).await.map(Some) },
+    )
+    .await
+}
+
+async fn healthy_means_ready(Stack { env, readiness, .. }: &Stack, service: &str) -> Outcome {
+    let health = settled_health(env, service).await?;
+    let probe = probe_within_grace(readiness, service).await;
+    match (health.as_str(), probe) {
+        ("healthy", Ok(())) => Ok("healthy and ready".to_string()),
+        ("healthy", Err(e)) => Err(format!("Reported healthy but not ready: {}", e)),
+        (_, Ok(())) => {
+            let healthcheck = env.inspect(service).await?.healthcheck;
+            Err(format!(
+                "Reported {} but serves fine; the healthcheck {:?} is broken",
+                health, healthcheck
+            ))
+        }
+        (_, Err(e)) => Err(format!("Reported {} and not ready: {}", health, e)),
+    }
+}
+
+async fn healthy_means_ready_after_restart(
+    Stack { env, readiness, .. }: &Stack,
+    service: &str,
+) -> Outcome {
+    env.restart(service, RESTART_TIMEOUT_SECS).await?;
+    let restarted = std::time::Instant::now();
+    // Catch the first moment Docker calls it healthy
+    let health = settled_health(env, service).await?;
+    if health != "healthy" {
+        return Err(format!("Reported {} after the restart", health));
+    }
+    let healthy_after = restarted.elapsed();
+    probe_within_grace(readiness, service).await.map_err(|e| {
+        format!(
+            "Reported healthy after {:?} but not ready: {}",
+            healthy_after, e
+        )
+    })?;
+    Ok(format!(
+        "healthy {:?} after the restart and ready by then",
+        healthy_after
+    ))
+}
+
+/// Restarts take dependants down with them for a while; the stack must be
+/// ready again once they are done.
+async fn test_stack_ready_after_restarts(Stack { readiness, probed, .. }: &Stack) -> Outcome {
+    let probed: Vec<&str> = probed.iter().map(String::as_str).collect();
+    readiness.wait(&probed).await?;
+    Ok(format!("{} services ready", probed.len()))
+}
@@ -0,0 +1,63 @@
+//! Shared helpers for the bitkit-docker integration test binaries
+//!
+//! Thin clients for the regtest services in docker-compose.yml plus small
+//! polling utilities, so each test binary only contains scenario logic.
+
+pub mod bitcoind;
+pub mod blocktank;
+pub mod cli;
+pub mod clock;
+pub mod compose;
+pub mod config;
+pub mod doctor;
+pub mod electrum;
+pub mod faucet;
+pub mod fixtures;
+pub mod graph;
+pub mod lnd;
+pub mod load;
+pub mod raw_http;
+pub mod retry;
+pub mod tls;
+pub mod vss;
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Poll `check` every `interval` until it yields `Some`, or fail after `timeout`.
+pub async fn wait_for<T, F, Fut>(
+    what: &str,
+    timeout: Duration,
+    interval: Duration,
+    mut check: F,
+) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<T>, String>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let last_error = match check().await {
+            Ok(Some(value)) => return Ok(value),
+            Ok(None) => None,
+            Err(e) => Some(e),
+        };
+        if Instant::now() >= deadline {
+            return Err(match last_error {
+                Some(e) => format!("Timed out after {:?} waiting for {}: {}", timeout, what, e),
+                None => format!("Timed out after {:?} waiting for {}", timeout, what),
+            });
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Read a numeric setting from the environment, falling back to `default`.
+pub fn env_u64(name: &str, default: u64) -> Result<u64, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|e| format!("Invalid value for {}: {:?} ({})", name, value, e)),
+        Err(_) => Ok(default),
+    }
+}
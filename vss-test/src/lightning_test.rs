@@ -0,0 +1,365 @@
+//! Lightning Payment Integration Test Binary
+//!
+//! Tests payments between the two regtest LND nodes (`lnd` and `lnd2`)
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::test_cases;
+use vss_test::bitcoind::Bitcoind;
+use vss_test::lnd::{Channel, Lnd, LND_A_P2P_HOST, LND_B_P2P_HOST};
+use vss_test::{env_u64, wait_for};
+
+// Checked against the services of `--profile`, if given
+const REQUIRED_SERVICES: [&str; 3] = ["bitcoind", "lnd", "lnd2"];
+
+// Capacity of the B -> A channel opened when none exists
+const CHANNEL_CAPACITY_SAT: u64 = 2_000_000;
+
+// Invoice amount range, overridable via LN_TEST_MIN_AMOUNT_SAT / LN_TEST_MAX_AMOUNT_SAT
+const DEFAULT_MIN_AMOUNT_SAT: u64 = 1_000;
+const DEFAULT_MAX_AMOUNT_SAT: u64 = 250_000;
+
+// Zero-conf channel node A (acting as LSP) opens to node B, and the payment routed over it
+const ZERO_CONF_CAPACITY_SAT: u64 = 500_000;
+const ZERO_CONF_PAYMENT_SAT: u64 = 25_000;
+
+// Channel node A force-closes, after pushing part of it to node B
+const FORCE_CLOSE_CAPACITY_SAT: u64 = 400_000;
+const FORCE_CLOSE_PAYMENT_SAT: u64 = 50_000;
+// Upper bound on commitment, anchor and sweep fees paid out of node A's balance
+const MAX_SWEEP_FEES_SAT: i64 = 30_000;
+const SWEEP_TIMEOUT: Duration = Duration::from_secs(120);
+
+const CHANNEL_TIMEOUT: Duration = Duration::from_secs(30);
+const BALANCE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Lightning Payment Integration Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The chain and the two nodes every case pays between.
+struct Nodes {
+    bitcoind: Bitcoind,
+    node_a: Lnd,
+    node_b: Lnd,
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    // Each case spends node A's wallet and mines, so none runs alongside another
+    let cases = test_cases!(Nodes;
+        #[exclusive] test_invoice_create_and_pay,
+        #[exclusive] test_zero_conf_channel,
+        #[exclusive] test_force_close_and_sweep,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("Lightning Payment Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select_or(&REQUIRED_SERVICES, &profile::LIGHTNING).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let bitcoind = Bitcoind::local();
+    let (node_a, node_b) = match (Lnd::node_a(), Lnd::node_b()) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            let e = format!("Failed to set up LND clients: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let nodes = Nodes {
+        bitcoind,
+        node_a,
+        node_b,
+    };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &nodes, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// Amounts exercised across the configured range: both bounds and their geometric mean.
+fn amounts_in_range(min_sat: u64, max_sat: u64) -> Vec<u64> {
+    let mid = ((min_sat as f64) * (max_sat as f64)).sqrt().round() as u64;
+    let mut amounts = vec![min_sat, mid, max_sat];
+    amounts.dedup();
+    amounts
+}
+
+/// Settle one invoice created on `payee` from `payer`, checking preimage and balance deltas.
+async fn pay_and_verify(payer: &Lnd, payee: &Lnd, amount_sat: u64) -> Result<i64, String> {
+    let payer_before = payer.channel_balance().await?;
+    let payee_before = payee.channel_balance().await?;
+
+    let invoice = payee
+        .add_invoice(amount_sat, &format!("vss-test {} sat", amount_sat))
+        .await?;
+
+    let payment = payer.pay_invoice(&invoice.payment_request).await?;
+    if !payment.payment_error.is_empty() {
+        return Err(format!("Payment of {} sat failed: {}", amount_sat, payment.payment_error));
+    }
+
+    // Preimage must hash to the invoice's payment hash
+    let preimage_hash = Sha256::digest(&payment.payment_preimage);
+    if preimage_hash.as_slice() != invoice.r_hash.as_slice() {
+        return Err(format!(
+            "Preimage {} does not hash to payment hash {}",
+            hex::encode(&payment.payment_preimage),
+            hex::encode(&invoice.r_hash)
+        ));
+    }
+
+    let settled = payee.lookup_invoice(&invoice.r_hash).await?;
+    if settled.state != "SETTLED" {
+        return Err(format!("Invoice state is {}, expected SETTLED", settled.state));
+    }
+    if settled.r_preimage != payment.payment_preimage {
+        return Err("Payee preimage differs from the one returned to the payer".to_string());
+    }
+    if settled.amt_paid_sat as u64 != amount_sat {
+        return Err(format!(
+            "Invoice paid {} sat, expected {} sat",
+            settled.amt_paid_sat, amount_sat
+        ));
+    }
+
+    let fee_sat = payment.payment_route.map(|r| r.total_fees).unwrap_or(0);
+    let amount = amount_sat as i64;
+
+    // Channel balances are updated asynchronously after the HTLC resolves
+    wait_for("balance deltas", BALANCE_TIMEOUT, POLL_INTERVAL, || async {
+        let payee_delta = payee.channel_balance().await?.local_balance.sat
+            - payee_before.local_balance.sat;
+        let payer_delta = payer_before.local_balance.sat
+            - payer.channel_balance().await?.local_balance.sat;
+        if payee_delta == amount && payer_delta == amount + fee_sat {
+            Ok(Some(()))
+        } else {
+            Err(format!(
+                "payee +{} sat (expected +{}), payer -{} sat (expected -{})",
+                payee_delta,
+                amount,
+                payer_delta,
+                amount + fee_sat
+            ))
+        }
+    })
+    .await?;
+
+    Ok(fee_sat)
+}
+
+async fn test_invoice_create_and_pay(Nodes { bitcoind, node_a, node_b }: &Nodes) -> Outcome {
+    let min_sat = env_u64("LN_TEST_MIN_AMOUNT_SAT", DEFAULT_MIN_AMOUNT_SAT)?;
+    let max_sat = env_u64("LN_TEST_MAX_AMOUNT_SAT", DEFAULT_MAX_AMOUNT_SAT)?;
+    if min_sat == 0 || min_sat > max_sat {
+        return Err(format!("Invalid amount range {}..={} sat", min_sat, max_sat));
+    }
+    let amounts = amounts_in_range(min_sat, max_sat);
+
+    // Node B pays invoices created on node A over a direct B -> A channel
+    let required_sat: u64 = amounts.iter().sum();
+    let setup = async {
+        let node_a_pubkey = node_a.get_info().await?.identity_pubkey;
+        node_b
+            .ensure_channel(bitcoind, &node_a_pubkey, LND_A_P2P_HOST, CHANNEL_CAPACITY_SAT, required_sat)
+            .await?;
+        node_a.wait_synced(bitcoind).await
+    };
+    setup.await.map_err(|e| format!("Channel setup failed: {}", e))?;
+
+    let mut total_fees = 0;
+    for amount_sat in &amounts {
+        total_fees += pay_and_verify(node_b, node_a, *amount_sat).await?;
+    }
+    Ok(format!("Paid {:?} sat, fees {} sat", amounts, total_fees))
+}
+
+/// Wait until `node` sees the channel funded by `funding_txid` as active.
+async fn wait_channel_by_funding(node: &Lnd, funding_txid: &str) -> Result<Channel, String> {
+    wait_for("zero-conf channel to become active", CHANNEL_TIMEOUT, POLL_INTERVAL, || async {
+        let channels = node.list_channels().await?;
+        Ok(channels
+            .into_iter()
+            .find(|c| c.active && c.funding_txid() == funding_txid))
+    })
+    .await
+}
+
+/// Pay `amount_sat` from `payer` to `payee` over the given channel and check it settled.
+async fn pay_over_channel(payer: &Lnd, payee: &Lnd, chan_id: &str, amount_sat: u64) -> Result<(), String> {
+    let invoice = payee.add_invoice(amount_sat, "vss-test zero-conf").await?;
+    let payment = payer.pay_invoice_via(&invoice.payment_request, chan_id).await?;
+    if !payment.payment_error.is_empty() {
+        return Err(format!("Payment over channel {} failed: {}", chan_id, payment.payment_error));
+    }
+    let settled = payee.lookup_invoice(&invoice.r_hash).await?;
+    if settled.state != "SETTLED" {
+        return Err(format!("Invoice state is {}, expected SETTLED", settled.state));
+    }
+    Ok(())
+}
+
+async fn test_zero_conf_channel(Nodes { bitcoind, node_a, node_b }: &Nodes) -> Outcome {
+    let node_b_pubkey = node_b.get_info().await?.identity_pubkey;
+    node_a
+        .ensure_wallet_funds(bitcoind, ZERO_CONF_CAPACITY_SAT * 2)
+        .await?;
+
+    // Node B only takes zero-conf channels through a channel acceptor
+    let acceptor = node_b.spawn_channel_acceptor().await?;
+    let opened = async {
+        node_a.connect_peer(&node_b_pubkey, LND_B_P2P_HOST).await?;
+        node_a
+            .open_zero_conf_channel(&node_b_pubkey, ZERO_CONF_CAPACITY_SAT, 0)
+            .await
+    }
+    .await;
+    acceptor.abort();
+    let funding_txid = opened?.funding_txid();
+
+    // Both sides must consider the channel usable with the funding tx unconfirmed
+    let channel_a = wait_channel_by_funding(node_a, &funding_txid).await?;
+    let channel_b = wait_channel_by_funding(node_b, &funding_txid).await?;
+    if !channel_a.zero_conf || !channel_b.zero_conf {
+        return Err(format!("Channel {} is not flagged zero-conf", channel_a.channel_point));
+    }
+    if bitcoind.get_tx_height(&funding_txid).await?.is_some() {
+        return Err(format!("Funding tx {} confirmed before the payment", funding_txid));
+    }
+
+    pay_over_channel(node_a, node_b, &channel_a.chan_id, ZERO_CONF_PAYMENT_SAT).await?;
+    if bitcoind.get_tx_height(&funding_txid).await?.is_some() {
+        return Err(format!("Funding tx {} confirmed during the payment", funding_txid));
+    }
+
+    // After confirmation the channel keeps its balances and stays usable
+    bitcoind.mine(6).await?;
+    node_a.wait_synced(bitcoind).await?;
+    node_b.wait_synced(bitcoind).await?;
+    if bitcoind.get_tx_height(&funding_txid).await?.is_none() {
+        return Err(format!("Funding tx {} did not confirm", funding_txid));
+    }
+    let channel_b = wait_channel_by_funding(node_b, &funding_txid).await?;
+    if channel_b.local_balance as u64 != ZERO_CONF_PAYMENT_SAT {
+        return Err(format!(
+            "Node B holds {} sat in the confirmed channel, expected {} sat",
+            channel_b.local_balance, ZERO_CONF_PAYMENT_SAT
+        ));
+    }
+    let channel_a = wait_channel_by_funding(node_a, &funding_txid).await?;
+    pay_over_channel(node_a, node_b, &channel_a.chan_id, ZERO_CONF_PAYMENT_SAT).await?;
+    Ok(format!("Paid over zero-conf channel {}", funding_txid))
+}
+
+async fn test_force_close_and_sweep(Nodes { bitcoind, node_a, node_b }: &Nodes) -> Outcome {
+    let node_b_pubkey = node_b.get_info().await?.identity_pubkey;
+    node_a
+        .ensure_wallet_funds(bitcoind, FORCE_CLOSE_CAPACITY_SAT * 2)
+        .await?;
+
+    // A dedicated channel so the close does not disturb other scenarios
+    node_a.connect_peer(&node_b_pubkey, LND_B_P2P_HOST).await?;
+    let point = node_a
+        .open_channel(&node_b_pubkey, FORCE_CLOSE_CAPACITY_SAT, 0)
+        .await?;
+    bitcoind.mine(6).await?;
+    node_a.wait_synced(bitcoind).await?;
+    let channel = wait_channel_by_funding(node_a, &point.funding_txid()).await?;
+    pay_over_channel(node_a, node_b, &channel.chan_id, FORCE_CLOSE_PAYMENT_SAT).await?;
+    let channel = wait_channel_by_funding(node_a, &point.funding_txid()).await?;
+
+    let balance_before = node_a.wallet_balance().await?.confirmed_balance;
+    let closing_txid = node_a.close_channel(&channel.channel_point, true).await?;
+    bitcoind.mine(1).await?;
+    node_a.wait_synced(bitcoind).await?;
+    if bitcoind.get_tx_height(&closing_txid).await?.is_none() {
+        return Err(format!("Force-close tx {} did not confirm", closing_txid));
+    }
+
+    // Our output is timelocked by the CSV delay: mine past it
+    let pending = node_a.pending_channels().await?;
+    let closing = pending
+        .pending_force_closing_channels
+        .iter()
+        .find(|c| c["channel"]["channel_point"].as_str() == Some(channel.channel_point.as_str()))
+        .ok_or_else(|| format!("Channel {} is not pending force-close", channel.channel_point))?;
+    let blocks_til_maturity = closing["blocks_til_maturity"].as_i64().unwrap_or(0);
+    if blocks_til_maturity <= 0 {
+        return Err(format!("Unexpected CSV maturity {} for our output", blocks_til_maturity));
+    }
+    bitcoind.mine(blocks_til_maturity as u64).await?;
+
+    // Keep mining until the sweeper's transaction confirms and the channel resolves
+    wait_for("force-close sweep to confirm", SWEEP_TIMEOUT, POLL_INTERVAL, || async {
+        let pending = node_a.pending_channels().await?;
+        let still_pending = pending
+            .pending_force_closing_channels
+            .iter()
+            .any(|c| c["channel"]["channel_point"].as_str() == Some(channel.channel_point.as_str()));
+        if still_pending {
+            bitcoind.mine(1).await?;
+            node_a.wait_synced(bitcoind).await?;
+            return Ok(None);
+        }
+        let balance = node_a.wallet_balance().await?;
+        Ok((balance.unconfirmed_balance == 0).then_some(()))
+    })
+    .await?;
+
+    let swept = node_a.wallet_balance().await?.confirmed_balance - balance_before;
+    if swept < channel.local_balance - MAX_SWEEP_FEES_SAT || swept > channel.local_balance {
+        return Err(format!(
+            "Swept {} sat back on-chain, expected close to the {} sat channel balance",
+            swept, channel.local_balance
+        ));
+    }
+    Ok(format!("Force-closed in {}, swept {} sat", closing_txid, swept))
+}
@@ -0,0 +1,879 @@
+//! Minimal LND REST client for the regtest Lightning nodes
+//!
+//! LND serves REST over its self-signed TLS certificate and authenticates with a
+//! hex-encoded macaroon header, mirroring lnurl-server/services/lnd.js.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use reqwest::{Client, Method};
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+
+use crate::bitcoind::Bitcoind;
+use crate::config;
+use crate::faucet::Faucet;
+use crate::wait_for;
+
+/// Node "A": the `lnd` compose service also used by lnurl-server.
+pub const LND_A_REST_URL: &str = "https://localhost:8080";
+pub const LND_A_MACAROON_PATH: &str = "../lnd/data/chain/bitcoin/regtest/admin.macaroon";
+pub const LND_A_P2P_HOST: &str = "lnd:9735";
+
+/// Node "B": the `lnd2` compose service, a second peer for payment scenarios.
+pub const LND_B_REST_URL: &str = "https://localhost:8081";
+pub const LND_B_MACAROON_PATH: &str = "../lnd2/data/chain/bitcoin/regtest/admin.macaroon";
+pub const LND_B_P2P_HOST: &str = "lnd2:9735";
+
+/// Node "C": the seeded `lnd3` service that restore scenarios wipe and recreate.
+/// It starts without a wallet, so it has no macaroon on disk until initialized.
+pub const LND_C_REST_URL: &str = "https://localhost:8082";
+pub const LND_C_SERVICE: &str = "lnd3";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+// On-chain top-up sent to a node before it opens a channel
+const NODE_FUNDING_SAT: u64 = 10_000_000;
+
+// Time for the REST proxy to register a freshly connected stream with LND
+const STREAM_SETTLE: Duration = Duration::from_millis(500);
+
+/// grpc-gateway encodes 64-bit integers as JSON strings.
+fn de_i64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int {
+        Str(String),
+        Num(i64),
+    }
+    match Int::deserialize(deserializer)? {
+        Int::Str(s) => s.parse().map_err(de::Error::custom),
+        Int::Num(n) => Ok(n),
+    }
+}
+
+/// grpc-gateway encodes `bytes` fields as standard base64.
+fn de_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    BASE64.decode(s).map_err(de::Error::custom)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetInfo {
+    pub identity_pubkey: String,
+    pub alias: String,
+    #[serde(default)]
+    pub block_hash: String,
+    #[serde(default)]
+    pub num_active_channels: i64,
+    #[serde(default)]
+    pub block_height: i64,
+    #[serde(default)]
+    pub synced_to_chain: bool,
+    #[serde(default)]
+    pub uris: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletBalance {
+    #[serde(default, deserialize_with = "de_i64")]
+    pub confirmed_balance: i64,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub unconfirmed_balance: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Amount {
+    #[serde(default, deserialize_with = "de_i64")]
+    pub sat: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelBalance {
+    #[serde(default)]
+    pub local_balance: Amount,
+    #[serde(default)]
+    pub remote_balance: Amount,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Channel {
+    #[serde(default)]
+    pub active: bool,
+    pub remote_pubkey: String,
+    pub channel_point: String,
+    #[serde(default)]
+    pub chan_id: String,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub capacity: i64,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub local_balance: i64,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub remote_balance: i64,
+    #[serde(default)]
+    pub zero_conf: bool,
+    #[serde(default)]
+    pub private: bool,
+}
+
+impl Channel {
+    /// Funding txid half of `channel_point` (`txid:index`).
+    pub fn funding_txid(&self) -> &str {
+        self.channel_point
+            .split(':')
+            .next()
+            .unwrap_or(&self.channel_point)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListChannels {
+    #[serde(default)]
+    channels: Vec<Channel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelEdge {
+    pub channel_id: String,
+    pub chan_point: String,
+    pub node1_pub: String,
+    pub node2_pub: String,
+    /// Routing policies; `None` until that side's channel update arrives.
+    #[serde(default)]
+    pub node1_policy: Option<Value>,
+    #[serde(default)]
+    pub node2_policy: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LightningNode {
+    pub pub_key: String,
+    #[serde(default)]
+    pub alias: String,
+    /// Announced addresses; empty until the node announcement arrives.
+    #[serde(default)]
+    pub addresses: Vec<NodeAddress>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NodeAddress {
+    #[serde(default)]
+    pub network: String,
+    pub addr: String,
+}
+
+/// The node's view of the public channel graph, built from gossip.
+#[derive(Debug, Deserialize)]
+pub struct ChannelGraph {
+    #[serde(default)]
+    pub nodes: Vec<LightningNode>,
+    #[serde(default)]
+    pub edges: Vec<ChannelEdge>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelCloseSummary {
+    pub channel_point: String,
+    #[serde(default)]
+    pub closing_tx_hash: String,
+    /// e.g. `COOPERATIVE_CLOSE`, `LOCAL_FORCE_CLOSE`, `BREACH_CLOSE`.
+    #[serde(default)]
+    pub close_type: String,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub settled_balance: i64,
+}
+
+/// Backup progress of the watchtower client.
+#[derive(Debug, Deserialize)]
+pub struct TowerClientStats {
+    #[serde(default)]
+    pub num_backups: u32,
+    #[serde(default)]
+    pub num_pending_backups: u32,
+    #[serde(default)]
+    pub num_failed_backup_attempts: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PendingChannels {
+    #[serde(default)]
+    pub pending_open_channels: Vec<Value>,
+    #[serde(default)]
+    pub pending_force_closing_channels: Vec<Value>,
+    #[serde(default)]
+    pub waiting_close_channels: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Transaction {
+    pub tx_hash: String,
+    #[serde(default)]
+    pub num_confirmations: i64,
+    #[serde(default)]
+    pub block_height: i64,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub amount: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionDetails {
+    #[serde(default)]
+    transactions: Vec<Transaction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddInvoice {
+    #[serde(deserialize_with = "de_base64")]
+    pub r_hash: Vec<u8>,
+    pub payment_request: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Invoice {
+    #[serde(default)]
+    pub state: String,
+    #[serde(default, deserialize_with = "de_base64")]
+    pub r_preimage: Vec<u8>,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub value: i64,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub amt_paid_sat: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Route {
+    #[serde(default, deserialize_with = "de_i64")]
+    pub total_fees: i64,
+    #[serde(default, deserialize_with = "de_i64")]
+    pub total_amt: i64,
+    #[serde(default)]
+    pub hops: Vec<Hop>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Hop {
+    pub chan_id: String,
+    #[serde(default)]
+    pub pub_key: String,
+    /// Amount `pub_key` passes on (or receives, on the last hop).
+    #[serde(default, deserialize_with = "de_i64")]
+    pub amt_to_forward_msat: i64,
+    /// Fee `pub_key` keeps for forwarding.
+    #[serde(default, deserialize_with = "de_i64")]
+    pub fee_msat: i64,
+}
+
+/// Why a payment attempt failed, as reported back by the failing hop.
+#[derive(Debug, Deserialize)]
+pub struct HtlcFailure {
+    /// e.g. `TEMPORARY_CHANNEL_FAILURE`, `CHANNEL_DISABLED`; `UNKNOWN_FAILURE` if undecodable.
+    #[serde(default)]
+    pub code: String,
+    /// Index in the route of the hop that returned the failure.
+    #[serde(default)]
+    pub failure_source_index: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HtlcAttempt {
+    /// `IN_FLIGHT`, `SUCCEEDED` or `FAILED`
+    pub status: String,
+    pub route: Route,
+    pub failure: Option<HtlcFailure>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Payment {
+    pub payment_hash: String,
+    /// `IN_FLIGHT`, `SUCCEEDED` or `FAILED`
+    pub status: String,
+    /// `FAILURE_REASON_NONE` unless the payment failed as a whole.
+    #[serde(default)]
+    pub failure_reason: String,
+    #[serde(default)]
+    pub htlcs: Vec<HtlcAttempt>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendResponse {
+    #[serde(default)]
+    pub payment_error: String,
+    #[serde(default, deserialize_with = "de_base64")]
+    pub payment_preimage: Vec<u8>,
+    #[serde(default, deserialize_with = "de_base64")]
+    pub payment_hash: Vec<u8>,
+    pub payment_route: Option<Route>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelPoint {
+    #[serde(default, deserialize_with = "de_base64")]
+    pub funding_txid_bytes: Vec<u8>,
+    #[serde(default)]
+    pub output_index: u32,
+}
+
+impl ChannelPoint {
+    /// Funding txid in the usual display (reversed) byte order.
+    pub fn funding_txid(&self) -> String {
+        let mut bytes = self.funding_txid_bytes.clone();
+        bytes.reverse();
+        hex::encode(bytes)
+    }
+}
+
+pub struct Lnd {
+    client: Client,
+    url: String,
+    macaroon_hex: String,
+}
+
+impl Lnd {
+    pub fn new(url: &str, macaroon_path: &str) -> Result<Self, String> {
+        let macaroon = fs::read(macaroon_path)
+            .map_err(|e| format!("Failed to read macaroon {}: {:?}", macaroon_path, e))?;
+        Self::with_macaroon(url, &macaroon)
+    }
+
+    /// Client for a node whose wallet is not initialized yet; only the
+    /// wallet unlocker endpoints answer until [`Lnd::init_wallet`] runs.
+    pub fn uninitialized(url: &str) -> Result<Self, String> {
+        Self::with_macaroon(url, &[])
+    }
+
+    pub fn with_macaroon(url: &str, macaroon: &[u8]) -> Result<Self, String> {
+        let client = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| format!("Failed to build LND client: {:?}", e))?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            macaroon_hex: hex::encode(macaroon),
+        })
+    }
+
+    /// Node A (`lnd` service).
+    pub fn node_a() -> Result<Self, String> {
+        let settings = &config::get().lnd;
+        Self::new(&settings.node_a_rest_url, &settings.node_a_macaroon_path)
+    }
+
+    /// Node B (`lnd2` service).
+    pub fn node_b() -> Result<Self, String> {
+        let settings = &config::get().lnd;
+        Self::new(&settings.node_b_rest_url, &settings.node_b_macaroon_path)
+    }
+
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<T, String> {
+        let mut req = self
+            .client
+            .request(method.clone(), format!("{}{}", self.url, path))
+            .header("Grpc-Metadata-macaroon", &self.macaroon_hex);
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| format!("LND {} {} request failed: {:?}", method, path, e))?;
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| format!("LND {} {} body read failed: {:?}", method, path, e))?;
+
+        if !status.is_success() {
+            return Err(format!("LND {} {} returned {}: {}", method, path, status, text));
+        }
+        serde_json::from_str(&text)
+            .map_err(|e| format!("LND {} {} returned unparsable body {}: {}", method, path, text, e))
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        self.request(Method::GET, path, None).await
+    }
+
+    pub async fn post<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T, String> {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    /// Generate a fresh aezeed mnemonic (no passphrase).
+    pub async fn gen_seed(&self) -> Result<Vec<String>, String> {
+        let resp: Value = self.get("/v1/genseed").await?;
+        serde_json::from_value(resp["cipher_seed_mnemonic"].clone())
+            .map_err(|e| format!("LND genseed returned no mnemonic: {} ({})", resp, e))
+    }
+
+    /// Create the wallet from `mnemonic`, optionally restoring channels from a
+    /// multi-channel backup, and return a client holding the new admin macaroon.
+    pub async fn init_wallet(
+        &self,
+        password: &str,
+        mnemonic: &[String],
+        multi_chan_backup: Option<&[u8]>,
+        recovery_window: u32,
+    ) -> Result<Lnd, String> {
+        let mut body = json!({
+            "wallet_password": BASE64.encode(password),
+            "cipher_seed_mnemonic": mnemonic,
+            "recovery_window": recovery_window,
+            "stateless_init": true,
+        });
+        if let Some(backup) = multi_chan_backup {
+            body["channel_backups"] = json!({
+                "multi_chan_backup": { "multi_chan_backup": BASE64.encode(backup) },
+            });
+        }
+        let resp: Value = self.post("/v1/initwallet", body).await?;
+        let macaroon = resp["admin_macaroon"]
+            .as_str()
+            .and_then(|m| BASE64.decode(m).ok())
+            .ok_or_else(|| format!("LND initwallet returned no admin macaroon: {}", resp))?;
+        Self::with_macaroon(&self.url, &macaroon)
+    }
+
+    /// Static backup of all open channels, as LND's packed multi-channel blob.
+    pub async fn export_channel_backups(&self) -> Result<Vec<u8>, String> {
+        let resp: Value = self.get("/v1/channels/backup").await?;
+        resp["multi_chan_backup"]["multi_chan_backup"]
+            .as_str()
+            .and_then(|b| BASE64.decode(b).ok())
+            .ok_or_else(|| format!("LND returned no multi-channel backup: {}", resp))
+    }
+
+    pub async fn get_info(&self) -> Result<GetInfo, String> {
+        self.get("/v1/getinfo").await
+    }
+
+    pub async fn new_address(&self) -> Result<String, String> {
+        let resp: Value = self.get("/v1/newaddress").await?;
+        resp["address"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("LND newaddress returned no address: {}", resp))
+    }
+
+    /// New P2TR (bech32m) address from the node wallet.
+    pub async fn new_taproot_address(&self) -> Result<String, String> {
+        let resp: Value = self.get("/v1/newaddress?type=TAPROOT_PUBKEY").await?;
+        resp["address"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("LND newaddress returned no address: {}", resp))
+    }
+
+    /// Spend exactly the wallet output `txid:output_index` to `address`, returning the txid.
+    pub async fn sweep_outpoint(
+        &self,
+        txid: &str,
+        output_index: u32,
+        address: &str,
+        sat_per_vbyte: u64,
+    ) -> Result<String, String> {
+        let body = json!({
+            "addr": address,
+            "send_all": true,
+            "outpoints": [{ "txid_str": txid, "output_index": output_index }],
+            "sat_per_vbyte": sat_per_vbyte.to_string(),
+        });
+        let resp: Value = self.post("/v1/transactions", body).await?;
+        resp["txid"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("LND sendcoins returned no txid: {}", resp))
+    }
+
+    pub async fn wallet_balance(&self) -> Result<WalletBalance, String> {
+        self.get("/v1/balance/blockchain").await
+    }
+
+    pub async fn channel_balance(&self) -> Result<ChannelBalance, String> {
+        self.get("/v1/balance/channels").await
+    }
+
+    pub async fn list_channels(&self) -> Result<Vec<Channel>, String> {
+        let resp: ListChannels = self.get("/v1/channels").await?;
+        Ok(resp.channels)
+    }
+
+    pub async fn describe_graph(&self) -> Result<ChannelGraph, String> {
+        self.get("/v1/graph").await
+    }
+
+    pub async fn closed_channels(&self) -> Result<Vec<ChannelCloseSummary>, String> {
+        let resp: Value = self.get("/v1/channels/closed").await?;
+        serde_json::from_value(resp["channels"].clone())
+            .map_err(|e| format!("Unexpected closed channels response {}: {}", resp, e))
+    }
+
+    /// Public key (hex) and URIs of this node's watchtower server.
+    pub async fn tower_info(&self) -> Result<(String, Vec<String>), String> {
+        let resp: Value = self.get("/v2/watchtower/server").await?;
+        let pubkey = resp["pubkey"]
+            .as_str()
+            .and_then(|p| BASE64.decode(p).ok())
+            .map(hex::encode)
+            .ok_or_else(|| format!("LND watchtower returned no pubkey: {}", resp))?;
+        let uris = serde_json::from_value(resp["uris"].clone()).unwrap_or_default();
+        Ok((pubkey, uris))
+    }
+
+    /// Register a watchtower with this node's tower client.
+    pub async fn add_tower(&self, pubkey: &str, address: &str) -> Result<(), String> {
+        let pubkey_bytes =
+            hex::decode(pubkey).map_err(|e| format!("Invalid tower pubkey {}: {:?}", pubkey, e))?;
+        let body = json!({ "pubkey": BASE64.encode(pubkey_bytes), "address": address });
+        self.post::<Value>("/v2/watchtower/client", body).await?;
+        Ok(())
+    }
+
+    pub async fn tower_client_stats(&self) -> Result<TowerClientStats, String> {
+        self.get("/v2/watchtower/client/stats").await
+    }
+
+    pub async fn pending_channels(&self) -> Result<PendingChannels, String> {
+        self.get("/v1/channels/pending").await
+    }
+
+    pub async fn get_transactions(&self) -> Result<Vec<Transaction>, String> {
+        let resp: TransactionDetails = self.get("/v1/transactions").await?;
+        Ok(resp.transactions)
+    }
+
+    /// Fee rate LND's estimator uses for `conf_target`, in sat/kw.
+    pub async fn estimate_fee_sat_per_kw(&self, conf_target: u32) -> Result<i64, String> {
+        let resp: Value = self
+            .get(&format!("/v2/wallet/estimatefee/{}", conf_target))
+            .await?;
+        resp["sat_per_kw"]
+            .as_str()
+            .and_then(|s| s.parse().ok())
+            .or_else(|| resp["sat_per_kw"].as_i64())
+            .ok_or_else(|| format!("LND estimatefee returned no sat_per_kw: {}", resp))
+    }
+
+    /// Fee-bump an unconfirmed output this wallet owns; for incoming
+    /// transactions LND does this by spending the output in a CPFP child.
+    pub async fn bump_fee(&self, txid: &str, output_index: u32, sat_per_vbyte: u64) -> Result<(), String> {
+        let body = json!({
+            "outpoint": { "txid_str": txid, "output_index": output_index },
+            "sat_per_vbyte": sat_per_vbyte.to_string(),
+            "immediate": true,
+        });
+        self.post::<Value>("/v2/wallet/bumpfee", body).await?;
+        Ok(())
+    }
+
+    /// Connect to a peer, treating "already connected" as success.
+    pub async fn connect_peer(&self, pubkey: &str, host: &str) -> Result<(), String> {
+        let body = json!({ "addr": { "pubkey": pubkey, "host": host }, "perm": false });
+        match self.post::<Value>("/v1/peers", body).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.contains("already connected") => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn open_channel(
+        &self,
+        pubkey: &str,
+        local_funding_sat: u64,
+        push_sat: u64,
+    ) -> Result<ChannelPoint, String> {
+        let pubkey_bytes =
+            hex::decode(pubkey).map_err(|e| format!("Invalid node pubkey {}: {:?}", pubkey, e))?;
+        let body = json!({
+            "node_pubkey": BASE64.encode(pubkey_bytes),
+            "local_funding_amount": local_funding_sat.to_string(),
+            "push_sat": push_sat.to_string(),
+        });
+        self.post("/v1/channels", body).await
+    }
+
+    /// Start closing the channel at `channel_point` (`txid:index`) and return the
+    /// closing txid once it is broadcast, without waiting for confirmation.
+    pub async fn close_channel(&self, channel_point: &str, force: bool) -> Result<String, String> {
+        let (txid, index) = channel_point
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid channel point {}", channel_point))?;
+        let path = format!("/v1/channels/{}/{}?force={}", txid, index, force);
+        let mut resp = self
+            .client
+            .delete(format!("{}{}", self.url, path))
+            .header("Grpc-Metadata-macaroon", &self.macaroon_hex)
+            .send()
+            .await
+            .map_err(|e| format!("LND DELETE {} request failed: {:?}", path, e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("LND DELETE {} returned {}: {}", path, status, text));
+        }
+
+        // Close updates are streamed as newline-delimited JSON; the first is close_pending
+        let mut buffer = Vec::new();
+        let line = loop {
+            if let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                break buffer[..end].to_vec();
+            }
+            match resp.chunk().await {
+                Ok(Some(chunk)) => buffer.extend_from_slice(&chunk),
+                Ok(None) => break buffer.clone(),
+                Err(e) => return Err(format!("LND DELETE {} stream failed: {:?}", path, e)),
+            }
+        };
+        let update: Value = serde_json::from_slice(&line)
+            .map_err(|e| format!("LND DELETE {} returned unparsable update: {}", path, e))?;
+        let txid_bytes = update["result"]["close_pending"]["txid"]
+            .as_str()
+            .and_then(|t| BASE64.decode(t).ok())
+            .ok_or_else(|| format!("LND DELETE {} returned no closing txid: {}", path, update))?;
+        let mut txid_bytes = txid_bytes;
+        txid_bytes.reverse();
+        Ok(hex::encode(txid_bytes))
+    }
+
+    /// Open a private zero-conf channel; the peer must accept it through a
+    /// channel acceptor (see [`Lnd::spawn_channel_acceptor`]).
+    pub async fn open_zero_conf_channel(
+        &self,
+        pubkey: &str,
+        local_funding_sat: u64,
+        push_sat: u64,
+    ) -> Result<ChannelPoint, String> {
+        let pubkey_bytes =
+            hex::decode(pubkey).map_err(|e| format!("Invalid node pubkey {}: {:?}", pubkey, e))?;
+        let body = json!({
+            "node_pubkey": BASE64.encode(pubkey_bytes),
+            "local_funding_amount": local_funding_sat.to_string(),
+            "push_sat": push_sat.to_string(),
+            "private": true,
+            "zero_conf": true,
+            "scid_alias": true,
+            "commitment_type": "ANCHORS",
+        });
+        self.post("/v1/channels", body).await
+    }
+
+    /// Accept every incoming channel, granting zero-conf to peers that ask
+    /// for it, until the returned task is aborted. LND only accepts zero-conf
+    /// channels through such an acceptor.
+    pub async fn spawn_channel_acceptor(&self) -> Result<JoinHandle<()>, String> {
+        let url = format!(
+            "{}/v1/channels/acceptor?method=POST",
+            self.url.replacen("https://", "wss://", 1)
+        );
+        let mut request = url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| format!("Invalid acceptor URL {}: {:?}", url, e))?;
+        let macaroon = HeaderValue::from_str(&self.macaroon_hex)
+            .map_err(|e| format!("Invalid macaroon header: {:?}", e))?;
+        request
+            .headers_mut()
+            .insert("Grpc-Metadata-macaroon", macaroon);
+
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| format!("Failed to build TLS connector: {:?}", e))?;
+        let (mut socket, _) = tokio_tungstenite::connect_async_tls_with_config(
+            request,
+            None,
+            false,
+            Some(Connector::NativeTls(tls)),
+        )
+        .await
+        .map_err(|e| format!("Failed to open channel acceptor stream: {:?}", e))?;
+
+        let handle = tokio::spawn(async move {
+            while let Some(Ok(message)) = socket.next().await {
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let Ok(frame) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+                let request = &frame["result"];
+                let zero_conf = request["wants_zero_conf"].as_bool().unwrap_or(false);
+                let response = json!({
+                    "accept": true,
+                    "pending_chan_id": request["pending_chan_id"],
+                    "zero_conf": zero_conf,
+                    "min_accept_depth": if zero_conf { 0 } else { 1 },
+                });
+                if socket.send(Message::Text(response.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        });
+        tokio::time::sleep(STREAM_SETTLE).await;
+        Ok(handle)
+    }
+
+    pub async fn add_invoice(&self, value_sat: u64, memo: &str) -> Result<AddInvoice, String> {
+        let body = json!({ "value": value_sat.to_string(), "memo": memo });
+        self.post("/v1/invoices", body).await
+    }
+
+    pub async fn lookup_invoice(&self, r_hash: &[u8]) -> Result<Invoice, String> {
+        self.get(&format!("/v1/invoice/{}", hex::encode(r_hash)))
+            .await
+    }
+
+    /// Pay a BOLT11 invoice synchronously; routing failures are reported in `payment_error`.
+    pub async fn pay_invoice(&self, payment_request: &str) -> Result<SendResponse, String> {
+        let body = json!({ "payment_request": payment_request });
+        self.post("/v1/channels/transactions", body).await
+    }
+
+    /// Pay a BOLT11 invoice forcing the first hop over `outgoing_chan_id`.
+    pub async fn pay_invoice_via(
+        &self,
+        payment_request: &str,
+        outgoing_chan_id: &str,
+    ) -> Result<SendResponse, String> {
+        let body = json!({
+            "payment_request": payment_request,
+            "outgoing_chan_id": outgoing_chan_id,
+        });
+        self.post("/v1/channels/transactions", body).await
+    }
+
+    /// Outgoing payment with hash `payment_hash`, including every HTLC attempt.
+    pub async fn lookup_payment(&self, payment_hash: &[u8]) -> Result<Payment, String> {
+        let hash = hex::encode(payment_hash);
+        let resp: Value = self
+            .get("/v1/payments?include_incomplete=true&reversed=true&max_payments=100")
+            .await?;
+        let payments: Vec<Payment> = serde_json::from_value(resp["payments"].clone())
+            .map_err(|e| format!("Unexpected payments response: {}", e))?;
+        payments
+            .into_iter()
+            .find(|p| p.payment_hash == hash)
+            .ok_or_else(|| format!("LND has no payment with hash {}", hash))
+    }
+
+    /// Manually enable or disable a channel for forwarding (`ENABLE`, `DISABLE` or `AUTO`).
+    pub async fn update_channel_status(&self, channel_point: &str, action: &str) -> Result<(), String> {
+        let (txid, index) = channel_point
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid channel point {}", channel_point))?;
+        let output_index: u32 = index
+            .parse()
+            .map_err(|e| format!("Invalid channel point {}: {:?}", channel_point, e))?;
+        let body = json!({
+            "chan_point": { "funding_txid_str": txid, "output_index": output_index },
+            "action": action,
+        });
+        self.post::<Value>("/v2/router/updatechanstatus", body).await?;
+        Ok(())
+    }
+
+    /// Pay our own invoice around a cycle, leaving over `outgoing_chan_id` and
+    /// returning through `last_hop_pubkey`, to shift liquidity between channels.
+    pub async fn pay_circular(
+        &self,
+        payment_request: &str,
+        outgoing_chan_id: &str,
+        last_hop_pubkey: &str,
+    ) -> Result<SendResponse, String> {
+        let last_hop = hex::decode(last_hop_pubkey)
+            .map_err(|e| format!("Invalid node pubkey {}: {:?}", last_hop_pubkey, e))?;
+        let body = json!({
+            "payment_request": payment_request,
+            "outgoing_chan_id": outgoing_chan_id,
+            "last_hop_pubkey": BASE64.encode(last_hop),
+            "allow_self_payment": true,
+        });
+        self.post("/v1/channels/transactions", body).await
+    }
+
+    /// Wait until LND has caught up with bitcoind's tip.
+    pub async fn wait_synced(&self, bitcoind: &Bitcoind) -> Result<(), String> {
+        let height = bitcoind.get_block_count().await? as i64;
+        wait_for("LND to sync to chain tip", SYNC_TIMEOUT, POLL_INTERVAL, || async {
+            let info = self.get_info().await?;
+            Ok((info.synced_to_chain && info.block_height >= height).then_some(()))
+        })
+        .await
+    }
+
+    /// Make sure the on-chain wallet holds at least `needed_sat` confirmed.
+    pub async fn ensure_wallet_funds(&self, bitcoind: &Bitcoind, needed_sat: u64) -> Result<(), String> {
+        if (self.wallet_balance().await?.confirmed_balance as u64) >= needed_sat {
+            return Ok(());
+        }
+        let funding_sat = NODE_FUNDING_SAT.max(needed_sat);
+        let address = self.new_address().await?;
+        Faucet::from_env(bitcoind)?.fund(&address, funding_sat, 1).await?;
+        self.wait_synced(bitcoind).await?;
+        wait_for("node funds to confirm", SYNC_TIMEOUT, POLL_INTERVAL, || async {
+            let balance = self.wallet_balance().await?;
+            Ok((balance.confirmed_balance as u64 >= needed_sat).then_some(()))
+        })
+        .await
+    }
+
+    /// Make sure this node has an active public channel to `pubkey` with at least
+    /// `min_local_sat` outbound, funding the wallet and opening one if needed.
+    pub async fn ensure_channel(
+        &self,
+        bitcoind: &Bitcoind,
+        pubkey: &str,
+        host: &str,
+        capacity_sat: u64,
+        min_local_sat: u64,
+    ) -> Result<Channel, String> {
+        let channels = self.list_channels().await?;
+        if let Some(channel) = channels
+            .into_iter()
+            .find(|c| {
+                c.active && !c.private && c.remote_pubkey == pubkey && c.local_balance as u64 >= min_local_sat
+            })
+        {
+            return Ok(channel);
+        }
+
+        self.ensure_wallet_funds(bitcoind, capacity_sat * 2).await?;
+        self.connect_peer(pubkey, host).await?;
+        let point = self.open_channel(pubkey, capacity_sat, 0).await?;
+        bitcoind.mine(6).await?;
+        self.wait_synced(bitcoind).await?;
+        let funding_txid = point.funding_txid();
+        wait_for("channel to become active", SYNC_TIMEOUT, POLL_INTERVAL, || async {
+            let channels = self.list_channels().await?;
+            Ok(channels
+                .into_iter()
+                .find(|c| c.active && c.funding_txid() == funding_txid))
+        })
+        .await
+    }
+
+    /// Wait for an active channel with `pubkey` and return it.
+    pub async fn wait_channel_active(&self, pubkey: &str) -> Result<Channel, String> {
+        wait_for("channel to become active", SYNC_TIMEOUT, POLL_INTERVAL, || async {
+            let channels = self.list_channels().await?;
+            Ok(channels
+                .into_iter()
+                .find(|c| c.active && c.remote_pubkey == pubkey))
+        })
+        .await
+    }
+}
@@ -0,0 +1,1374 @@
+//! Load generation against vss-server
+//!
+//! A `Workload` keeps a number of workers busy for a while, each sending one
+//! request after the other: a put, get or list drawn from the `Mix`, on a key
+//! drawn from a fixed key space in one store. Each operation draws its keys
+//! evenly or, as a wallet's traffic does, mostly from a few hot ones (see
+//! `KeyDistribution`). The keys are written once
+//! before the clock starts, so gets find what they ask for and a put
+//! overwrites rather than creates. Every request is counted by operation, as
+//! a success, an error status or a transport failure, with its duration in a
+//! histogram, and the `Report` turns the counts into throughput, error rates
+//! and latency percentiles.
+//!
+//! With a `rate` the workers pace themselves to that many requests per second
+//! between them, and `Report::sustains` tells whether the server kept up with
+//! it, for searching the highest rate it can take. `Workload::run_open` sends
+//! at a rate as an open loop instead, each request at its own moment of a
+//! Poisson process, with no workers to hold the next one back while the server
+//! is slow.
+//!
+//! An isolated workload gives each worker a store and a user of its own
+//! instead, so the workers are tenants that share only the server, for
+//! telling whether its throughput grows with the tenants or stalls on locks in
+//! the tables they all live in.
+//!
+//! A `Ramp` holds some of the workers back for part of the run, so the load
+//! grows, steps or spikes instead of arriving all at once; the report then
+//! also keeps a timeline, second by second, of how many workers sent and what
+//! came back, and names the first second whose error rate went over a limit.
+//!
+//! `PoolSetting` is the client side of a run: HTTP version and how many idle
+//! connections are kept, for sweeping them over the same workload.
+//!
+//! `TokenPool` signs tokens for many subjects before the clock starts, for a
+//! workload spread over many users and for `TokenBench`, which drives the
+//! same workers with one request and many tokens to measure what checking the
+//! JWT costs the server.
+//!
+//! A workload given a `SampleFile` also writes every request it sent to it,
+//! with its endpoint, status and latency, for analysis the report does not
+//! cover.
+//!
+//! Workers draw from the run's seed (see `test_harness::rng`), so a replayed
+//! run sends the same sequence of operations, if not at the same moments.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures_util::future::join_all;
+use rand::Rng;
+use reqwest::Client;
+use test_harness::latency::{self, Latencies};
+use test_harness::rng;
+use tokio::task::{JoinError, JoinSet};
+use vss_client::types::{GetObjectRequest, KeyValue, ListKeyVersionsRequest, PutObjectRequest};
+
+use crate::config;
+use crate::vss::{sign_tokens, Vss};
+
+// Keys written by the load, with the index appended
+const KEY_PREFIX: &str = "load-";
+// Version VSS takes as "write whatever is there", so puts never conflict
+const UNCONDITIONAL_VERSION: i64 = -1;
+const LIST_PAGE_SIZE: i32 = 100;
+// How often a worker the ramp holds back checks whether it may send
+const RAMP_POLL: Duration = Duration::from_millis(10);
+// Steps of `Ramp::Step`
+const RAMP_STEPS: usize = 5;
+// Share of the asked-for rate a run must reach to have sustained it
+const SUSTAINED_SHARE: f64 = 0.95;
+// Rows the timeline is printed in at most; seconds are grouped to fit
+const TIMELINE_ROWS: usize = 20;
+// Exponent of `zipfian` without one, YCSB's
+const ZIPF_EXPONENT: f64 = 0.99;
+// Open-loop requests waiting for an answer at most; arrivals beyond count as
+// transport errors rather than piling up without end against a stalled server
+const MAX_IN_FLIGHT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Operation {
+    Put,
+    Get,
+    List,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Put, Operation::Get, Operation::List];
+
+    /// The VSS endpoint the operation calls.
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            Operation::Put => "putObjects",
+            Operation::Get => "getObject",
+            Operation::List => "listKeyVersions",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Put => "put",
+            Operation::Get => "get",
+            Operation::List => "list",
+        })
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|operation| operation.to_string() == s)
+            .ok_or_else(|| format!("Unknown operation {:?}; expected put, get or list", s))
+    }
+}
+
+/// Relative weights of the operations, as `get=80,list=15,put=5`; operations
+/// left out are not sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mix {
+    weights: BTreeMap<Operation, u32>,
+}
+
+impl Mix {
+    pub fn weight(&self, operation: Operation) -> u32 {
+        self.weights.get(&operation).copied().unwrap_or(0)
+    }
+
+    fn total(&self) -> u32 {
+        self.weights.values().sum()
+    }
+
+    /// An operation drawn with the mix's weights.
+    pub fn pick(&self, rng: &mut impl Rng) -> Operation {
+        let mut ticket = rng.gen_range(0..self.total());
+        for (operation, weight) in &self.weights {
+            if ticket < *weight {
+                return *operation;
+            }
+            ticket -= weight;
+        }
+        unreachable!("ticket drawn below the total weight")
+    }
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        "get=70,put=20,list=10".parse().expect("default mix parses")
+    }
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = BTreeMap::new();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (name, weight) = part.split_once('=').ok_or_else(|| {
+                format!("Invalid mix entry {:?}; expected OPERATION=WEIGHT", part)
+            })?;
+            let operation: Operation = name.trim().parse()?;
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid weight for {} in mix: {:?} ({})", name, weight, e))?;
+            if weights.insert(operation, weight).is_some() {
+                return Err(format!("Operation {} given twice in mix", operation));
+            }
+        }
+        let mix = Self { weights };
+        if mix.total() == 0 {
+            return Err(format!(
+                "Mix {:?} sends nothing; give an operation a weight",
+                s
+            ));
+        }
+        Ok(mix)
+    }
+}
+
+impl fmt::Display for Mix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .weights
+            .iter()
+            .filter(|(_, weight)| **weight > 0)
+            .map(|(operation, weight)| format!("{}={}", operation, weight))
+            .collect();
+        f.write_str(&parts.join(","))
+    }
+}
+
+/// How an operation draws its keys from the key space.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KeyDistribution {
+    /// Every key as likely as any other.
+    #[default]
+    Uniform,
+    /// The key of rank `i` drawn with weight `1 / (i + 1)^s`: a few keys take
+    /// most of the requests and the rest a long tail, as the channel monitors
+    /// and wallet state a Bitkit node touches all the time do next to old
+    /// records.
+    Zipfian(f64),
+}
+
+impl fmt::Display for KeyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyDistribution::Uniform => f.write_str("uniform"),
+            KeyDistribution::Zipfian(exponent) => write!(f, "zipfian:{}", exponent),
+        }
+    }
+}
+
+impl FromStr for KeyDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, exponent) = match s.split_once(':') {
+            Some((name, exponent)) => (name, Some(exponent)),
+            None => (s, None),
+        };
+        match (name, exponent) {
+            ("uniform", None) => Ok(KeyDistribution::Uniform),
+            ("zipfian", None) => Ok(KeyDistribution::Zipfian(ZIPF_EXPONENT)),
+            ("zipfian", Some(exponent)) => {
+                let exponent: f64 = exponent
+                    .parse()
+                    .map_err(|e| format!("Invalid zipfian exponent {:?} ({})", exponent, e))?;
+                if !(exponent > 0.0 && exponent.is_finite()) {
+                    return Err(format!(
+                        "Zipfian exponent must be above 0, got {}",
+                        exponent
+                    ));
+                }
+                Ok(KeyDistribution::Zipfian(exponent))
+            }
+            _ => Err(format!(
+                "Unknown key distribution {:?}; expected uniform, zipfian or zipfian:EXPONENT",
+                s
+            )),
+        }
+    }
+}
+
+/// The key distribution of each operation, as `zipfian` for all of them or
+/// `uniform,get=zipfian:1.2` for a default and the operations that differ.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyDistributions {
+    default: KeyDistribution,
+    operations: BTreeMap<Operation, KeyDistribution>,
+}
+
+impl KeyDistributions {
+    pub fn get(&self, operation: Operation) -> KeyDistribution {
+        self.operations
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl FromStr for KeyDistributions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut distributions = Self::default();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.split_once('=') {
+                Some((name, distribution)) => {
+                    let operation: Operation = name.trim().parse()?;
+                    if distributions
+                        .operations
+                        .insert(operation, distribution.trim().parse()?)
+                        .is_some()
+                    {
+                        return Err(format!(
+                            "Operation {} given twice in key distributions",
+                            operation
+                        ));
+                    }
+                }
+                None => distributions.default = part.parse()?,
+            }
+        }
+        Ok(distributions)
+    }
+}
+
+impl fmt::Display for KeyDistributions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![self.default.to_string()];
+        parts.extend(
+            self.operations
+                .iter()
+                .filter(|(_, distribution)| **distribution != self.default)
+                .map(|(operation, distribution)| format!("{}={}", operation, distribution)),
+        );
+        f.write_str(&parts.join(","))
+    }
+}
+
+/// Draws key indices for each operation; the zipfian ones from a cumulative
+/// table over the key space, built once per run.
+struct KeyDraw {
+    tables: BTreeMap<Operation, Option<Arc<[f64]>>>,
+}
+
+impl KeyDraw {
+    fn new(distributions: &KeyDistributions, key_space: usize) -> Self {
+        let mut built: Vec<(u64, Arc<[f64]>)> = Vec::new();
+        let tables = Operation::ALL
+            .into_iter()
+            .map(|operation| {
+                let table = match distributions.get(operation) {
+                    KeyDistribution::Uniform => None,
+                    KeyDistribution::Zipfian(exponent) => {
+                        // Operations with the same exponent share a table
+                        let bits = exponent.to_bits();
+                        let table = match built.iter().find(|(built, _)| *built == bits) {
+                            Some((_, table)) => Arc::clone(table),
+                            None => {
+                                let table = zipf_table(key_space, exponent);
+                                built.push((bits, Arc::clone(&table)));
+                                table
+                            }
+                        };
+                        Some(table)
+                    }
+                };
+                (operation, table)
+            })
+            .collect();
+        Self { tables }
+    }
+
+    fn index(&self, operation: Operation, key_space: usize, rng: &mut impl Rng) -> usize {
+        match self.tables.get(&operation).and_then(Option::as_ref) {
+            Some(table) => {
+                let ticket = rng.gen::<f64>() * table[table.len() - 1];
+                table
+                    .partition_point(|cumulative| *cumulative <= ticket)
+                    .min(key_space - 1)
+            }
+            None => rng.gen_range(0..key_space),
+        }
+    }
+}
+
+/// Cumulative weights of the ranks of a zipfian distribution over `n` keys.
+fn zipf_table(n: usize, exponent: f64) -> Arc<[f64]> {
+    let mut total = 0.0;
+    (1..=n)
+        .map(|rank| {
+            total += 1.0 / (rank as f64).powf(exponent);
+            total
+        })
+        .collect()
+}
+
+/// What the workers send, and for how long.
+#[derive(Debug, Clone)]
+pub struct Workload {
+    /// Store every key lives in, one the load has to itself.
+    pub store_id: String,
+    pub workers: usize,
+    pub duration: Duration,
+    pub mix: Mix,
+    /// Number of distinct keys.
+    pub key_space: usize,
+    /// How each operation picks its keys.
+    pub keys: KeyDistributions,
+    /// Bytes in each value put.
+    pub value_size: usize,
+    /// How many of the workers send at each moment of the run.
+    pub ramp: Ramp,
+    /// Users the key space is spread over, one token each, so requests come
+    /// from many subjects; `None` sends everything as the client's own.
+    pub users: Option<Arc<TokenPool>>,
+    /// Requests per second across the workers; `None` sends as fast as they
+    /// can.
+    pub rate: Option<f64>,
+    /// Give each worker a store of its own, `store_id` with the worker's
+    /// number appended, and send as the worker's user rather than the key's:
+    /// as many tenants as workers, sharing nothing but the server.
+    pub isolated: bool,
+    /// File every request of the runs is written to, when one is kept.
+    pub samples: Option<Arc<SampleFile>>,
+}
+
+impl Workload {
+    fn key(&self, index: usize) -> String {
+        format!("{}{:08}", KEY_PREFIX, index)
+    }
+
+    /// The store `worker` sends to.
+    fn store(&self, worker: usize) -> String {
+        if self.isolated {
+            format!("{}-{}", self.store_id, worker)
+        } else {
+            self.store_id.clone()
+        }
+    }
+
+    /// The client that sends for `worker` on key `index`: the user owning the
+    /// worker or the key, or `vss` itself.
+    fn client(&self, vss: &Vss, worker: usize, index: usize) -> Vss {
+        let user = if self.isolated { worker } else { index };
+        match &self.users {
+            Some(users) => vss.authenticated_as(users.get(user)),
+            None => vss.clone(),
+        }
+    }
+
+    fn put_request(&self, store_id: String, key: String, rng: &mut impl Rng) -> PutObjectRequest {
+        let mut value = vec![0u8; self.value_size];
+        rng.fill(value.as_mut_slice());
+        PutObjectRequest {
+            store_id,
+            global_version: None,
+            transaction_items: vec![KeyValue {
+                key,
+                version: UNCONDITIONAL_VERSION,
+                value,
+            }],
+            delete_items: vec![],
+        }
+    }
+
+    /// Write every key of the key space once, as its user, `workers` at a
+    /// time; when isolated, into every worker's store.
+    pub async fn prefill(&self, vss: &Vss) -> Result<(), String> {
+        let mut rng = rng::rng("load prefill");
+        let tenants = if self.isolated { self.workers } else { 1 };
+        let keys: Vec<(usize, usize)> = (0..tenants)
+            .flat_map(|worker| (0..self.key_space).map(move |index| (worker, index)))
+            .collect();
+        for chunk in keys.chunks(self.workers.max(1)) {
+            let requests: Vec<_> = chunk
+                .iter()
+                .map(|(worker, index)| {
+                    (
+                        self.client(vss, *worker, *index),
+                        self.put_request(self.store(*worker), self.key(*index), &mut rng),
+                    )
+                })
+                .collect();
+            let results = join_all(
+                requests
+                    .iter()
+                    .map(|(client, request)| client.request("putObjects", request)),
+            )
+            .await;
+            for result in results {
+                let (status, body) = result?;
+                if !(200..300).contains(&status) {
+                    return Err(format!(
+                        "Prefilling the key space: putObjects returned {}: {}",
+                        status,
+                        String::from_utf8_lossy(&body)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the workers sending for the workload's duration and count what
+    /// came back.
+    pub async fn run(&self, vss: Arc<Vss>) -> Report {
+        let workload = Arc::new(self.clone());
+        let keys = Arc::new(KeyDraw::new(&self.keys, self.key_space));
+        let report = drive(self.workers, self.duration, |worker, deadline| {
+            let workload = Arc::clone(&workload);
+            let keys = Arc::clone(&keys);
+            let vss = Arc::clone(&vss);
+            async move { workload.work(&vss, &keys, worker, deadline).await }
+        })
+        .await;
+        self.keep_samples(&report);
+        report
+    }
+
+    fn keep_samples(&self, report: &Report) {
+        if let Some(file) = &self.samples {
+            file.append(report.started, &report.samples);
+        }
+    }
+
+    async fn work(&self, vss: &Vss, keys: &KeyDraw, worker: usize, deadline: Instant) -> Tally {
+        let mut rng = rng::rng(&format!("load worker {}", worker));
+        let mut tally = Tally::new(self.samples.is_some());
+        let store_id = self.store(worker);
+        let started = deadline - self.duration;
+        // Each worker sends its share of the rate, the workers staggered
+        let interval = self
+            .rate
+            .map(|rate| Duration::from_secs_f64(self.workers as f64 / rate));
+        let mut next_send =
+            started + interval.unwrap_or_default() * worker as u32 / self.workers as u32;
+        while Instant::now() < deadline {
+            let elapsed = started.elapsed();
+            let active = self.ramp.workers_at(elapsed, self.duration, self.workers);
+            if worker >= active {
+                tokio::time::sleep(RAMP_POLL).await;
+                continue;
+            }
+            if let Some(interval) = interval {
+                tokio::time::sleep_until(next_send.min(deadline).into()).await;
+                // A worker that fell behind skips the slots it missed rather
+                // than bursting to catch up, so the rate is never exceeded
+                next_send = (next_send + interval).max(Instant::now());
+                if Instant::now() >= deadline {
+                    break;
+                }
+            }
+            let elapsed = started.elapsed();
+            let operation = self.mix.pick(&mut rng);
+            let index = keys.index(operation, self.key_space, &mut rng);
+            let client = self.client(vss, worker, index);
+            let call = self.call(operation, store_id.clone(), index, &mut rng);
+            let start = Instant::now();
+            let result = call.send(&client).await;
+            tally.record(operation, &result, start.elapsed(), elapsed, active);
+        }
+        tally
+    }
+
+    fn call(
+        &self,
+        operation: Operation,
+        store_id: String,
+        index: usize,
+        rng: &mut impl Rng,
+    ) -> Call {
+        let key = self.key(index);
+        match operation {
+            Operation::Put => Call::Put(self.put_request(store_id, key, rng)),
+            Operation::Get => Call::Get(GetObjectRequest { store_id, key }),
+            Operation::List => Call::List(ListKeyVersionsRequest {
+                store_id,
+                key_prefix: Some(KEY_PREFIX.to_string()),
+                page_size: Some(LIST_PAGE_SIZE),
+                page_token: None,
+            }),
+        }
+    }
+
+    /// Send requests for the workload's duration as an open loop: they
+    /// arrive at `rate` per second on average, at exponentially spread
+    /// moments, and each is sent at its moment whether or not the earlier ones
+    /// have come back. Its latency runs from that moment, so time a request
+    /// spent waiting for the client to get to it counts; a closed loop, whose
+    /// workers only send once the last request is back, leaves that out and
+    /// understates the tail of a server that stalls. Requests go out as the
+    /// workers' would, so an isolated workload spreads them over its tenants;
+    /// the ramp does not apply. `rate` must be finite and above 0.
+    pub async fn run_open(&self, vss: Arc<Vss>, rate: f64) -> Report {
+        let workload = Arc::new(self.clone());
+        let keys = KeyDraw::new(&self.keys, self.key_space);
+        let mut rng = rng::rng("load arrivals");
+        let mut tally = Tally::new(self.samples.is_some());
+        let mut in_flight = JoinSet::new();
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        let deadline = started + self.duration;
+        let mut arrival = started;
+        for sent in 0.. {
+            arrival += Duration::from_secs_f64(-(1.0 - rng.gen::<f64>()).ln() / rate);
+            if arrival >= deadline {
+                break;
+            }
+            tokio::time::sleep_until(arrival.into()).await;
+            while let Some(done) = in_flight.try_join_next() {
+                record_arrival(&mut tally, done);
+            }
+            let worker = sent % self.workers.max(1);
+            let operation = self.mix.pick(&mut rng);
+            let index = keys.index(operation, self.key_space, &mut rng);
+            let elapsed = arrival - started;
+            if in_flight.len() >= MAX_IN_FLIGHT {
+                let backlog = Err(format!("more than {} requests in flight", MAX_IN_FLIGHT));
+                tally.record(
+                    operation,
+                    &backlog,
+                    Duration::ZERO,
+                    elapsed,
+                    in_flight.len(),
+                );
+                continue;
+            }
+            let client = self.client(&vss, worker, index);
+            let call = workload.call(operation, workload.store(worker), index, &mut rng);
+            let waiting = in_flight.len() + 1;
+            in_flight.spawn(async move {
+                let result = call.send(&client).await;
+                (operation, result, arrival.elapsed(), elapsed, waiting)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            record_arrival(&mut tally, done);
+        }
+        let mut samples = tally.samples.unwrap_or_default();
+        samples.sort_by_key(|sample| sample.offset);
+        let report = Report {
+            started: started_at,
+            elapsed: self.duration,
+            counts: tally.counts,
+            timeline: tally.timeline,
+            samples,
+        };
+        self.keep_samples(&report);
+        report
+    }
+}
+
+/// Outcome of an open-loop request: the operation, what came back, its
+/// latency from its arrival, the arrival's offset into the run and how many
+/// requests were then in flight.
+type Arrival = (
+    Operation,
+    Result<(u16, Vec<u8>), String>,
+    Duration,
+    Duration,
+    usize,
+);
+
+fn record_arrival(tally: &mut Tally, done: Result<Arrival, JoinError>) {
+    // A request whose task panicked counts for nothing
+    if let Ok((operation, result, duration, elapsed, in_flight)) = done {
+        tally.record(operation, &result, duration, elapsed, in_flight);
+    }
+}
+
+/// One request of a workload, built before it is sent.
+enum Call {
+    Put(PutObjectRequest),
+    Get(GetObjectRequest),
+    List(ListKeyVersionsRequest),
+}
+
+impl Call {
+    async fn send(&self, client: &Vss) -> Result<(u16, Vec<u8>), String> {
+        match self {
+            Call::Put(request) => client.request(Operation::Put.endpoint(), request).await,
+            Call::Get(request) => client.request(Operation::Get.endpoint(), request).await,
+            Call::List(request) => client.request(Operation::List.endpoint(), request).await,
+        }
+    }
+}
+
+/// How many workers send over time: all of them from the start, or a shape
+/// that shows how the server copes as load grows or jumps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Ramp {
+    /// Every worker for the whole run
+    #[default]
+    Constant,
+    /// From one worker to all of them, evenly over the run
+    Linear,
+    /// All workers in `RAMP_STEPS` equal steps, each held for as long
+    Step,
+    /// A tenth of the workers, all of them in the middle fifth of the run
+    Spike,
+    /// From one worker to all and back, one sine period over the run
+    Sine,
+}
+
+impl fmt::Display for Ramp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Ramp::Constant => "constant",
+            Ramp::Linear => "linear",
+            Ramp::Step => "step",
+            Ramp::Spike => "spike",
+            Ramp::Sine => "sine",
+        })
+    }
+}
+
+impl Ramp {
+    /// Workers sending `elapsed` into a run of `duration` with `workers` in all.
+    pub fn workers_at(&self, elapsed: Duration, duration: Duration, workers: usize) -> usize {
+        let progress =
+            (elapsed.as_secs_f64() / duration.as_secs_f64().max(f64::EPSILON)).clamp(0.0, 1.0);
+        let share = |fraction: f64| ((workers as f64 * fraction).ceil() as usize).clamp(1, workers);
+        match self {
+            Ramp::Constant => workers,
+            Ramp::Linear => share(progress),
+            Ramp::Step => {
+                let step = ((progress * RAMP_STEPS as f64).floor() as usize).min(RAMP_STEPS - 1);
+                share((step + 1) as f64 / RAMP_STEPS as f64)
+            }
+            Ramp::Spike if (0.4..0.6).contains(&progress) => workers,
+            Ramp::Spike => share(0.1),
+            Ramp::Sine => share((1.0 - (progress * std::f64::consts::TAU).cos()) / 2.0),
+        }
+    }
+}
+
+/// What one second of a run saw, across the workers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Second {
+    /// Workers the ramp had sending.
+    pub workers: usize,
+    pub requests: u64,
+    pub errors: u64,
+    latency_total: Duration,
+}
+
+impl Second {
+    fn add(&mut self, other: &Second) {
+        self.workers = self.workers.max(other.workers);
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.latency_total += other.latency_total;
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        percent(self.errors, self.requests)
+    }
+
+    pub fn mean_latency_ms(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.latency_total.as_secs_f64() * 1000.0 / self.requests as f64
+        }
+    }
+}
+
+/// What one worker counted: by operation, and second by second.
+#[derive(Debug, Default)]
+struct Tally {
+    counts: BTreeMap<Operation, Counts>,
+    timeline: Vec<Second>,
+    /// Every request, when they are kept.
+    samples: Option<Vec<Sample>>,
+}
+
+impl Tally {
+    fn new(keep_samples: bool) -> Self {
+        Self {
+            samples: keep_samples.then(Vec::new),
+            ..Default::default()
+        }
+    }
+
+    /// Count a request sent `elapsed` into the run while `workers` were
+    /// sending.
+    fn record(
+        &mut self,
+        operation: Operation,
+        result: &Result<(u16, Vec<u8>), String>,
+        duration: Duration,
+        elapsed: Duration,
+        workers: usize,
+    ) {
+        let counts = self.counts.entry(operation).or_default();
+        let errors_before = counts.errors();
+        counts.record(result, duration);
+        let index = elapsed.as_secs() as usize;
+        if self.timeline.len() <= index {
+            self.timeline.resize(index + 1, Second::default());
+        }
+        let second = &mut self.timeline[index];
+        second.workers = second.workers.max(workers);
+        second.requests += 1;
+        second.errors += counts.errors() - errors_before;
+        second.latency_total += duration;
+        if let Some(samples) = &mut self.samples {
+            samples.push(Sample {
+                offset: elapsed,
+                operation,
+                status: result.as_ref().ok().map(|(status, _)| *status),
+                latency: duration,
+                workers,
+            });
+        }
+    }
+}
+
+/// One request of a run, as the sample file has it.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// When it was sent, from the start of the run.
+    pub offset: Duration,
+    pub operation: Operation,
+    /// Status of the response, or `None` for a request that got none.
+    pub status: Option<u16>,
+    pub latency: Duration,
+    /// Workers sending, or open-loop requests in flight, when it was sent.
+    pub workers: usize,
+}
+
+/// CSV file every request of the runs it is given goes to, for analysis
+/// outside the harness. One row per request:
+///
+/// `run,unix_ms,offset_ms,endpoint,status,latency_ms,workers`
+///
+/// with the runs of the process numbered from 1, the moment the request was
+/// sent both as Unix milliseconds and from the start of its run, and the
+/// status empty for a request that got no response.
+#[derive(Debug)]
+pub struct SampleFile {
+    path: PathBuf,
+    state: Mutex<SampleState>,
+}
+
+#[derive(Debug)]
+struct SampleState {
+    out: BufWriter<File>,
+    runs: usize,
+    rows: u64,
+    /// First write that failed; the rows after it are dropped.
+    error: Option<String>,
+}
+
+impl SampleFile {
+    const HEADER: &'static str = "run,unix_ms,offset_ms,endpoint,status,latency_ms,workers";
+
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create sample file {}: {}", path.display(), e))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{}", Self::HEADER)
+            .map_err(|e| format!("Failed to write sample file {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(SampleState {
+                out,
+                runs: 0,
+                rows: 0,
+                error: None,
+            }),
+        })
+    }
+
+    /// Append the samples of a run that started at `started`.
+    fn append(&self, started: SystemTime, samples: &[Sample]) {
+        let mut state = self.state.lock().unwrap();
+        state.runs += 1;
+        if state.error.is_some() {
+            return;
+        }
+        let run = state.runs;
+        let started_ms = started
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+        for sample in samples {
+            let offset_ms = sample.offset.as_secs_f64() * 1000.0;
+            let written = writeln!(
+                state.out,
+                "{},{:.3},{:.3},{},{},{:.3},{}",
+                run,
+                started_ms + offset_ms,
+                offset_ms,
+                sample.operation.endpoint(),
+                sample
+                    .status
+                    .map(|status| status.to_string())
+                    .unwrap_or_default(),
+                sample.latency.as_secs_f64() * 1000.0,
+                sample.workers
+            );
+            if let Err(e) = written {
+                state.error = Some(format!(
+                    "Failed to write sample file {}: {}",
+                    self.path.display(),
+                    e
+                ));
+                return;
+            }
+            state.rows += 1;
+        }
+    }
+
+    /// Flush what was appended; returns the number of rows written, or the
+    /// first write that failed.
+    pub fn finish(&self) -> Result<u64, String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(e) = &state.error {
+            return Err(e.clone());
+        }
+        state
+            .out
+            .flush()
+            .map_err(|e| format!("Failed to write sample file {}: {}", self.path.display(), e))?;
+        Ok(state.rows)
+    }
+}
+
+/// A byte count as `512`, `64K` or `16M` (KiB and MiB; `KiB`, `MiB` and a
+/// trailing `B` are accepted too).
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let number = upper.trim_end_matches('B').trim_end_matches('I');
+    let (digits, unit) = match number.char_indices().last() {
+        Some((i, 'K')) => (&number[..i], 1024),
+        Some((i, 'M')) => (&number[..i], 1024 * 1024),
+        Some((i, 'G')) => (&number[..i], 1024 * 1024 * 1024),
+        _ => (number, 1),
+    };
+    digits
+        .trim()
+        .parse::<usize>()
+        .map(|n| n * unit)
+        .map_err(|_| format!("Invalid size {:?}; expected e.g. 512, 64K or 16M", s))
+}
+
+/// `bytes` as `512 B`, `64 KiB` or `16 MiB`, whichever fits.
+pub fn format_size(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 && bytes.is_multiple_of(1024 * 1024) {
+        format!("{} MiB", bytes / (1024 * 1024))
+    } else if bytes >= 1024 && bytes.is_multiple_of(1024) {
+        format!("{} KiB", bytes / 1024)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Valid tokens for distinct subjects, signed before a run starts.
+///
+/// Signing an RS256 token takes the client about as long as a request takes
+/// the server, so a load that signed as it went would measure the client. The
+/// pool signs every token up front, on every core, and hands them out by
+/// index.
+#[derive(Debug, Clone)]
+pub struct TokenPool {
+    tokens: Vec<String>,
+    /// How long signing took.
+    pub signing_time: Duration,
+}
+
+impl TokenPool {
+    /// Sign `count` tokens with the VSS signing key, for subjects
+    /// `<prefix>-0` to `<prefix>-<count - 1>`.
+    pub fn mint(prefix: &str, count: usize) -> Result<Self, String> {
+        if count == 0 {
+            return Err("A token pool needs at least one token".to_string());
+        }
+        let signing_key_path = config::get().vss.signing_key_path.clone();
+        let subjects: Vec<String> = (0..count).map(|i| format!("{}-{}", prefix, i)).collect();
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let started = Instant::now();
+        let chunks: Vec<Result<Vec<String>, String>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = subjects
+                .chunks(subjects.len().div_ceil(threads).max(1))
+                .map(|chunk| scope.spawn(|| sign_tokens(&signing_key_path, chunk)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err("A signing thread panicked".to_string()))
+                })
+                .collect()
+        });
+        let mut tokens = Vec::with_capacity(count);
+        for chunk in chunks {
+            tokens.extend(chunk?);
+        }
+        Ok(Self {
+            tokens,
+            signing_time: started.elapsed(),
+        })
+    }
+
+    /// A pool of this one's first token only.
+    pub fn first(&self) -> TokenPool {
+        Self {
+            tokens: self.tokens.iter().take(1).cloned().collect(),
+            signing_time: Duration::ZERO,
+        }
+    }
+
+    /// The token at `index`, wrapping around past the end.
+    pub fn get(&self, index: usize) -> &str {
+        &self.tokens[index % self.tokens.len()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+/// The JWT benchmark: the smallest listKeyVersions, one page of one key of a
+/// store, as fast as the workers can send them. Each request carries the next
+/// of a pool of tokens signed beforehand, so signing costs the client nothing;
+/// what is left is mostly the server checking the token. A pool of one token
+/// shows what that costs when the server can cache its verdict, a pool larger
+/// than the number of requests what it costs when it cannot.
+#[derive(Debug, Clone)]
+pub struct TokenBench {
+    pub store_id: String,
+    pub workers: usize,
+    pub duration: Duration,
+}
+
+impl TokenBench {
+    /// List for the bench's duration with `tokens` in turn and count what
+    /// came back.
+    pub async fn run(&self, vss: Arc<Vss>, tokens: Arc<TokenPool>) -> Report {
+        let workers = self.workers;
+        let duration = self.duration;
+        drive(workers, self.duration, |worker, deadline| {
+            let vss = Arc::clone(&vss);
+            let tokens = Arc::clone(&tokens);
+            let request = ListKeyVersionsRequest {
+                store_id: self.store_id.clone(),
+                key_prefix: None,
+                page_size: Some(1),
+                page_token: None,
+            };
+            async move {
+                let started = deadline - duration;
+                let mut tally = Tally::default();
+                // Workers take turns through the pool, so no token repeats
+                // before the pool is used up
+                let mut next = worker;
+                while Instant::now() < deadline {
+                    let client = vss.authenticated_as(tokens.get(next));
+                    next += workers;
+                    let start = Instant::now();
+                    let result = client.request("listKeyVersions", &request).await;
+                    tally.record(
+                        Operation::List,
+                        &result,
+                        start.elapsed(),
+                        start - started,
+                        workers,
+                    );
+                }
+                tally
+            }
+        })
+        .await
+    }
+}
+
+/// Run `work` for each of `workers` on a task of its own until `duration`
+/// has passed, and add up what they counted.
+async fn drive<F, Fut>(workers: usize, duration: Duration, work: F) -> Report
+where
+    F: Fn(usize, Instant) -> Fut,
+    Fut: Future<Output = Tally> + Send + 'static,
+{
+    let started_at = SystemTime::now();
+    let started = Instant::now();
+    let deadline = started + duration;
+    let tasks = (0..workers).map(|worker| tokio::spawn(work(worker, deadline)));
+    let mut counts: BTreeMap<Operation, Counts> = BTreeMap::new();
+    let mut timeline: Vec<Second> = Vec::new();
+    let mut samples = Vec::new();
+    for task in join_all(tasks).await {
+        // A worker that panicked counts for nothing
+        let Ok(tally) = task else {
+            continue;
+        };
+        for (operation, worker_counts) in tally.counts {
+            counts.entry(operation).or_default().add(&worker_counts);
+        }
+        if timeline.len() < tally.timeline.len() {
+            timeline.resize(tally.timeline.len(), Second::default());
+        }
+        for (second, worker_second) in timeline.iter_mut().zip(&tally.timeline) {
+            second.add(worker_second);
+        }
+        samples.extend(tally.samples.unwrap_or_default());
+    }
+    samples.sort_by_key(|sample| sample.offset);
+    Report {
+        started: started_at,
+        elapsed: started.elapsed(),
+        counts,
+        timeline,
+        samples,
+    }
+}
+
+/// HTTP version the client speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Http1,
+    /// HTTP/2 from the first byte, which plain-HTTP servers must support
+    /// without an upgrade (h2c with prior knowledge).
+    Http2,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Http1 => "http1",
+            Protocol::Http2 => "http2",
+        })
+    }
+}
+
+/// Connection settings of the client a workload runs through.
+///
+/// reqwest opens as many connections as there are requests in flight; the
+/// pool size caps how many of them it keeps open for reuse once idle. With
+/// fewer than the workers, connections are closed and opened again all the
+/// time. HTTP/2 sends every request over one connection instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolSetting {
+    pub protocol: Protocol,
+    /// Idle connections kept per host; `None` keeps all.
+    pub max_idle: Option<usize>,
+}
+
+impl PoolSetting {
+    /// Every protocol with every pool size of `sizes`, and with no cap.
+    pub fn grid(sizes: &[usize]) -> Vec<PoolSetting> {
+        let caps: Vec<Option<usize>> = sizes.iter().copied().map(Some).chain([None]).collect();
+        [Protocol::Http1, Protocol::Http2]
+            .into_iter()
+            .flat_map(|protocol| {
+                caps.iter().map(move |max_idle| PoolSetting {
+                    protocol,
+                    max_idle: *max_idle,
+                })
+            })
+            .collect()
+    }
+
+    /// A client for the services under test with these settings.
+    pub fn client(&self) -> Result<Client, String> {
+        let timeout = Duration::from_secs(config::get().timeouts.request_secs);
+        let mut builder = config::http_client_builder(timeout)?;
+        if let Some(max_idle) = self.max_idle {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if self.protocol == Protocol::Http2 {
+            builder = builder.http2_prior_knowledge();
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client for {}: {:?}", self, e))
+    }
+}
+
+impl fmt::Display for PoolSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_idle {
+            Some(max_idle) => write!(f, "{}, {} idle", self.protocol, max_idle),
+            None => write!(f, "{}, all idle", self.protocol),
+        }
+    }
+}
+
+/// The setting of `results` with the highest throughput among those whose
+/// error rate is at most `max_error_rate` percent.
+pub fn best_setting(
+    results: &[(PoolSetting, Report)],
+    max_error_rate: f64,
+) -> Option<&(PoolSetting, Report)> {
+    results
+        .iter()
+        .filter(|(_, report)| {
+            report.total().requests() > 0 && report.error_rate() <= max_error_rate
+        })
+        .max_by(|(_, a), (_, b)| a.throughput().total_cmp(&b.throughput()))
+}
+
+/// Outcomes of the requests of one operation.
+#[derive(Debug, Clone, Default)]
+pub struct Counts {
+    pub succeeded: u64,
+    /// Error responses by HTTP status.
+    pub statuses: BTreeMap<u16, u64>,
+    /// Requests that got no response: refused, reset, timed out.
+    pub transport_errors: u64,
+    /// How long the answered requests took, errors included.
+    pub latency: Latencies,
+}
+
+impl Counts {
+    fn record(&mut self, result: &Result<(u16, Vec<u8>), String>, duration: Duration) {
+        if result.is_ok() {
+            self.latency.record(duration);
+        }
+        match result {
+            Ok((status, _)) if (200..300).contains(status) => self.succeeded += 1,
+            Ok((status, _)) => *self.statuses.entry(*status).or_default() += 1,
+            Err(_) => self.transport_errors += 1,
+        }
+    }
+
+    fn add(&mut self, other: &Counts) {
+        self.succeeded += other.succeeded;
+        for (status, count) in &other.statuses {
+            *self.statuses.entry(*status).or_default() += count;
+        }
+        self.transport_errors += other.transport_errors;
+        self.latency.add(&other.latency);
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.statuses.values().sum::<u64>() + self.transport_errors
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.succeeded + self.errors()
+    }
+}
+
+/// What a workload's run sent and got back.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub started: SystemTime,
+    pub elapsed: Duration,
+    pub counts: BTreeMap<Operation, Counts>,
+    /// Second by second from the start.
+    pub timeline: Vec<Second>,
+    /// Every request in the order they were sent, when the workload keeps
+    /// samples.
+    pub samples: Vec<Sample>,
+}
+
+impl Report {
+    pub fn total(&self) -> Counts {
+        let mut total = Counts::default();
+        for counts in self.counts.values() {
+            total.add(counts);
+        }
+        total
+    }
+
+    /// Requests per second, all operations together.
+    pub fn throughput(&self) -> f64 {
+        self.total().requests() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Percentage of all requests that failed.
+    pub fn error_rate(&self) -> f64 {
+        percent(self.total().errors(), self.total().requests())
+    }
+
+    /// Throughput, error rate and latency percentiles of each operation and
+    /// of all together, as printed at the end of a run.
+    pub fn render(&self) -> String {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        let mut text = format!(
+            "{:<6} {:>10} {:>10} {:>8} {:>8}  {}\n",
+            "op", "requests", "req/s", "errors", "error %", "by cause"
+        );
+        let total = self.total();
+        let rows = self
+            .counts
+            .iter()
+            .map(|(operation, counts)| (operation.to_string(), counts))
+            .chain(std::iter::once(("total".to_string(), &total)));
+        for (name, counts) in rows {
+            let mut causes: Vec<String> = counts
+                .statuses
+                .iter()
+                .map(|(status, count)| format!("{}: {}", status, count))
+                .collect();
+            if counts.transport_errors > 0 {
+                causes.push(format!("transport: {}", counts.transport_errors));
+            }
+            let row = format!(
+                "{:<6} {:>10} {:>10.1} {:>8} {:>8.2}  {}",
+                name,
+                counts.requests(),
+                counts.requests() as f64 / secs,
+                counts.errors(),
+                percent(counts.errors(), counts.requests()),
+                causes.join(", ")
+            );
+            text.push_str(row.trim_end());
+            text.push('\n');
+        }
+        text.push_str("\nLatency (ms):\n");
+        let rows: Vec<(String, &Latencies)> = self
+            .counts
+            .iter()
+            .map(|(operation, counts)| (operation.to_string(), &counts.latency))
+            .chain(std::iter::once(("total".to_string(), &total.latency)))
+            .collect();
+        text.push_str(&latency::render(
+            "op",
+            rows.iter()
+                .map(|(name, latencies)| (name.as_str(), *latencies)),
+        ));
+        text
+    }
+
+    /// Whether the run kept up `rate` requests per second with at most
+    /// `max_error_rate` percent errors and a p99 of at most `max_p99_ms`; the
+    /// reason if not.
+    pub fn sustains(&self, rate: f64, max_error_rate: f64, max_p99_ms: f64) -> Result<(), String> {
+        let total = self.total();
+        if self.error_rate() > max_error_rate {
+            return Err(format!(
+                "{:.2}% errors, above {}%",
+                self.error_rate(),
+                max_error_rate
+            ));
+        }
+        let p99 = total.latency.percentile_ms(99.0);
+        if p99 > max_p99_ms {
+            return Err(format!("p99 {:.1} ms, above {} ms", p99, max_p99_ms));
+        }
+        if self.throughput() < rate * SUSTAINED_SHARE {
+            return Err(format!(
+                "only {:.1} of {:.1} req/s sent; the server or the workers fell behind",
+                self.throughput(),
+                rate
+            ));
+        }
+        Ok(())
+    }
+
+    /// The first second in which more than `max_error_rate` percent of the
+    /// requests failed, with its offset in seconds.
+    pub fn first_failure(&self, max_error_rate: f64) -> Option<(usize, &Second)> {
+        self.timeline
+            .iter()
+            .enumerate()
+            .find(|(_, second)| second.requests > 0 && second.error_rate() > max_error_rate)
+    }
+
+    /// Workers, throughput, error rate and mean latency over the run, in at
+    /// most `TIMELINE_ROWS` rows of equal length.
+    pub fn render_timeline(&self) -> String {
+        let group = self.timeline.len().div_ceil(TIMELINE_ROWS).max(1);
+        let mut text = format!(
+            "{:>6} {:>8} {:>10} {:>8} {:>9}\n",
+            "from", "workers", "req/s", "error %", "mean ms"
+        );
+        for (i, seconds) in self.timeline.chunks(group).enumerate() {
+            let mut sum = Second::default();
+            for second in seconds {
+                sum.add(second);
+            }
+            text.push_str(&format!(
+                "{:>5}s {:>8} {:>10.1} {:>8.2} {:>9.1}\n",
+                i * group,
+                sum.workers,
+                sum.requests as f64 / seconds.len() as f64,
+                sum.error_rate(),
+                sum.mean_latency_ms()
+            ));
+        }
+        text
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64 * 100.0
+    }
+}
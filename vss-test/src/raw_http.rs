@@ -0,0 +1,335 @@
+//! HTTP/1.1 written by hand, for requests a client library refuses to send
+//!
+//! reqwest checks header values, so a CR, LF or NUL in one never reaches the
+//! server. `send` writes the request bytes exactly as given, over TCP or, for
+//! an https URL, TLS trusting the configured CAs, and reads back whatever the
+//! server answers. `parse` then splits those bytes into the responses they
+//! hold, so a caller can tell one response from two.
+
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config;
+
+// Once a whole response is in, how long to wait for anything after it
+const QUIET: Duration = Duration::from_millis(300);
+
+/// Where requests go: the scheme, host and port of a base URL, and its path.
+#[derive(Clone, Debug)]
+pub struct Target {
+    host: String,
+    port: u16,
+    tls: bool,
+    base_path: String,
+}
+
+/// A connection to a `Target`, with or without TLS.
+pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+/// One response read back, body as sent.
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+impl Target {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+        let tls = match parsed.scheme() {
+            "http" => false,
+            "https" => true,
+            scheme => return Err(format!("Unsupported scheme {} in {}", scheme, url)),
+        };
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| format!("No host in {}", url))?
+            .to_string();
+        let port = parsed
+            .port_or_known_default()
+            .ok_or_else(|| format!("No port in {}", url))?;
+        Ok(Self {
+            host,
+            port,
+            tls,
+            base_path: parsed.path().trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// `host:port`, for the Host header.
+    pub fn authority(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// `path` under the URL's own path, for the request line.
+    pub fn path(&self, path: &str) -> String {
+        format!("{}{}", self.base_path, path)
+    }
+
+    /// Open a connection, with TLS for an https URL.
+    pub async fn connect(&self) -> Result<Box<dyn Connection>, String> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {:?}", self.authority(), e))?;
+        if !self.tls {
+            return Ok(Box::new(stream));
+        }
+        let mut builder = native_tls::TlsConnector::builder();
+        for path in config::get().ca_cert_paths() {
+            let pem =
+                std::fs::read(path).map_err(|e| format!("Failed to read {}: {:?}", path, e))?;
+            let certificate = native_tls::Certificate::from_pem(&pem)
+                .map_err(|e| format!("Failed to parse CA certificate {}: {:?}", path, e))?;
+            builder.add_root_certificate(certificate);
+        }
+        let connector = builder
+            .build()
+            .map_err(|e| format!("Failed to build TLS connector: {:?}", e))?;
+        let stream = tokio_native_tls::TlsConnector::from(connector)
+            .connect(&self.host, stream)
+            .await
+            .map_err(|e| format!("TLS handshake with {} failed: {:?}", self.authority(), e))?;
+        Ok(Box::new(stream))
+    }
+}
+
+impl Response {
+    /// The first value of header `name`, whatever its case.
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// Write `request` to a new connection and return every byte the server sent
+/// back, until it closes the connection or goes quiet after whole responses.
+/// The server closing or resetting the connection, even before the request is
+/// all written, ends the exchange with what arrived, possibly nothing; only
+/// failing to connect, or no whole response within `timeout`, is an error.
+pub async fn send(target: &Target, request: &[u8], timeout: Duration) -> Result<Vec<u8>, String> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = target.connect().await?;
+    // A server refusing the request may close before taking all of it, and
+    // still have answered
+    let _ = tokio::time::timeout_at(deadline.into(), stream.write_all(request)).await;
+    let mut bytes = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let complete = is_complete(&bytes);
+        let left = deadline.saturating_duration_since(Instant::now());
+        let wait = if complete { QUIET.min(left) } else { left };
+        match tokio::time::timeout(wait, stream.read(&mut buf)).await {
+            Ok(Ok(0)) | Ok(Err(_)) => break,
+            Ok(Ok(n)) => bytes.extend_from_slice(&buf[..n]),
+            Err(_) if complete => break,
+            Err(_) => {
+                return Err(format!(
+                    "no whole response from {} within {:?}",
+                    target.authority(),
+                    timeout
+                ))
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// The responses in `bytes`, in order. A response cut short by the connection
+/// closing is left out; bytes that do not start a response are an error.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Response>, String> {
+    let mut responses = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        if !rest.starts_with(b"HTTP/") && !b"HTTP/".starts_with(rest) {
+            return Err(format!(
+                "{} byte(s) after {} response(s) are not a response: {}",
+                rest.len(),
+                responses.len(),
+                rest[..rest.len().min(60)].escape_ascii()
+            ));
+        }
+        match split_response(rest)? {
+            Some((response, _, used)) => {
+                responses.push(response);
+                rest = &rest[used..];
+            }
+            None => break,
+        }
+    }
+    Ok(responses)
+}
+
+/// Whether `bytes` hold whole responses with nothing after, the last framed
+/// by its headers rather than by the connection closing.
+fn is_complete(bytes: &[u8]) -> bool {
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        match split_response(rest) {
+            Ok(Some((_, true, used))) => rest = &rest[used..],
+            _ => return false,
+        }
+    }
+    !bytes.is_empty()
+}
+
+/// The response at the start of `bytes`, whether its length was known from
+/// its headers, and the bytes it took; `None` while it is incomplete.
+fn split_response(bytes: &[u8]) -> Result<Option<(Response, bool, usize)>, String> {
+    let Some(head_end) = find(bytes, b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let head = &bytes[..head_end];
+    let mut lines = head
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+    let status_line = lines.next().unwrap_or_default();
+    let status = status_line
+        .split(|b| *b == b' ')
+        .nth(1)
+        .and_then(|code| std::str::from_utf8(code).ok()?.parse::<u16>().ok())
+        .ok_or_else(|| format!("Malformed status line: {}", status_line.escape_ascii()))?;
+    let headers = lines
+        .filter_map(|line| {
+            let colon = line.iter().position(|b| *b == b':')?;
+            let name = String::from_utf8_lossy(&line[..colon]).trim().to_string();
+            Some((name, line[colon + 1..].trim_ascii().to_vec()))
+        })
+        .collect();
+    let mut response = Response {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    let body = &bytes[head_end + 4..];
+    let chunked = response
+        .header("Transfer-Encoding")
+        .is_some_and(|value| find(&value.to_ascii_lowercase(), b"chunked").is_some());
+    let length = response
+        .header("Content-Length")
+        .and_then(|value| std::str::from_utf8(value).ok()?.parse::<usize>().ok());
+    let (framed, used) = if (100..200).contains(&status) || status == 204 || status == 304 {
+        (true, 0)
+    } else if chunked {
+        match dechunk(body) {
+            Some((decoded, used)) => {
+                response.body = decoded;
+                (true, used)
+            }
+            None => return Ok(None),
+        }
+    } else if let Some(length) = length {
+        if body.len() < length {
+            return Ok(None);
+        }
+        response.body = body[..length].to_vec();
+        (true, length)
+    } else {
+        response.body = body.to_vec();
+        (false, body.len())
+    };
+    Ok(Some((response, framed, head_end + 4 + used)))
+}
+
+/// A chunked body's contents and the bytes it took, trailers included;
+/// `None` while it is incomplete or unreadable.
+fn dechunk(bytes: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let mut decoded = Vec::new();
+    let mut at = 0;
+    loop {
+        let line_end = at + find(&bytes[at..], b"\r\n")?;
+        let size = std::str::from_utf8(&bytes[at..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        at = line_end + 2;
+        if size == 0 {
+            // Trailers, if any, end with an empty line
+            let trailers_end = find(&bytes[at..], b"\r\n")?;
+            if trailers_end == 0 {
+                return Some((decoded, at + 2));
+            }
+            let end = at + find(&bytes[at..], b"\r\n\r\n")?;
+            return Some((decoded, end + 4));
+        }
+        decoded.extend_from_slice(bytes.get(at..at + size)?);
+        at += size;
+        if bytes.get(at..at + 2)? != b"\r\n" {
+            return None;
+        }
+        at += 2;
+    }
+}
+
+/// Where `needle` first starts in `haystack`.
+pub fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_splits_framed_responses() {
+        let bytes = b"HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/1.1 400 Bad Request\r\nContent-Length: 3\r\nX-Id: a\r\n\r\nbad\
+            HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n";
+        let responses = parse(bytes).unwrap();
+        let statuses: Vec<u16> = responses.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [100, 400, 200]);
+        assert_eq!(responses[1].body, b"bad");
+        assert_eq!(responses[1].header("x-id"), Some(b"a".as_slice()));
+        assert_eq!(responses[2].body, b"ok");
+        assert!(is_complete(bytes));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_status_lines() {
+        for bad in [
+            b"HTTP/1.1 abc OK\r\n\r\n".as_slice(),
+            b"HTTP/1.1\r\n\r\n",
+            b"HTTP/1.1 99999 Big\r\n\r\n",
+        ] {
+            let e = parse(bad).unwrap_err();
+            assert!(e.starts_with("Malformed status line"), "{}", e);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bytes_that_are_not_a_response() {
+        let e = parse(b"HTTP/1.1 204 No Content\r\n\r\n<html>").unwrap_err();
+        assert!(e.contains("after 1 response(s)"), "{}", e);
+        assert!(parse(b"SSH-2.0\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_leaves_out_truncated_responses() {
+        for cut in [
+            b"HTT".as_slice(),
+            b"HTTP/1.1 200 OK\r\nContent-Le",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nab",
+        ] {
+            assert!(parse(cut).unwrap().is_empty(), "{}", cut.escape_ascii());
+            assert!(!is_complete(cut), "{}", cut.escape_ascii());
+        }
+        let after = b"HTTP/1.1 204 No Content\r\n\r\nHTTP/1.1 200 OK\r\nConn";
+        assert_eq!(parse(after).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unframed_body_runs_to_the_close() {
+        let bytes = b"HTTP/1.0 200 OK\r\n\r\nall of it";
+        assert_eq!(parse(bytes).unwrap()[0].body, b"all of it");
+        assert!(!is_complete(bytes));
+    }
+}
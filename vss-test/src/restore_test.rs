@@ -0,0 +1,282 @@
+//! Seed Restore Integration Test Binary
+//!
+//! Mirrors Bitkit's restore story: a seeded node backs its channel state up to
+//! VSS, its container is destroyed, and a new one is restored from the seed
+//! plus the VSS backup
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::teardown::Teardown;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::test_cases;
+use vss_test::bitcoind::Bitcoind;
+use vss_test::compose::{destroy_service, start_service};
+use vss_test::lnd::{Channel, Lnd, LND_B_P2P_HOST, LND_C_REST_URL, LND_C_SERVICE};
+use vss_test::vss::Vss;
+use vss_test::wait_for;
+
+// lnd3 is created by the test itself; checked against `--profile`
+const REQUIRED_SERVICES: [&str; 5] = [
+    "bitcoind",
+    "lnd2",
+    "postgres",
+    "lnurl-auth-server",
+    "vss-server",
+];
+
+const WALLET_PASSWORD: &str = "vss-test-password";
+// Addresses scanned per branch when rescanning the restored wallet
+const RECOVERY_WINDOW: u32 = 2_500;
+
+// Channel to lnd2 whose funds must survive the restore
+const CHANNEL_CAPACITY_SAT: u64 = 500_000;
+const PAYMENT_SAT: u64 = 60_000;
+// Upper bound on what the peer's force-close and our sweep cost us
+const MAX_RECOVERY_FEES_SAT: i64 = 30_000;
+
+// VSS keys the node state is backed up under
+const VSS_CHANNEL_BACKUP_KEY: &str = "channel_backup";
+const VSS_WALLET_STATE_KEY: &str = "wallet_state";
+
+const UNLOCKER_TIMEOUT: Duration = Duration::from_secs(60);
+const CHANNEL_TIMEOUT: Duration = Duration::from_secs(30);
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(180);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What the node looked like when it was backed up, stored next to the channel backup.
+#[derive(Debug, Serialize, Deserialize)]
+struct WalletState {
+    identity_pubkey: String,
+    onchain_sat: i64,
+    channel_point: String,
+    channel_local_sat: i64,
+}
+
+/// Seed Restore Integration Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The chain and the peer the restored node has its channel with.
+struct Restore {
+    bitcoind: Bitcoind,
+    peer: Lnd,
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let cases = test_cases!(Restore;
+        // Destroys and recreates lnd3
+        #[exclusive] #[tags(Vss, Destructive, Docker)] test_restore_from_seed_and_vss,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("Seed Restore Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select_or(&REQUIRED_SERVICES, &profile::LIGHTNING).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let teardown = match Teardown::local() {
+        Ok(teardown) => teardown,
+        Err(e) => {
+            let e = format!("Failed to connect to Docker: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    teardown.handle_ctrl_c();
+    teardown.remove_on_exit(LND_C_SERVICE);
+
+    let bitcoind = Bitcoind::local();
+    let peer = match Lnd::node_b() {
+        Ok(node) => node,
+        Err(e) => {
+            let e = format!("Failed to set up LND client: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let restore = Restore { bitcoind, peer };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &restore, &cases)
+    })
+    .await;
+
+    teardown.run().await;
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// Start a wallet-less `lnd3` container and wait for its wallet unlocker.
+async fn fresh_node() -> Result<Lnd, String> {
+    start_service(LND_C_SERVICE)?;
+    let node = Lnd::uninitialized(LND_C_REST_URL)?;
+    wait_for("lnd3 wallet unlocker", UNLOCKER_TIMEOUT, POLL_INTERVAL, || async {
+        // genseed only answers once the unlocker service is up
+        node.gen_seed().await.map(|_| Some(()))
+    })
+    .await?;
+    Ok(node)
+}
+
+/// Wait for the RPC server to come up after wallet init.
+async fn wait_node_ready(node: &Lnd, bitcoind: &Bitcoind) -> Result<String, String> {
+    let pubkey = wait_for("lnd3 RPC after wallet init", UNLOCKER_TIMEOUT, POLL_INTERVAL, || async {
+        node.get_info().await.map(|info| Some(info.identity_pubkey))
+    })
+    .await?;
+    node.wait_synced(bitcoind).await?;
+    Ok(pubkey)
+}
+
+async fn wait_channel_by_funding(node: &Lnd, funding_txid: &str) -> Result<Channel, String> {
+    wait_for("channel to lnd2 to become active", CHANNEL_TIMEOUT, POLL_INTERVAL, || async {
+        let channels = node.list_channels().await?;
+        Ok(channels
+            .into_iter()
+            .find(|c| c.active && c.funding_txid() == funding_txid))
+    })
+    .await
+}
+
+/// Give the node on-chain funds and a channel to `peer` with balance on both sides.
+async fn build_wallet_state(bitcoind: &Bitcoind, node: &Lnd, peer: &Lnd) -> Result<WalletState, String> {
+    let identity_pubkey = node.get_info().await?.identity_pubkey;
+    node.ensure_wallet_funds(bitcoind, CHANNEL_CAPACITY_SAT * 2).await?;
+
+    let peer_pubkey = peer.get_info().await?.identity_pubkey;
+    node.connect_peer(&peer_pubkey, LND_B_P2P_HOST).await?;
+    let point = node.open_channel(&peer_pubkey, CHANNEL_CAPACITY_SAT, 0).await?;
+    bitcoind.mine(6).await?;
+    node.wait_synced(bitcoind).await?;
+    let channel = wait_channel_by_funding(node, &point.funding_txid()).await?;
+
+    let invoice = peer.add_invoice(PAYMENT_SAT, "vss-test restore").await?;
+    let payment = node.pay_invoice(&invoice.payment_request).await?;
+    if !payment.payment_error.is_empty() {
+        return Err(format!("Payment to lnd2 failed: {}", payment.payment_error));
+    }
+    let channel = wait_channel_by_funding(node, channel.funding_txid()).await?;
+
+    Ok(WalletState {
+        identity_pubkey,
+        onchain_sat: node.wallet_balance().await?.confirmed_balance,
+        channel_point: channel.channel_point,
+        channel_local_sat: channel.local_balance,
+    })
+}
+
+async fn test_restore_from_seed_and_vss(Restore { bitcoind, peer }: &Restore) -> Outcome {
+    // Start from an empty node; the seed is all Bitkit keeps outside VSS
+    destroy_service(LND_C_SERVICE)?;
+    let unlocker = fresh_node().await?;
+    let mnemonic = unlocker.gen_seed().await?;
+    let node = unlocker
+        .init_wallet(WALLET_PASSWORD, &mnemonic, None, 0)
+        .await?;
+    wait_node_ready(&node, bitcoind).await?;
+    let state = build_wallet_state(bitcoind, &node, peer).await?;
+
+    let vss = Vss::local(&state.identity_pubkey).await?;
+    let store_id = format!("restore-{}", state.identity_pubkey);
+    let backup = node.export_channel_backups().await?;
+    vss.put_object(&store_id, VSS_CHANNEL_BACKUP_KEY, backup).await?;
+    let state_json =
+        serde_json::to_vec(&state).map_err(|e| format!("Failed to encode wallet state: {}", e))?;
+    vss.put_object(&store_id, VSS_WALLET_STATE_KEY, state_json).await?;
+
+    destroy_service(LND_C_SERVICE)?;
+    if node.get_info().await.is_ok() {
+        return Err("lnd3 still answers after its container was destroyed".to_string());
+    }
+
+    // Restore purely from the seed and what VSS hands back
+    let backup = vss.get_object(&store_id, VSS_CHANNEL_BACKUP_KEY).await?;
+    let saved: WalletState = serde_json::from_slice(&vss.get_object(&store_id, VSS_WALLET_STATE_KEY).await?)
+        .map_err(|e| format!("VSS returned an unreadable wallet state: {}", e))?;
+    let restored = fresh_node()
+        .await?
+        .init_wallet(WALLET_PASSWORD, &mnemonic, Some(&backup), RECOVERY_WINDOW)
+        .await?;
+    let pubkey = wait_node_ready(&restored, bitcoind).await?;
+    if pubkey != saved.identity_pubkey {
+        return Err(format!(
+            "Restored node is {}, expected {}",
+            pubkey, saved.identity_pubkey
+        ));
+    }
+
+    // The backup makes lnd2 force-close the channel; mine until our share is swept home
+    let expected_sat = saved.onchain_sat + saved.channel_local_sat;
+    let recovered = wait_for("channel funds to be recovered", RECOVERY_TIMEOUT, POLL_INTERVAL, || async {
+        let pending = restored.pending_channels().await?;
+        let closing = pending
+            .waiting_close_channels
+            .iter()
+            .chain(pending.pending_force_closing_channels.iter())
+            .any(|c| c["channel"]["channel_point"].as_str() == Some(saved.channel_point.as_str()));
+        let balance = restored.wallet_balance().await?;
+        if !closing
+            && balance.unconfirmed_balance == 0
+            && balance.confirmed_balance >= expected_sat - MAX_RECOVERY_FEES_SAT
+        {
+            return Ok(Some(balance.confirmed_balance));
+        }
+        bitcoind.mine(1).await?;
+        restored.wait_synced(bitcoind).await?;
+        Ok(None)
+    })
+    .await?;
+
+    if recovered > expected_sat {
+        return Err(format!(
+            "Recovered {} sat, more than the {} sat backed up",
+            recovered, expected_sat
+        ));
+    }
+    Ok(format!(
+        "{} restored, {} of {} sat recovered",
+        saved.identity_pubkey,
+        recovered,
+        saved.onchain_sat + saved.channel_local_sat
+    ))
+}
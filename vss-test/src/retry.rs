@@ -0,0 +1,134 @@
+//! Retrying network steps that fail for transient reasons
+//!
+//! A step opts in by running under `RetryPolicy::run` and sorting its errors
+//! into `StepError::Transient` (refused or reset connections, timeouts, a
+//! gateway answering 502/503/504 while a container warms up) and
+//! `StepError::Fatal`. Only transient errors are retried, with exponential
+//! backoff; anything a test asserts on happens after the step and is never
+//! retried. `RETRY_ATTEMPTS`, `RETRY_BACKOFF_MS` and `RETRY_MAX_BACKOFF_MS`
+//! tune the policy; `RETRY_ATTEMPTS=1` turns retries off.
+
+use std::error::Error;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use crate::env_u64;
+
+pub const DEFAULT_RETRY_ATTEMPTS: u64 = 3;
+pub const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
+pub const DEFAULT_RETRY_MAX_BACKOFF_MS: u64 = 5_000;
+
+/// Why a step failed, and whether trying again could help.
+#[derive(Debug, Clone)]
+pub enum StepError {
+    Transient(String),
+    Fatal(String),
+}
+
+impl StepError {
+    pub fn message(self) -> String {
+        match self {
+            StepError::Transient(message) | StepError::Fatal(message) => message,
+        }
+    }
+}
+
+impl From<reqwest::Error> for StepError {
+    fn from(e: reqwest::Error) -> Self {
+        let message = format!("HTTP request failed: {:?}", e);
+        if is_transient(&e) {
+            StepError::Transient(message)
+        } else {
+            StepError::Fatal(message)
+        }
+    }
+}
+
+/// Connection refused or reset, or no answer in time. Errors from building the
+/// request or decoding the response are not transient.
+pub fn is_transient(e: &reqwest::Error) -> bool {
+    if e.is_connect() || e.is_timeout() {
+        return true;
+    }
+    let mut source = e.source();
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// Statuses a server or proxy answers with while it is not ready yet.
+pub fn is_transient_status(status: u16) -> bool {
+    matches!(status, 502..=504)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Tries in total, the first one included.
+    pub attempts: u64,
+    /// Pause before the first retry; doubled for each one after.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// A single try.
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let attempts = env_u64("RETRY_ATTEMPTS", DEFAULT_RETRY_ATTEMPTS)?;
+        if attempts == 0 {
+            return Err("RETRY_ATTEMPTS must be at least 1".to_string());
+        }
+        Ok(Self {
+            attempts,
+            backoff: Duration::from_millis(env_u64("RETRY_BACKOFF_MS", DEFAULT_RETRY_BACKOFF_MS)?),
+            max_backoff: Duration::from_millis(env_u64(
+                "RETRY_MAX_BACKOFF_MS",
+                DEFAULT_RETRY_MAX_BACKOFF_MS,
+            )?),
+        })
+    }
+
+    /// Run `step` until it succeeds, fails fatally or runs out of attempts.
+    /// The error names `what` and how many tries it took.
+    pub async fn run<T, F, Fut>(&self, what: &str, mut step: F) -> Result<T, String>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, StepError>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match step().await {
+                Ok(value) => return Ok(value),
+                Err(StepError::Transient(_)) if attempt < self.attempts => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                Err(StepError::Transient(e)) if attempt > 1 => {
+                    return Err(format!("{} failed after {} tries: {}", what, attempt, e))
+                }
+                Err(e) => return Err(e.message()),
+            }
+        }
+    }
+}
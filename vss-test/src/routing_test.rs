@@ -0,0 +1,527 @@
+//! Routing Integration Test Binary
+//!
+//! Tests multi-hop payments over a graph of LND nodes built by
+//! `vss_test::graph`, including how senders cope with route churn,
+//! circular rebalancing and how fast a new node learns the graph
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::snapshot::{Snapshots, GRAPH_DATA};
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::teardown::Teardown;
+use harness_docker::DockerEnv;
+use std::time::Duration;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::test_cases;
+use vss_test::bitcoind::Bitcoind;
+use vss_test::graph::{
+    start_blank_node, start_node, wait_channels_active, Graph, GraphChannel, GraphConfig, Topology,
+    GRAPH_NODES,
+};
+use vss_test::lnd::{Lnd, Payment, LND_C_SERVICE};
+use vss_test::{env_u64, wait_for};
+
+// GRAPH_NODES and the chain; checked against `--profile`
+const REQUIRED_SERVICES: [&str; 5] = ["bitcoind", "lnd", "lnd2", "lnd4", "lnd5"];
+
+// Two disjoint two-hop routes from node 0 to node 3: via node 1 and via node 2
+const DIAMOND: [(usize, usize); 4] = [(0, 1), (1, 3), (0, 2), (2, 3)];
+const DIAMOND_NODES: usize = 4;
+const DIAMOND_CAPACITY_SAT: u64 = 1_000_000;
+const ROUTED_PAYMENT_SAT: u64 = 20_000;
+
+// LND reports failures it could not decode with this code
+const UNDECODABLE_FAILURE: &str = "UNKNOWN_FAILURE";
+
+// Three-node ring whose channels each start with all liquidity on the opener's side
+const RING_NODES: usize = 3;
+const REBALANCE_SAT: u64 = 100_000;
+// Channel balances are reported in whole sat while fees accrue in msat
+const BALANCE_TOLERANCE_SAT: i64 = 1;
+const BALANCE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Time a fresh node gets to learn the graph, overridable via GOSSIP_SYNC_BUDGET_SECS
+const DEFAULT_GOSSIP_SYNC_BUDGET_SECS: u64 = 60;
+// Every node in the stack listens for peers on this port
+const P2P_PORT_SUFFIX: &str = ":9735";
+
+const GOSSIP_TIMEOUT: Duration = Duration::from_secs(180);
+
+// Reuse the funded diamond across runs via a data snapshot, with `--snapshot`
+const DIAMOND_SNAPSHOT: &str = "routing-diamond";
+
+/// Routing Integration Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    /// Restore the funded diamond from its data snapshot, seeding and
+    /// snapshotting it first if there is none
+    #[arg(long)]
+    snapshot: bool,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The chain and the diamond graph every case routes over.
+struct Routing {
+    bitcoind: Bitcoind,
+    graph: Graph,
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    // Each case reshapes the graph, or pays and mines over it
+    let cases = test_cases!(Routing;
+        #[exclusive] test_route_around_disabled_hop,
+        #[exclusive] test_no_route_fails_cleanly,
+        #[exclusive] test_circular_rebalance,
+        #[exclusive] test_fresh_node_gossip_sync,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("Routing Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select_or(&REQUIRED_SERVICES, &profile::LIGHTNING).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let teardown = match Teardown::local() {
+        Ok(teardown) => teardown,
+        Err(e) => {
+            let e = format!("Failed to connect to Docker: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    teardown.handle_ctrl_c();
+    for spec in &GRAPH_NODES {
+        if let Err(e) = teardown.stop_on_exit(spec.service).await {
+            let e = format!("Failed to inspect {}: {}", spec.service, e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    }
+    teardown.remove_on_exit(LND_C_SERVICE);
+
+    let bitcoind = Bitcoind::local();
+    let config = GraphConfig {
+        nodes: DIAMOND_NODES,
+        topology: Topology::Edges(DIAMOND.to_vec()),
+        channel_capacity_sat: DIAMOND_CAPACITY_SAT,
+        gossip_timeout: GOSSIP_TIMEOUT,
+    };
+    let bootstrapped = if cli.snapshot {
+        bootstrap_from_snapshot(&bitcoind, &config).await
+    } else {
+        Graph::bootstrap(&bitcoind, &config).await
+    };
+    let graph = match bootstrapped {
+        Ok(graph) => graph,
+        Err(e) => {
+            let e = format!("Failed to bootstrap the routing graph: {}", e);
+            report.error(&e);
+            teardown.run().await;
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let routing = Routing { bitcoind, graph };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &routing, &cases)
+    })
+    .await;
+
+    teardown.run().await;
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// Channel point of the graph edge opened from `from` to `to`.
+fn edge_point(graph: &Graph, from: usize, to: usize) -> Result<String, String> {
+    graph
+        .channels
+        .iter()
+        .find(|c| c.from == from && c.to == to)
+        .map(|c| c.channel.channel_point.clone())
+        .ok_or_else(|| format!("Graph has no channel {} -> {}", from, to))
+}
+
+/// Restore the diamond's chain and node data from a snapshot, seeding and
+/// snapshotting it first if there is none, then pick the graph up from there.
+async fn bootstrap_from_snapshot(
+    bitcoind: &Bitcoind,
+    config: &GraphConfig,
+) -> Result<Graph, String> {
+    let env = DockerEnv::local()?;
+    let snapshots = Snapshots::local(&env)?;
+    snapshots
+        .restore_or_take(DIAMOND_SNAPSHOT, &GRAPH_DATA, || async {
+            Graph::bootstrap(bitcoind, config).await.map(|_| ())
+        })
+        .await?;
+
+    // The nodes were just restarted either way; let their channels come back
+    // so bootstrap reuses them instead of opening new ones
+    let mut nodes = Vec::new();
+    for spec in &GRAPH_NODES[..config.nodes] {
+        let node = start_node(spec).await?;
+        node.wait_synced(bitcoind).await?;
+        nodes.push(node);
+    }
+    let mut pubkeys = Vec::new();
+    for node in &nodes {
+        pubkeys.push(node.get_info().await?.identity_pubkey);
+    }
+    for node in &nodes {
+        wait_channels_active(node, &pubkeys).await?;
+    }
+    Graph::bootstrap(bitcoind, config).await
+}
+
+/// Pay `amount_sat` from `payer` to `payee` and return the payer's record of it.
+async fn pay(
+    payer: &Lnd,
+    payee: &Lnd,
+    amount_sat: u64,
+    memo: &str,
+) -> Result<(Payment, String), String> {
+    let invoice = payee.add_invoice(amount_sat, memo).await?;
+    let response = payer.pay_invoice(&invoice.payment_request).await?;
+    let payment = payer.lookup_payment(&invoice.r_hash).await?;
+    Ok((payment, response.payment_error))
+}
+
+/// Every failed attempt must carry a failure code LND could decode.
+fn check_failures_decodable(payment: &Payment) -> Result<usize, String> {
+    let failed: Vec<_> = payment
+        .htlcs
+        .iter()
+        .filter(|h| h.status == "FAILED")
+        .collect();
+    for attempt in &failed {
+        match &attempt.failure {
+            Some(failure) if !failure.code.is_empty() && failure.code != UNDECODABLE_FAILURE => {}
+            other => {
+                return Err(format!(
+                    "Failed attempt without a decodable failure: {:?}",
+                    other
+                ))
+            }
+        }
+    }
+    Ok(failed.len())
+}
+
+/// Disable the given edges on their forwarding nodes, run `scenario`, then re-enable them.
+async fn with_disabled<T, F, Fut>(
+    graph: &Graph,
+    edges: &[(usize, usize)],
+    scenario: F,
+) -> Result<T, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<T, String>>,
+{
+    let mut disabled = Vec::new();
+    let mut result = Ok(());
+    for &(from, to) in edges {
+        let disable: Result<String, String> = async {
+            let point = edge_point(graph, from, to)?;
+            graph.nodes[from]
+                .update_channel_status(&point, "DISABLE")
+                .await?;
+            Ok(point)
+        }
+        .await;
+        match disable {
+            Ok(point) => disabled.push((from, point)),
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    let outcome = match result {
+        Ok(()) => scenario().await,
+        Err(e) => Err(e),
+    };
+    // Restore the graph for later scenarios even if this one failed
+    for (from, point) in disabled {
+        graph.nodes[from]
+            .update_channel_status(&point, "ENABLE")
+            .await?;
+    }
+    outcome
+}
+
+async fn test_route_around_disabled_hop(Routing { graph, .. }: &Routing) -> Outcome {
+    // The sender still has the channel as enabled in its gossip view when it pays
+    let failed_attempts = with_disabled(graph, &[(1, 3)], || async {
+        let disabled_point = edge_point(graph, 1, 3)?;
+        let disabled_id = graph.nodes[1]
+            .list_channels()
+            .await?
+            .into_iter()
+            .find(|c| c.channel_point == disabled_point)
+            .map(|c| c.chan_id)
+            .ok_or_else(|| format!("Node 1 lost channel {}", disabled_point))?;
+
+        let (payment, error) = pay(
+            &graph.nodes[0],
+            &graph.nodes[3],
+            ROUTED_PAYMENT_SAT,
+            "vss-test reroute",
+        )
+        .await?;
+        if payment.status != "SUCCEEDED" {
+            return Err(format!(
+                "Payment {} ({}): {}",
+                payment.status, payment.failure_reason, error
+            ));
+        }
+        let failed_attempts = check_failures_decodable(&payment)?;
+        let route = payment
+            .htlcs
+            .iter()
+            .find(|h| h.status == "SUCCEEDED")
+            .map(|h| &h.route)
+            .ok_or_else(|| "Succeeded payment has no settled attempt".to_string())?;
+        if route.hops.iter().any(|hop| hop.chan_id == disabled_id) {
+            return Err(format!(
+                "Payment settled over disabled channel {}",
+                disabled_id
+            ));
+        }
+        if route.hops.first().map(|h| h.pub_key.as_str()) != Some(graph.pubkeys[2].as_str()) {
+            return Err(format!("Payment took unexpected route {:?}", route.hops));
+        }
+        Ok(failed_attempts)
+    })
+    .await?;
+    Ok(format!("Settled via node 2 after {} failed attempt(s)", failed_attempts))
+}
+
+async fn test_no_route_fails_cleanly(Routing { graph, .. }: &Routing) -> Outcome {
+    let (reason, failed_attempts) = with_disabled(graph, &[(1, 3), (2, 3)], || async {
+        let (payment, error) = pay(
+            &graph.nodes[0],
+            &graph.nodes[3],
+            ROUTED_PAYMENT_SAT,
+            "vss-test no route",
+        )
+        .await?;
+        if payment.status != "FAILED" {
+            return Err(format!("Payment is {}, expected FAILED", payment.status));
+        }
+        if error.is_empty()
+            || payment.failure_reason.is_empty()
+            || payment.failure_reason == "FAILURE_REASON_NONE"
+        {
+            return Err(format!(
+                "Failed payment gives no reason: error {:?}, reason {:?}",
+                error, payment.failure_reason
+            ));
+        }
+        let failed_attempts = check_failures_decodable(&payment)?;
+        Ok((payment.failure_reason, failed_attempts))
+    })
+    .await?;
+    Ok(format!("Failed with {} after {} attempt(s)", reason, failed_attempts))
+}
+
+/// Local balance of each side of a graph channel, opener first.
+async fn channel_sides(graph: &Graph, channel: &GraphChannel) -> Result<(i64, i64), String> {
+    let point = &channel.channel.channel_point;
+    let mut sides = Vec::new();
+    for node in [channel.from, channel.to] {
+        let local = graph.nodes[node]
+            .list_channels()
+            .await?
+            .into_iter()
+            .find(|c| &c.channel_point == point)
+            .map(|c| c.local_balance)
+            .ok_or_else(|| format!("Node {} has no channel {}", node, point))?;
+        sides.push(local);
+    }
+    Ok((sides[0], sides[1]))
+}
+
+async fn test_circular_rebalance(Routing { bitcoind, .. }: &Routing) -> Outcome {
+    let config = GraphConfig {
+        nodes: RING_NODES,
+        topology: Topology::Ring,
+        channel_capacity_sat: DIAMOND_CAPACITY_SAT,
+        gossip_timeout: GOSSIP_TIMEOUT,
+    };
+    let ring = Graph::bootstrap(bitcoind, &config).await?;
+    let mut before = Vec::new();
+    for channel in &ring.channels {
+        before.push(channel_sides(&ring, channel).await?);
+    }
+
+    // Node 0 moves liquidity from its outbound-heavy channel to node 1 into
+    // its inbound-heavy channel from node 2: 0 -> 1 -> 2 -> 0
+    let outgoing = &ring.channels[0];
+    let invoice = ring.nodes[0]
+        .add_invoice(REBALANCE_SAT, "vss-test rebalance")
+        .await?;
+    let payment = ring.nodes[0]
+        .pay_circular(
+            &invoice.payment_request,
+            &outgoing.channel.chan_id,
+            &ring.pubkeys[RING_NODES - 1],
+        )
+        .await?;
+    if !payment.payment_error.is_empty() {
+        return Err(format!(
+            "Rebalance payment failed: {}",
+            payment.payment_error
+        ));
+    }
+    let route = payment
+        .payment_route
+        .ok_or_else(|| "Rebalance payment returned no route".to_string())?;
+    let route_ids: Vec<&str> = route.hops.iter().map(|h| h.chan_id.as_str()).collect();
+    let ring_ids: Vec<&str> = ring
+        .channels
+        .iter()
+        .map(|c| c.channel.chan_id.as_str())
+        .collect();
+    if route_ids != ring_ids {
+        return Err(format!(
+            "Rebalance took {:?}, expected the ring {:?}",
+            route_ids, ring_ids
+        ));
+    }
+
+    // Each channel moves exactly what its hop carried, from opener to peer
+    wait_for(
+        "ring balances to settle",
+        BALANCE_TIMEOUT,
+        POLL_INTERVAL,
+        || async {
+            for ((channel, hop), (from_before, to_before)) in
+                ring.channels.iter().zip(&route.hops).zip(&before)
+            {
+                let carried = (hop.amt_to_forward_msat + hop.fee_msat) / 1000;
+                let (from_after, to_after) = channel_sides(&ring, channel).await?;
+                let from_delta = from_before - from_after;
+                let to_delta = to_after - to_before;
+                if (from_delta - carried).abs() > BALANCE_TOLERANCE_SAT
+                    || (to_delta - carried).abs() > BALANCE_TOLERANCE_SAT
+                {
+                    return Err(format!(
+                        "Channel {} -> {} moved {} / {} sat, expected {} sat",
+                        channel.from, channel.to, from_delta, to_delta, carried
+                    ));
+                }
+            }
+            Ok(Some(()))
+        },
+    )
+    .await?;
+    let fees = route.total_fees;
+    Ok(format!("Moved {} sat around the ring for {} sat in fees", REBALANCE_SAT, fees))
+}
+
+async fn test_fresh_node_gossip_sync(Routing { bitcoind, graph }: &Routing) -> Outcome {
+    let budget = Duration::from_secs(env_u64(
+        "GOSSIP_SYNC_BUDGET_SECS",
+        DEFAULT_GOSSIP_SYNC_BUDGET_SECS,
+    )?);
+
+    // What a well-connected node knows is the reference the newcomer must reach
+    let reference = graph.nodes[0].describe_graph().await?;
+    let fresh = start_blank_node().await?;
+    fresh.wait_synced(bitcoind).await?;
+    let connected_at = std::time::Instant::now();
+    fresh
+        .connect_peer(&graph.pubkeys[0], graph.specs[0].p2p_host)
+        .await?;
+
+    let view = wait_for(
+        "fresh node to sync the graph",
+        budget,
+        POLL_INTERVAL,
+        || async {
+            let view = fresh.describe_graph().await?;
+            if let Some(missing) = reference
+                .nodes
+                .iter()
+                .find(|n| !view.nodes.iter().any(|v| v.pub_key == n.pub_key))
+            {
+                return Err(format!(
+                    "node {} ({}) not learned",
+                    missing.pub_key, missing.alias
+                ));
+            }
+            if let Some(missing) = reference
+                .edges
+                .iter()
+                .find(|e| !view.edges.iter().any(|v| v.chan_point == e.chan_point))
+            {
+                return Err(format!("channel {} not learned", missing.chan_point));
+            }
+            Ok(Some(view))
+        },
+    )
+    .await?;
+    let sync_time = connected_at.elapsed();
+
+    // Graph nodes must announce where peers can reach them
+    for pubkey in &graph.pubkeys {
+        let node = view
+            .nodes
+            .iter()
+            .find(|n| &n.pub_key == pubkey)
+            .ok_or_else(|| format!("Graph node {} missing from fresh view", pubkey))?;
+        if node.addresses.is_empty() {
+            return Err(format!("{} ({}) announces no address", node.alias, pubkey));
+        }
+        if let Some(bad) = node
+            .addresses
+            .iter()
+            .find(|a| !a.addr.ends_with(P2P_PORT_SUFFIX))
+        {
+            return Err(format!(
+                "{} announces {}, expected the P2P port {}",
+                node.alias, bad.addr, P2P_PORT_SUFFIX
+            ));
+        }
+    }
+    let (nodes, edges, sync_time) = (view.nodes.len(), view.edges.len(), sync_time);
+    Ok(format!("Learned {} nodes and {} channels in {:?}", nodes, edges, sync_time))
+}
@@ -0,0 +1,182 @@
+//! TLS in front of vss-server, with certificates made for the run
+//!
+//! The compose stack speaks plain HTTP; production VSS sits behind TLS. The
+//! `vss-tls` service (nginx, in the `tls` compose profile) terminates TLS in
+//! front of vss-server with certificates `generate` writes to `TLS_DIR`: a
+//! throwaway CA and three server certificates it signed, one per port:
+//!
+//! - `VALID_URL`: a certificate for `localhost` and `127.0.0.1`
+//! - `WRONG_HOST_URL`: a valid certificate for another host name
+//! - `EXPIRED_URL`: a certificate for `localhost` that expired in 2021
+//! - `MTLS_URL`: the `localhost` one, and clients must show a certificate
+//!   the CA signed as well
+//!
+//! Clients trust the CA through `vss.ca_cert_path`, so `--tls` on any test
+//! binary (see `crate::cli::ConfigArgs`) runs its VSS traffic over HTTPS, and
+//! the wrong-host and expired ports must be refused by a client that checks
+//! certificates. For mutual TLS it also issues the client certificate `CLIENT`
+//! from the CA, and `ROGUE_CLIENT` from another CA nginx does not trust;
+//! `client_identity` loads either for a client to present.
+
+use std::fs;
+use std::path::Path;
+
+use rcgen::{
+    date_time_ymd, BasicConstraints, Certificate, CertificateParams, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
+};
+use reqwest::Identity;
+
+use crate::compose::compose;
+
+pub const TLS_SERVICE: &str = "vss-tls";
+// Where nginx reads the certificates from, relative to vss-test
+pub const TLS_DIR: &str = "../tls";
+pub const CA_CERT_PATH: &str = "../tls/ca.pem";
+
+pub const VALID_URL: &str = "https://localhost:5443";
+pub const WRONG_HOST_URL: &str = "https://localhost:5444";
+pub const EXPIRED_URL: &str = "https://localhost:5445";
+pub const MTLS_URL: &str = "https://localhost:5446";
+
+// Client certificates, by file name
+pub const CLIENT: &str = "client";
+pub const ROGUE_CLIENT: &str = "rogue-client";
+
+// Names in the certificate served on the wrong-host port
+const OTHER_HOST: &str = "vss.invalid";
+
+/// A server certificate nginx serves, as the files it reads.
+struct ServerCert {
+    name: &'static str,
+    hosts: &'static [&'static str],
+    expired: bool,
+}
+
+const SERVER_CERTS: [ServerCert; 3] = [
+    ServerCert {
+        name: "localhost",
+        hosts: &["localhost", "127.0.0.1"],
+        expired: false,
+    },
+    ServerCert {
+        name: "wrong-host",
+        hosts: &[OTHER_HOST],
+        expired: false,
+    },
+    ServerCert {
+        name: "expired",
+        hosts: &["localhost", "127.0.0.1"],
+        expired: true,
+    },
+];
+
+/// Generate the certificates if they are missing, or always with
+/// `regenerate`, and bring `vss-tls` up with them. Returns whether new
+/// certificates were written.
+pub fn prepare(regenerate: bool) -> Result<bool, String> {
+    let dir = Path::new(TLS_DIR);
+    let complete = Path::new(CA_CERT_PATH).exists()
+        && SERVER_CERTS
+            .iter()
+            .map(|server| server.name)
+            .chain([CLIENT, ROGUE_CLIENT])
+            .all(|name| dir.join(format!("{}.key", name)).exists());
+    let generated = regenerate || !complete;
+    if generated {
+        generate(dir)?;
+        // nginx reads its certificates at start only
+        compose(&["up", "--detach", "--force-recreate", TLS_SERVICE])?;
+    } else {
+        compose(&["up", "--detach", TLS_SERVICE])?;
+    }
+    Ok(generated)
+}
+
+/// Write a new CA to `dir` as `ca.pem`, and each server and client
+/// certificate as `<name>.pem` with its key as `<name>.key`.
+pub fn generate(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {:?}", dir.display(), e))?;
+    let (ca, ca_key) = certificate_authority("bitkit-docker test CA")?;
+    write(&dir.join("ca.pem"), &ca.pem())?;
+    for server in &SERVER_CERTS {
+        let (cert, key) = server_cert(server, &ca, &ca_key)?;
+        write(&dir.join(format!("{}.pem", server.name)), &cert)?;
+        write(&dir.join(format!("{}.key", server.name)), &key)?;
+    }
+    // The rogue CA is thrown away: nothing may trust it
+    let (rogue_ca, rogue_ca_key) = certificate_authority("rogue CA")?;
+    for (name, ca, ca_key) in [
+        (CLIENT, &ca, &ca_key),
+        (ROGUE_CLIENT, &rogue_ca, &rogue_ca_key),
+    ] {
+        let (cert, key) = client_cert(name, ca, ca_key)?;
+        write(&dir.join(format!("{}.pem", name)), &cert)?;
+        write(&dir.join(format!("{}.key", name)), &key)?;
+    }
+    Ok(())
+}
+
+/// The client certificate `name` and its key, for a client to present.
+pub fn client_identity(name: &str) -> Result<Identity, String> {
+    let dir = Path::new(TLS_DIR);
+    let read = |file: String| {
+        let path = dir.join(file);
+        fs::read(&path).map_err(|e| format!("Failed to read {}: {:?}", path.display(), e))
+    };
+    let cert = read(format!("{}.pem", name))?;
+    let key = read(format!("{}.key", name))?;
+    Identity::from_pkcs8_pem(&cert, &key)
+        .map_err(|e| format!("Failed to load client certificate {}: {:?}", name, e))
+}
+
+/// A self-signed CA named `name`, and its key.
+fn certificate_authority(name: &str) -> Result<(Certificate, KeyPair), String> {
+    let fail = |e: rcgen::Error| format!("Failed to make {}: {}", name, e);
+    let key = KeyPair::generate().map_err(fail)?;
+    let mut params = CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, name);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    let cert = params.self_signed(&key).map_err(fail)?;
+    Ok((cert, key))
+}
+
+/// A certificate for `server` signed by the CA, and its key, in PEM.
+fn server_cert(
+    server: &ServerCert,
+    ca: &Certificate,
+    ca_key: &KeyPair,
+) -> Result<(String, String), String> {
+    let fail = |e: rcgen::Error| format!("Failed to make {} certificate: {}", server.name, e);
+    let key = KeyPair::generate().map_err(fail)?;
+    let hosts: Vec<String> = server.hosts.iter().map(|host| host.to_string()).collect();
+    let mut params = CertificateParams::new(hosts).map_err(fail)?;
+    params
+        .distinguished_name
+        .push(DnType::CommonName, server.hosts[0]);
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    if server.expired {
+        params.not_before = date_time_ymd(2020, 1, 1);
+        params.not_after = date_time_ymd(2021, 1, 1);
+    }
+    let cert = params.signed_by(&key, ca, ca_key).map_err(fail)?;
+    Ok((cert.pem(), key.serialize_pem()))
+}
+
+/// A client certificate named `name` signed by `ca`, and its key, in PEM.
+fn client_cert(name: &str, ca: &Certificate, ca_key: &KeyPair) -> Result<(String, String), String> {
+    let fail = |e: rcgen::Error| format!("Failed to make {} certificate: {}", name, e);
+    let key = KeyPair::generate().map_err(fail)?;
+    let mut params = CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, name);
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let cert = params.signed_by(&key, ca, ca_key).map_err(fail)?;
+    Ok((cert.pem(), key.serialize_pem()))
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {:?}", path.display(), e))
+}
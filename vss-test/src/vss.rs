@@ -0,0 +1,250 @@
+//! Minimal VSS client speaking the protobuf-over-HTTP API of vss-server
+//!
+//! Requests carry a JWT signed with the lnurl-server key, the same way Bitkit
+//! authenticates after LNURL-auth.
+
+use crate::config;
+use harness_docker::DockerEnv;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use prost::Message;
+use reqwest::Client;
+use serde::Serialize;
+use test_harness::{cassette, log};
+use tracing::Instrument;
+use std::fs;
+use std::time::{Duration, SystemTime};
+use vss_client::types::{
+    ErrorResponse, GetObjectRequest, GetObjectResponse, KeyValue, PutObjectRequest, PutObjectResponse,
+};
+
+pub const VSS_SERVICE: &str = "vss-server";
+// Port vss-server listens on inside its container
+pub const VSS_CONTAINER_PORT: u16 = 5050;
+
+// Private key lnurl-server signs its JWTs with, and the public half VSS checks them with
+pub const VSS_SIGNING_KEY_PATH: &str = "../lnurl-server/keys/private.pem";
+pub const VSS_PUBLIC_KEY_PATH: &str = "../lnurl-server/keys/public.pem";
+
+// Store the token checks list keys of
+pub const VSS_STORE_ID: &str = "test_store";
+
+// Postgres service holding the vss_db table
+pub const VSS_DB_SERVICE: &str = "postgres";
+
+const TOKEN_LIFETIME_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Serialize)]
+struct Claims {
+    sub: String,
+    iat: i64,
+    nbf: i64,
+    exp: i64,
+}
+
+#[derive(Clone)]
+pub struct Vss {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+impl Vss {
+    /// Client authenticated as `subject` (a node pubkey) with a freshly signed JWT.
+    pub fn new(url: &str, signing_key_path: &str, subject: &str) -> Result<Self, String> {
+        Self::with_token(url, &sign_token(signing_key_path, subject)?)
+    }
+
+    /// Client sending `token` as is.
+    pub fn with_token(url: &str, token: &str) -> Result<Self, String> {
+        let timeout = Duration::from_secs(config::get().timeouts.request_secs);
+        Ok(Self {
+            client: config::http_client(timeout)?,
+            url: url.to_string(),
+            token: token.to_string(),
+        })
+    }
+
+    /// Client for the compose vss-server.
+    pub async fn local(subject: &str) -> Result<Self, String> {
+        Self::new(&local_url().await?, &config::get().vss.signing_key_path, subject)
+    }
+
+    /// Another client for the same server sending `token`, sharing this one's
+    /// connections.
+    pub fn authenticated_as(&self, token: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            token: token.to_string(),
+        }
+    }
+
+    /// Send requests with `client` instead, e.g. one with other pool settings.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Give up on requests that take longer than `timeout` instead of waiting forever.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self, String> {
+        self.client = config::http_client(timeout)?;
+        Ok(self)
+    }
+
+    /// Send `request` to `endpoint` and return the HTTP status and body as is.
+    /// Only transport failures (refused, reset, timed out) are errors.
+    pub async fn request<Req: Message>(&self, endpoint: &str, request: &Req) -> Result<(u16, Vec<u8>), String> {
+        let url = format!("{}/vss/{}", self.url, endpoint);
+        let request_id = log::request_id();
+        async {
+            let request = self
+                .client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .header("Content-Type", "application/x-protobuf")
+                .header(log::REQUEST_ID_HEADER, &request_id)
+                .body(request.encode_to_vec());
+            let resp = cassette::send(request)
+                .await
+                .map_err(|e| format!("VSS {} request failed: {:?}", endpoint, e))?;
+            let status = resp.status().as_u16();
+            tracing::debug!(status, "response");
+            let body = resp
+                .bytes()
+                .await
+                .map_err(|e| format!("VSS {} body read failed: {:?}", endpoint, e))?;
+            Ok((status, body.to_vec()))
+        }
+        .instrument(log::http_span("POST", &url, &request_id))
+        .await
+    }
+
+    async fn call<Req: Message, Resp: Message + Default>(
+        &self,
+        endpoint: &str,
+        request: &Req,
+    ) -> Result<Resp, String> {
+        let (status, body) = self.request(endpoint, request).await?;
+        if !(200..300).contains(&status) {
+            let message = ErrorResponse::decode(body.as_ref())
+                .map(|e| format!("{} (code {})", e.message, e.error_code))
+                .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string());
+            return Err(format!("VSS {} returned {}: {}", endpoint, status, message));
+        }
+        Resp::decode(body.as_ref()).map_err(|e| format!("VSS {} returned unparsable body: {:?}", endpoint, e))
+    }
+
+    /// Create `key` in `store_id`; fails if the key already exists.
+    pub async fn put_object(&self, store_id: &str, key: &str, value: Vec<u8>) -> Result<(), String> {
+        let request = PutObjectRequest {
+            store_id: store_id.to_string(),
+            global_version: None,
+            transaction_items: vec![KeyValue {
+                key: key.to_string(),
+                version: 0,
+                value,
+            }],
+            delete_items: vec![],
+        };
+        self.call::<_, PutObjectResponse>("putObjects", &request)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn get_object(&self, store_id: &str, key: &str) -> Result<Vec<u8>, String> {
+        let request = GetObjectRequest {
+            store_id: store_id.to_string(),
+            key: key.to_string(),
+        };
+        let resp: GetObjectResponse = self.call("getObject", &request).await?;
+        resp.value
+            .map(|kv| kv.value)
+            .ok_or_else(|| format!("VSS has no value for {}/{}", store_id, key))
+    }
+
+    /// Like `get_object`, but a key VSS does not know is `None` rather than an error.
+    pub async fn find_object(&self, store_id: &str, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let request = GetObjectRequest {
+            store_id: store_id.to_string(),
+            key: key.to_string(),
+        };
+        let (status, body) = self.request("getObject", &request).await?;
+        if status == 404 {
+            return Ok(None);
+        }
+        if !(200..300).contains(&status) {
+            return Err(format!(
+                "VSS getObject returned {}: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        let resp = GetObjectResponse::decode(body.as_ref())
+            .map_err(|e| format!("VSS getObject returned unparsable body: {:?}", e))?;
+        Ok(resp.value.map(|kv| kv.value))
+    }
+}
+
+/// A JWT for `subject` valid for a day, signed with the key at
+/// `signing_key_path` as lnurl-server signs the ones it issues.
+pub fn sign_token(signing_key_path: &str, subject: &str) -> Result<String, String> {
+    sign_with(&encoding_key(signing_key_path)?, subject)
+}
+
+/// Like `sign_token` for each of `subjects`, reading the key once.
+pub fn sign_tokens(signing_key_path: &str, subjects: &[String]) -> Result<Vec<String>, String> {
+    let encoding_key = encoding_key(signing_key_path)?;
+    subjects.iter().map(|subject| sign_with(&encoding_key, subject)).collect()
+}
+
+fn encoding_key(signing_key_path: &str) -> Result<EncodingKey, String> {
+    let private_key = fs::read_to_string(signing_key_path)
+        .map_err(|e| format!("Failed to load private key {}: {:?}", signing_key_path, e))?;
+    EncodingKey::from_rsa_pem(private_key.as_bytes())
+        .map_err(|e| format!("Failed to create encoding key: {:?}", e))
+}
+
+fn sign_with(encoding_key: &EncodingKey, subject: &str) -> Result<String, String> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|e| format!("System clock before epoch: {:?}", e))?
+        .as_secs() as i64;
+    let claims = Claims {
+        sub: subject.to_string(),
+        iat: now,
+        nbf: now,
+        exp: now + TOKEN_LIFETIME_SECS,
+    };
+    encode(&Header::new(Algorithm::RS256), &claims, encoding_key)
+        .map_err(|e| format!("Failed to encode JWT: {:?}", e))
+}
+
+/// Base URL of the compose vss-server: `vss.url` when set (`VSS_URL` or
+/// `--set`), otherwise the host port Docker mapped its port 5050 to.
+pub async fn local_url() -> Result<String, String> {
+    if let Some(url) = &config::get().vss.url {
+        return Ok(url.clone());
+    }
+    DockerEnv::local()?
+        .service_url(VSS_SERVICE, VSS_CONTAINER_PORT, "http")
+        .await
+}
+
+/// Run `sql` with psql inside the VSS Postgres container and return the rows,
+/// one `|`-separated line each.
+pub async fn query_db(sql: &str) -> Result<Vec<String>, String> {
+    let output = DockerEnv::local()?
+        .exec(
+            VSS_DB_SERVICE,
+            &["psql", "-U", "postgres", "-d", "postgres", "-tAc", sql],
+        )
+        .await?;
+    if !output.success() {
+        return Err(format!(
+            "psql exited with {}: {}",
+            output.exit_code,
+            output.stderr.trim()
+        ));
+    }
+    Ok(output.stdout.lines().map(|l| l.to_string()).collect())
+}
@@ -0,0 +1,1211 @@
+//! VSS Chaos Integration Test Binary
+//!
+//! Injects faults (partitions, DNS outages, full disks) between vss-server and
+//! its dependencies while requests are in flight, and checks clients get clean
+//! errors and the server recovers on its own, even after an OOM kill. SIGTERM
+//! mid-write must leave no torn writes behind, and more requests than the
+//! database connection pool holds must queue or be refused, not hang. Also runs
+//! VSS traffic over slow, jittery links like mobile clients see, and
+//! backup-sized payloads over 2G/3G-class bandwidth.
+//!
+//! With `--chaos` it instead loops a VSS workload while random stack containers
+//! are SIGKILLed, then checks the suite converges and no data was corrupted
+
+use clap::Parser;
+use futures_util::future::join_all;
+use harness_docker::chaos::{
+    ChaosMonkey, DiskLimit, DnsOutage, MemoryLimit, Netem, Partition, Shaping,
+};
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::readiness::Readiness;
+use harness_docker::stats::ResourceMonitor;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::DockerEnv;
+use prost::Message;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use test_harness::case::{self, CaseFuture, Outcome, TestCase};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::rng;
+use test_harness::runner::{Case, Tag};
+use test_harness::setup::Setup;
+use test_harness::test_cases;
+use vss_client::types::{ErrorResponse, KeyValue, PutObjectRequest};
+use vss_test::config::{self, Config};
+use vss_test::fixtures::unique_id;
+use vss_test::vss::{query_db, Vss};
+use vss_test::{env_u64, wait_for};
+
+// Checked against `--profile`; CHAOS_SERVICES must be among them
+const REQUIRED_SERVICES: [&str; 3] = ["postgres", "lnurl-auth-server", "vss-server"];
+
+const SUBJECT: &str = "vss-chaos-test";
+const VSS_SERVICE: &str = "vss-server";
+
+// Requests racing the partition
+const IN_FLIGHT_REQUESTS: usize = 20;
+const PARTITION_DELAY: Duration = Duration::from_millis(50);
+// A request still unanswered after this counts as hung
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Added one-way delays, with jitter of a tenth of the delay
+const LATENCIES_MS: [u64; 2] = [200, 500];
+const LATENCY_OPS: usize = 5;
+// How late a timed-out request may give up
+const TIMEOUT_SLACK: Duration = Duration::from_secs(1);
+
+// Mobile links as (name, kbit/s both ways, one-way delay in ms)
+const BANDWIDTH_PROFILES: [(&str, u64, u64); 2] = [("2g", 200, 300), ("3g", 1_000, 100)];
+// Backup-sized values: a channel monitor, a channel manager, a wallet backup
+const BACKUP_PAYLOAD_BYTES: [usize; 3] = [16 * 1024, 64 * 1024, 256 * 1024];
+// Request timeout the transfers have to fit in, as a client would default
+// to; overridable via CLIENT_TIMEOUT_SECS
+const DEFAULT_CLIENT_TIMEOUT_SECS: u64 = 30;
+
+// Drops vss-server's database sessions so it has to look postgres up again
+const TERMINATE_VSS_SESSIONS: &str = "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+     WHERE pid <> pg_backend_pid() AND backend_type = 'client backend'";
+
+// Requests held up at once behind a lock on the VSS table, more than any
+// connection pool vss-server would open
+const SATURATION_REQUESTS: usize = 200;
+// How long the lock holds every query up
+const SATURATION_LOCK_SECS: u64 = 5;
+// Every held-up request must be answered this soon after the lock goes
+const SATURATION_SLACK: Duration = Duration::from_secs(10);
+
+// Size of the tmpfs Postgres runs on, overridable via DISK_LIMIT_MB; it must
+// fit a fresh cluster plus its first WAL segment
+const DEFAULT_DISK_LIMIT_MB: u64 = 128;
+// Writes big enough to exhaust the preallocated WAL soon after the disk is full
+const DISK_FULL_VALUE_BYTES: usize = 256 * 1024;
+const DISK_FULL_MAX_WRITES: usize = 200;
+
+// Memory cap for vss-server, overridable via OOM_MEMORY_LIMIT_MB; a few bursts
+// of large writes have to be more than it can buffer
+const DEFAULT_OOM_MEMORY_LIMIT_MB: u64 = 48;
+const OOM_VALUE_BYTES: usize = 2 * 1024 * 1024;
+const OOM_WRITES_PER_BURST: usize = 16;
+const OOM_LOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Writers looping multi-key puts while vss-server receives SIGTERM
+const SHUTDOWN_WRITERS: usize = 8;
+const SHUTDOWN_BATCH_KEYS: usize = 3;
+const SHUTDOWN_DELAY: Duration = Duration::from_secs(1);
+// Time to drain in-flight requests and exit, as `docker stop` allows by default
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+// Sequential writes across a restart of the postgres primary
+const DB_SERVICE: &str = "postgres";
+const FAILOVER_DELAY: Duration = Duration::from_secs(2);
+const FAILOVER_STOP_TIMEOUT_SECS: i64 = 10;
+// Writes in a row that must succeed after the restart before the run ends
+const FAILOVER_WRITES_AFTER: usize = 20;
+// Longest allowed gap between two acknowledged writes, overridable via
+// FAILOVER_MAX_DOWNTIME_SECS
+const DEFAULT_FAILOVER_MAX_DOWNTIME_SECS: u64 = 30;
+
+// Containers the monkey may kill; vss-server and postgres both restart unless stopped
+const CHAOS_SERVICES: [&str; 2] = ["vss-server", "postgres"];
+// Overridable via CHAOS_INTERVAL_SECS / CHAOS_DURATION_SECS / CHAOS_DOWNTIME_SECS
+const DEFAULT_CHAOS_INTERVAL_SECS: u64 = 10;
+const DEFAULT_CHAOS_DURATION_SECS: u64 = 120;
+const DEFAULT_CHAOS_DOWNTIME_SECS: u64 = 2;
+// Requests during chaos give up sooner so the workload keeps cycling
+const CHAOS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const CONVERGENCE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// VSS Chaos Integration Test
+#[derive(Parser)]
+struct Cli {
+    /// Loop a VSS workload while random stack containers are SIGKILLed,
+    /// instead of the tests
+    #[arg(long)]
+    chaos: bool,
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The stack the faults go into and a VSS client with a timeout.
+struct Chaos {
+    env: DockerEnv,
+    vss: Vss,
+}
+
+/// VSS traffic with `delay_ms` of added one-way delay.
+struct Latency {
+    delay_ms: u64,
+}
+
+impl TestCase<Chaos> for Latency {
+    fn name(&self) -> String {
+        format!("test_vss_under_latency ({}ms)", self.delay_ms)
+    }
+
+    fn exclusive(&self) -> bool {
+        true
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        vec![Tag::Chaos, Tag::Docker]
+    }
+
+    fn run<'a>(&'a self, chaos: &'a Chaos) -> CaseFuture<'a> {
+        Box::pin(vss_under_latency(
+            chaos,
+            Duration::from_millis(self.delay_ms),
+        ))
+    }
+}
+
+/// Backup-sized payloads over one of `BANDWIDTH_PROFILES`.
+struct Bandwidth {
+    name: &'static str,
+    rate_kbit: u64,
+    delay_ms: u64,
+}
+
+impl TestCase<Chaos> for Bandwidth {
+    fn name(&self) -> String {
+        format!("test_backup_payloads_over_{}", self.name)
+    }
+
+    fn exclusive(&self) -> bool {
+        true
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        vec![Tag::Chaos, Tag::Docker]
+    }
+
+    fn run<'a>(&'a self, chaos: &'a Chaos) -> CaseFuture<'a> {
+        Box::pin(backup_payloads_over(
+            chaos,
+            self.name,
+            self.rate_kbit,
+            Duration::from_millis(self.delay_ms),
+        ))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    // Every case puts faults into the stack the others use
+    let cases = if cli.chaos {
+        test_cases!(Chaos;
+            #[exclusive] #[tags(Chaos, Docker)] test_chaos_converges,
+        )
+    } else {
+        let mut cases = test_cases!(Chaos;
+            #[exclusive] #[tags(Chaos, Docker)] test_db_partition_fails_cleanly_and_recovers,
+            #[exclusive] #[tags(Chaos, Docker)] test_db_restart_bounded_downtime,
+            #[exclusive] #[tags(Chaos, Docker)] test_db_pool_saturation_backpressure,
+            #[exclusive] #[tags(Chaos, Docker)] test_dns_outage_fails_cleanly_and_recovers,
+            #[exclusive] #[tags(Chaos, Docker)] test_disk_full_fails_cleanly_and_recovers,
+            #[exclusive] #[tags(Chaos, Docker)] test_oom_kill_loses_no_acknowledged_writes,
+            #[exclusive] #[tags(Chaos, Docker)] test_sigterm_leaves_no_torn_writes,
+        );
+        for delay_ms in LATENCIES_MS {
+            cases.push(Box::new(Latency { delay_ms }));
+        }
+        for (name, rate_kbit, delay_ms) in BANDWIDTH_PROFILES {
+            cases.push(Box::new(Bandwidth {
+                name,
+                rate_kbit,
+                delay_ms,
+            }));
+        }
+        cases
+    };
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    let notifier = Config::load(None, &[]).and_then(|settings| {
+        let notifier = settings.notify.notifier();
+        config::init(settings)?;
+        notifier
+    });
+    match notifier {
+        Ok(notifier) => run.notify(notifier),
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+    }
+    report.say("===");
+    report.say("VSS Chaos Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select(&REQUIRED_SERVICES).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let env = match DockerEnv::local() {
+        Ok(env) => env,
+        Err(e) => {
+            let e = format!("Failed to connect to Docker: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    if let Err(e) = wait_vss_ready(&env).await {
+        let e = format!("VSS stack not ready: {}", e);
+        report.error(&e);
+        std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+    }
+    let vss = match Vss::local(SUBJECT)
+        .await
+        .and_then(|vss| vss.with_timeout(REQUEST_TIMEOUT))
+    {
+        Ok(vss) => vss,
+        Err(e) => {
+            let e = format!("Failed to set up VSS client: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let chaos = Chaos { env, vss };
+
+    let mut monitor = match ResourceMonitor::from_args() {
+        Ok(monitor) => Mutex::new(monitor),
+        Err(e) => {
+            let e = format!("Failed to start resource monitoring: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases_with(
+            &report,
+            cli.suite.jobs,
+            Setup::new(),
+            &cli.filter,
+            &chaos,
+            &cases,
+            |_, runnable| monitored(&monitor, runnable),
+        )
+    })
+    .await;
+
+    if let Some(peaks) = monitor.get_mut().unwrap().report() {
+        report.say("");
+        report.say(peaks.trim_end());
+    }
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run
+        .finish(passed, failed, Some(report.failed_cases()))
+        .await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// `case` opening a resource monitor section under its name when it starts.
+fn monitored<'a>(monitor: &'a Mutex<ResourceMonitor>, case: Case<'a>) -> Case<'a> {
+    let section = case.name.clone();
+    case.around(move |run| async move {
+        monitor.lock().unwrap().begin(&section);
+        run.await
+    })
+}
+
+async fn wait_vss_ready(env: &DockerEnv) -> Result<(), String> {
+    Readiness::stack_for(env)
+        .await?
+        .wait(&[VSS_SERVICE])
+        .await
+        .map(|_| ())
+}
+
+fn put_request(store_id: &str, key: &str) -> PutObjectRequest {
+    PutObjectRequest {
+        store_id: store_id.to_string(),
+        global_version: None,
+        transaction_items: vec![KeyValue {
+            key: key.to_string(),
+            version: 0,
+            value: key.as_bytes().to_vec(),
+        }],
+        delete_items: vec![],
+    }
+}
+
+/// Put `key`: `Ok(true)` if stored, `Ok(false)` if refused with a proper VSS
+/// error response, `Err` for anything a client could not make sense of.
+async fn put_cleanly(vss: &Vss, store_id: &str, key: &str) -> Result<bool, String> {
+    let (status, body) = vss
+        .request("putObjects", &put_request(store_id, key))
+        .await?;
+    if (200..300).contains(&status) {
+        return Ok(true);
+    }
+    let error = ErrorResponse::decode(body.as_slice()).map_err(|_| {
+        format!(
+            "{} failed with {} and a body that is not an ErrorResponse: {:?}",
+            key,
+            status,
+            String::from_utf8_lossy(&body)
+        )
+    })?;
+    if status < 500 {
+        return Err(format!(
+            "{} rejected with {} as if it were a client error: {}",
+            key, status, error.message
+        ));
+    }
+    Ok(false)
+}
+
+async fn test_db_partition_fails_cleanly_and_recovers(Chaos { env, vss }: &Chaos) -> Outcome {
+    let store = unique_id("chaos-partition");
+    vss.put_object(&store, "before", b"before".to_vec()).await?;
+    let server_before = env.inspect(VSS_SERVICE).await?;
+
+    // Cut the database link while a burst of writes is in flight
+    let keys: Vec<String> = (0..IN_FLIGHT_REQUESTS)
+        .map(|i| format!("in-flight-{}", i))
+        .collect();
+    let writes = join_all(keys.iter().map(|key| put_cleanly(vss, &store, key)));
+    let partition = async {
+        tokio::time::sleep(PARTITION_DELAY).await;
+        Partition::vss_from_db(env).await
+    };
+    let (outcomes, partition) = tokio::join!(writes, partition);
+    let partition = partition?;
+    let mut stored = Vec::new();
+    for (key, outcome) in keys.iter().zip(outcomes) {
+        if outcome? {
+            stored.push(key.clone());
+        }
+    }
+
+    // Fully partitioned, the server must answer with an error rather than hang
+    if put_cleanly(vss, &store, "during").await? {
+        return Err("Write succeeded while vss-server was cut off from postgres".to_string());
+    }
+    partition.heal().await?;
+
+    // The connection pool must recover on its own
+    wait_for(
+        "vss-server to serve writes again",
+        RECOVERY_TIMEOUT,
+        POLL_INTERVAL,
+        || async { Ok(put_cleanly(vss, &store, "after").await?.then_some(())) },
+    )
+    .await?;
+    for key in stored.iter().map(String::as_str).chain(["before", "after"]) {
+        let value = vss.get_object(&store, key).await?;
+        if value != key.as_bytes() {
+            return Err(format!(
+                "{} reads back as {:?}",
+                key,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+
+    let server_after = env.inspect(VSS_SERVICE).await?;
+    if server_after.started_at != server_before.started_at
+        || server_after.restart_count != server_before.restart_count
+    {
+        return Err(format!(
+            "vss-server restarted during the partition (started {:?} -> {:?})",
+            server_before.started_at, server_after.started_at
+        ));
+    }
+    Ok(format!(
+        "{}/{} in-flight writes stored, the rest failed cleanly; recovered without restart",
+        stored.len(),
+        IN_FLIGHT_REQUESTS
+    ))
+}
+
+async fn test_db_restart_bounded_downtime(Chaos { env, .. }: &Chaos) -> Outcome {
+    let max_downtime = Duration::from_secs(env_u64(
+        "FAILOVER_MAX_DOWNTIME_SECS",
+        DEFAULT_FAILOVER_MAX_DOWNTIME_SECS,
+    )?);
+    // Short timeouts so the writer notices the outage instead of waiting on it
+    let vss = Vss::local(SUBJECT)
+        .await?
+        .with_timeout(CHAOS_REQUEST_TIMEOUT)?;
+    let store = unique_id("chaos-failover");
+    let restarted = AtomicBool::new(false);
+
+    let writer = async {
+        // (key, acknowledged, when the answer came)
+        let mut attempts: Vec<(String, bool, std::time::Instant)> = Vec::new();
+        let mut acknowledged_after = 0;
+        let deadline = std::time::Instant::now() + FAILOVER_DELAY + max_downtime * 2;
+        while acknowledged_after < FAILOVER_WRITES_AFTER && std::time::Instant::now() < deadline {
+            let key = format!("failover-{}", attempts.len());
+            let acknowledged = matches!(
+                vss.request("putObjects", &put_request(&store, &key)).await,
+                Ok((status, _)) if (200..300).contains(&status)
+            );
+            if !acknowledged {
+                acknowledged_after = 0;
+                tokio::time::sleep(POLL_INTERVAL).await;
+            } else if restarted.load(Ordering::SeqCst) {
+                acknowledged_after += 1;
+            }
+            attempts.push((key, acknowledged, std::time::Instant::now()));
+        }
+        attempts
+    };
+    let restart = async {
+        tokio::time::sleep(FAILOVER_DELAY).await;
+        let restart = env.restart(DB_SERVICE, FAILOVER_STOP_TIMEOUT_SECS).await;
+        restarted.store(true, Ordering::SeqCst);
+        restart
+    };
+    let (attempts, restart) = tokio::join!(writer, restart);
+    restart?;
+
+    // Downtime is the longest stretch without an acknowledged write
+    let mut downtime = Duration::ZERO;
+    let mut last_ack = None;
+    for (_, acknowledged, at) in &attempts {
+        if *acknowledged {
+            if let Some(last) = last_ack {
+                downtime = downtime.max(at.duration_since(last));
+            }
+            last_ack = Some(*at);
+        }
+    }
+    let acknowledged: Vec<&str> = attempts
+        .iter()
+        .filter(|(_, a, _)| *a)
+        .map(|(key, _, _)| key.as_str())
+        .collect();
+    let recovered = attempts
+        .iter()
+        .rev()
+        .take(FAILOVER_WRITES_AFTER)
+        .all(|(_, a, _)| *a);
+    if !recovered {
+        return Err(format!(
+            "VSS did not get back to {} writes in a row within {:?} of the restart",
+            FAILOVER_WRITES_AFTER,
+            max_downtime * 2
+        ));
+    }
+    if downtime > max_downtime {
+        return Err(format!(
+            "No write was acknowledged for {:?}, more than the allowed {:?}",
+            downtime, max_downtime
+        ));
+    }
+
+    // The database holds every acknowledged write and nothing that was never sent
+    let rows = query_db(&format!(
+        "SELECT key FROM vss_db WHERE store_id = '{}'",
+        store
+    ))
+    .await?;
+    if let Some(lost) = acknowledged.iter().find(|k| !rows.iter().any(|r| r == *k)) {
+        return Err(format!(
+            "Acknowledged write {} is missing from vss_db",
+            lost
+        ));
+    }
+    if let Some(stray) = rows
+        .iter()
+        .find(|r| !attempts.iter().any(|(key, _, _)| key == *r))
+    {
+        return Err(format!("vss_db holds {}, which was never written", stray));
+    }
+    for key in &acknowledged {
+        let value = vss.get_object(&store, key).await?;
+        if value != key.as_bytes() {
+            return Err(format!(
+                "{} reads back as {:?}",
+                key,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+    Ok(format!(
+        "{:?} without acknowledged writes; {}/{} writes acknowledged and stored",
+        downtime,
+        acknowledged.len(),
+        attempts.len()
+    ))
+}
+
+async fn test_db_pool_saturation_backpressure(Chaos { env, vss }: &Chaos) -> Outcome {
+    let store = unique_id("chaos-saturation");
+    vss.put_object(&store, "before", b"before".to_vec()).await?;
+    let server_before = env.inspect(VSS_SERVICE).await?;
+
+    // Every query waits on the lock, so each request holds its database
+    // connection until it goes and the pool runs dry within the burst
+    let lock_sql = format!(
+        "BEGIN; LOCK TABLE vss_db IN ACCESS EXCLUSIVE MODE; SELECT pg_sleep({}); COMMIT;",
+        SATURATION_LOCK_SECS
+    );
+    let lock = query_db(&lock_sql);
+    let keys: Vec<String> = (0..SATURATION_REQUESTS)
+        .map(|i| format!("saturating-{}", i))
+        .collect();
+    let burst = async {
+        tokio::time::sleep(PARTITION_DELAY).await;
+        let store = store.as_str();
+        let burst_start = std::time::Instant::now();
+        join_all(keys.iter().map(|key| async move {
+            let outcome = put_cleanly(vss, store, key).await;
+            (outcome, burst_start.elapsed())
+        }))
+        .await
+    };
+    let (lock, outcomes) = tokio::join!(lock, burst);
+    lock?;
+
+    let mut stored = Vec::new();
+    let mut slowest = Duration::ZERO;
+    for (key, (outcome, elapsed)) in keys.iter().zip(outcomes) {
+        // A hung request comes back as the client's timeout here
+        if outcome.map_err(|e| format!("{} after {:?}: {}", key, elapsed, e))? {
+            stored.push(key.clone());
+        }
+        slowest = slowest.max(elapsed);
+    }
+    let bound = Duration::from_secs(SATURATION_LOCK_SECS) + SATURATION_SLACK;
+    if slowest > bound {
+        return Err(format!(
+            "slowest request answered after {:?}, more than {:?} after the lock went",
+            slowest, SATURATION_SLACK
+        ));
+    }
+
+    wait_for(
+        "vss-server to serve writes again",
+        RECOVERY_TIMEOUT,
+        POLL_INTERVAL,
+        || async { Ok(put_cleanly(vss, &store, "after").await?.then_some(())) },
+    )
+    .await?;
+    for key in &stored {
+        let value = vss.get_object(&store, key).await?;
+        if value != key.as_bytes() {
+            return Err(format!(
+                "{} reads back as {:?}",
+                key,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+
+    let server_after = env.inspect(VSS_SERVICE).await?;
+    if server_after.started_at != server_before.started_at
+        || server_after.restart_count != server_before.restart_count
+    {
+        return Err(format!(
+            "vss-server restarted under saturation (started {:?} -> {:?})",
+            server_before.started_at, server_after.started_at
+        ));
+    }
+    Ok(format!(
+        "{}/{} requests queued and stored, the rest refused cleanly; slowest answered in {:?}",
+        stored.len(),
+        SATURATION_REQUESTS,
+        slowest
+    ))
+}
+
+async fn test_dns_outage_fails_cleanly_and_recovers(Chaos { env, vss }: &Chaos) -> Outcome {
+    let store = unique_id("chaos-dns");
+    vss.put_object(&store, "before", b"before".to_vec()).await?;
+    let server_before = env.inspect(VSS_SERVICE).await?;
+
+    // Open connections survive a DNS outage, so drop them to force lookups
+    let outage = DnsOutage::start(env, VSS_SERVICE).await?;
+    query_db(TERMINATE_VSS_SESSIONS).await?;
+
+    // Lookups time out inside the server; the client still gets an answer
+    let op_start = std::time::Instant::now();
+    if put_cleanly(vss, &store, "during").await? {
+        return Err("Write succeeded while vss-server could not resolve postgres".to_string());
+    }
+    let failed_after = op_start.elapsed();
+    outage.heal().await?;
+
+    wait_for(
+        "vss-server to reconnect once DNS is back",
+        RECOVERY_TIMEOUT,
+        POLL_INTERVAL,
+        || async { Ok(put_cleanly(vss, &store, "after").await?.then_some(())) },
+    )
+    .await?;
+    for key in ["before", "after"] {
+        let value = vss.get_object(&store, key).await?;
+        if value != key.as_bytes() {
+            return Err(format!(
+                "{} reads back as {:?}",
+                key,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+
+    let server_after = env.inspect(VSS_SERVICE).await?;
+    if server_after.started_at != server_before.started_at
+        || server_after.restart_count != server_before.restart_count
+    {
+        return Err(format!(
+            "vss-server restarted during the DNS outage (started {:?} -> {:?})",
+            server_before.started_at, server_after.started_at
+        ));
+    }
+    Ok(format!(
+        "write failed cleanly after {:?} without DNS; recovered without restart",
+        failed_after
+    ))
+}
+
+async fn test_disk_full_fails_cleanly_and_recovers(Chaos { env, vss }: &Chaos) -> Outcome {
+    let limit = DiskLimit::postgres(env, env_u64("DISK_LIMIT_MB", DEFAULT_DISK_LIMIT_MB)?).await?;
+    let checked = async {
+        wait_vss_ready(env).await?;
+        let store = unique_id("chaos-disk-full");
+        // vss-server's pooled connections went with the old postgres container
+        wait_for(
+            "vss-server to reach the new postgres",
+            RECOVERY_TIMEOUT,
+            POLL_INTERVAL,
+            || async { Ok(put_cleanly(vss, &store, "before").await?.then_some(())) },
+        )
+        .await?;
+
+        limit.fill().await?;
+
+        // Writes either land or fail with a proper error, and once full, stay refused
+        let mut stored = Vec::new();
+        let mut refused = None;
+        for i in 0..DISK_FULL_MAX_WRITES {
+            let key = format!("full-{}", i);
+            let mut request = put_request(&store, &key);
+            request.transaction_items[0].value = vec![i as u8; DISK_FULL_VALUE_BYTES];
+            let (status, body) = vss.request("putObjects", &request).await?;
+            if (200..300).contains(&status) {
+                stored.push((key, i as u8));
+                continue;
+            }
+            let error = ErrorResponse::decode(body.as_slice()).map_err(|_| {
+                format!(
+                    "{} failed with {} and a body that is not an ErrorResponse",
+                    key, status
+                )
+            })?;
+            if status < 500 {
+                return Err(format!(
+                    "{} rejected with {} as if it were a client error: {}",
+                    key, status, error.message
+                ));
+            }
+            refused = Some(i);
+            break;
+        }
+        let Some(refused_at) = refused else {
+            return Err(format!(
+                "All {} writes of {} bytes succeeded on a full disk",
+                DISK_FULL_MAX_WRITES, DISK_FULL_VALUE_BYTES
+            ));
+        };
+        if put_cleanly(vss, &store, "still-full").await? {
+            return Err("Write succeeded again while the disk was still full".to_string());
+        }
+
+        // Reads keep working on a full disk
+        let before = vss.get_object(&store, "before").await?;
+        if before != b"before" {
+            return Err(format!(
+                "before reads back as {:?} on a full disk",
+                String::from_utf8_lossy(&before)
+            ));
+        }
+
+        limit.free().await?;
+        wait_for(
+            "vss-server to accept writes after space was freed",
+            RECOVERY_TIMEOUT,
+            POLL_INTERVAL,
+            || async { Ok(put_cleanly(vss, &store, "after").await?.then_some(())) },
+        )
+        .await?;
+        // Nothing acknowledged before the disk filled up may be lost
+        for (key, byte) in &stored {
+            let value = vss.get_object(&store, key).await?;
+            if value.len() != DISK_FULL_VALUE_BYTES || value.iter().any(|b| b != byte) {
+                return Err(format!("Acknowledged write {} did not survive", key));
+            }
+        }
+        Ok(refused_at)
+    }
+    .await;
+    limit.heal().await?;
+    wait_vss_ready(env).await?;
+    let refused_at = checked?;
+    Ok(format!(
+        "{} large writes landed before a clean refusal; reads held up, writes recovered",
+        refused_at
+    ))
+}
+
+async fn test_oom_kill_loses_no_acknowledged_writes(Chaos { env, vss }: &Chaos) -> Outcome {
+    let limit_mb = env_u64("OOM_MEMORY_LIMIT_MB", DEFAULT_OOM_MEMORY_LIMIT_MB)?;
+    let limit = MemoryLimit::start(env, VSS_SERVICE, limit_mb).await?;
+    let checked = async {
+        wait_vss_ready(env).await?;
+        let store = unique_id("chaos-oom");
+        let before = env.inspect(VSS_SERVICE).await?;
+
+        // Concurrent large writes until the kernel steps in
+        let mut acknowledged = Vec::new();
+        let mut bursts = 0;
+        let load_start = std::time::Instant::now();
+        while limit.oom_kills() == 0 {
+            if load_start.elapsed() > OOM_LOAD_TIMEOUT {
+                return Err(format!(
+                    "vss-server survived {} bursts under a {}MiB limit without being OOM-killed",
+                    bursts, limit_mb
+                ));
+            }
+            let keys: Vec<(String, u8)> = (0..OOM_WRITES_PER_BURST)
+                .map(|i| {
+                    let n = bursts * OOM_WRITES_PER_BURST + i;
+                    (format!("oom-{}", n), n as u8)
+                })
+                .collect();
+            let outcomes = join_all(keys.iter().map(|(key, byte)| {
+                let mut request = put_request(&store, key);
+                request.transaction_items[0].value = vec![*byte; OOM_VALUE_BYTES];
+                async move { vss.request("putObjects", &request).await }
+            }))
+            .await;
+            // Connections dropped by the kill are expected; only a 2xx is a promise
+            for ((key, byte), outcome) in keys.into_iter().zip(outcomes) {
+                if matches!(outcome, Ok((status, _)) if (200..300).contains(&status)) {
+                    acknowledged.push((key, byte));
+                }
+            }
+            bursts += 1;
+        }
+
+        // The restart policy, not the harness, brings it back
+        wait_for(
+            "docker to restart vss-server",
+            RECOVERY_TIMEOUT,
+            POLL_INTERVAL,
+            || async {
+                let state = env.inspect(VSS_SERVICE).await?;
+                Ok((state.running && state.restart_count > before.restart_count).then_some(()))
+            },
+        )
+        .await?;
+        wait_vss_ready(env).await?;
+        wait_for(
+            "vss-server to serve writes after the restart",
+            RECOVERY_TIMEOUT,
+            POLL_INTERVAL,
+            || async { Ok(put_cleanly(vss, &store, "after").await?.then_some(())) },
+        )
+        .await?;
+
+        for (key, byte) in &acknowledged {
+            let value = vss.get_object(&store, key).await?;
+            if value.len() != OOM_VALUE_BYTES || value.iter().any(|b| b != byte) {
+                return Err(format!(
+                    "Acknowledged write {} did not survive the OOM kill",
+                    key
+                ));
+            }
+        }
+        Ok((bursts, acknowledged.len(), limit.oom_kills()))
+    }
+    .await;
+    limit.heal().await?;
+    wait_vss_ready(env).await?;
+    let (bursts, acknowledged, oom_kills) = checked?;
+    Ok(format!(
+        "OOM-killed {} time(s) after {} bursts; restarted by docker, {} acknowledged writes intact",
+        oom_kills, bursts, acknowledged
+    ))
+}
+
+/// Put batches of `SHUTDOWN_BATCH_KEYS` keys until `stopped` is set or a put
+/// fails, returning each batch's keys and whether it was acknowledged.
+async fn shutdown_writer(
+    vss: &Vss,
+    store: &str,
+    writer: usize,
+    stopped: &AtomicBool,
+) -> Vec<(Vec<String>, bool)> {
+    let mut batches = Vec::new();
+    for batch in 0.. {
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let keys: Vec<String> = (0..SHUTDOWN_BATCH_KEYS)
+            .map(|i| format!("term-{}-{}-{}", writer, batch, i))
+            .collect();
+        let request = PutObjectRequest {
+            store_id: store.to_string(),
+            global_version: None,
+            transaction_items: keys
+                .iter()
+                .map(|key| KeyValue {
+                    key: key.clone(),
+                    version: 0,
+                    value: chaos_value(key),
+                })
+                .collect(),
+            delete_items: vec![],
+        };
+        let acknowledged = matches!(
+            vss.request("putObjects", &request).await,
+            Ok((status, _)) if (200..300).contains(&status)
+        );
+        batches.push((keys, acknowledged));
+        if !acknowledged {
+            break;
+        }
+    }
+    batches
+}
+
+async fn test_sigterm_leaves_no_torn_writes(Chaos { env, vss }: &Chaos) -> Outcome {
+    let store = unique_id("chaos-sigterm");
+    let stopped = AtomicBool::new(false);
+    let writers =
+        join_all((0..SHUTDOWN_WRITERS).map(|w| shutdown_writer(vss, &store, w, &stopped)));
+    let terminate = async {
+        tokio::time::sleep(SHUTDOWN_DELAY).await;
+        let exited = async {
+            env.kill(VSS_SERVICE, "SIGTERM").await?;
+            wait_for(
+                "vss-server to exit on SIGTERM",
+                SHUTDOWN_GRACE,
+                POLL_INTERVAL,
+                || async {
+                    let state = env.inspect(VSS_SERVICE).await?;
+                    Ok((!state.running).then_some(state.exit_code))
+                },
+            )
+            .await
+        }
+        .await;
+        stopped.store(true, Ordering::SeqCst);
+        exited
+    };
+    let (batches, exited) = tokio::join!(writers, terminate);
+
+    // A killed container stays down under `unless-stopped`, so start it by hand
+    if exited.is_err() {
+        env.stop(VSS_SERVICE, 0).await?;
+    }
+    env.start(VSS_SERVICE).await?;
+    wait_vss_ready(env).await?;
+    let exit_code = exited?;
+
+    // Each batch is all there if acknowledged and not there at all otherwise
+    let batches: Vec<(Vec<String>, bool)> = batches.into_iter().flatten().collect();
+    for (keys, acknowledged) in &batches {
+        let mut present = 0;
+        for key in keys {
+            match vss.find_object(&store, key).await? {
+                Some(value) if value == chaos_value(key) => present += 1,
+                Some(value) => {
+                    return Err(format!(
+                        "Corruption: {} reads back as {:?} after the restart",
+                        key,
+                        String::from_utf8_lossy(&value)
+                    ))
+                }
+                None => {}
+            }
+        }
+        match (acknowledged, present) {
+            (true, n) if n == keys.len() => {}
+            (false, 0) => {}
+            (true, n) => {
+                return Err(format!(
+                    "Acknowledged batch {} lost {} of {} keys",
+                    keys[0],
+                    keys.len() - n,
+                    keys.len()
+                ))
+            }
+            (false, n) => {
+                return Err(format!(
+                    "Failed batch {} left {} of {} keys behind",
+                    keys[0],
+                    n,
+                    keys.len()
+                ))
+            }
+        }
+    }
+    let acknowledged = batches.iter().filter(|(_, a)| *a).count();
+    Ok(format!(
+        "exited with {:?}; {} acknowledged batches durable, {} failed batches absent",
+        exit_code,
+        acknowledged,
+        batches.len() - acknowledged
+    ))
+}
+
+async fn vss_under_latency(Chaos { env, vss }: &Chaos, delay: Duration) -> Outcome {
+    let jitter = delay / 10;
+    let store = unique_id("chaos-latency");
+    let shaping = Shaping::start(env, VSS_SERVICE, &Netem::delay(delay).jitter(jitter)).await?;
+
+    // A patient client still gets every round trip through, just slower
+    let mut round_trips = Vec::new();
+    for i in 0..LATENCY_OPS {
+        let key = format!("slow-{}", i);
+        let op_start = std::time::Instant::now();
+        vss.put_object(&store, &key, key.as_bytes().to_vec())
+            .await?;
+        round_trips.push(op_start.elapsed());
+        let op_start = std::time::Instant::now();
+        let value = vss.get_object(&store, &key).await?;
+        round_trips.push(op_start.elapsed());
+        if value != key.as_bytes() {
+            return Err(format!(
+                "{} reads back as {:?}",
+                key,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+    let fastest = round_trips.iter().min().copied().unwrap_or_default();
+    let slowest = round_trips.iter().max().copied().unwrap_or_default();
+    if fastest < delay - jitter {
+        return Err(format!(
+            "Fastest round trip took {:?}, so the {:?} delay was not applied",
+            fastest, delay
+        ));
+    }
+
+    // An impatient client gives up on time instead of hanging
+    let client_timeout = delay / 2;
+    let impatient = Vss::local(SUBJECT).await?.with_timeout(client_timeout)?;
+    let op_start = std::time::Instant::now();
+    let outcome = impatient
+        .request("putObjects", &put_request(&store, "impatient"))
+        .await;
+    let gave_up_after = op_start.elapsed();
+    if outcome.is_ok() {
+        return Err(format!(
+            "Request with a {:?} timeout completed despite the {:?} delay",
+            client_timeout, delay
+        ));
+    }
+    if gave_up_after > client_timeout + TIMEOUT_SLACK {
+        return Err(format!(
+            "Request with a {:?} timeout only gave up after {:?}",
+            client_timeout, gave_up_after
+        ));
+    }
+
+    shaping.heal().await?;
+    vss.get_object(&store, "slow-0").await?;
+    Ok(format!(
+        "{} round trips took {:?}..{:?}; short client timeout fired",
+        LATENCY_OPS * 2,
+        fastest,
+        slowest
+    ))
+}
+
+async fn backup_payloads_over(
+    Chaos { env, .. }: &Chaos,
+    name: &str,
+    rate_kbit: u64,
+    delay: Duration,
+) -> Outcome {
+    let timeout = Duration::from_secs(env_u64("CLIENT_TIMEOUT_SECS", DEFAULT_CLIENT_TIMEOUT_SECS)?);
+    let vss = Vss::local(SUBJECT).await?.with_timeout(timeout)?;
+    let store = unique_id(&format!("chaos-{}", name));
+    let netem = Netem::delay(delay).rate_kbit(rate_kbit);
+    let shaping = Shaping::start(env, VSS_SERVICE, &netem).await?;
+
+    // Random bytes, so nothing on the way can compress them
+    let mut payloads = rng::rng(&format!("backup-payloads:{}", name));
+    let mut transfers = Vec::new();
+    for bytes in BACKUP_PAYLOAD_BYTES {
+        let key = format!("backup-{}", bytes);
+        let mut value = vec![0u8; bytes];
+        payloads.fill_bytes(&mut value);
+        let op_start = std::time::Instant::now();
+        vss.put_object(&store, &key, value.clone())
+            .await
+            .map_err(|e| format!("Uploading {} bytes: {}", bytes, e))?;
+        let upload = op_start.elapsed();
+        let op_start = std::time::Instant::now();
+        let read = vss
+            .get_object(&store, &key)
+            .await
+            .map_err(|e| format!("Downloading {} bytes: {}", bytes, e))?;
+        let download = op_start.elapsed();
+        if read != value {
+            return Err(format!("{} reads back different over {}", key, name));
+        }
+        transfers.push((bytes, upload, download));
+    }
+    shaping.heal().await?;
+
+    // The largest download cannot beat the cap unless shaping was not applied
+    let (bytes, _, download) = transfers[transfers.len() - 1];
+    let floor = Duration::from_secs_f64(bytes as f64 * 8.0 / (rate_kbit as f64 * 1000.0));
+    if download < floor / 2 {
+        return Err(format!(
+            "{} bytes downloaded in {:?}, too fast for {} kbit/s",
+            bytes, download, rate_kbit
+        ));
+    }
+    let times: Vec<String> = transfers
+        .iter()
+        .map(|(bytes, up, down)| format!("{}KiB up {:?}/down {:?}", bytes / 1024, up, down))
+        .collect();
+    Ok(format!(
+        "{} kbit/s, {}ms: {}",
+        rate_kbit,
+        delay.as_millis(),
+        times.join(", ")
+    ))
+}
+
+/// Value stored under `key` by the chaos workload, so any mix-up is detectable.
+fn chaos_value(key: &str) -> Vec<u8> {
+    format!("{}:{}", key, hex::encode(Sha256::digest(key.as_bytes()))).into_bytes()
+}
+
+/// One pass of the functional workload: write a new key, read back an older one.
+async fn chaos_round(
+    vss: &Vss,
+    store: &str,
+    round: usize,
+    acknowledged: &mut Vec<String>,
+) -> Result<(), String> {
+    let key = format!("round-{}", round);
+    vss.put_object(store, &key, chaos_value(&key)).await?;
+    acknowledged.push(key);
+    let older = &acknowledged[round % acknowledged.len()];
+    let value = vss.get_object(store, older).await?;
+    if value != chaos_value(older) {
+        return Err(format!(
+            "Corruption: {} reads back as {:?}",
+            older,
+            String::from_utf8_lossy(&value)
+        ));
+    }
+    Ok(())
+}
+
+async fn test_chaos_converges(Chaos { env, .. }: &Chaos) -> Outcome {
+    // CHAOS_SEED picks the victims alone; the run's seed otherwise
+    let seed = std::env::var("CHAOS_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(rng::seed);
+
+    let converged = async {
+        let interval =
+            Duration::from_secs(env_u64("CHAOS_INTERVAL_SECS", DEFAULT_CHAOS_INTERVAL_SECS)?);
+        let duration =
+            Duration::from_secs(env_u64("CHAOS_DURATION_SECS", DEFAULT_CHAOS_DURATION_SECS)?);
+        let downtime =
+            Duration::from_secs(env_u64("CHAOS_DOWNTIME_SECS", DEFAULT_CHAOS_DOWNTIME_SECS)?);
+        let vss = Vss::local(SUBJECT)
+            .await?
+            .with_timeout(CHAOS_REQUEST_TIMEOUT)?;
+        let store = unique_id("chaos-monkey");
+
+        // Loop the workload while containers die; failures are expected here,
+        // wrong data never is
+        let monkey = ChaosMonkey::start(env, &CHAOS_SERVICES, interval, downtime, seed);
+        let mut acknowledged = Vec::new();
+        let (mut rounds, mut failed_rounds) = (0, 0);
+        let chaos_start = std::time::Instant::now();
+        while chaos_start.elapsed() < duration {
+            match chaos_round(&vss, &store, rounds, &mut acknowledged).await {
+                Ok(()) => {}
+                Err(e) if e.starts_with("Corruption") => return Err(e),
+                Err(_) => {
+                    failed_rounds += 1;
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+            rounds += 1;
+        }
+        let kills = monkey.stop().await?;
+
+        // Back to green without outside help
+        wait_vss_ready(env).await?;
+        wait_for(
+            "suite to converge back to green",
+            CONVERGENCE_TIMEOUT,
+            POLL_INTERVAL,
+            || async {
+                let mut probe = Vec::new();
+                chaos_round(&vss, &unique_id("chaos-converge"), 0, &mut probe).await?;
+                Ok(Some(()))
+            },
+        )
+        .await?;
+
+        // Every write the server acknowledged survived the kills intact
+        for key in &acknowledged {
+            let value = vss.get_object(&store, key).await?;
+            if value != chaos_value(key) {
+                return Err(format!(
+                    "Corruption: {} reads back as {:?} after chaos",
+                    key,
+                    String::from_utf8_lossy(&value)
+                ));
+            }
+        }
+        Ok((kills.len(), rounds, failed_rounds, acknowledged.len()))
+    }
+    .await;
+    let (kills, rounds, failed_rounds, acknowledged) =
+        converged.map_err(|e| format!("seed {}: {}", seed, e))?;
+    Ok(format!(
+        "seed {}: {} kills, {} rounds ({} failed), {} acknowledged writes intact",
+        seed, kills, rounds, failed_rounds, acknowledged
+    ))
+}
@@ -0,0 +1,259 @@
+//! VSS Cold-Start Benchmark Binary
+//!
+//! Measures what a restart of vss-server costs the clients, as a rollout does
+//! to every replica in turn. `--restarts` times over, it restarts the
+//! container, then from the moment Docker reports it started:
+//!
+//! - polls getObject every 20 ms with a fresh client until one succeeds: the
+//!   time to the first successful request
+//! - sends `--requests` more, one after the other: the latency of a cold
+//!   server, with new connections, an empty database pool and cold caches
+//! - sends as many again: the same server warm, for comparison
+//!
+//! It prints each restart's time to first success and the percentiles of its
+//! cold and warm requests, and keeps the median time to first success and
+//! cold p50 in the summary's `metrics`:
+//!
+//!   cargo run --bin vss_cold_start_bench -- --restarts 5 --requests 200
+//!
+//! It exits 1 if the server took longer than `--max-time-to-first` to answer
+//! after a restart, and 3 if the stack cannot be reached or restarted.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use harness_docker::readiness::Readiness;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::DockerEnv;
+use test_harness::latency::{self, Latencies};
+use test_harness::log::{self, LogFormat};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::vss::{Vss, VSS_SERVICE};
+
+const SUBJECT: &str = "vss-cold-start-bench";
+const KEY: &str = "cold-start";
+const RESTART_TIMEOUT_SECS: i64 = 10;
+// Pause between attempts while the server comes up
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// An attempt the server has not answered by then is tried again
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+const GIVE_UP: Duration = Duration::from_secs(120);
+
+#[derive(Parser)]
+#[command(about = "Measure vss-server's time to first request and cold latency after a restart")]
+struct Cli {
+    /// Restarts measured
+    #[arg(long, default_value_t = 3)]
+    restarts: usize,
+    /// Requests sent one after the other once the server answers, cold and
+    /// then again warm
+    #[arg(long, default_value_t = 100)]
+    requests: usize,
+    /// Longest the server may take to answer after a restart, e.g. 30s
+    #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+    max_time_to_first: Duration,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// What one restart cost.
+struct ColdStart {
+    /// From the container starting to the first successful request.
+    time_to_first: Duration,
+    /// Attempts until then, the successful one included.
+    attempts: usize,
+    cold: Latencies,
+    warm: Latencies,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    log::init(LogFormat::from_env());
+    if let Err(e) = setup(&cli, &mut run) {
+        println!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    println!("===");
+    println!("VSS Cold-Start Benchmark");
+    println!();
+
+    let env = match DockerEnv::local() {
+        Ok(env) => env,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let store_id = unique_id("cold-start");
+    let ready = async {
+        Readiness::stack_for(&env)
+            .await?
+            .wait(&[VSS_SERVICE])
+            .await?;
+        let vss = Vss::local(SUBJECT).await?;
+        vss.put_object(&store_id, KEY, KEY.as_bytes().to_vec())
+            .await
+    }
+    .await;
+    if let Err(e) = ready {
+        let e = format!("VSS stack not ready: {}", e);
+        println!("{}", e);
+        std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+    }
+
+    let mut starts = Vec::new();
+    let mut failed_tests = Vec::new();
+    for restart in 1..=cli.restarts {
+        print!("restart {} ... ", restart);
+        let start = match cold_start(&env, &store_id, &cli).await {
+            Ok(start) => start,
+            Err(e) => {
+                println!("failed");
+                let e = format!("Restart {}: {}", restart, e);
+                println!("{}", e);
+                std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+            }
+        };
+        println!(
+            "first success after {:?} ({} attempts)",
+            start.time_to_first, start.attempts
+        );
+        if start.time_to_first > cli.max_time_to_first {
+            failed_tests.push(format!(
+                "restart {}: first success after {:?}, above {:?}",
+                restart, start.time_to_first, cli.max_time_to_first
+            ));
+        }
+        starts.push(start);
+    }
+
+    println!();
+    let names: Vec<(String, String)> = (1..=starts.len())
+        .map(|restart| (format!("{} cold", restart), format!("{} warm", restart)))
+        .collect();
+    let rows = starts.iter().zip(&names).flat_map(|(start, (cold, warm))| {
+        [(cold.as_str(), &start.cold), (warm.as_str(), &start.warm)]
+    });
+    println!("Latency after each restart (ms):");
+    print!("{}", latency::render("restart", rows));
+    println!();
+
+    let time_to_first_ms = median(
+        starts
+            .iter()
+            .map(|start| start.time_to_first.as_secs_f64() * 1000.0)
+            .collect(),
+    );
+    let cold_p50_ms = median(
+        starts
+            .iter()
+            .map(|start| start.cold.percentile_ms(50.0))
+            .collect(),
+    );
+    let warm_p50_ms = median(
+        starts
+            .iter()
+            .map(|start| start.warm.percentile_ms(50.0))
+            .collect(),
+    );
+    println!(
+        "Median time to first success {:.0} ms; cold p50 {:.1} ms against {:.1} ms warm",
+        time_to_first_ms, cold_p50_ms, warm_p50_ms
+    );
+    run.metric("time_to_first_success_ms", time_to_first_ms);
+    run.metric("cold_p50_ms", cold_p50_ms);
+    run.metric("warm_p50_ms", warm_p50_ms);
+
+    for failure in &failed_tests {
+        println!("FAIL: {}", failure);
+    }
+    let passed = if failed_tests.is_empty() { 1 } else { 0 };
+    let code = run
+        .finish(passed, failed_tests.len(), Some(failed_tests))
+        .await;
+    std::process::exit(code);
+}
+
+/// Check the arguments, then settle the settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.restarts == 0 {
+        return Err("--restarts must be at least 1".to_string());
+    }
+    if cli.requests == 0 {
+        return Err("--requests must be at least 1".to_string());
+    }
+    cli.config.apply()?;
+    if config::get().remote() {
+        return Err(
+            "vss_cold_start_bench restarts the local stack; it cannot run against a remote target"
+                .to_string(),
+        );
+    }
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// Restart vss-server and measure the requests that follow.
+async fn cold_start(env: &DockerEnv, store_id: &str, cli: &Cli) -> Result<ColdStart, String> {
+    env.restart(VSS_SERVICE, RESTART_TIMEOUT_SECS).await?;
+    let started = Instant::now();
+    // Slower than the limit fails the run; this much slower means the server
+    // is not coming back at all
+    let deadline = started + GIVE_UP.max(cli.max_time_to_first * 2);
+    let mut attempts = 0;
+    // A new client each time, so no connection outlives the restart
+    let vss = loop {
+        attempts += 1;
+        let attempt = async {
+            let vss = Vss::local(SUBJECT).await?.with_timeout(ATTEMPT_TIMEOUT)?;
+            vss.get_object(store_id, KEY).await?;
+            Ok::<_, String>(vss)
+        }
+        .await;
+        match attempt {
+            Ok(vss) => break Arc::new(vss),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(format!(
+                    "no successful request within {:?} of the restart: {}",
+                    deadline - started,
+                    e
+                ))
+            }
+            Err(_) => tokio::time::sleep(POLL_INTERVAL).await,
+        }
+    };
+    let time_to_first = started.elapsed();
+    let cold = timed_gets(&vss, store_id, cli.requests).await?;
+    let warm = timed_gets(&vss, store_id, cli.requests).await?;
+    Ok(ColdStart {
+        time_to_first,
+        attempts,
+        cold,
+        warm,
+    })
+}
+
+/// Get the key `count` times in a row; fails on the first error.
+async fn timed_gets(vss: &Vss, store_id: &str, count: usize) -> Result<Latencies, String> {
+    let mut latencies = Latencies::default();
+    for _ in 0..count {
+        let start = Instant::now();
+        vss.get_object(store_id, KEY).await?;
+        latencies.record(start.elapsed());
+    }
+    Ok(latencies)
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => values[n / 2],
+        n => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+    }
+}
@@ -0,0 +1,696 @@
+//! VSS Fuzz Test Binary
+//!
+//! Sends VSS hostile input where a client library would never put it, and
+//! checks it is turned away cleanly. Headers are written by hand (see
+//! `vss_test::raw_http`), so CR/LF sequences, NULs and oversized values reach
+//! the server as they are, in turn in the Authorization header, the request id
+//! header and a custom header of a getObject request:
+//!
+//! - a fixed set of classic payloads: CRLF, bare LF or bare CR before an
+//!   injected header, CRLFCRLF before an injected body, a whole injected
+//!   response, URL-encoded CRLF, NUL, bytes above 0x7f, and values of 64 KiB
+//!   and 1 MiB
+//! - `--payloads` more, the same pieces put together at random from the
+//!   run's seed (`--seed` replays them)
+//!
+//!   cargo run --bin vss_fuzz_test -- --payloads 500
+//!
+//! Every payload carries a marker of its own. Whatever comes back must frame
+//! as whole responses, one per request, none of them a 5xx, and none may
+//! carry the injected header or the marker in its headers or body; the server
+//! closing the connection counts as a refusal. After each header's payloads a
+//! normal request must still get an answer.
+//!
+//! Then key names and store ids carry SQL meta-characters, `$` quoting,
+//! quotes, LIKE wildcards and Unicode look-alikes of plain names, checking how
+//! VSS builds its Postgres queries from them:
+//!
+//! - every such key must read back with its own value, and a listing must
+//!   hold exactly the keys written, byte for byte
+//! - a store named after each must hold only its own key, and a store written
+//!   before them must be untouched after
+//! - a listing by a prefix with `%`, `_` or `\` in it must match the prefix
+//!   as written, not as a pattern
+//! - against the compose VSS, the rows in `vss_db` must hold the keys and
+//!   values verbatim
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use clap::Parser;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use prost::Message;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, SuiteArgs};
+use test_harness::log::REQUEST_ID_HEADER;
+use test_harness::rng;
+use test_harness::runner::Tag;
+use test_harness::test_cases;
+use vss_client::types::{GetObjectRequest, ListKeyVersionsRequest, ListKeyVersionsResponse};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::raw_http::{self, Target};
+use vss_test::vss::{local_url, query_db, sign_token, Vss};
+
+const SUBJECT: &str = "vss-fuzz-test";
+const KEY: &str = "fuzzed";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const AUTHORIZATION: &str = "Authorization";
+// Not a header VSS knows; whatever it does with it must be harmless
+const CUSTOM_HEADER: &str = "X-Fuzz";
+// The header the payloads try to add to the request, or the response
+const INJECTED_HEADER: &str = "X-Injected";
+// Failures printed per case; the rest are only counted
+const SHOWN_FAILURES: usize = 3;
+
+// Key names and store ids written as they are, each with a name to report it
+// by: SQL meta-characters, `$` quoting, quotes, LIKE wildcards, and names that
+// only look like others or like plain ASCII
+const INJECTIONS: [(&str, &str); 21] = [
+    ("plain", "key"),
+    ("quote", "it's"),
+    ("double quotes", "say \"hi\""),
+    ("quote break-out", "x' OR '1'='1"),
+    ("stacked statement", "x'; DELETE FROM vss_db; --"),
+    ("comments", "x /* c */ -- c"),
+    ("backslashes", "back\\slash\\'"),
+    ("escape string", "E'\\x27'"),
+    ("dollar quoting", "$$; DELETE FROM vss_db; $$"),
+    ("tagged dollar quoting", "$q$x$q$"),
+    ("placeholder", "$1"),
+    ("LIKE wildcards", "100%_off"),
+    ("percent", "%"),
+    ("underscore", "_"),
+    ("NULL", "NULL"),
+    ("Cyrillic look-alike", "\u{43a}\u{435}\u{443}"),
+    ("fullwidth look-alike", "\u{ff4b}\u{ff45}\u{ff59}"),
+    ("zero-width space", "k\u{200b}ey"),
+    ("decomposed accent", "cafe\u{301}"),
+    ("precomposed accent", "caf\u{e9}"),
+    ("curly quote break-out", "x\u{2019} OR 1=1"),
+];
+// Keys of the prefix listing, and each prefix with the keys it must list
+const PREFIX_KEYS: [&str; 6] = ["100%_off", "100%off", "100x_off", "1000", "100_%", "100\\x"];
+const PREFIXES: [(&str, &[&str]); 4] = [
+    ("100%", &["100%_off", "100%off"]),
+    ("100%_", &["100%_off"]),
+    ("100_", &["100_%"]),
+    ("100\\", &["100\\x"]),
+];
+// Written before the injected store ids, and checked after
+const CANARY_KEY: &str = "canary";
+
+// Pieces the random payloads are made of, besides the marker and text
+const PIECES: [&[u8]; 11] = [
+    b"\r\n",
+    b"\r",
+    b"\n",
+    b"\r\n\r\n",
+    b"\0",
+    b"%0d%0a",
+    b"\x7f",
+    b"\xff",
+    b": ",
+    b"\t",
+    b" ",
+];
+
+#[derive(Parser)]
+#[command(about = "Fuzz VSS request headers with CR/LF, NUL and oversized values")]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    /// Random payloads per header, on top of the fixed ones
+    #[arg(long, default_value_t = 100)]
+    payloads: usize,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// Makes a fixed payload from the injected header line and the marker.
+type Build = fn(&str, &str) -> Vec<u8>;
+
+/// A key and its value, as `vss_db` holds them.
+type Row = (Vec<u8>, Vec<u8>);
+
+/// A header value to send, and the marker that must not come back.
+struct Payload {
+    name: String,
+    bytes: Vec<u8>,
+    marker: String,
+}
+
+/// What a run sends requests with.
+struct Fuzzer {
+    target: Target,
+    token: String,
+    /// The body of every request: a getObject of a store nothing wrote.
+    body: Vec<u8>,
+    /// For the normal request after each header's payloads.
+    vss: Vss,
+    store_id: String,
+    /// Random payloads per header.
+    payloads: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    let mut run = Run::start();
+    let mut cli = Cli::parse();
+    if let Err(e) = setup(&cli, &mut run) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let cases = test_cases!(Fuzzer;
+        #[tags(Vss, Auth)] test_authorization_header_injection,
+        #[tags(Vss)] test_request_id_header_injection,
+        #[tags(Vss)] test_custom_header_injection,
+        #[tags(Vss)] test_injected_keys_round_trip,
+        #[tags(Vss)] test_injected_store_ids_isolated,
+        #[tags(Vss)] test_key_prefix_matched_literally,
+        #[tags(Vss, Docker)] test_injected_rows_in_postgres,
+    );
+    // Only the compose VSS has a database to look into
+    if config::get().vss.url.is_some() {
+        cli.filter.exclude.push(Tag::Docker);
+    }
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("VSS Fuzz Test");
+    report.say("");
+
+    let fuzzer = match fuzzer(cli.payloads).await {
+        Ok(fuzzer) => fuzzer,
+        Err(e) => {
+            let e = format!("VSS not reachable: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say(&format!("Testing against {}", fuzzer.target.authority()));
+    report.say("");
+
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &fuzzer, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    let code = run
+        .finish(passed, failed, Some(report.failed_cases()))
+        .await;
+    std::process::exit(code);
+}
+
+/// Settle the seed and settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    cli.suite.init(&cli.filter)?;
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// Find VSS and check it answers a normal request.
+async fn fuzzer(payloads: usize) -> Result<Fuzzer, String> {
+    let url = local_url().await?;
+    let signing_key_path = &config::get().vss.signing_key_path;
+    let vss = Vss::new(&url, signing_key_path, SUBJECT)?.with_timeout(REQUEST_TIMEOUT)?;
+    let store_id = unique_id("fuzz");
+    vss.find_object(&store_id, KEY).await?;
+    let body = GetObjectRequest {
+        store_id: store_id.clone(),
+        key: KEY.to_string(),
+    }
+    .encode_to_vec();
+    Ok(Fuzzer {
+        target: Target::parse(&url)?,
+        token: sign_token(signing_key_path, SUBJECT)?,
+        body,
+        vss,
+        store_id,
+        payloads,
+    })
+}
+
+/// The fixed payloads, then `count` random ones, for `header`.
+fn payloads(header: &str, count: usize) -> Vec<Payload> {
+    let mut rng = rng::rng(&format!("fuzz:{}", header));
+    let injected_line = |marker: &str| format!("{}: {}", INJECTED_HEADER, marker);
+    let fixed: [(&str, Build); 10] = [
+        ("CRLF header", |line, _| {
+            format!("\r\n{}", line).into_bytes()
+        }),
+        ("LF header", |line, _| format!("\n{}", line).into_bytes()),
+        ("CR header", |line, _| format!("\r{}", line).into_bytes()),
+        ("CRLFCRLF body", |_, marker| {
+            format!("\r\n\r\n<html>{}</html>", marker).into_bytes()
+        }),
+        ("injected response", |line, _| {
+            format!(
+                "\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n{}\r\nContent-Length: 0\r\n\r\n",
+                line
+            )
+            .into_bytes()
+        }),
+        ("URL-encoded CRLF", |line, _| {
+            format!("%0d%0a{}", line.replace(' ', "%20")).into_bytes()
+        }),
+        ("NUL", |_, marker| format!("\0{}", marker).into_bytes()),
+        ("bytes above 0x7f", |_, marker| {
+            [b"\xff\xfe".as_slice(), marker.as_bytes(), b"\x80"].concat()
+        }),
+        ("64 KiB", |_, marker| oversized(marker, 64 * 1024)),
+        ("1 MiB", |_, marker| oversized(marker, 1024 * 1024)),
+    ];
+    let mut payloads: Vec<Payload> = fixed
+        .into_iter()
+        .map(|(name, payload)| {
+            let marker = marker(&mut rng);
+            Payload {
+                name: name.to_string(),
+                bytes: payload(&injected_line(&marker), &marker),
+                marker,
+            }
+        })
+        .collect();
+    for index in 0..count {
+        let marker = marker(&mut rng);
+        let mut bytes = Vec::new();
+        for _ in 0..rng.gen_range(1..=6) {
+            match rng.gen_range(0..5) {
+                0 => bytes.extend_from_slice(PIECES.choose(&mut rng).expect("pieces")),
+                1 => bytes.extend_from_slice(injected_line(&marker).as_bytes()),
+                2 => bytes.extend_from_slice(marker.as_bytes()),
+                3 => {
+                    let len = rng.gen_range(1..=32);
+                    bytes.extend((0..len).map(|_| rng.gen_range(b' '..=b'~')));
+                }
+                _ => bytes.extend(std::iter::repeat_n(b'A', rng.gen_range(1..=16) * 1024)),
+            }
+        }
+        if raw_http::find(&bytes, marker.as_bytes()).is_none() {
+            bytes.extend_from_slice(marker.as_bytes());
+        }
+        payloads.push(Payload {
+            name: format!("random #{}", index + 1),
+            bytes,
+            marker,
+        });
+    }
+    payloads
+}
+
+fn marker(rng: &mut StdRng) -> String {
+    format!("fuzz{:016x}", rng.gen::<u64>())
+}
+
+/// `marker`, then filler up to `size` bytes.
+fn oversized(marker: &str, size: usize) -> Vec<u8> {
+    let mut bytes = marker.as_bytes().to_vec();
+    bytes.resize(size.max(bytes.len()), b'A');
+    bytes
+}
+
+async fn test_authorization_header_injection(fuzzer: &Fuzzer) -> Outcome {
+    header_injection(fuzzer, AUTHORIZATION).await
+}
+
+async fn test_request_id_header_injection(fuzzer: &Fuzzer) -> Outcome {
+    header_injection(fuzzer, REQUEST_ID_HEADER).await
+}
+
+async fn test_custom_header_injection(fuzzer: &Fuzzer) -> Outcome {
+    header_injection(fuzzer, CUSTOM_HEADER).await
+}
+
+/// Send each payload in `header` and check what comes back, then that VSS
+/// still answers a normal request.
+async fn header_injection(fuzzer: &Fuzzer, header: &str) -> Outcome {
+    let payloads = payloads(header, fuzzer.payloads);
+    let mut outcomes: BTreeMap<String, usize> = BTreeMap::new();
+    let mut failures = Vec::new();
+    for payload in &payloads {
+        let request = fuzzer.request(header, &payload.bytes);
+        let reply = raw_http::send(&fuzzer.target, &request, REQUEST_TIMEOUT).await;
+        match reply.and_then(|bytes| check(&bytes, &payload.marker)) {
+            Ok(outcome) => *outcomes.entry(outcome).or_default() += 1,
+            Err(e) => failures.push(format!(
+                "{}: {} (payload {})",
+                payload.name,
+                e,
+                shown(&payload.bytes)
+            )),
+        }
+    }
+    if let Err(e) = fuzzer.vss.find_object(&fuzzer.store_id, KEY).await {
+        return Err(format!("VSS stopped answering after the payloads: {}", e));
+    }
+    if !failures.is_empty() {
+        return Err(format!(
+            "{} of {} payloads: {}",
+            failures.len(),
+            payloads.len(),
+            failures[..failures.len().min(SHOWN_FAILURES)].join("; ")
+        ));
+    }
+    let outcomes: Vec<String> = outcomes
+        .iter()
+        .map(|(outcome, count)| format!("{} {}", count, outcome))
+        .collect();
+    Ok(format!(
+        "{} payloads: {}",
+        payloads.len(),
+        outcomes.join(", ")
+    ))
+}
+
+impl Fuzzer {
+    /// A getObject request with `payload` in `header`, after the token when
+    /// it is the Authorization header. The headers that frame the request
+    /// come first, so whatever the payload breaks, the server was told to
+    /// close the connection after its answer.
+    fn request(&self, header: &str, payload: &[u8]) -> Vec<u8> {
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: application/x-protobuf\r\nContent-Length: {}\r\n",
+            self.target.path("/vss/getObject"),
+            self.target.authority(),
+            self.body.len()
+        )
+        .into_bytes();
+        if header == AUTHORIZATION {
+            request
+                .extend_from_slice(format!("{}: Bearer {}", AUTHORIZATION, self.token).as_bytes());
+        } else {
+            request.extend_from_slice(
+                format!("{}: Bearer {}\r\n", AUTHORIZATION, self.token).as_bytes(),
+            );
+            request.extend_from_slice(format!("{}: ", header).as_bytes());
+        }
+        request.extend_from_slice(payload);
+        request.extend_from_slice(b"\r\n\r\n");
+        request.extend_from_slice(&self.body);
+        request
+    }
+}
+
+/// What the server made of a payload, e.g. `4xx`, or why that is a failure.
+fn check(bytes: &[u8], marker: &str) -> Result<String, String> {
+    let responses = raw_http::parse(bytes)?;
+    let Some(response) = responses.iter().find(|response| response.status >= 200) else {
+        return Ok("closed".to_string());
+    };
+    let finals = responses
+        .iter()
+        .filter(|response| response.status >= 200)
+        .count();
+    if finals > 1 {
+        return Err(format!("{} responses to one request", finals));
+    }
+    for response in &responses {
+        if response.status >= 500 {
+            return Err(format!("answered {}", response.status));
+        }
+        if response.header(INJECTED_HEADER).is_some() {
+            return Err(format!("the response carries {}", INJECTED_HEADER));
+        }
+        if let Some((name, _)) = response
+            .headers
+            .iter()
+            .find(|(_, value)| raw_http::find(value, marker.as_bytes()).is_some())
+        {
+            return Err(format!(
+                "the response's {} header reflects the payload",
+                name
+            ));
+        }
+        if raw_http::find(&response.body, marker.as_bytes()).is_some() {
+            return Err("the response body reflects the payload".to_string());
+        }
+    }
+    Ok(format!("{}xx", response.status / 100))
+}
+
+/// The start of `payload`, escaped, and its size when that is not all of it.
+fn shown(payload: &[u8]) -> String {
+    const SHOWN: usize = 60;
+    if payload.len() <= SHOWN {
+        format!("\"{}\"", payload.escape_ascii())
+    } else {
+        format!(
+            "\"{}\"... {} bytes",
+            payload[..SHOWN].escape_ascii(),
+            payload.len()
+        )
+    }
+}
+
+/// Every injected key reads back with its own value, and the listing holds
+/// exactly the keys written.
+async fn test_injected_keys_round_trip(fuzzer: &Fuzzer) -> Outcome {
+    let vss = &fuzzer.vss;
+    let store_id = unique_id("injected-keys");
+    write_injected_keys(vss, &store_id).await?;
+    for (name, key) in INJECTIONS {
+        let value = vss
+            .get_object(&store_id, key)
+            .await
+            .map_err(|e| format!("{} key: {}", name, e))?;
+        if value != injected_value(name) {
+            return Err(format!(
+                "{} key {:?} reads back as {:?}",
+                name,
+                key,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+    let mut listed = list_keys(vss, &store_id, None).await?;
+    listed.sort();
+    let mut written: Vec<String> = INJECTIONS.iter().map(|(_, key)| key.to_string()).collect();
+    written.sort();
+    if listed != written {
+        return Err(format!(
+            "the listing holds {:?}, not the {} keys written",
+            listed,
+            written.len()
+        ));
+    }
+    Ok(format!("{} keys", INJECTIONS.len()))
+}
+
+/// A store named after each injection holds only its own key, and a store
+/// written first is untouched.
+async fn test_injected_store_ids_isolated(fuzzer: &Fuzzer) -> Outcome {
+    let vss = &fuzzer.vss;
+    let canary = unique_id("injected-canary");
+    let canary_value = canary.as_bytes().to_vec();
+    vss.put_object(&canary, CANARY_KEY, canary_value.clone())
+        .await?;
+    let prefix = unique_id("injected-store");
+    for (name, text) in INJECTIONS {
+        let store_id = format!("{}-{}", prefix, text);
+        vss.put_object(&store_id, KEY, injected_value(name))
+            .await
+            .map_err(|e| format!("{} store: {}", name, e))?;
+    }
+    for (name, text) in INJECTIONS {
+        let store_id = format!("{}-{}", prefix, text);
+        let keys = list_keys(vss, &store_id, None).await?;
+        if keys != [KEY] {
+            return Err(format!("{} store {:?} holds {:?}", name, store_id, keys));
+        }
+        let value = vss.get_object(&store_id, KEY).await?;
+        if value != injected_value(name) {
+            return Err(format!(
+                "{} store {:?} reads back as {:?}",
+                name,
+                store_id,
+                String::from_utf8_lossy(&value)
+            ));
+        }
+    }
+    let keys = list_keys(vss, &canary, None).await?;
+    if keys != [CANARY_KEY] {
+        return Err(format!("the store written first now holds {:?}", keys));
+    }
+    if vss.get_object(&canary, CANARY_KEY).await? != canary_value {
+        return Err("the store written first has another value now".to_string());
+    }
+    Ok(format!(
+        "{} stores, and the one before them untouched",
+        INJECTIONS.len()
+    ))
+}
+
+/// A listing by a prefix holding LIKE wildcards or the escape character
+/// lists the keys starting with it as written, and no others.
+async fn test_key_prefix_matched_literally(fuzzer: &Fuzzer) -> Outcome {
+    let vss = &fuzzer.vss;
+    let store_id = unique_id("injected-prefixes");
+    for key in PREFIX_KEYS {
+        vss.put_object(&store_id, key, key.as_bytes().to_vec())
+            .await?;
+    }
+    for (prefix, expected) in PREFIXES {
+        let mut listed = list_keys(vss, &store_id, Some(prefix)).await?;
+        listed.sort();
+        if listed != expected {
+            return Err(format!(
+                "prefix {:?} lists {:?}, not {:?}",
+                prefix, listed, expected
+            ));
+        }
+    }
+    Ok(format!("{} prefixes", PREFIXES.len()))
+}
+
+/// The rows VSS wrote for injected keys hold them and their values verbatim,
+/// read back by hex so no key is quoted into SQL here either.
+async fn test_injected_rows_in_postgres(fuzzer: &Fuzzer) -> Outcome {
+    let vss = &fuzzer.vss;
+    let store_id = unique_id("injected-rows");
+    write_injected_keys(vss, &store_id).await?;
+    let rows = query_db(&format!(
+        "SELECT encode(convert_to(key, 'UTF8'), 'hex'), encode(value, 'hex') FROM vss_db \
+         WHERE store_id = convert_from(decode('{}', 'hex'), 'UTF8')",
+        hex::encode(&store_id)
+    ))
+    .await?;
+    let mut stored = decode_rows(&rows)?;
+    stored.sort();
+    let mut written: Vec<Row> = INJECTIONS
+        .iter()
+        .map(|(name, key)| (key.as_bytes().to_vec(), injected_value(name)))
+        .collect();
+    written.sort();
+    if stored != written {
+        let missing: Vec<&str> = INJECTIONS
+            .iter()
+            .filter(|(name, key)| {
+                !stored.contains(&(key.as_bytes().to_vec(), injected_value(name)))
+            })
+            .map(|(name, _)| *name)
+            .collect();
+        return Err(format!(
+            "vss_db holds {} row(s) for the store, {} written; not verbatim: {}",
+            stored.len(),
+            written.len(),
+            missing.join(", ")
+        ));
+    }
+    Ok(format!("{} rows stored verbatim", stored.len()))
+}
+
+/// The key and value of each `key|value` row of hex psql printed; blank
+/// lines are no rows.
+fn decode_rows(rows: &[String]) -> Result<Vec<Row>, String> {
+    rows.iter()
+        .filter(|row| !row.is_empty())
+        .map(|row| {
+            let (key, value) = row
+                .split_once('|')
+                .ok_or_else(|| format!("Row {:?} is not key|value", row))?;
+            let decode = |hex: &str| {
+                hex::decode(hex).map_err(|e| format!("Row {:?} is not hex: {}", row, e))
+            };
+            Ok((decode(key)?, decode(value)?))
+        })
+        .collect()
+}
+
+async fn write_injected_keys(vss: &Vss, store_id: &str) -> Result<(), String> {
+    for (name, key) in INJECTIONS {
+        vss.put_object(store_id, key, injected_value(name))
+            .await
+            .map_err(|e| format!("{} key: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn injected_value(name: &str) -> Vec<u8> {
+    format!("value of the {} injection", name).into_bytes()
+}
+
+/// Every key of `store_id`, or of those starting with `prefix`, page by page.
+async fn list_keys(vss: &Vss, store_id: &str, prefix: Option<&str>) -> Result<Vec<String>, String> {
+    let mut keys = Vec::new();
+    let mut page_token = None;
+    loop {
+        let request = ListKeyVersionsRequest {
+            store_id: store_id.to_string(),
+            key_prefix: prefix.map(str::to_string),
+            page_size: None,
+            page_token: page_token.take(),
+        };
+        let (status, body) = vss.request("listKeyVersions", &request).await?;
+        if status != 200 {
+            return Err(format!(
+                "listKeyVersions of {:?} returned {}: {}",
+                store_id,
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        let listing = ListKeyVersionsResponse::decode(body.as_ref())
+            .map_err(|e| format!("listKeyVersions returned unparsable body: {:?}", e))?;
+        keys.extend(listing.key_versions.iter().map(|kv| kv.key.clone()));
+        match listing.next_page_token {
+            Some(token) if !token.is_empty() && !listing.key_versions.is_empty() => {
+                page_token = Some(token)
+            }
+            _ => return Ok(keys),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(key: &[u8], value: &[u8]) -> String {
+        format!("{}|{}", hex::encode(key), hex::encode(value))
+    }
+
+    #[test]
+    fn decode_rows_round_trips_injected_keys() {
+        let rows: Vec<String> = INJECTIONS
+            .iter()
+            .map(|(name, key)| row(key.as_bytes(), &injected_value(name)))
+            .collect();
+        let decoded = decode_rows(&rows).unwrap();
+        for ((name, key), (stored_key, stored_value)) in INJECTIONS.iter().zip(&decoded) {
+            assert_eq!(stored_key, key.as_bytes(), "{} key", name);
+            assert_eq!(*stored_value, injected_value(name), "{} value", name);
+        }
+    }
+
+    #[test]
+    fn decode_rows_skips_blank_lines_and_keeps_empty_values() {
+        let rows = vec![String::new(), row(b"k", b""), String::new()];
+        assert_eq!(
+            decode_rows(&rows).unwrap(),
+            vec![(b"k".to_vec(), Vec::new())]
+        );
+    }
+
+    #[test]
+    fn decode_rows_rejects_rows_that_are_not_hex_pairs() {
+        for bad in ["6b6579", "6b657|76", "6b6579|zz", "6b6579|76|76"] {
+            let e = decode_rows(&[bad.to_string()]).unwrap_err();
+            assert!(e.contains(bad), "{}: {}", bad, e);
+        }
+    }
+}
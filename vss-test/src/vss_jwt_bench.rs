@@ -0,0 +1,197 @@
+//! VSS JWT Benchmark Binary
+//!
+//! Measures how much checking the JWT of a request costs vss-server. Workers
+//! send the smallest listKeyVersions there is as fast as they can, with tokens
+//! signed before the clock starts (see `vss_test::load::TokenBench`), in two
+//! phases of `--duration` each:
+//!
+//! - cached: every request carries the same token
+//! - distinct: every request carries a token of its own, for a subject of its
+//!   own, from a pool of `--tokens`
+//!
+//! It prints both phases and how much lower the throughput and how much higher
+//! the latency of the distinct one is, which is what the server spends on a
+//! token it has not seen before:
+//!
+//!   cargo run --bin vss_jwt_bench -- --workers 32 --duration 20s --tokens 50000
+//!
+//! It exits 1 if more than `--max-error-rate` percent of the requests of a
+//! phase failed, and 3 if VSS cannot be reached.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use test_harness::log::{self, LogFormat};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::load::{Report, TokenBench, TokenPool};
+use vss_test::vss::Vss;
+
+const SUBJECT_PREFIX: &str = "vss-jwt-bench";
+
+#[derive(Parser)]
+#[command(about = "Measure what checking JWTs costs vss-server, with cached and distinct tokens")]
+struct Cli {
+    /// Clients sending requests at the same time
+    #[arg(long, default_value_t = 16)]
+    workers: usize,
+    /// How long each phase sends for, e.g. 20s
+    #[arg(long, default_value = "20s", value_parser = humantime::parse_duration)]
+    duration: Duration,
+    /// Tokens signed for the distinct phase; tokens repeat once the phase
+    /// sends more requests than this
+    #[arg(long, default_value_t = 10_000)]
+    tokens: usize,
+    /// Percentage of failed requests in a phase above which the run fails
+    #[arg(long, default_value_t = 1.0)]
+    max_error_rate: f64,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    log::init(LogFormat::from_env());
+    if let Err(e) = setup(&cli, &mut run) {
+        println!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    println!("===");
+    println!("VSS JWT Benchmark");
+    println!();
+
+    let tokens = match TokenPool::mint(SUBJECT_PREFIX, cli.tokens) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+    };
+    println!(
+        "Signed {} tokens in {:.1}s",
+        tokens.len(),
+        tokens.signing_time.as_secs_f64()
+    );
+    let vss = match Vss::local(SUBJECT_PREFIX).await {
+        Ok(vss) => Arc::new(vss),
+        Err(e) => {
+            let e = format!("Failed to set up VSS client: {}", e);
+            println!("{}", e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let bench = TokenBench {
+        store_id: unique_id("jwt-bench"),
+        workers: cli.workers,
+        duration: cli.duration,
+    };
+    println!(
+        "{} workers for {} per phase, listing store {}",
+        bench.workers,
+        humantime::format_duration(bench.duration),
+        bench.store_id
+    );
+
+    let cached = Arc::new(tokens.first());
+    let phases = [("cached", cached), ("distinct", Arc::new(tokens))];
+    let mut reports = Vec::new();
+    let mut failed_tests = Vec::new();
+    for (phase, tokens) in phases {
+        println!();
+        println!("--- {}: {} token(s)", phase, tokens.len());
+        let report = bench.run(Arc::clone(&vss), Arc::clone(&tokens)).await;
+        print!("{}", report.render());
+        let requests = report.total().requests() as usize;
+        if requests == 0 {
+            let failure = format!("{}: no request completed", phase);
+            println!("FAIL: {}", failure);
+            failed_tests.push(failure);
+            continue;
+        }
+        if requests > tokens.len() && tokens.len() > 1 {
+            println!(
+                "Note: {} requests reused the {} tokens; raise --tokens for none to repeat",
+                requests,
+                tokens.len()
+            );
+        }
+        let error_rate = report.error_rate();
+        if error_rate > cli.max_error_rate {
+            let failure = format!(
+                "{}: error rate {:.2}% above {}%",
+                phase, error_rate, cli.max_error_rate
+            );
+            println!("FAIL: {}", failure);
+            failed_tests.push(failure);
+        }
+        reports.push(report);
+    }
+    if let [cached, distinct] = reports.as_slice() {
+        println!();
+        print!("{}", comparison(cached, distinct));
+    }
+    let passed = if failed_tests.is_empty() { 1 } else { 0 };
+    let code = run
+        .finish(passed, failed_tests.len(), Some(failed_tests))
+        .await;
+    std::process::exit(code);
+}
+
+/// Check the arguments, then settle the settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.workers == 0 {
+        return Err("--workers must be at least 1".to_string());
+    }
+    if cli.tokens == 0 {
+        return Err("--tokens must be at least 1".to_string());
+    }
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// What distinct tokens cost over a cached one: throughput and latency.
+fn comparison(cached: &Report, distinct: &Report) -> String {
+    let change = |before: f64, after: f64| {
+        if before > 0.0 {
+            format!("{:+.1}%", (after - before) / before * 100.0)
+        } else {
+            "n/a".to_string()
+        }
+    };
+    let mut out = format!(
+        "{:<10} {:>10} {:>10} {:>10}\n",
+        "", "cached", "distinct", "change"
+    );
+    out.push_str(&format!(
+        "{:<10} {:>10.1} {:>10.1} {:>10}\n",
+        "req/s",
+        cached.throughput(),
+        distinct.throughput(),
+        change(cached.throughput(), distinct.throughput())
+    ));
+    let (cached, distinct) = (cached.total().latency, distinct.total().latency);
+    for (name, percentile) in [("p50 ms", 50.0), ("p99 ms", 99.0)] {
+        let (before, after) = (
+            cached.percentile_ms(percentile),
+            distinct.percentile_ms(percentile),
+        );
+        out.push_str(&format!(
+            "{:<10} {:>10.2} {:>10.2} {:>10}\n",
+            name,
+            before,
+            after,
+            change(before, after)
+        ));
+    }
+    out.push_str(&format!(
+        "A token the server has not seen costs about {:.2} ms more at the median\n",
+        distinct.percentile_ms(50.0) - cached.percentile_ms(50.0)
+    ));
+    out
+}
@@ -0,0 +1,665 @@
+//! VSS Load Generator Binary
+//!
+//! Keeps `--workers` concurrent clients sending puts, gets and lists to
+//! vss-server for `--duration`, in the proportions of `--mix`, over a key
+//! space of `--key-space` keys in a store of its own (see `vss_test::load`),
+//! then prints the throughput and error rate of each operation:
+//!
+//!   cargo run --bin vss_load -- --workers 32 --duration 1m --mix get=80,list=15,put=5
+//!
+//! With `--pool-sweep` it runs the same workload once per client setting,
+//! HTTP/1.1 and HTTP/2 with each of `--pool-sizes` idle connections kept and
+//! with no cap, and names the one with the most throughput.
+//!
+//! `--key-distribution` sets how each operation picks its keys: evenly
+//! (`uniform`, the default) or mostly from a few hot ones (`zipfian`, with
+//! YCSB's exponent 0.99 or another as `zipfian:1.2`), as `get=zipfian` for
+//! one operation and a bare name for the rest. With the mix this makes runs
+//! look like a wallet's traffic rather than one operation spread evenly.
+//!
+//! `--users` spreads the key space over that many users, each key owned by
+//! one, so requests come from as many subjects as a server with that many
+//! Bitkit wallets sees. Their tokens are signed before the clock starts (see
+//! `vss_test::load::TokenPool`), and the run measures the server rather than
+//! the signing.
+//!
+//! `--rate` paces the workers to that many requests per second between them.
+//! With `--find-capacity` it binary-searches the highest rate between
+//! `--min-rate` and `--max-rate` at which the error rate stays within
+//! `--max-error-rate` and p99 within `--max-p99-ms`, running `--duration` at
+//! each rate tried, and prints it as the stack's sustainable capacity. The
+//! summary keeps it under `metrics.capacity_rps` for tracking across builds.
+//!
+//! `--open-loop` runs the workload twice: as the usual closed loop, where a
+//! worker sends its next request only once the last is back, and as an open
+//! loop, where requests arrive as a Poisson process at `--rate` (or at the
+//! closed loop's throughput) and go out on time however many are still
+//! waiting. A closed loop slows down with a stalling server and so never
+//! times the requests the stall held back (coordinated omission); the open
+//! loop does, and the latency tables side by side show how much of the tail
+//! the closed loop hid. Both p99s go in the summary's `metrics`.
+//!
+//! `--tenant-sweep` gives every worker a store and a user of its own and runs
+//! the workload with 1, 2, 4 and so on up to `--workers` of them. Tenants
+//! share no rows, so throughput should grow with them until the server runs
+//! out of cores or connections; each count's throughput per tenant, as a share
+//! of one tenant's alone, shows where it stops. A share that falls early
+//! points at locks in the tables every store lives in. The run fails if the
+//! share with the most tenants is below `--min-scaling`, and keeps it under
+//! `metrics.scaling`.
+//!
+//! `--ramp` sets how many of the workers send over the run: all of them
+//! throughout (`constant`), one more at a time (`linear`), in five steps
+//! (`step`), a tenth with all of them in the middle of the run (`spike`), or
+//! rising and falling again (`sine`). The timeline then shows throughput,
+//! error rate and latency as the load changes, and the number of workers at
+//! which the error rate first went above `--max-error-rate`.
+//!
+//! `--samples <PATH>` writes every request of the run, or of every run of a
+//! sweep or search, to a CSV file, one row each with the run's number, when
+//! it was sent, the endpoint, the status, the latency and how many requests
+//! were being sent at the time (see `vss_test::load::SampleFile`), for
+//! analysis in other tools.
+//!
+//! `--max-memory-slope <MiB/h>` also samples the memory of the vss-server
+//! container during the run (see `harness_docker::memory`) and fails the run
+//! if it grew faster than that.
+//!
+//! It exits 1 if more than `--max-error-rate` percent of the requests failed
+//! (in a sweep: of every setting's), and 3 if VSS cannot be reached to set the
+//! key space up.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use harness_docker::memory::{MemoryWatch, SAMPLE_INTERVAL};
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::DockerEnv;
+use test_harness::cli::SeedArgs;
+use test_harness::latency;
+use test_harness::log::{self, LogFormat};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::load::{
+    best_setting, KeyDistributions, Mix, PoolSetting, Ramp, Report, SampleFile, TokenPool, Workload,
+};
+use vss_test::vss::{Vss, VSS_SERVICE};
+
+const SUBJECT: &str = "vss-load";
+
+#[derive(Parser)]
+#[command(about = "Drive a concurrent put/get/list workload against vss-server")]
+struct Cli {
+    /// Clients sending requests at the same time
+    #[arg(long, default_value_t = 8)]
+    workers: usize,
+    /// How long to send for, e.g. 30s or 5m
+    #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+    duration: Duration,
+    /// Relative weights of the operations
+    #[arg(long, default_value_t = Mix::default())]
+    mix: Mix,
+    /// Distinct keys the operations pick from
+    #[arg(long, default_value_t = 1000)]
+    key_space: usize,
+    /// How the operations pick their keys: uniform or zipfian[:EXPONENT],
+    /// for all of them or per operation, e.g. uniform,get=zipfian
+    #[arg(long, default_value_t = KeyDistributions::default())]
+    key_distribution: KeyDistributions,
+    /// Bytes in each value put
+    #[arg(long, default_value_t = 1024)]
+    value_size: usize,
+    /// Users the keys are spread over, each with a token of its own
+    #[arg(long, default_value_t = 1)]
+    users: usize,
+    /// Requests per second across the workers, instead of as fast as they can
+    #[arg(long, value_name = "RPS")]
+    rate: Option<f64>,
+    /// Percentage of failed requests above which the run fails
+    #[arg(long, default_value_t = 1.0)]
+    max_error_rate: f64,
+    /// How many of the workers send over the run
+    #[arg(long, value_enum, default_value_t = Ramp::Constant)]
+    ramp: Ramp,
+    /// Run the workload once for each HTTP version and pool size instead,
+    /// and report which gave the most throughput
+    #[arg(long)]
+    pool_sweep: bool,
+    /// Idle connections per host to try in the sweep, besides keeping all
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 4, 16, 64])]
+    pool_sizes: Vec<usize>,
+    /// Binary-search the highest rate the stack sustains instead, running
+    /// the workload for --duration at each rate tried
+    #[arg(long, conflicts_with_all = ["pool_sweep", "rate"])]
+    find_capacity: bool,
+    /// Run the workload as a closed loop and then as an open loop of Poisson
+    /// arrivals at --rate, or at the closed loop's throughput, and compare
+    /// their latencies
+    #[arg(long, conflicts_with_all = ["pool_sweep", "find_capacity", "tenant_sweep"])]
+    open_loop: bool,
+    /// Run the workload with 1, 2, 4 and so on up to --workers workers
+    /// instead, each with a store and user of its own, and report how the
+    /// throughput scales with them
+    #[arg(long, conflicts_with_all = ["pool_sweep", "find_capacity", "rate", "users"])]
+    tenant_sweep: bool,
+    /// Throughput per tenant with the most tenants, as a share of one
+    /// tenant's alone, below which the sweep fails
+    #[arg(long, default_value_t = 0.5)]
+    min_scaling: f64,
+    /// Lowest rate the search tries, in requests per second
+    #[arg(long, default_value_t = 10.0)]
+    min_rate: f64,
+    /// Highest rate the search tries, in requests per second
+    #[arg(long, default_value_t = 10_000.0)]
+    max_rate: f64,
+    /// p99 latency above which a rate counts as not sustained
+    #[arg(long, default_value_t = 1000.0)]
+    max_p99_ms: f64,
+    /// Stop the search once the bounds are this close, in percent
+    #[arg(long, default_value_t = 5.0)]
+    precision: f64,
+    /// Write every request sent to this CSV file, one row each with its run,
+    /// time, endpoint, status, latency and concurrency
+    #[arg(long, value_name = "PATH")]
+    samples: Option<PathBuf>,
+    /// Fail if the vss-server container's memory grows faster than this, in
+    /// MiB per hour
+    #[arg(long, value_name = "MIB_PER_HOUR")]
+    max_memory_slope: Option<f64>,
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    log::init(LogFormat::from_env());
+    if let Err(e) = setup(&cli, &mut run) {
+        println!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    println!("===");
+    println!("VSS Load");
+    println!();
+
+    let samples = match cli.samples.as_deref().map(SampleFile::create) {
+        Some(Ok(file)) => Some(Arc::new(file)),
+        Some(Err(e)) => {
+            println!("{}", e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+        None => None,
+    };
+    let vss = match Vss::local(SUBJECT).await {
+        Ok(vss) => Arc::new(vss),
+        Err(e) => {
+            let e = format!("Failed to set up VSS client: {}", e);
+            println!("{}", e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    // In a tenant sweep every worker is a user of its own
+    let subjects = if cli.tenant_sweep {
+        cli.workers
+    } else {
+        cli.users
+    };
+    let users = if subjects > 1 {
+        match TokenPool::mint(SUBJECT, subjects) {
+            Ok(users) => {
+                println!(
+                    "Signed {} tokens in {:.1}s",
+                    users.len(),
+                    users.signing_time.as_secs_f64()
+                );
+                Some(Arc::new(users))
+            }
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+            }
+        }
+    } else {
+        None
+    };
+    let workload = Workload {
+        store_id: unique_id("load"),
+        workers: cli.workers,
+        duration: cli.duration,
+        mix: cli.mix.clone(),
+        key_space: cli.key_space,
+        keys: cli.key_distribution.clone(),
+        value_size: cli.value_size,
+        ramp: cli.ramp,
+        users,
+        rate: cli.rate,
+        isolated: cli.tenant_sweep,
+        samples,
+    };
+    println!(
+        "{} workers ({} ramp) for {} with mix {}, {} keys ({}) of {} bytes for {} user(s) in store {}",
+        workload.workers,
+        workload.ramp,
+        humantime::format_duration(workload.duration),
+        workload.mix,
+        workload.key_space,
+        workload.keys,
+        workload.value_size,
+        subjects,
+        workload.store_id
+    );
+    if let Err(e) = workload.prefill(&vss).await {
+        println!("{}", e);
+        std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+    }
+
+    let memory = match cli.max_memory_slope.map(|_| DockerEnv::local()) {
+        Some(Ok(env)) => Some(MemoryWatch::start(&env, VSS_SERVICE, SAMPLE_INTERVAL)),
+        Some(Err(e)) => {
+            let e = format!("Failed to start memory monitoring: {}", e);
+            println!("{}", e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+        None => None,
+    };
+    let outcome = if cli.pool_sweep {
+        pool_sweep(&cli, &workload, &vss).await
+    } else if cli.open_loop {
+        open_loop(&cli, &workload, vss, &mut run).await
+    } else if cli.tenant_sweep {
+        Ok(tenant_sweep(&cli, &workload, vss, &mut run).await)
+    } else if cli.find_capacity {
+        Ok(find_capacity(&cli, &workload, vss, &mut run).await)
+    } else {
+        Ok(single(&cli, &workload, vss).await)
+    };
+    let mut failed_tests = match outcome {
+        Ok(failed_tests) => failed_tests,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+    };
+    if let (Some(file), Some(path)) = (&workload.samples, &cli.samples) {
+        match file.finish() {
+            Ok(rows) => println!("Wrote {} samples to {}", rows, path.display()),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+            }
+        }
+    }
+    if let (Some(memory), Some(max)) = (&memory, cli.max_memory_slope) {
+        let growth = memory.growth();
+        if growth.exceeds(max) {
+            let failure = format!("{}, above {} MiB/h", growth.describe(), max);
+            println!("FAIL: {}", failure);
+            failed_tests.push(failure);
+        } else {
+            println!("{} (limit {} MiB/h)", growth.describe(), max);
+        }
+    }
+    let passed = if failed_tests.is_empty() { 1 } else { 0 };
+    let code = run
+        .finish(passed, failed_tests.len(), Some(failed_tests))
+        .await;
+    std::process::exit(code);
+}
+
+/// Run the workload once; returns what failed.
+async fn single(cli: &Cli, workload: &Workload, vss: Arc<Vss>) -> Vec<String> {
+    let report = workload.run(vss).await;
+    println!();
+    print!("{}", report.render());
+    println!();
+    if workload.ramp != Ramp::Constant {
+        println!("Timeline:");
+        print!("{}", report.render_timeline());
+        println!();
+        match report.first_failure(cli.max_error_rate) {
+            Some((offset, second)) => println!(
+                "First failures at {} workers, {}s in: {:.2}% errors of {} requests",
+                second.workers,
+                offset,
+                second.error_rate(),
+                second.requests
+            ),
+            None => println!(
+                "No failure threshold reached: the error rate stayed at most {}% every second",
+                cli.max_error_rate
+            ),
+        }
+        println!();
+    }
+    let error_rate = report.error_rate();
+    if error_rate > cli.max_error_rate {
+        let failure = format!(
+            "error rate {:.2}% above {}%",
+            error_rate, cli.max_error_rate
+        );
+        println!("FAIL: {}", failure);
+        vec![failure]
+    } else {
+        println!("PASS: error rate {:.2}%", error_rate);
+        Vec::new()
+    }
+}
+
+/// Run the workload once per pool setting and name the best; returns what
+/// failed, or an error if a client cannot be built.
+async fn pool_sweep(cli: &Cli, workload: &Workload, vss: &Vss) -> Result<Vec<String>, String> {
+    let settings = PoolSetting::grid(&cli.pool_sizes);
+    let mut results = Vec::new();
+    for (i, setting) in settings.into_iter().enumerate() {
+        println!("--- [{}] {}", i + 1, setting);
+        let client = Arc::new(vss.clone().with_client(setting.client()?));
+        let report = workload.run(client).await;
+        println!(
+            "{:.1} req/s, p99 {:.1} ms, {:.2}% errors",
+            report.throughput(),
+            report.total().latency.percentile_ms(99.0),
+            report.error_rate()
+        );
+        results.push((setting, report));
+    }
+    println!();
+    println!(
+        "{:<20} {:>10} {:>9} {:>9} {:>8}",
+        "setting", "req/s", "p50 ms", "p99 ms", "error %"
+    );
+    for (setting, report) in &results {
+        let latency = report.total().latency;
+        println!(
+            "{:<20} {:>10.1} {:>9.1} {:>9.1} {:>8.2}",
+            setting.to_string(),
+            report.throughput(),
+            latency.percentile_ms(50.0),
+            latency.percentile_ms(99.0),
+            report.error_rate()
+        );
+    }
+    println!();
+    Ok(match best_setting(&results, cli.max_error_rate) {
+        Some((setting, report)) => {
+            println!(
+                "Best: {} at {:.1} req/s with {:.2}% errors",
+                setting,
+                report.throughput(),
+                report.error_rate()
+            );
+            Vec::new()
+        }
+        None => {
+            let failure = format!(
+                "every setting failed more than {}% of its requests",
+                cli.max_error_rate
+            );
+            println!("FAIL: {}", failure);
+            vec![failure]
+        }
+    })
+}
+
+/// Run the workload as a closed loop, then as an open loop at `--rate` or the
+/// closed loop's throughput, and print their latencies side by side; keeps
+/// both p99s as metrics and returns what failed. A closed loop that got no
+/// request through leaves no rate to send at, which is an error.
+async fn open_loop(
+    cli: &Cli,
+    workload: &Workload,
+    vss: Arc<Vss>,
+    run: &mut Run,
+) -> Result<Vec<String>, String> {
+    println!("--- closed loop");
+    let closed = workload.run(Arc::clone(&vss)).await;
+    println!(
+        "{:.1} req/s, p99 {:.1} ms, {:.2}% errors",
+        closed.throughput(),
+        closed.total().latency.percentile_ms(99.0),
+        closed.error_rate()
+    );
+    let rate = cli.rate.unwrap_or_else(|| closed.throughput());
+    if rate <= 0.0 || !rate.is_finite() {
+        return Err(format!(
+            "The closed loop ran at {:.1} req/s, so there is no rate for the open loop; pass --rate",
+            rate
+        ));
+    }
+    println!("--- open loop at {:.1} req/s", rate);
+    let open = workload.run_open(vss, rate).await;
+    println!(
+        "{:.1} req/s, p99 {:.1} ms, {:.2}% errors",
+        open.throughput(),
+        open.total().latency.percentile_ms(99.0),
+        open.error_rate()
+    );
+    println!();
+    let (closed_total, open_total) = (closed.total(), open.total());
+    println!("Latency (ms):");
+    print!(
+        "{}",
+        latency::render(
+            "loop",
+            [
+                ("closed", &closed_total.latency),
+                ("open", &open_total.latency)
+            ]
+        )
+    );
+    println!();
+    let closed_p99 = closed_total.latency.percentile_ms(99.0);
+    let open_p99 = open_total.latency.percentile_ms(99.0);
+    run.metric("closed_p99_ms", closed_p99);
+    run.metric("open_p99_ms", open_p99);
+    if closed_p99 > 0.0 {
+        println!(
+            "Open-loop p99 is {:.2}x the closed loop's: the difference is queueing the closed loop did not time",
+            open_p99 / closed_p99
+        );
+        println!();
+    }
+
+    let mut failed_tests = Vec::new();
+    for (name, report) in [("closed", &closed), ("open", &open)] {
+        if report.error_rate() > cli.max_error_rate {
+            failed_tests.push(format!(
+                "{} loop error rate {:.2}% above {}%",
+                name,
+                report.error_rate(),
+                cli.max_error_rate
+            ));
+        }
+    }
+    for failure in &failed_tests {
+        println!("FAIL: {}", failure);
+    }
+    if failed_tests.is_empty() {
+        println!(
+            "PASS: error rates {:.2}% closed, {:.2}% open",
+            closed.error_rate(),
+            open.error_rate()
+        );
+    }
+    Ok(failed_tests)
+}
+
+/// Run the isolated workload with a doubling number of tenants up to
+/// `--workers`, compare each one's throughput with a tenant's alone and keep
+/// the share at the most tenants as the `scaling` metric; returns what failed.
+async fn tenant_sweep(cli: &Cli, workload: &Workload, vss: Arc<Vss>, run: &mut Run) -> Vec<String> {
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |tenants| Some(tenants * 2))
+        .take_while(|tenants| *tenants < cli.workers)
+        .collect();
+    counts.push(cli.workers);
+    let mut results = Vec::new();
+    for tenants in counts {
+        // The stores of the first `tenants` workers, written by the prefill
+        let report = Workload {
+            workers: tenants,
+            ..workload.clone()
+        }
+        .run(Arc::clone(&vss))
+        .await;
+        println!(
+            "--- {} tenant(s): {:.1} req/s, p99 {:.1} ms, {:.2}% errors",
+            tenants,
+            report.throughput(),
+            report.total().latency.percentile_ms(99.0),
+            report.error_rate()
+        );
+        results.push((tenants, report));
+    }
+    let alone = results[0].1.throughput();
+    let scaling = |tenants: usize, report: &Report| {
+        if alone > 0.0 {
+            report.throughput() / tenants as f64 / alone
+        } else {
+            0.0
+        }
+    };
+    println!();
+    println!(
+        "{:>8} {:>10} {:>11} {:>8} {:>9} {:>8}",
+        "tenants", "req/s", "per tenant", "scaling", "p99 ms", "error %"
+    );
+    for (tenants, report) in &results {
+        println!(
+            "{:>8} {:>10.1} {:>11.1} {:>8.2} {:>9.1} {:>8.2}",
+            tenants,
+            report.throughput(),
+            report.throughput() / *tenants as f64,
+            scaling(*tenants, report),
+            report.total().latency.percentile_ms(99.0),
+            report.error_rate()
+        );
+    }
+    println!();
+
+    let mut failed_tests = Vec::new();
+    let (most, report) = results.last().expect("at least one tenant count");
+    let share = scaling(*most, report);
+    run.metric("scaling", share);
+    if share < cli.min_scaling {
+        failed_tests.push(format!(
+            "with {} tenants each sends {:.2} of what one does alone, below {}",
+            most, share, cli.min_scaling
+        ));
+    } else {
+        println!(
+            "With {} tenants each sends {:.2} of what one does alone",
+            most, share
+        );
+    }
+    for (tenants, report) in &results {
+        if report.error_rate() > cli.max_error_rate {
+            failed_tests.push(format!(
+                "error rate {:.2}% with {} tenant(s), above {}%",
+                report.error_rate(),
+                tenants,
+                cli.max_error_rate
+            ));
+        }
+    }
+    for failure in &failed_tests {
+        println!("FAIL: {}", failure);
+    }
+    failed_tests
+}
+
+/// Binary-search the highest rate between `--min-rate` and `--max-rate` at
+/// which the workload stays within `--max-error-rate` and `--max-p99-ms`,
+/// and keep it as the `capacity_rps` metric; returns what failed.
+async fn find_capacity(
+    cli: &Cli,
+    workload: &Workload,
+    vss: Arc<Vss>,
+    run: &mut Run,
+) -> Vec<String> {
+    let probe = |rate: f64| {
+        let workload = Workload {
+            rate: Some(rate),
+            ..workload.clone()
+        };
+        let vss = Arc::clone(&vss);
+        async move {
+            let report = workload.run(vss).await;
+            let verdict = report.sustains(rate, cli.max_error_rate, cli.max_p99_ms);
+            println!(
+                "{:>10.1} req/s asked, {:>10.1} sent, p99 {:>8.1} ms, {:>6.2}% errors: {}",
+                rate,
+                report.throughput(),
+                report.total().latency.percentile_ms(99.0),
+                report.error_rate(),
+                match &verdict {
+                    Ok(()) => "sustained".to_string(),
+                    Err(reason) => reason.clone(),
+                }
+            );
+            verdict.is_ok()
+        }
+    };
+    if !probe(cli.min_rate).await {
+        let failure = format!("the stack does not sustain even {} req/s", cli.min_rate);
+        println!();
+        println!("FAIL: {}", failure);
+        return vec![failure];
+    }
+    let (mut low, mut high) = (cli.min_rate, cli.max_rate);
+    if probe(high).await {
+        low = high;
+    }
+    while high - low > low * cli.precision / 100.0 {
+        let middle = (low + high) / 2.0;
+        if probe(middle).await {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    println!();
+    if low >= cli.max_rate {
+        println!(
+            "Sustainable capacity: at least {:.0} req/s (raise --max-rate to search higher)",
+            low
+        );
+    } else {
+        println!("Sustainable capacity: {:.0} req/s", low);
+    }
+    run.metric("capacity_rps", low);
+    Vec::new()
+}
+
+/// Check the arguments, then settle the seed and settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.workers == 0 {
+        return Err("--workers must be at least 1".to_string());
+    }
+    if cli.key_space == 0 {
+        return Err("--key-space must be at least 1".to_string());
+    }
+    if cli.rate.is_some_and(|rate| rate <= 0.0 || !rate.is_finite()) {
+        return Err("--rate must be a number above 0".to_string());
+    }
+    if cli.find_capacity && !(0.0 < cli.min_rate && cli.min_rate < cli.max_rate) {
+        return Err("--min-rate must be above 0 and below --max-rate".to_string());
+    }
+    if cli.find_capacity && cli.precision <= 0.0 {
+        return Err("--precision must be above 0".to_string());
+    }
+    if cli.tenant_sweep && cli.min_scaling < 0.0 {
+        return Err("--min-scaling must be at least 0".to_string());
+    }
+    if cli.users == 0 || cli.users > cli.key_space {
+        return Err("--users must be at least 1 and at most --key-space".to_string());
+    }
+    cli.seed.init()?;
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
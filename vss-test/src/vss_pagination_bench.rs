@@ -0,0 +1,324 @@
+//! VSS Pagination Benchmark Binary
+//!
+//! Checks that listKeyVersions pages cost the same however deep into a
+//! listing they are. A store of `--keys` keys is written first; then for
+//! `--duration` the listing is walked page by page, `--page-size` keys at a
+//! time, from the first page to the last and over again, while `--writers`
+//! workers keep overwriting keys of the same store (see `vss_test::load`).
+//!
+//! The latency of each page is kept by its depth, and the pages are printed
+//! in ten groups from the first to the last. A query plan that scans every
+//! key before the page token rather than seeking to it makes the last pages
+//! slower than the first in proportion to the store's size; the run fails if
+//! the median of the last group is more than `--max-growth` times that of the
+//! first:
+//!
+//!   cargo run --bin vss_pagination_bench -- --keys 100000 --writers 8 --duration 2m
+//!
+//! It also fails if a walk saw fewer keys than the store holds or a page
+//! failed, and exits 3 if VSS cannot be reached to write the store.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use prost::Message;
+use test_harness::cli::SeedArgs;
+use test_harness::latency::{self, Latencies};
+use test_harness::log::{self, LogFormat};
+use vss_client::types::{ListKeyVersionsRequest, ListKeyVersionsResponse};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::load::{KeyDistributions, Ramp, Workload};
+use vss_test::vss::Vss;
+
+const SUBJECT: &str = "vss-pagination-bench";
+// Keys written at once while filling the store
+const PREFILL_CONCURRENCY: usize = 32;
+// Groups the pages are printed and compared in, from shallow to deep
+const GROUPS: usize = 10;
+
+#[derive(Parser)]
+#[command(about = "Measure listKeyVersions page latency by depth while writers run")]
+struct Cli {
+    /// Keys in the store walked
+    #[arg(long, default_value_t = 20_000)]
+    keys: usize,
+    /// Keys asked for per page
+    #[arg(long, default_value_t = 100)]
+    page_size: i32,
+    /// Workers overwriting keys of the store during the walks
+    #[arg(long, default_value_t = 4)]
+    writers: usize,
+    /// How long to walk for, e.g. 1m; a walk under way is finished
+    #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+    duration: Duration,
+    /// Bytes in each value written
+    #[arg(long, default_value_t = 64)]
+    value_size: usize,
+    /// Median latency of the deepest pages, as a multiple of the first ones',
+    /// above which the run fails
+    #[arg(long, default_value_t = 3.0)]
+    max_growth: f64,
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+/// What the walks of a run saw.
+#[derive(Default)]
+struct Walks {
+    /// Latency of each page, by its depth from the first.
+    pages: Vec<Latencies>,
+    walks: usize,
+    /// Keys each complete walk saw.
+    keys_seen: Vec<usize>,
+    /// Pages that failed, which end their walk.
+    errors: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    log::init(LogFormat::from_env());
+    if let Err(e) = setup(&cli, &mut run) {
+        println!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    println!("===");
+    println!("VSS Pagination Benchmark");
+    println!();
+
+    let vss = match Vss::local(SUBJECT).await {
+        Ok(vss) => Arc::new(vss),
+        Err(e) => {
+            let e = format!("Failed to set up VSS client: {}", e);
+            println!("{}", e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let writers = Workload {
+        store_id: unique_id("pagination"),
+        workers: cli.writers,
+        duration: cli.duration,
+        mix: "put=1".parse().expect("mix parses"),
+        key_space: cli.keys,
+        keys: KeyDistributions::default(),
+        value_size: cli.value_size,
+        ramp: Ramp::Constant,
+        users: None,
+        rate: None,
+        isolated: false,
+        samples: None,
+    };
+    let filling = Instant::now();
+    let prefill = Workload {
+        workers: PREFILL_CONCURRENCY,
+        ..writers.clone()
+    };
+    if let Err(e) = prefill.prefill(&vss).await {
+        println!("{}", e);
+        std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+    }
+    println!(
+        "Wrote {} keys to store {} in {:.1}s",
+        cli.keys,
+        writers.store_id,
+        filling.elapsed().as_secs_f64()
+    );
+    println!(
+        "Walking pages of {} for {} while {} writer(s) put",
+        cli.page_size,
+        humantime::format_duration(cli.duration),
+        cli.writers
+    );
+
+    let deadline = Instant::now() + cli.duration;
+    let (written, walks) = tokio::join!(
+        writers.run(Arc::clone(&vss)),
+        walk(&vss, &writers.store_id, cli.page_size, deadline)
+    );
+    println!();
+    println!(
+        "Writers: {} puts, {:.1} req/s, {:.2}% errors",
+        written.total().requests(),
+        written.throughput(),
+        written.error_rate()
+    );
+    println!(
+        "Walks: {} complete, {} page(s) deep",
+        walks.keys_seen.len(),
+        walks.pages.len()
+    );
+    println!();
+
+    let groups = groups(&walks.pages);
+    let rows: Vec<(String, &Latencies)> = groups
+        .iter()
+        .map(|(pages, latencies)| (pages.clone(), latencies))
+        .collect();
+    print!(
+        "{}",
+        latency::render(
+            "pages",
+            rows.iter()
+                .map(|(pages, latencies)| (pages.as_str(), *latencies))
+        )
+    );
+    println!();
+
+    let mut failed_tests = Vec::new();
+    match growth(&groups) {
+        Some(growth) if growth > cli.max_growth => {
+            failed_tests.push(format!(
+                "median page latency grows {:.2}x from the first pages to the last, above {}x",
+                growth, cli.max_growth
+            ));
+        }
+        Some(growth) => println!(
+            "Median page latency grows {:.2}x from the first pages to the last",
+            growth
+        ),
+        None => println!("Too few pages to compare the first with the last"),
+    }
+    if let Some((walk, seen)) = walks
+        .keys_seen
+        .iter()
+        .enumerate()
+        .find(|(_, seen)| **seen < cli.keys)
+    {
+        failed_tests.push(format!(
+            "walk {} saw {} of the store's {} keys",
+            walk + 1,
+            seen,
+            cli.keys
+        ));
+    }
+    if let Some(first) = walks.errors.first() {
+        failed_tests.push(format!(
+            "{} page(s) failed, the first: {}",
+            walks.errors.len(),
+            first
+        ));
+    }
+    for failure in &failed_tests {
+        println!("FAIL: {}", failure);
+    }
+    if failed_tests.is_empty() {
+        println!("PASS: {} walks", walks.walks);
+    }
+    let passed = if failed_tests.is_empty() { 1 } else { 0 };
+    let code = run
+        .finish(passed, failed_tests.len(), Some(failed_tests))
+        .await;
+    std::process::exit(code);
+}
+
+/// Check the arguments, then settle the seed and settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.keys == 0 {
+        return Err("--keys must be at least 1".to_string());
+    }
+    if cli.page_size < 1 {
+        return Err("--page-size must be at least 1".to_string());
+    }
+    if cli.writers == 0 {
+        return Err("--writers must be at least 1".to_string());
+    }
+    cli.seed.init()?;
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// Walk the listing of `store_id` from the first page to the last, over and
+/// over until `deadline`.
+async fn walk(vss: &Vss, store_id: &str, page_size: i32, deadline: Instant) -> Walks {
+    let mut walks = Walks::default();
+    while Instant::now() < deadline {
+        walks.walks += 1;
+        let mut page_token = None;
+        let mut seen = 0;
+        let mut depth = 0;
+        loop {
+            let request = ListKeyVersionsRequest {
+                store_id: store_id.to_string(),
+                key_prefix: None,
+                page_size: Some(page_size),
+                page_token: page_token.take(),
+            };
+            let start = Instant::now();
+            let result = vss.request("listKeyVersions", &request).await;
+            let duration = start.elapsed();
+            let listing = match page(result) {
+                Ok(listing) => listing,
+                Err(e) => {
+                    walks
+                        .errors
+                        .push(format!("walk {}, page {}: {}", walks.walks, depth + 1, e));
+                    break;
+                }
+            };
+            if walks.pages.len() <= depth {
+                walks.pages.push(Latencies::default());
+            }
+            walks.pages[depth].record(duration);
+            depth += 1;
+            seen += listing.key_versions.len();
+            match listing.next_page_token {
+                Some(token) if !token.is_empty() && !listing.key_versions.is_empty() => {
+                    page_token = Some(token)
+                }
+                _ => {
+                    walks.keys_seen.push(seen);
+                    break;
+                }
+            }
+        }
+    }
+    walks
+}
+
+fn page(result: Result<(u16, Vec<u8>), String>) -> Result<ListKeyVersionsResponse, String> {
+    let (status, body) = result?;
+    if status != 200 {
+        return Err(format!(
+            "listKeyVersions returned {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ));
+    }
+    ListKeyVersionsResponse::decode(body.as_ref())
+        .map_err(|e| format!("listKeyVersions returned unparsable body: {:?}", e))
+}
+
+/// The pages in `GROUPS` groups of equal depth, named by their range.
+fn groups(pages: &[Latencies]) -> Vec<(String, Latencies)> {
+    let size = pages.len().div_ceil(GROUPS).max(1);
+    pages
+        .chunks(size)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut latencies = Latencies::default();
+            for page in chunk {
+                latencies.add(page);
+            }
+            let first = i * size + 1;
+            (format!("{}-{}", first, first + chunk.len() - 1), latencies)
+        })
+        .collect()
+}
+
+/// Median latency of the deepest group as a multiple of the shallowest's.
+fn growth(groups: &[(String, Latencies)]) -> Option<f64> {
+    if groups.len() < 2 {
+        return None;
+    }
+    let first = groups.first()?.1.percentile_ms(50.0);
+    let last = groups.last()?.1.percentile_ms(50.0);
+    (first > 0.0).then(|| last / first)
+}
@@ -0,0 +1,285 @@
+//! VSS Payload-Size Benchmark Binary
+//!
+//! Measures how putObjects and getObject scale with the size of the value.
+//! Starting at `--min-size` and doubling up to `--max-size`, each size gets a
+//! store of its own, a few keys written beforehand, and `--duration` of
+//! workers putting and getting values of that size (see `vss_test::load`).
+//! A size the server refuses to store ends the sweep: that is its limit.
+//!
+//! It prints throughput, bandwidth and latency percentiles per size and
+//! operation, and the first size at which the median latency grows almost as
+//! fast as the size does: below it the cost of a request is mostly fixed,
+//! above it mostly the bytes. `--csv <path>` writes the same rows for a
+//! spreadsheet or plotting script:
+//!
+//!   cargo run --bin vss_payload_bench -- --max-size 16M --csv payload.csv
+//!
+//! It exits 1 if more than `--max-error-rate` percent of the requests of a
+//! size below the limit failed, and 3 if VSS cannot be reached.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use test_harness::cli::SeedArgs;
+use test_harness::latency::PERCENTILES;
+use test_harness::log::{self, LogFormat};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::load::{
+    format_size, parse_size, Counts, KeyDistributions, Mix, Operation, Ramp, Report, Workload,
+};
+use vss_test::vss::Vss;
+
+const SUBJECT: &str = "vss-payload-bench";
+// Keys per worker, so workers rarely write the same key at once
+const KEYS_PER_WORKER: usize = 4;
+// Median latency growing by this factor or more from one size to the double
+// of it means the bytes dominate
+const BEND_FACTOR: f64 = 1.5;
+
+#[derive(Parser)]
+#[command(about = "Measure putObjects and getObject across payload sizes")]
+struct Cli {
+    /// Smallest value size, e.g. 1K
+    #[arg(long, default_value = "1K", value_parser = parse_size)]
+    min_size: usize,
+    /// Largest value size tried, unless the server refuses a smaller one
+    #[arg(long, default_value = "64M", value_parser = parse_size)]
+    max_size: usize,
+    /// Clients sending requests at the same time
+    #[arg(long, default_value_t = 4)]
+    workers: usize,
+    /// How long each size is measured, e.g. 10s
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    duration: Duration,
+    /// Percentage of failed requests at a size above which the run fails
+    #[arg(long, default_value_t = 1.0)]
+    max_error_rate: f64,
+    /// Also write the results to this CSV file
+    #[arg(long, value_name = "PATH")]
+    csv: Option<std::path::PathBuf>,
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(flatten)]
+    seed: SeedArgs,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    log::init(LogFormat::from_env());
+    if let Err(e) = setup(&cli, &mut run) {
+        println!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    println!("===");
+    println!("VSS Payload-Size Benchmark");
+    println!();
+
+    let vss = match Vss::local(SUBJECT).await {
+        Ok(vss) => Arc::new(vss),
+        Err(e) => {
+            let e = format!("Failed to set up VSS client: {}", e);
+            println!("{}", e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let mix: Mix = "put=1,get=1".parse().expect("mix parses");
+    let mut results: Vec<(usize, Report)> = Vec::new();
+    let mut failed_tests = Vec::new();
+    let mut size = cli.min_size;
+    while size <= cli.max_size {
+        let workload = Workload {
+            store_id: unique_id("payload"),
+            workers: cli.workers,
+            duration: cli.duration,
+            mix: mix.clone(),
+            key_space: cli.workers * KEYS_PER_WORKER,
+            keys: KeyDistributions::default(),
+            value_size: size,
+            ramp: Ramp::Constant,
+            users: None,
+            rate: None,
+            isolated: false,
+            samples: None,
+        };
+        println!("--- {}", format_size(size));
+        if let Err(e) = workload.prefill(&vss).await {
+            if results.is_empty() {
+                let e = format!("Failed to store {} values: {}", format_size(size), e);
+                println!("{}", e);
+                std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+            }
+            println!(
+                "The server refuses {} values, ending the sweep: {}",
+                format_size(size),
+                e
+            );
+            break;
+        }
+        let report = workload.run(Arc::clone(&vss)).await;
+        let error_rate = report.error_rate();
+        println!(
+            "{:.1} req/s, p50 {:.1} ms, {:.2}% errors",
+            report.throughput(),
+            report.total().latency.percentile_ms(50.0),
+            error_rate
+        );
+        if error_rate > cli.max_error_rate {
+            let failure = format!(
+                "{}: error rate {:.2}% above {}%",
+                format_size(size),
+                error_rate,
+                cli.max_error_rate
+            );
+            println!("FAIL: {}", failure);
+            failed_tests.push(failure);
+        }
+        results.push((size, report));
+        size = match size.checked_mul(2) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    println!();
+    print!("{}", table(&results));
+    for operation in [Operation::Put, Operation::Get] {
+        match bend(&results, operation) {
+            Some(size) => println!(
+                "{}: latency grows with the payload from about {}",
+                operation,
+                format_size(size)
+            ),
+            None => println!(
+                "{}: latency stays mostly fixed across the sizes tried",
+                operation
+            ),
+        }
+    }
+    if let Some(path) = &cli.csv {
+        match std::fs::write(path, csv(&results)) {
+            Ok(()) => println!("Results written to {}", path.display()),
+            Err(e) => {
+                let e = format!("Failed to write {}: {:?}", path.display(), e);
+                println!("{}", e);
+                std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+            }
+        }
+    }
+    let passed = if failed_tests.is_empty() { 1 } else { 0 };
+    let code = run
+        .finish(passed, failed_tests.len(), Some(failed_tests))
+        .await;
+    std::process::exit(code);
+}
+
+/// Check the arguments, then settle the seed and settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.workers == 0 {
+        return Err("--workers must be at least 1".to_string());
+    }
+    if cli.min_size == 0 || cli.min_size > cli.max_size {
+        return Err("--min-size must be at least 1 byte and at most --max-size".to_string());
+    }
+    cli.seed.init()?;
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// The counts of each size and operation, with their requests per second.
+fn rows(results: &[(usize, Report)]) -> Vec<(usize, Operation, f64, &Counts)> {
+    let mut rows = Vec::new();
+    for (size, report) in results {
+        let secs = report.elapsed.as_secs_f64().max(f64::EPSILON);
+        for operation in [Operation::Put, Operation::Get] {
+            if let Some(counts) = report.counts.get(&operation) {
+                rows.push((*size, operation, counts.requests() as f64 / secs, counts));
+            }
+        }
+    }
+    rows
+}
+
+fn table(results: &[(usize, Report)]) -> String {
+    let mut out = format!(
+        "{:>8} {:<4} {:>9} {:>9} {:>9} {:>9} {:>9} {:>8}\n",
+        "size", "op", "req/s", "MiB/s", "p50 ms", "p99 ms", "max ms", "error %"
+    );
+    for (size, operation, per_sec, counts) in rows(results) {
+        out.push_str(&format!(
+            "{:>8} {:<4} {:>9.1} {:>9.2} {:>9.1} {:>9.1} {:>9.1} {:>8.2}\n",
+            format_size(size),
+            operation.to_string(),
+            per_sec,
+            mebibytes(per_sec, size),
+            counts.latency.percentile_ms(50.0),
+            counts.latency.percentile_ms(99.0),
+            counts.latency.max_ms(),
+            error_percent(counts)
+        ));
+    }
+    out
+}
+
+fn csv(results: &[(usize, Report)]) -> String {
+    let percentiles: Vec<String> = PERCENTILES
+        .iter()
+        .map(|(name, _)| format!("{}_ms", name))
+        .collect();
+    let mut out = format!(
+        "size_bytes,operation,requests,errors,requests_per_s,mib_per_s,{},max_ms\n",
+        percentiles.join(",")
+    );
+    for (size, operation, per_sec, counts) in rows(results) {
+        let values: Vec<String> = PERCENTILES
+            .iter()
+            .map(|(_, percentile)| format!("{:.3}", counts.latency.percentile_ms(*percentile)))
+            .collect();
+        out.push_str(&format!(
+            "{},{},{},{},{:.3},{:.3},{},{:.3}\n",
+            size,
+            operation,
+            counts.requests(),
+            counts.errors(),
+            per_sec,
+            mebibytes(per_sec, size),
+            values.join(","),
+            counts.latency.max_ms()
+        ));
+    }
+    out
+}
+
+/// The first size whose median latency for `operation` is `BEND_FACTOR`
+/// times that of the size before it or more.
+fn bend(results: &[(usize, Report)], operation: Operation) -> Option<usize> {
+    let medians: Vec<(usize, f64)> = results
+        .iter()
+        .filter_map(|(size, report)| {
+            let counts = report.counts.get(&operation)?;
+            Some((*size, counts.latency.percentile_ms(50.0)))
+        })
+        .collect();
+    medians
+        .windows(2)
+        .find(|pair| pair[0].1 > 0.0 && pair[1].1 >= pair[0].1 * BEND_FACTOR)
+        .map(|pair| pair[1].0)
+}
+
+fn mebibytes(per_sec: f64, size: usize) -> f64 {
+    per_sec * size as f64 / (1024.0 * 1024.0)
+}
+
+fn error_percent(counts: &Counts) -> f64 {
+    if counts.requests() == 0 {
+        0.0
+    } else {
+        counts.errors() as f64 / counts.requests() as f64 * 100.0
+    }
+}
@@ -0,0 +1,465 @@
+//! VSS Slow-Body Test Binary
+//!
+//! Checks VSS holds up against slowloris-style clients: connections that send
+//! a request's headers, then trickle its body a few bytes at a time so the
+//! server waits on them. `--connections` of them open at once, each declaring
+//! a putObjects body it could not finish within `--max-read-timeout` and
+//! writing `--bytes-per-second` of it each second (see `vss_test::raw_http`).
+//! Meanwhile normal requests keep going, each put and get from a new client,
+//! so each needs a connection slot of its own:
+//!
+//!   cargo run --bin vss_slowloris_test -- --connections 200 --max-read-timeout 30s
+//!
+//! The server must time every trickling connection out before
+//! `--max-read-timeout` is up: answer 408, or close it without an answer no
+//! sooner than `--min-read-timeout`. Any other answer, or an earlier close,
+//! is the server turning the request down for something else, and fails.
+//! Every normal request must succeed, and their p99 under the slow
+//! connections must stay within `--max-p99-ms`. A baseline of normal requests
+//! alone is timed first, for comparison.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use futures_util::future::join_all;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, SuiteArgs};
+use test_harness::latency::{self, Latencies};
+use test_harness::setup::Setup;
+use test_harness::test_cases;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::raw_http::{self, Target};
+use vss_test::vss::{local_url, sign_token, Vss};
+
+const SUBJECT: &str = "vss-slowloris-test";
+const KEY: &str = "unaffected";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Between two writes of a trickling body
+const TICK: Duration = Duration::from_secs(1);
+// Between two rounds of normal requests
+const PAUSE: Duration = Duration::from_millis(50);
+// What a server that timed a request out answers, if anything
+const REQUEST_TIMEOUT_STATUS: u16 = 408;
+const SLOW_BODIES_STEP: &str = "slow bodies sent";
+
+#[derive(Parser)]
+#[command(about = "Check VSS times out slow request bodies and keeps serving others")]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    /// Connections trickling a body at once
+    #[arg(long, default_value_t = 20)]
+    connections: usize,
+    /// Body bytes each connection writes per second
+    #[arg(long, default_value_t = 4)]
+    bytes_per_second: usize,
+    /// Longest the server may keep a trickling connection open, e.g. 30s
+    #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+    max_read_timeout: Duration,
+    /// Shortest read timeout the server may have; a close without an answer
+    /// before it is a refusal, not a timeout
+    #[arg(long, default_value = "1s", value_parser = humantime::parse_duration)]
+    min_read_timeout: Duration,
+    /// How long normal requests are timed alone first
+    #[arg(long, default_value = "5s", value_parser = humantime::parse_duration)]
+    baseline_duration: Duration,
+    /// p99 latency of normal requests under the slow connections, in ms,
+    /// above which the run fails
+    #[arg(long, default_value_t = 500.0)]
+    max_p99_ms: f64,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// How a trickling connection ended.
+enum Trickle {
+    /// The server closed it, or answered, this long after the headers.
+    Closed {
+        after: Duration,
+        status: Option<u16>,
+    },
+    /// Still open when `--max-read-timeout` was up.
+    Open,
+    Failed(String),
+}
+
+/// Normal requests sent while something else went on.
+#[derive(Default)]
+struct Traffic {
+    latencies: Latencies,
+    errors: Vec<String>,
+}
+
+/// How the trickling connections ended, and the normal requests sent
+/// meanwhile.
+struct Attack {
+    trickles: Vec<Trickle>,
+    traffic: Traffic,
+}
+
+/// The VSS server under attack, the limits from the command line and the
+/// last attack on it.
+struct Slowloris {
+    target: Target,
+    url: String,
+    store_id: String,
+    connections: usize,
+    bytes_per_second: usize,
+    max_read_timeout: Duration,
+    min_read_timeout: Duration,
+    max_p99_ms: f64,
+    /// Set by `SLOW_BODIES_STEP` for the cases to check
+    attack: Mutex<Option<Attack>>,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    if let Err(e) = setup(&cli, &mut run) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let cases = test_cases!(Slowloris;
+        #[tags(Vss, Slow)] #[needs(SLOW_BODIES_STEP)] test_slow_bodies_timed_out,
+        #[tags(Vss, Slow)] #[needs(SLOW_BODIES_STEP)] test_requests_unaffected_by_slow_bodies,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("VSS Slow-Body Test");
+    report.say("");
+
+    let ready = async {
+        let url = local_url().await?;
+        let vss = Vss::new(&url, &config::get().vss.signing_key_path, SUBJECT)?;
+        vss.find_object(&unique_id("slowloris"), KEY).await?;
+        Ok::<_, String>((Target::parse(&url)?, url))
+    }
+    .await;
+    let (target, url) = match ready {
+        Ok(ready) => ready,
+        Err(e) => {
+            let e = format!("VSS not reachable: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    let slowloris = Slowloris {
+        target,
+        url,
+        store_id: unique_id("slowloris"),
+        connections: cli.connections,
+        bytes_per_second: cli.bytes_per_second,
+        max_read_timeout: cli.max_read_timeout,
+        min_read_timeout: cli.min_read_timeout,
+        max_p99_ms: cli.max_p99_ms,
+        attack: Mutex::new(None),
+    };
+    report.say(&format!(
+        "Timing normal requests alone for {}",
+        humantime::format_duration(cli.baseline_duration)
+    ));
+    let end = Instant::now() + cli.baseline_duration;
+    let baseline = traffic(&slowloris.url, &slowloris.store_id, || Instant::now() < end).await;
+    report.say("");
+
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        let setup = Setup::new().step(SLOW_BODIES_STEP, &[], send_slow_bodies(&slowloris));
+        case::run_cases_with(
+            &report,
+            cli.suite.jobs,
+            setup,
+            &cli.filter,
+            &slowloris,
+            &cases,
+            |_, case| case,
+        )
+    })
+    .await;
+
+    let attack = slowloris.attack.lock().unwrap().take();
+    if let Some(Attack { traffic, .. }) = attack {
+        report.say("");
+        report.say("Normal request latency (ms):");
+        report.say(
+            latency::render(
+                "traffic",
+                [
+                    ("alone", &baseline.latencies),
+                    ("slow bodies", &traffic.latencies),
+                ],
+            )
+            .trim_end(),
+        );
+        run.metric("baseline_p99_ms", baseline.latencies.percentile_ms(99.0));
+        run.metric("slow_bodies_p99_ms", traffic.latencies.percentile_ms(99.0));
+    }
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    let code = run
+        .finish(passed, failed, Some(report.failed_cases()))
+        .await;
+    std::process::exit(code);
+}
+
+/// Check the arguments, then settle the settings.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.connections == 0 {
+        return Err("--connections must be at least 1".to_string());
+    }
+    if cli.bytes_per_second == 0 {
+        return Err("--bytes-per-second must be at least 1".to_string());
+    }
+    if cli.min_read_timeout >= cli.max_read_timeout {
+        return Err("--min-read-timeout must be shorter than --max-read-timeout".to_string());
+    }
+    cli.suite.init(&cli.filter)?;
+    cli.config.apply()?;
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// Setup: open the trickling connections and send normal requests until they
+/// all ended, then keep how it went for the cases.
+async fn send_slow_bodies(slowloris: &Slowloris) -> Result<(), String> {
+    let token = sign_token(&config::get().vss.signing_key_path, SUBJECT)?;
+    let trickling = AtomicBool::new(true);
+    let slow = async {
+        let trickles =
+            join_all((0..slowloris.connections).map(|_| trickle(slowloris, &token))).await;
+        trickling.store(false, Ordering::Relaxed);
+        trickles
+    };
+    let going = || trickling.load(Ordering::Relaxed);
+    let (trickles, traffic) =
+        tokio::join!(slow, traffic(&slowloris.url, &slowloris.store_id, going));
+    *slowloris.attack.lock().unwrap() = Some(Attack { trickles, traffic });
+    Ok(())
+}
+
+/// Put and get a key over and over while `going` holds, each call from a new
+/// client and so a new connection.
+async fn traffic(url: &str, store_id: &str, going: impl Fn() -> bool) -> Traffic {
+    let mut traffic = Traffic::default();
+    while going() {
+        let client = || {
+            Vss::new(url, &config::get().vss.signing_key_path, SUBJECT)?
+                .with_timeout(REQUEST_TIMEOUT)
+        };
+        let result = async {
+            let vss = client()?;
+            let start = Instant::now();
+            vss.put_object(store_id, KEY, KEY.as_bytes().to_vec())
+                .await?;
+            traffic.latencies.record(start.elapsed());
+            let vss = client()?;
+            let start = Instant::now();
+            vss.get_object(store_id, KEY).await?;
+            traffic.latencies.record(start.elapsed());
+            Ok::<_, String>(())
+        }
+        .await;
+        if let Err(e) = result {
+            traffic.errors.push(e);
+        }
+        tokio::time::sleep(PAUSE).await;
+    }
+    traffic
+}
+
+/// Send the headers of a putObjects, then its body a few bytes a second until
+/// the server closes the connection or answers, or `--max-read-timeout` is
+/// up. The body is longer than could be sent by then.
+async fn trickle(slowloris: &Slowloris, token: &str) -> Trickle {
+    let Slowloris {
+        target,
+        bytes_per_second,
+        max_read_timeout,
+        ..
+    } = slowloris;
+    let mut stream = match target.connect().await {
+        Ok(stream) => stream,
+        Err(e) => return Trickle::Failed(e),
+    };
+    let seconds = max_read_timeout.as_secs() as usize + 1;
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\nContent-Type: application/x-protobuf\r\nContent-Length: {}\r\n\r\n",
+        target.path("/vss/putObjects"),
+        target.authority(),
+        token,
+        bytes_per_second * seconds * 2
+    );
+    if let Err(e) = stream.write_all(head.as_bytes()).await {
+        return Trickle::Failed(format!("Failed to send the headers: {:?}", e));
+    }
+    let started = Instant::now();
+    let chunk = vec![0u8; *bytes_per_second];
+    let mut answer = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let closed = |answer: &[u8]| Trickle::Closed {
+            after: started.elapsed(),
+            status: raw_http::parse(answer)
+                .ok()
+                .and_then(|responses| Some(responses.first()?.status)),
+        };
+        if started.elapsed() >= *max_read_timeout {
+            return Trickle::Open;
+        }
+        match tokio::time::timeout(TICK, stream.read(&mut buf)).await {
+            Ok(Ok(0)) | Ok(Err(_)) => return closed(&answer),
+            Ok(Ok(n)) => {
+                answer.extend_from_slice(&buf[..n]);
+                if raw_http::parse(&answer).is_ok_and(|responses| !responses.is_empty()) {
+                    return closed(&answer);
+                }
+                continue;
+            }
+            Err(_) => {}
+        }
+        if stream.write_all(&chunk).await.is_err() {
+            return closed(&answer);
+        }
+    }
+}
+
+/// Every trickling connection must have been timed out: answered 408, or
+/// closed without an answer no sooner than `--min-read-timeout`, and before
+/// `--max-read-timeout` was up.
+async fn test_slow_bodies_timed_out(slowloris: &Slowloris) -> Outcome {
+    let attack = slowloris.attack.lock().unwrap();
+    let Some(Attack { trickles, .. }) = attack.as_ref() else {
+        return Err("The slow bodies were not sent".to_string());
+    };
+    let mut closed = Vec::new();
+    let mut open = 0;
+    let mut failures = Vec::new();
+    let mut refusals = Vec::new();
+    for trickle in trickles {
+        match trickle {
+            Trickle::Closed {
+                status: Some(REQUEST_TIMEOUT_STATUS),
+                after,
+            } => closed.push((*after, Some(REQUEST_TIMEOUT_STATUS))),
+            Trickle::Closed {
+                status: Some(status),
+                after,
+            } => refusals.push(format!(
+                "answered {} after {:.1}s",
+                status,
+                after.as_secs_f64()
+            )),
+            Trickle::Closed {
+                status: None,
+                after,
+            } if *after < slowloris.min_read_timeout => refusals.push(format!(
+                "closed without an answer after {:.1}s",
+                after.as_secs_f64()
+            )),
+            Trickle::Closed {
+                status: None,
+                after,
+            } => closed.push((*after, None)),
+            Trickle::Open => open += 1,
+            Trickle::Failed(e) => failures.push(e.clone()),
+        }
+    }
+    if let Some(first) = failures.first() {
+        return Err(format!(
+            "{} connection(s) failed before trickling, the first: {}",
+            failures.len(),
+            first
+        ));
+    }
+    if let Some(first) = refusals.first() {
+        return Err(format!(
+            "{} connection(s) ended other than by a read timeout, the first {}",
+            refusals.len(),
+            first
+        ));
+    }
+    if open > 0 {
+        return Err(format!(
+            "{} of {} connection(s) still open after {}",
+            open,
+            trickles.len(),
+            humantime::format_duration(slowloris.max_read_timeout)
+        ));
+    }
+    let first = closed
+        .iter()
+        .map(|(after, _)| *after)
+        .min()
+        .unwrap_or_default();
+    let last = closed
+        .iter()
+        .map(|(after, _)| *after)
+        .max()
+        .unwrap_or_default();
+    let mut endings: BTreeMap<String, usize> = BTreeMap::new();
+    for (_, status) in &closed {
+        let ending = match status {
+            Some(status) => format!("answered {}", status),
+            None => "closed without an answer".to_string(),
+        };
+        *endings.entry(ending).or_default() += 1;
+    }
+    let endings: Vec<String> = endings
+        .iter()
+        .map(|(ending, count)| format!("{} {}", count, ending))
+        .collect();
+    Ok(format!(
+        "{} connection(s) timed out after {:.1}s to {:.1}s: {}",
+        closed.len(),
+        first.as_secs_f64(),
+        last.as_secs_f64(),
+        endings.join(", ")
+    ))
+}
+
+/// Normal requests sent alongside the slow bodies all succeeded, and fast
+/// enough.
+async fn test_requests_unaffected_by_slow_bodies(slowloris: &Slowloris) -> Outcome {
+    let attack = slowloris.attack.lock().unwrap();
+    let Some(Attack { traffic, .. }) = attack.as_ref() else {
+        return Err("The slow bodies were not sent".to_string());
+    };
+    let max_p99_ms = slowloris.max_p99_ms;
+    if let Some(first) = traffic.errors.first() {
+        return Err(format!(
+            "{} request(s) failed, the first: {}",
+            traffic.errors.len(),
+            first
+        ));
+    }
+    let p99 = traffic.latencies.percentile_ms(99.0);
+    if p99 > max_p99_ms {
+        return Err(format!(
+            "p99 of {} requests was {:.1} ms, above {} ms",
+            traffic.latencies.count(),
+            p99,
+            max_p99_ms
+        ));
+    }
+    Ok(format!(
+        "{} requests, p99 {:.1} ms",
+        traffic.latencies.count(),
+        p99
+    ))
+}
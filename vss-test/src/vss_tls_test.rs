@@ -0,0 +1,372 @@
+//! VSS TLS Integration Test Binary
+//!
+//! Runs VSS over HTTPS like production does, through the `vss-tls` front of
+//! the compose stack (see `vss_test::tls`), and checks the certificates a
+//! client must refuse are refused, and the client certificates the front must
+//! refuse as well. It generates a CA and the server and client
+//! certificates first if `../tls` has none, or always with `--regenerate`,
+//! and starts `vss-tls` with them:
+//!
+//!   cargo run --bin vss_tls_test
+//!
+//! A client trusting the CA must be able to put, get and be refused with a bad
+//! token over HTTPS. A client without the CA, a certificate for another host
+//! name and an expired certificate must each fail the handshake, while the
+//! same port answers a client that checks nothing, so the failure is down to
+//! the certificate and not a port that is not listening.
+//!
+//! The mutual TLS port must take a put and a get from a client showing the
+//! client certificate the CA issued, and still refuse it a bad token. A put
+//! without a client certificate, or with one from a CA the front does not
+//! trust, must fail the handshake or get nginx's 400 for the certificate, and
+//! the key must not be there after, while the same put with the issued
+//! certificate goes through.
+//!
+//! The rest of the suites run over the same front with `--tls`, e.g.
+//! `cargo run --bin vss_jwt_test -- --tls`.
+
+use std::time::Duration;
+
+use clap::Parser;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, SuiteArgs};
+use test_harness::test_cases;
+use vss_client::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+use vss_test::cli::ConfigArgs;
+use vss_test::config;
+use vss_test::fixtures::unique_id;
+use vss_test::tls::{self, CLIENT, EXPIRED_URL, MTLS_URL, ROGUE_CLIENT, VALID_URL, WRONG_HOST_URL};
+use vss_test::vss::Vss;
+use vss_test::wait_for;
+
+const SUBJECT: &str = "vss-tls-test";
+const KEY: &str = "over-tls";
+// vss-tls and vss-server behind it coming up
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// What nginx answers with `ssl_verify_client on` when the client shows no
+// certificate, or one it cannot verify, once the handshake went through
+const NO_CLIENT_CERTIFICATE: &str = "No required SSL certificate was sent";
+const BAD_CLIENT_CERTIFICATE: &str = "The SSL certificate error";
+
+#[derive(Parser)]
+#[command(about = "Check VSS over HTTPS and that bad certificates are refused")]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    /// Generate a new CA and certificates even if there are some
+    #[arg(long)]
+    regenerate: bool,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// Clients trusting the generated CA, of the HTTPS port and of the mutual
+/// TLS port with the client certificate the CA issued.
+struct Fronts {
+    https: Vss,
+    mtls: Vss,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut run = Run::start();
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let cases = test_cases!(Fronts;
+        test_round_trip_over_https,
+        #[tags(Auth)] test_bad_token_refused_over_https,
+        test_untrusted_ca_refused,
+        test_hostname_mismatch_refused,
+        test_expired_certificate_refused,
+        test_client_certificate_accepted,
+        #[tags(Auth)] test_bad_token_refused_with_client_certificate,
+        test_missing_client_certificate_refused,
+        test_untrusted_client_certificate_refused,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    // Starts the front, which listing the cases has no need for
+    if let Err(e) = setup(&cli, &mut run) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("VSS TLS Integration Test");
+    report.say("");
+
+    let ready = wait_for("VSS over HTTPS", READY_TIMEOUT, POLL_INTERVAL, || async {
+        let vss = https_client(VALID_URL)?;
+        Ok(vss.find_object(&unique_id("tls-ready"), KEY).await.ok())
+    })
+    .await;
+    if let Err(e) = ready {
+        let e = format!("{} not ready: {}", tls::TLS_SERVICE, e);
+        report.error(&e);
+        std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+    }
+    let fronts = match https_client(VALID_URL).and_then(|https| {
+        Ok(Fronts {
+            https,
+            mtls: mtls_client(Some(CLIENT))?,
+        })
+    }) {
+        Ok(fronts) => fronts,
+        Err(e) => {
+            let e = format!("Failed to set up VSS clients: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+        }
+    };
+    report.say(&format!("Testing against {}", VALID_URL));
+    report.say("");
+
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &fronts, &cases)
+    })
+    .await;
+
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    let code = run
+        .finish(passed, failed, Some(report.failed_cases()))
+        .await;
+    std::process::exit(code);
+}
+
+/// Generate the certificates if asked, then settle the settings with the
+/// front up and its CA trusted.
+fn setup(cli: &Cli, run: &mut Run) -> Result<(), String> {
+    if cli.regenerate {
+        tls::prepare(true)?;
+    }
+    let config = ConfigArgs {
+        tls: true,
+        ..cli.config.clone()
+    };
+    config.apply()?;
+    if config::get().remote() {
+        return Err(
+            "vss_tls_test checks the compose TLS front; it cannot run against a remote target"
+                .to_string(),
+        );
+    }
+    run.notify(config::get().notify.notifier()?);
+    Ok(())
+}
+
+/// A client of `url` trusting the generated CA.
+fn https_client(url: &str) -> Result<Vss, String> {
+    Vss::new(url, &config::get().vss.signing_key_path, SUBJECT)?.with_timeout(REQUEST_TIMEOUT)
+}
+
+/// A client of the mutual TLS port showing the client certificate `identity`,
+/// or none.
+fn mtls_client(identity: Option<&str>) -> Result<Vss, String> {
+    let mut builder = config::http_client_builder(REQUEST_TIMEOUT)?;
+    if let Some(identity) = identity {
+        builder = builder.identity(tls::client_identity(identity)?);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {:?}", e))?;
+    Ok(https_client(MTLS_URL)?.with_client(client))
+}
+
+async fn test_round_trip_over_https(Fronts { https, .. }: &Fronts) -> Outcome {
+    let store = unique_id("tls");
+    https
+        .put_object(&store, KEY, KEY.as_bytes().to_vec())
+        .await?;
+    let value = https.get_object(&store, KEY).await?;
+    if value != KEY.as_bytes() {
+        return Err(format!(
+            "{} reads back as {:?}",
+            KEY,
+            String::from_utf8_lossy(&value)
+        ));
+    }
+    if let Some(value) = https.find_object(&store, "missing").await? {
+        return Err(format!(
+            "a key never written reads back as {:?}",
+            String::from_utf8_lossy(&value)
+        ));
+    }
+    Ok("put and get over HTTPS".to_string())
+}
+
+async fn test_bad_token_refused_over_https(_: &Fronts) -> Outcome {
+    let request = GetObjectRequest {
+        store_id: unique_id("tls"),
+        key: KEY.to_string(),
+    };
+    let vss = Vss::with_token(VALID_URL, "not-a-jwt")?;
+    let (status, _) = vss.request("getObject", &request).await?;
+    if status != 401 && status != 403 {
+        return Err(format!("a bad token got {} instead of 401 or 403", status));
+    }
+    Ok(format!("refused with {}", status))
+}
+
+async fn test_untrusted_ca_refused(_: &Fronts) -> Outcome {
+    refused(VALID_URL, test_harness::http_client(REQUEST_TIMEOUT)).await
+}
+
+async fn test_hostname_mismatch_refused(_: &Fronts) -> Outcome {
+    refused(WRONG_HOST_URL, config::http_client(REQUEST_TIMEOUT)).await
+}
+
+async fn test_expired_certificate_refused(_: &Fronts) -> Outcome {
+    refused(EXPIRED_URL, config::http_client(REQUEST_TIMEOUT)).await
+}
+
+/// A request to `url` with `client` must fail on the certificate, while the
+/// same request with certificate checks off gets an answer.
+async fn refused(url: &str, client: Result<reqwest::Client, String>) -> Outcome {
+    let request = GetObjectRequest {
+        store_id: unique_id("tls"),
+        key: KEY.to_string(),
+    };
+    let unchecked = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .danger_accept_invalid_certs(true)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {:?}", e))?;
+    let vss = https_client(url)?;
+    vss.clone()
+        .with_client(unchecked)
+        .request("getObject", &request)
+        .await
+        .map_err(|e| {
+            format!(
+                "{} does not answer even without certificate checks: {}",
+                url, e
+            )
+        })?;
+    match vss
+        .with_client(client?)
+        .request("getObject", &request)
+        .await
+    {
+        Ok((status, _)) => Err(format!("the request went through with {}", status)),
+        Err(e) if e.to_lowercase().contains("certificate") => {
+            Ok("refused on the certificate".to_string())
+        }
+        Err(e) => Err(format!("failed, but not on the certificate: {}", e)),
+    }
+}
+
+async fn test_client_certificate_accepted(Fronts { mtls, .. }: &Fronts) -> Outcome {
+    let store = unique_id("mtls");
+    mtls.put_object(&store, KEY, KEY.as_bytes().to_vec())
+        .await?;
+    let value = mtls.get_object(&store, KEY).await?;
+    if value != KEY.as_bytes() {
+        return Err(format!(
+            "{} reads back as {:?}",
+            KEY,
+            String::from_utf8_lossy(&value)
+        ));
+    }
+    Ok("put and get with the client certificate".to_string())
+}
+
+async fn test_bad_token_refused_with_client_certificate(Fronts { mtls, .. }: &Fronts) -> Outcome {
+    let request = GetObjectRequest {
+        store_id: unique_id("mtls"),
+        key: KEY.to_string(),
+    };
+    let vss = mtls.authenticated_as("not-a-jwt");
+    let (status, _) = vss.request("getObject", &request).await?;
+    if status != 401 && status != 403 {
+        return Err(format!(
+            "a bad token with a client certificate got {} instead of 401 or 403",
+            status
+        ));
+    }
+    Ok(format!("refused with {}", status))
+}
+
+async fn test_missing_client_certificate_refused(fronts: &Fronts) -> Outcome {
+    client_certificate_refused(fronts, None, NO_CLIENT_CERTIFICATE).await
+}
+
+async fn test_untrusted_client_certificate_refused(fronts: &Fronts) -> Outcome {
+    client_certificate_refused(fronts, Some(ROGUE_CLIENT), BAD_CLIENT_CERTIFICATE).await
+}
+
+/// A put to the mutual TLS port showing the client certificate `identity`, or
+/// none, must fail the handshake or get nginx's 400 with `rejection` in the
+/// body, and leave nothing behind. The same put with the client certificate
+/// the CA issued must then go through, so the refusal is down to the
+/// certificate and not the request.
+async fn client_certificate_refused(
+    Fronts { mtls, .. }: &Fronts,
+    identity: Option<&str>,
+    rejection: &str,
+) -> Outcome {
+    let store = unique_id("mtls");
+    let request = PutObjectRequest {
+        store_id: store.clone(),
+        global_version: None,
+        transaction_items: vec![KeyValue {
+            key: KEY.to_string(),
+            version: 0,
+            value: KEY.as_bytes().to_vec(),
+        }],
+        delete_items: vec![],
+    };
+    let refusal = match mtls_client(identity)?.request("putObjects", &request).await {
+        Ok((400, body)) if String::from_utf8_lossy(&body).contains(rejection) => {
+            format!("refused with 400 {:?}", rejection)
+        }
+        Ok((status, body)) => {
+            return Err(format!(
+                "the put got {} instead of a refused handshake or nginx's 400 {:?}: {:?}",
+                status,
+                rejection,
+                String::from_utf8_lossy(&body)
+            ))
+        }
+        Err(e) if is_handshake_failure(&e) => format!("refused in the handshake: {}", e),
+        Err(e) => return Err(format!("failed, but not in the handshake: {}", e)),
+    };
+    if let Some(value) = mtls.find_object(&store, KEY).await? {
+        return Err(format!(
+            "{}, yet {} reads back as {:?}",
+            refusal,
+            KEY,
+            String::from_utf8_lossy(&value)
+        ));
+    }
+    let (status, _) = mtls.request("putObjects", &request).await?;
+    if !(200..300).contains(&status) {
+        return Err(format!(
+            "{}, but the trusted client certificate got {} for the same put",
+            refusal, status
+        ));
+    }
+    Ok(format!(
+        "{}; the trusted client certificate got {}",
+        refusal, status
+    ))
+}
+
+/// Whether `error` is a TLS handshake the front broke off over the client
+/// certificate, rather than a timeout or a port that is not listening.
+fn is_handshake_failure(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("handshake") || error.contains("certificate")
+}
@@ -0,0 +1,259 @@
+//! Watchtower Breach Integration Test Binary
+//!
+//! An attacker node (`lnd4`) broadcasts a revoked commitment while the victim
+//! (`lnd5`) is offline; the watchtower on `lnd` must punish the breach on the
+//! victim's behalf
+
+use clap::Parser;
+use harness_docker::matrix;
+use harness_docker::profile;
+use harness_docker::summary::{Run, ENVIRONMENT_UNAVAILABLE, HARNESS_ERROR};
+use harness_docker::teardown::Teardown;
+use std::path::PathBuf;
+use std::time::Duration;
+use test_harness::case::{self, Outcome};
+use test_harness::cli::{Filter, HarnessArgs, SuiteArgs};
+use test_harness::test_cases;
+use vss_test::bitcoind::Bitcoind;
+use vss_test::compose::{copy_from_service, copy_to_service, stop_service};
+use vss_test::graph::{start_node, GRAPH_NODES};
+use vss_test::lnd::{Channel, Lnd};
+use vss_test::wait_for;
+
+// Tower, victim and breacher; checked against `--profile`
+const REQUIRED_SERVICES: [&str; 4] = ["bitcoind", "lnd", "lnd4", "lnd5"];
+
+// Where the attacker keeps its channel state inside the container
+const CHANNEL_DB_PATH: &str = "/home/lnd/.lnd/data/graph/regtest/channel.db";
+const TOWER_ADDRESS: &str = "lnd:9911";
+
+// Victim -> attacker channel; the revoked state gives the attacker PUSH_SAT
+const CHANNEL_CAPACITY_SAT: u64 = 1_000_000;
+const PUSH_SAT: u64 = 400_000;
+const PAYBACK_SAT: u64 = 300_000;
+// Outputs of this size are anchors, which anyone may sweep
+const ANCHOR_SAT: u64 = 330;
+// Upper bound on commitment and justice fees taken from the victim's funds
+const MAX_JUSTICE_FEES_SAT: i64 = 30_000;
+
+const BACKUP_TIMEOUT: Duration = Duration::from_secs(60);
+const CHANNEL_TIMEOUT: Duration = Duration::from_secs(60);
+const JUSTICE_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watchtower Breach Integration Test
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    suite: SuiteArgs,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+/// The chain and the node running the watchtower.
+struct Breach {
+    bitcoind: Bitcoind,
+    tower: Lnd,
+}
+
+#[tokio::main]
+async fn main() {
+    let run = Run::start();
+    let cli = Cli::parse();
+    harness_docker::init(&cli.harness);
+    if let Err(e) = cli.suite.init(&cli.filter) {
+        eprintln!("{}", e);
+        std::process::exit(run.abort(HARNESS_ERROR, &e).await);
+    }
+    let cases = test_cases!(Breach;
+        // Stops the victim and rolls the attacker's channel state back
+        #[exclusive] #[tags(Destructive, Docker)] test_watchtower_punishes_breach,
+    );
+    if cli.filter.list {
+        cli.filter.print_list(&case::names(&cases));
+        return;
+    }
+    let report = cli.suite.reporter();
+    report.say("===");
+    report.say("Watchtower Breach Integration Test");
+    if let Some(code) = matrix::run_if_requested(&REQUIRED_SERVICES).await {
+        std::process::exit(code);
+    }
+    // Held for the whole run; an isolated environment is removed when it drops
+    let environment = match profile::select(&REQUIRED_SERVICES).await {
+        Ok(environment) => environment,
+        Err(e) => {
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+
+    let teardown = match Teardown::local() {
+        Ok(teardown) => teardown,
+        Err(e) => {
+            let e = format!("Failed to connect to Docker: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    teardown.handle_ctrl_c();
+    for spec in &GRAPH_NODES[2..] {
+        if let Err(e) = teardown.stop_on_exit(spec.service).await {
+            let e = format!("Failed to inspect {}: {}", spec.service, e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    }
+    teardown.remove_path_on_exit(snapshot_path());
+
+    let bitcoind = Bitcoind::local();
+    let tower = match Lnd::node_a() {
+        Ok(node) => node,
+        Err(e) => {
+            let e = format!("Failed to set up LND client: {}", e);
+            report.error(&e);
+            std::process::exit(run.abort(ENVIRONMENT_UNAVAILABLE, &e).await);
+        }
+    };
+    report.say("");
+
+    let breach = Breach { bitcoind, tower };
+    let (passed, mut failed) = case::iterate(&cli.suite, &report, || {
+        case::run_cases(&report, cli.suite.jobs, &cli.filter, &breach, &cases)
+    })
+    .await;
+
+    teardown.run().await;
+    failed += report.check_perf();
+    report.summary(passed, failed);
+    report.push_metrics(passed, failed).await;
+    report.export_traces().await;
+    // Before the environment goes, so the summary still sees its containers
+    let code = run.finish(passed, failed, Some(report.failed_cases())).await;
+    // exit() below skips destructors
+    drop(environment);
+    std::process::exit(code);
+}
+
+/// Host copy of the attacker's pre-revocation channel state.
+fn snapshot_path() -> PathBuf {
+    std::env::temp_dir().join("vss-test-breach-channel.db")
+}
+
+async fn wait_channel_by_funding(node: &Lnd, funding_txid: &str) -> Result<Channel, String> {
+    wait_for("breach channel to become active", CHANNEL_TIMEOUT, POLL_INTERVAL, || async {
+        let channels = node.list_channels().await?;
+        Ok(channels
+            .into_iter()
+            .find(|c| c.active && c.funding_txid() == funding_txid))
+    })
+    .await
+}
+
+/// Wait until the tower client has no state updates left to upload.
+async fn wait_backups_flushed(victim: &Lnd) -> Result<u32, String> {
+    wait_for("tower backups to flush", BACKUP_TIMEOUT, POLL_INTERVAL, || async {
+        let stats = victim.tower_client_stats().await?;
+        Ok((stats.num_backups > 0 && stats.num_pending_backups == 0).then_some(stats.num_backups))
+    })
+    .await
+}
+
+async fn test_watchtower_punishes_breach(Breach { bitcoind, tower }: &Breach) -> Outcome {
+    let (attacker_spec, victim_spec) = (GRAPH_NODES[2], GRAPH_NODES[3]);
+    let attacker = start_node(&attacker_spec).await?;
+    let victim = start_node(&victim_spec).await?;
+    let attacker_pubkey = attacker.get_info().await?.identity_pubkey;
+
+    let (tower_pubkey, _) = tower.tower_info().await?;
+    victim.add_tower(&tower_pubkey, TOWER_ADDRESS).await?;
+
+    victim
+        .ensure_wallet_funds(bitcoind, CHANNEL_CAPACITY_SAT * 2)
+        .await?;
+    victim
+        .connect_peer(&attacker_pubkey, attacker_spec.p2p_host)
+        .await?;
+    let point = victim
+        .open_channel(&attacker_pubkey, CHANNEL_CAPACITY_SAT, PUSH_SAT)
+        .await?;
+    bitcoind.mine(6).await?;
+    victim.wait_synced(bitcoind).await?;
+    wait_channel_by_funding(&victim, &point.funding_txid()).await?;
+
+    // Snapshot the attacker while it still holds PUSH_SAT
+    stop_service(attacker_spec.service)?;
+    let snapshot = snapshot_path().to_string_lossy().to_string();
+    copy_from_service(attacker_spec.service, CHANNEL_DB_PATH, &snapshot)?;
+    let attacker = start_node(&attacker_spec).await?;
+    attacker.wait_synced(bitcoind).await?;
+    wait_channel_by_funding(&attacker, &point.funding_txid()).await?;
+
+    // Move most of it back, revoking the snapshotted state
+    let invoice = victim.add_invoice(PAYBACK_SAT, "vss-test breach").await?;
+    let payment = attacker.pay_invoice(&invoice.payment_request).await?;
+    if !payment.payment_error.is_empty() {
+        return Err(format!("Payback to victim failed: {}", payment.payment_error));
+    }
+    let backups = wait_backups_flushed(&victim).await?;
+    let channel = wait_channel_by_funding(&victim, &point.funding_txid()).await?;
+    let victim_onchain = victim.wallet_balance().await?.confirmed_balance;
+
+    // Victim goes offline; the attacker rolls back and broadcasts the revoked state
+    stop_service(victim_spec.service)?;
+    stop_service(attacker_spec.service)?;
+    copy_to_service(&snapshot, attacker_spec.service, CHANNEL_DB_PATH)?;
+    let attacker = start_node(&attacker_spec).await?;
+    let breach_txid = attacker.close_channel(&channel.channel_point, true).await?;
+    bitcoind.mine(1).await?;
+    let breach_height = bitcoind
+        .get_tx_height(&breach_txid)
+        .await?
+        .ok_or_else(|| format!("Revoked commitment {} did not confirm", breach_txid))?;
+
+    // Only the tower can touch non-anchor outputs this early: the attacker's
+    // output is CSV-locked and the victim is offline
+    let values = bitcoind.output_values(&breach_txid).await?;
+    let justice = wait_for("justice transaction to confirm", JUSTICE_TIMEOUT, POLL_INTERVAL, || async {
+        let spenders = bitcoind.find_spenders(&breach_txid, breach_height).await?;
+        let justice = spenders
+            .into_iter()
+            .find(|s| s.vouts.iter().any(|v| values.get(*v as usize).copied().unwrap_or(0) > ANCHOR_SAT));
+        if justice.is_none() {
+            bitcoind.mine(1).await?;
+        }
+        Ok(justice)
+    })
+    .await?;
+
+    // Back online, the victim sees the breach and holds the whole channel
+    let victim = start_node(&victim_spec).await?;
+    let expected_sat = victim_onchain + channel.local_balance;
+    let recovered = wait_for("victim funds to settle", JUSTICE_TIMEOUT, POLL_INTERVAL, || async {
+        victim.wait_synced(bitcoind).await?;
+        let breached = victim
+            .closed_channels()
+            .await?
+            .into_iter()
+            .any(|c| c.channel_point == channel.channel_point && c.close_type == "BREACH_CLOSE");
+        let balance = victim.wallet_balance().await?;
+        if breached
+            && balance.unconfirmed_balance == 0
+            && balance.confirmed_balance >= expected_sat - MAX_JUSTICE_FEES_SAT
+        {
+            return Ok(Some(balance.confirmed_balance));
+        }
+        bitcoind.mine(1).await?;
+        Ok(None)
+    })
+    .await?;
+    Ok(format!(
+        "{} backups, breach {} punished by {}, {} sat recovered",
+        backups,
+        breach_txid,
+        justice.txid,
+        recovered - victim_onchain
+    ))
+}
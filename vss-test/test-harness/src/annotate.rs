@@ -0,0 +1,90 @@
+//! GitHub Actions annotations for failures
+//!
+//! With `--github-annotations`, or on its own when `GITHUB_ACTIONS` is set,
+//! every failed case is also written as an `::error` workflow command and
+//! every warning (a flaky case, a latency regression let through with
+//! `--perf-warn-only`) as `::warning`. The Actions UI shows them on the run's
+//! summary page and inline on the PR's diff, no extra tooling needed.
+//!
+//! An annotation points at the function named like the case in the suite's
+//! source, `src/<binary>.rs` under the working directory, found by looking
+//! for `fn <name>`; variants such as `test_x (5% loss)` point at `test_x`.
+//! The path is given relative to `GITHUB_WORKSPACE`, which is what GitHub
+//! matches against the diff. Cases without such a function, like the
+//! `perf ...` ones, are annotated without a location.
+
+use std::path::{Path, PathBuf};
+
+const GITHUB_ACTIONS_ENV: &str = "GITHUB_ACTIONS";
+const GITHUB_WORKSPACE_ENV: &str = "GITHUB_WORKSPACE";
+
+/// Whether the run is a GitHub Actions job.
+pub fn in_github_actions() -> bool {
+    std::env::var(GITHUB_ACTIONS_ENV).is_ok_and(|value| value == "true")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// The workflow command annotating case `name` of suite `binary` at `level`
+/// with `message`.
+pub fn annotation(level: Level, binary: &str, name: &str, message: &str) -> String {
+    let command = match level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+    };
+    let mut properties = Vec::new();
+    if let Some((file, line)) = locate(binary, name) {
+        properties.push(format!("file={}", property(&file)));
+        properties.push(format!("line={}", line));
+    }
+    properties.push(format!(
+        "title={}",
+        property(&format!("{}: {}", binary, name))
+    ));
+    format!("::{} {}::{}", command, properties.join(","), data(message))
+}
+
+/// The file and line of the function case `name` runs, if the suite's source
+/// is at hand.
+fn locate(binary: &str, name: &str) -> Option<(String, usize)> {
+    let function = name.split(" (").next()?.trim();
+    if function.is_empty() || function.contains(char::is_whitespace) {
+        return None;
+    }
+    let source = std::env::current_dir()
+        .ok()?
+        .join("src")
+        .join(format!("{}.rs", binary));
+    let text = std::fs::read_to_string(&source).ok()?;
+    let needle = format!("fn {}(", function);
+    let line = text.lines().position(|line| line.contains(&needle))? + 1;
+    Some((workspace_relative(&source), line))
+}
+
+/// `path` relative to the Actions workspace, or as is outside Actions.
+fn workspace_relative(path: &Path) -> String {
+    let relative = std::env::var_os(GITHUB_WORKSPACE_ENV)
+        .map(PathBuf::from)
+        .and_then(|workspace| path.strip_prefix(workspace).ok().map(Path::to_path_buf));
+    relative
+        .unwrap_or_else(|| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// `text` escaped for the message of a workflow command.
+fn data(text: &str) -> String {
+    text.trim_end()
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// `text` escaped for a property of a workflow command.
+fn property(text: &str) -> String {
+    data(text).replace(':', "%3A").replace(',', "%2C")
+}
@@ -0,0 +1,150 @@
+//! Checks that say what differed, not just that something did
+//!
+//! Each helper returns `Ok(())` or an `Err` that lays expected and actual
+//! side by side, so a failed case reads as a diff instead of "got 500":
+//!
+//! - `assert_status` shows the status wanted, the one received and the
+//!   start of the body (credentials redacted), where servers put the reason
+//! - `assert_proto_eq` compares two decoded messages, or any other
+//!   `PartialEq + Debug` values, and diffs their pretty-printed forms
+//! - `assert_json_matches` compares a JSON answer with a pattern and lists
+//!   every mismatch by its JSON pointer
+//!
+//! The errors are plain strings, to be returned with `?` from a case body
+//! or prefixed with what was being checked.
+
+use std::fmt::Debug;
+
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::{redact, snapshot};
+
+/// Matches any value in an `assert_json_matches` pattern, as long as the
+/// field is there.
+pub const ANY: &str = "<any>";
+
+// How much of a body a status mismatch shows
+const BODY_EXCERPT_CHARS: usize = 500;
+
+/// `actual` is one of `expected`; `body` is what came with it.
+pub fn assert_status(actual: u16, expected: &[u16], body: &[u8]) -> Result<(), String> {
+    if expected.contains(&actual) {
+        return Ok(());
+    }
+    let wanted: Vec<String> = expected.iter().map(|&status| describe(status)).collect();
+    let mut message = format!(
+        "Unexpected status\n  expected: {}\n  actual:   {}",
+        wanted.join(" or "),
+        describe(actual)
+    );
+    if !body.is_empty() {
+        message.push_str(&format!("\n  body:     {}", excerpt(&redact::body(body))));
+    }
+    Err(message)
+}
+
+/// `actual` equals `expected`; if not, the error diffs their `{:#?}` forms,
+/// `-` for lines only in `expected` and `+` for lines only in `actual`.
+pub fn assert_proto_eq<T: PartialEq + Debug>(expected: &T, actual: &T) -> Result<(), String> {
+    if expected == actual {
+        return Ok(());
+    }
+    Err(format!(
+        "Values differ (- expected, + actual):\n{}",
+        snapshot::diff(&format!("{:#?}", expected), &format!("{:#?}", actual))
+    ))
+}
+
+/// `actual` matches `pattern`: objects need the pattern's keys (extra keys
+/// are fine), arrays need the same length with every element matching,
+/// the string `ANY` matches anything, and other values must be equal.
+pub fn assert_json_matches(actual: &Value, pattern: &Value) -> Result<(), String> {
+    let mut mismatches = Vec::new();
+    compare("", actual, pattern, &mut mismatches);
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "JSON does not match ({} {}):\n{}",
+        mismatches.len(),
+        if mismatches.len() == 1 {
+            "difference"
+        } else {
+            "differences"
+        },
+        mismatches
+            .iter()
+            .map(|m| format!("  {}", m))
+            .collect::<Vec<_>>()
+            .join("\n")
+    ))
+}
+
+fn compare(pointer: &str, actual: &Value, pattern: &Value, mismatches: &mut Vec<String>) {
+    match (pattern, actual) {
+        (Value::String(any), _) if any == ANY => {}
+        (Value::Object(wanted), Value::Object(found)) => {
+            for (key, pattern) in wanted {
+                let pointer = format!("{}/{}", pointer, escape(key));
+                match found.get(key) {
+                    Some(actual) => compare(&pointer, actual, pattern, mismatches),
+                    None => mismatches.push(format!("{}: missing, expected {}", pointer, pattern)),
+                }
+            }
+        }
+        (Value::Array(wanted), Value::Array(found)) if wanted.len() != found.len() => {
+            mismatches.push(format!(
+                "{}: expected {} elements, got {}",
+                root(pointer),
+                wanted.len(),
+                found.len()
+            ));
+        }
+        (Value::Array(wanted), Value::Array(found)) => {
+            for (i, (pattern, actual)) in wanted.iter().zip(found).enumerate() {
+                compare(&format!("{}/{}", pointer, i), actual, pattern, mismatches);
+            }
+        }
+        _ if pattern != actual => {
+            mismatches.push(format!(
+                "{}: expected {}, got {}",
+                root(pointer),
+                pattern,
+                actual
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// A JSON pointer reference token for `key` (RFC 6901).
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn root(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "(root)"
+    } else {
+        pointer
+    }
+}
+
+fn describe(status: u16) -> String {
+    match StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+    {
+        Some(reason) => format!("{} {}", status, reason),
+        None => status.to_string(),
+    }
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(BODY_EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}... ({} bytes in all)", &text[..end], text.len()),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,258 @@
+//! Test cases as values
+//!
+//! A suite describes each check as a `TestCase`: a name and an async body that
+//! resolves to a detail for the result line, or to the error. Starting the
+//! result line, timing the body, reporting the outcome and counting passes and
+//! failures is left to `run_cases`, so a body holds only the scenario. Async
+//! functions taking the suite's context become cases through `test_cases!`;
+//! cases built at run time, such as one per parameter, implement `TestCase`.
+//! Either way a case can carry `Tag`s for `--include` and `--exclude`, and
+//! name the `setup` steps it needs. `run_cases_with` also hands each case to
+//! the suite as the runner will run it, for what goes around every case, such
+//! as a resource monitor section or logs attached to a failure. A suite runs
+//! its cases through `iterate`, which repeats them as `--repeat` asks or
+//! loops them for `--soak`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+
+use crate::cli::{Filter, SuiteArgs};
+use crate::report::Reporter;
+use crate::runner::{self, Case, Tag};
+use crate::setup::Setup;
+use crate::soak::Soak;
+
+/// A detail for the result line, or why the case failed.
+pub type Outcome = Result<String, String>;
+pub type CaseFuture<'a> = Pin<Box<dyn Future<Output = Outcome> + 'a>>;
+
+/// A check run against the suite's context `C`: its clients, URLs and settings.
+pub trait TestCase<C: ?Sized> {
+    fn name(&self) -> String;
+
+    /// Whether the case changes server state other cases rely on, and so runs alone.
+    fn exclusive(&self) -> bool {
+        false
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        Vec::new()
+    }
+
+    /// Setup steps that must have succeeded before the case runs.
+    fn needs(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn run<'a>(&'a self, ctx: &'a C) -> CaseFuture<'a>;
+}
+
+/// A case running an async function; see `test_cases!`.
+pub struct FnCase<C: ?Sized> {
+    name: &'static str,
+    exclusive: bool,
+    tags: Vec<Tag>,
+    needs: Vec<&'static str>,
+    check: for<'a> fn(&'a C) -> CaseFuture<'a>,
+}
+
+impl<C: ?Sized> FnCase<C> {
+    pub fn new(name: &'static str, check: for<'a> fn(&'a C) -> CaseFuture<'a>) -> Self {
+        Self {
+            name,
+            exclusive: false,
+            tags: Vec::new(),
+            needs: Vec::new(),
+            check,
+        }
+    }
+
+    /// Run this case with no other case alongside.
+    pub fn exclusive(mut self) -> Self {
+        self.exclusive = true;
+        self
+    }
+
+    /// Add `tags` to the case's tags.
+    pub fn tagged(mut self, tags: &[Tag]) -> Self {
+        self.tags.extend_from_slice(tags);
+        self
+    }
+
+    /// Run the case only once the setup steps `steps` have succeeded.
+    pub fn needs(mut self, steps: &[&'static str]) -> Self {
+        self.needs.extend_from_slice(steps);
+        self
+    }
+}
+
+impl<C: ?Sized> TestCase<C> for FnCase<C> {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        self.tags.clone()
+    }
+
+    fn needs(&self) -> Vec<&'static str> {
+        self.needs.clone()
+    }
+
+    fn run<'a>(&'a self, ctx: &'a C) -> CaseFuture<'a> {
+        (self.check)(ctx)
+    }
+}
+
+/// The cases running the given `async fn(&Ctx) -> Outcome`s, named after them
+/// and in order. `#[exclusive]` before a function makes its case run alone,
+/// `#[tags(...)]` tags it with the named `Tag` variants and `#[needs(...)]`
+/// makes it wait for the setup steps named by the given constants:
+///
+/// ```text
+/// let cases = test_cases!(Ctx;
+///     #[tags(Vss)] test_health,
+///     #[tags(Auth, Vss)] #[needs(SIGNED_IN_STEP)] test_login,
+///     #[exclusive] #[tags(Vss, Destructive)] test_restart,
+/// );
+/// ```
+#[macro_export]
+macro_rules! test_cases {
+    (@exclusive $case:expr) => {
+        $case.exclusive()
+    };
+    (@tags $case:expr, $($tag:ident),*) => {
+        $case.tagged(&[$($crate::runner::Tag::$tag),*])
+    };
+    (@needs $case:expr, $($step:ident),*) => {
+        $case.needs(&[$($step),*])
+    };
+    ($ctx:ty; $($(#[$mark:ident $(($($arg:ident),*))?])* $check:ident),* $(,)?) => {{
+        let mut cases: Vec<Box<dyn $crate::case::TestCase<$ctx>>> = Vec::new();
+        $({
+            fn boxed(ctx: &$ctx) -> $crate::case::CaseFuture<'_> {
+                Box::pin($check(ctx))
+            }
+            let case = $crate::case::FnCase::new(stringify!($check), boxed);
+            $(let case = $crate::test_cases!(@$mark case $(, $($arg),*)?);)*
+            cases.push(Box::new(case));
+        })*
+        cases
+    }};
+}
+
+/// Names of `cases` with their tags, for `--list`.
+pub fn names<C: ?Sized>(cases: &[Box<dyn TestCase<C>>]) -> Vec<(String, Vec<Tag>)> {
+    cases
+        .iter()
+        .map(|case| (case.name(), case.tags()))
+        .collect()
+}
+
+/// Run the `cases` that `filter` selects against `ctx`, at most `jobs` at a
+/// time, and return how many passed and failed.
+pub async fn run_cases<C: ?Sized>(
+    report: &Reporter,
+    jobs: usize,
+    filter: &Filter,
+    ctx: &C,
+    cases: &[Box<dyn TestCase<C>>],
+) -> (usize, usize) {
+    run_cases_with(report, jobs, Setup::new(), filter, ctx, cases, |_, case| {
+        case
+    })
+    .await
+}
+
+/// Run the `cases` that `filter` selects as `run_cases` does, after the
+/// steps of `setup` they need (see `runner::run_with_setup`). Each case goes
+/// through `wrap` first, with the runner's case for it to build on.
+pub async fn run_cases_with<'a, C: ?Sized>(
+    report: &'a Reporter,
+    jobs: usize,
+    setup: Setup<'a>,
+    filter: &Filter,
+    ctx: &'a C,
+    cases: &'a [Box<dyn TestCase<C>>],
+    wrap: impl Fn(&dyn TestCase<C>, Case<'a>) -> Case<'a>,
+) -> (usize, usize) {
+    let cases = cases
+        .iter()
+        .map(|case| {
+            let name = case.name();
+            let check = case.run(ctx);
+            let runnable = Case::new(
+                &case.name(),
+                async move { timed(report, &name, check).await },
+            )
+            .tagged(&case.tags())
+            .needs(&case.needs());
+            let runnable = if case.exclusive() {
+                runnable.exclusive()
+            } else {
+                runnable
+            };
+            wrap(case.as_ref(), runnable)
+        })
+        .collect();
+    runner::run_with_setup(report, jobs, setup, filter.retain(cases)).await
+}
+
+/// Run the cases `iteration` runs as many times as `suite` asks with
+/// `--repeat`, or over and over until its `--soak` is up, announcing each
+/// iteration on `report`. A soak run ends with the soak report. Returns how
+/// many passed and failed over all iterations.
+pub async fn iterate<F, Fut>(
+    suite: &SuiteArgs,
+    report: &Reporter,
+    mut iteration: F,
+) -> (usize, usize)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = (usize, usize)>,
+{
+    let (mut passed, mut failed) = (0, 0);
+    let repeat = suite.repeat.max(1) as usize;
+    let mut soak = suite.soak.map(Soak::new);
+    for number in 1.. {
+        match &mut soak {
+            Some(soak) if soak.running() => {
+                soak.begin();
+                report.say(&format!(
+                    "--- Soak iteration {} ({})",
+                    number,
+                    soak.progress()
+                ));
+            }
+            Some(_) => break,
+            None if number > repeat => break,
+            None => report.iteration(number),
+        }
+        let (ok, not_ok) = iteration().await;
+        if let Some(soak) = &mut soak {
+            soak.end(ok, not_ok);
+        }
+        passed += ok;
+        failed += not_ok;
+    }
+    if let Some(soak) = &soak {
+        report.soak(soak);
+    }
+    (passed, failed)
+}
+
+/// Run `check` as case `name`: start its result line, time it and report how
+/// it went. Returns whether it passed.
+pub async fn timed(report: &Reporter, name: &str, check: impl Future<Output = Outcome>) -> bool {
+    report.begin(name);
+    let start = Instant::now();
+    match check.await {
+        Ok(detail) => report.ok(start.elapsed(), &detail),
+        Err(e) => report.failed(start.elapsed(), &e),
+    }
+}
@@ -0,0 +1,332 @@
+//! Recording HTTP traffic to cassettes and replaying it without a stack
+//!
+//! With `--record <file>` every HTTP call made through `send` (and the
+//! clients built on `cassette::send`, such as the VSS client) is written to
+//! a JSON cassette as it completes: method, URL, headers and body of the
+//! request, status, headers and body of the response. Credentials are
+//! redacted as in verbose dumps, so cassettes can be checked in.
+//!
+//! With `--replay <file>` nothing goes over the network. Each call gets the
+//! response recorded for it, matched by method and URL path: the n-th call
+//! to `POST /vss/getObject` gets the n-th recorded one. Hosts, ports and
+//! queries are ignored, as they change from stack to stack, and so are
+//! request bodies, which carry run-specific store ids and tokens. Record and
+//! replay with one job (`-j 1`) so calls come in the same order. A call the
+//! cassette has no response for gets status 599 with a body saying so; no
+//! server sends 599, so no check can mistake it for a real answer.
+//!
+//! Replays run the client-side encoding and decoding against real server
+//! answers in milliseconds, which is what assertions are developed against.
+//! Checks that look inside containers still need the stack.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use serde_json::{json, Value};
+
+use crate::latency;
+use crate::redact;
+
+// Status of a replayed call with nothing recorded for it
+pub const MISSING_STATUS: u16 = 599;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Calls go over the network and are not kept
+    #[default]
+    Live,
+    /// Calls go over the network and are written to the cassette
+    Record(PathBuf),
+    /// Calls are answered from the cassette
+    Replay(PathBuf),
+}
+
+/// One recorded call.
+#[derive(Debug, Clone)]
+struct Interaction {
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    request_body: Vec<u8>,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    response_body: Vec<u8>,
+}
+
+struct Cassette {
+    mode: Mode,
+    interactions: Vec<Interaction>,
+    // Replay: calls answered so far per method and path
+    served: HashMap<(String, String), usize>,
+}
+
+static CASSETTE: OnceLock<Mutex<Cassette>> = OnceLock::new();
+
+/// Record to or replay from a cassette from now on; a replayed cassette is
+/// loaded here. Only the first call has an effect.
+pub fn init(mode: Mode) -> Result<(), String> {
+    let interactions = match &mode {
+        Mode::Replay(path) => load(path)?,
+        _ => Vec::new(),
+    };
+    if let Mode::Replay(path) = &mode {
+        tracing::info!(
+            "Replaying {} recorded calls from {}",
+            interactions.len(),
+            path.display()
+        );
+    }
+    let _ = CASSETTE.set(Mutex::new(Cassette {
+        mode,
+        interactions,
+        served: HashMap::new(),
+    }));
+    Ok(())
+}
+
+fn mode() -> Mode {
+    CASSETTE
+        .get()
+        .map(|cassette| cassette.lock().unwrap().mode.clone())
+        .unwrap_or_default()
+}
+
+pub fn replaying() -> bool {
+    matches!(mode(), Mode::Replay(_))
+}
+
+/// Build and execute `request` through the cassette.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    execute(&client, request?).await
+}
+
+/// Execute `request` with `client`, or answer it from the cassette when
+/// replaying; recorded calls are written out as they complete. Calls that
+/// went over the network count towards the latencies of their endpoint.
+pub async fn execute(client: &Client, request: Request) -> reqwest::Result<Response> {
+    let mode = mode();
+    if let Mode::Replay(path) = &mode {
+        return Ok(replay(path, &request));
+    }
+    let endpoint = latency::endpoint(request.method().as_str(), request.url());
+    let start = Instant::now();
+    let response = over_network(client, request, mode).await;
+    if response.is_ok() {
+        latency::record(&endpoint, start.elapsed());
+    }
+    response
+}
+
+async fn over_network(client: &Client, request: Request, mode: Mode) -> reqwest::Result<Response> {
+    match mode {
+        Mode::Live => client.execute(request).await,
+        Mode::Record(path) => {
+            let method = request.method().to_string();
+            let url = request.url().to_string();
+            let request_headers = headers(request.headers());
+            let request_body = request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .unwrap_or_default()
+                .to_vec();
+            let response = client.execute(request).await?;
+            let status = response.status();
+            let version = response.version();
+            let header_map = response.headers().clone();
+            let body = response.bytes().await?;
+            record(
+                &path,
+                Interaction {
+                    method,
+                    url,
+                    request_headers,
+                    request_body,
+                    status: status.as_u16(),
+                    response_headers: headers(&header_map),
+                    response_body: body.to_vec(),
+                },
+            );
+            let mut copy = http::Response::new(body);
+            *copy.status_mut() = status;
+            *copy.version_mut() = version;
+            *copy.headers_mut() = header_map;
+            Ok(Response::from(copy))
+        }
+        Mode::Replay(path) => Ok(replay(&path, &request)),
+    }
+}
+
+fn record(path: &Path, interaction: Interaction) {
+    let Some(cassette) = CASSETTE.get() else {
+        return;
+    };
+    let mut cassette = cassette.lock().unwrap();
+    cassette.interactions.push(interaction);
+    // Rewritten whole each time, so an aborted run still leaves a valid file
+    let text = format!("{:#}\n", to_json(&cassette.interactions));
+    let written = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(path, text));
+    if let Err(e) = written {
+        tracing::error!("Failed to write cassette {}: {:?}", path.display(), e);
+    }
+}
+
+fn replay(path: &Path, request: &Request) -> Response {
+    let method = request.method().to_string();
+    let route = request.url().path().to_string();
+    let found = CASSETTE.get().and_then(|cassette| {
+        let mut cassette = cassette.lock().unwrap();
+        let served = cassette
+            .served
+            .entry((method.clone(), route.clone()))
+            .or_default();
+        let nth = *served;
+        *served += 1;
+        cassette
+            .interactions
+            .iter()
+            .filter(|i| i.method == method && path_of(&i.url) == route)
+            .nth(nth)
+            .cloned()
+    });
+    let Some(interaction) = found else {
+        let message = format!(
+            "No recorded response for {} {} in {}",
+            method,
+            route,
+            path.display()
+        );
+        tracing::warn!("{}", message);
+        let mut missing = http::Response::new(message.into_bytes());
+        *missing.status_mut() = StatusCode::from_u16(MISSING_STATUS).unwrap();
+        return Response::from(missing);
+    };
+    let mut response = http::Response::new(interaction.response_body);
+    *response.status_mut() =
+        StatusCode::from_u16(interaction.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    for (name, value) in &interaction.response_headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            response.headers_mut().append(name, value);
+        }
+    }
+    Response::from(response)
+}
+
+fn path_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .map(|url| url.path().to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// `headers` as name and redacted value pairs.
+fn headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let line = redact::header(name.as_str(), &String::from_utf8_lossy(value.as_bytes()));
+            let value = line
+                .split_once(": ")
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_default();
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn to_json(interactions: &[Interaction]) -> Value {
+    let interactions: Vec<Value> = interactions
+        .iter()
+        .map(|i| {
+            json!({
+                "method": i.method,
+                "url": i.url,
+                "request": {
+                    "headers": i.request_headers,
+                    "body": body_json(&i.request_body),
+                },
+                "status": i.status,
+                "response": {
+                    "headers": i.response_headers,
+                    "body": body_json(&i.response_body),
+                },
+            })
+        })
+        .collect();
+    json!({ "interactions": interactions })
+}
+
+/// Text bodies as `{"text": ...}` (JWTs redacted), others as `{"hex": ...}`.
+fn body_json(body: &[u8]) -> Value {
+    match std::str::from_utf8(body) {
+        Ok(text) if !text.contains(|c: char| c.is_control() && !c.is_whitespace()) => {
+            json!({ "text": redact::text(text) })
+        }
+        _ => json!({ "hex": body.iter().map(|b| format!("{:02x}", b)).collect::<String>() }),
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<Interaction>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read cassette {}: {:?}", path.display(), e))?;
+    let cassette: Value = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to parse cassette {}: {:?}", path.display(), e))?;
+    let invalid = |what: &str| format!("Cassette {} has an invalid {}", path.display(), what);
+    cassette["interactions"]
+        .as_array()
+        .ok_or_else(|| invalid("interactions list"))?
+        .iter()
+        .map(|i| {
+            Ok(Interaction {
+                method: i["method"]
+                    .as_str()
+                    .ok_or_else(|| invalid("method"))?
+                    .to_string(),
+                url: i["url"].as_str().ok_or_else(|| invalid("url"))?.to_string(),
+                request_headers: header_pairs(&i["request"]["headers"]),
+                request_body: body_bytes(&i["request"]["body"])
+                    .ok_or_else(|| invalid("request body"))?,
+                status: i["status"]
+                    .as_u64()
+                    .and_then(|s| u16::try_from(s).ok())
+                    .ok_or_else(|| invalid("status"))?,
+                response_headers: header_pairs(&i["response"]["headers"]),
+                response_body: body_bytes(&i["response"]["body"])
+                    .ok_or_else(|| invalid("response body"))?,
+            })
+        })
+        .collect()
+}
+
+fn header_pairs(headers: &Value) -> Vec<(String, String)> {
+    headers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|pair| Some((pair[0].as_str()?.to_string(), pair[1].as_str()?.to_string())))
+        .collect()
+}
+
+fn body_bytes(body: &Value) -> Option<Vec<u8>> {
+    if let Some(text) = body["text"].as_str() {
+        return Some(text.as_bytes().to_vec());
+    }
+    let hex = body["hex"].as_str()?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,343 @@
+//! Command line shared by the suites
+//!
+//! `SuiteArgs` picks how results are reported, how much of them, whether a
+//! live status line shows on a terminal, the latency baseline the run is held
+//! to, where run metrics and traces go, how many times (or for how long)
+//! cases run, and how many run at once.
+//! `Filter` selects which cases of a suite run: positional patterns keep the
+//! cases whose name contains them (or, with `*` and `?`, matches them as a
+//! glob), `--skip` drops cases the same way, `--include` keeps the cases with
+//! any of the given tags, `--exclude` drops those with any of them,
+//! `--shard K/N` keeps this CI job's share of them (see `shard`), and
+//! `--list` prints the selection instead of running it. `SeedArgs` takes
+//! `--seed` for binaries without `SuiteArgs`. `HarnessArgs` declares
+//! the flags `harness_docker` reads on its own, so a clap parser accepts them
+//! alongside the suite's.
+
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Args;
+
+use crate::annotate;
+use crate::cassette::{self, Mode};
+use crate::log::{self, LogFormat};
+use crate::metrics::PUSHGATEWAY_ENV;
+use crate::otel::OTLP_ENV;
+use crate::perf::{PerfGate, DEFAULT_THRESHOLD_PERCENT};
+use crate::report::{Format, Reporter, Verbosity};
+use crate::rng;
+use crate::runner::{Case, Tag};
+use crate::shard::{self, Shard};
+use crate::snapshot::{Snapshots, SNAPSHOT_DIR};
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct SuiteArgs {
+    /// How to report results; json and tap keep stdout machine-readable,
+    /// markdown prints a summary for a PR comment
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+    /// Also write a self-contained HTML report of the run to this file
+    #[arg(long, value_name = "PATH")]
+    pub html: Option<PathBuf>,
+    /// Compare case timings in the markdown summary with this earlier
+    /// `--format json` report
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<PathBuf>,
+    /// Compare case and endpoint latencies with this baseline file and fail on
+    /// regressions
+    #[arg(long, value_name = "PATH")]
+    pub perf_baseline: Option<PathBuf>,
+    /// Write the latencies of this run to the perf baseline instead
+    #[arg(long, requires = "perf_baseline")]
+    pub update_perf_baseline: bool,
+    /// How much slower than the baseline, in percent, counts as a regression
+    #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_THRESHOLD_PERCENT)]
+    pub perf_threshold: f64,
+    /// Only warn about latency regressions
+    #[arg(long)]
+    pub perf_warn_only: bool,
+    /// How many cases may run at the same time
+    #[arg(long, short = 'j', default_value_t = 1)]
+    pub jobs: usize,
+    /// Run every case this many times and report the ones that both passed and failed
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: u32,
+    /// Loop the cases for this long, e.g. 4h, and report how failures and
+    /// latencies drift over time
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration, conflicts_with = "repeat")]
+    pub soak: Option<Duration>,
+    /// How diagnostics on stderr are written [default: LOG_FORMAT or text]
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+    /// Print only failures and the summary
+    #[arg(long, short = 'q', conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Also print every HTTP request and response in full, credentials redacted
+    #[arg(long, short = 'v')]
+    pub verbose: bool,
+    /// Also write failures as GitHub Actions annotations [default: on when
+    /// GITHUB_ACTIONS is true]
+    #[arg(long)]
+    pub github_annotations: bool,
+    /// Never color output, even on a terminal
+    #[arg(long)]
+    pub no_color: bool,
+    /// Print plain result lines without the live status line, even on a terminal
+    #[arg(long)]
+    pub no_progress: bool,
+    /// Rewrite golden files with the responses of this run instead of failing
+    #[arg(long)]
+    pub update_snapshots: bool,
+    /// Write every HTTP call and its response to this cassette
+    #[arg(long, value_name = "PATH", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Answer HTTP calls from this cassette instead of the network
+    #[arg(long, value_name = "PATH")]
+    pub replay: Option<PathBuf>,
+    #[command(flatten)]
+    pub seed: SeedArgs,
+    /// Push run metrics to this Prometheus pushgateway [default: PUSHGATEWAY_URL]
+    #[arg(long, value_name = "URL")]
+    pub pushgateway: Option<String>,
+    /// Trace cases and export the spans to this OTLP/HTTP collector
+    /// [default: OTEL_EXPORTER_OTLP_ENDPOINT]
+    #[arg(long, value_name = "URL")]
+    pub otlp: Option<String>,
+}
+
+impl SuiteArgs {
+    /// Start logging diagnostics in the chosen format, set up the cassette if
+    /// recording or replaying, note the shard of `filter` for reports, then
+    /// fix the seed of the run and log it. Quiet runs log only warnings and
+    /// errors unless RUST_LOG says otherwise.
+    pub fn init(&self, filter: &Filter) -> Result<(), String> {
+        if self.no_color {
+            log::disable_color();
+        }
+        let format = self.log_format.unwrap_or_else(LogFormat::from_env);
+        let level = if self.quiet {
+            "warn"
+        } else {
+            log::DEFAULT_FILTER
+        };
+        log::init_filtered(format, level);
+        cassette::init(self.cassette_mode())?;
+        shard::init(filter.shard);
+        self.seed.init().map(|_| ())
+    }
+
+    pub fn cassette_mode(&self) -> Mode {
+        match (&self.record, &self.replay) {
+            (Some(path), _) => Mode::Record(path.clone()),
+            (None, Some(path)) => Mode::Replay(path.clone()),
+            (None, None) => Mode::Live,
+        }
+    }
+
+    pub fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    /// The golden files under `snapshots/`, rewritten if asked to.
+    pub fn snapshots(&self) -> Snapshots {
+        Snapshots::new(Path::new(SNAPSHOT_DIR), self.update_snapshots)
+    }
+
+    /// The pushgateway to push metrics to, from `--pushgateway` or PUSHGATEWAY_URL.
+    pub fn pushgateway(&self) -> Option<String> {
+        self.pushgateway
+            .clone()
+            .or_else(|| std::env::var(PUSHGATEWAY_ENV).ok())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// The collector to export traces to, from `--otlp` or OTEL_EXPORTER_OTLP_ENDPOINT.
+    pub fn otlp(&self) -> Option<String> {
+        self.otlp
+            .clone()
+            .or_else(|| std::env::var(OTLP_ENV).ok())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// A reporter in the chosen format and verbosity, with a status line on a
+    /// terminal, writing the HTML report, pushing metrics and tracing if asked to.
+    pub fn reporter(&self) -> Reporter {
+        if self.no_color {
+            log::disable_color();
+        }
+        let mut report = Reporter::new(self.format)
+            .with_repeat(self.repeat as usize)
+            .with_verbosity(self.verbosity())
+            .with_color(log::color(&std::io::stdout()))
+            .with_progress(!self.no_progress && std::io::stdout().is_terminal())
+            .with_annotations(self.github_annotations || annotate::in_github_actions());
+        if let Some(path) = &self.html {
+            report = report.with_html(path);
+        }
+        if let Some(path) = &self.baseline {
+            report = report.with_baseline(path);
+        }
+        if let Some(path) = &self.perf_baseline {
+            report = report.with_perf(PerfGate {
+                path: path.clone(),
+                update: self.update_perf_baseline,
+                threshold_percent: self.perf_threshold,
+                warn_only: self.perf_warn_only,
+            });
+        }
+        if let Some(url) = self.pushgateway() {
+            report = report.with_pushgateway(&url);
+        }
+        if let Some(url) = self.otlp() {
+            report = report.with_otlp(&url);
+        }
+        report
+    }
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct Filter {
+    /// Run only cases whose name contains one of these, or matches it as a glob
+    #[arg(value_name = "FILTER")]
+    pub patterns: Vec<String>,
+    /// Leave out cases whose name contains this, or matches it as a glob
+    #[arg(long, value_name = "FILTER")]
+    pub skip: Vec<String>,
+    /// Run only cases tagged with one of these
+    #[arg(long, value_enum, value_name = "TAG", value_delimiter = ',')]
+    pub include: Vec<Tag>,
+    /// Leave out cases tagged with one of these
+    #[arg(long, value_enum, value_name = "TAG", value_delimiter = ',')]
+    pub exclude: Vec<Tag>,
+    /// Run only the cases of shard K of N, split by a stable hash of their names
+    #[arg(long, value_name = "K/N")]
+    pub shard: Option<Shard>,
+    /// Print the names of the selected cases and exit
+    #[arg(long)]
+    pub list: bool,
+}
+
+impl Filter {
+    /// Whether the case `name` tagged `tags` is selected.
+    pub fn runs(&self, name: &str, tags: &[Tag]) -> bool {
+        let included = self.patterns.is_empty() || self.patterns.iter().any(|p| matches(p, name));
+        let tagged = self.include.is_empty() || self.include.iter().any(|t| tags.contains(t));
+        included
+            && tagged
+            && !self.skip.iter().any(|p| matches(p, name))
+            && !self.exclude.iter().any(|t| tags.contains(t))
+            && self.shard.is_none_or(|shard| shard.holds(name))
+    }
+
+    /// The selected names among `cases`, given as names with their tags, in order.
+    pub fn select<'a>(&self, cases: &'a [(String, Vec<Tag>)]) -> Vec<&'a str> {
+        cases
+            .iter()
+            .filter(|(name, tags)| self.runs(name, tags))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// The selected cases among `cases`, in order.
+    pub fn retain<'a>(&self, cases: Vec<Case<'a>>) -> Vec<Case<'a>> {
+        cases
+            .into_iter()
+            .filter(|case| self.runs(&case.name, &case.tags))
+            .collect()
+    }
+
+    /// Print the selected names among `cases`, one per line.
+    pub fn print_list(&self, cases: &[(String, Vec<Tag>)]) {
+        for name in self.select(cases) {
+            println!("{}", name);
+        }
+    }
+}
+
+/// `--seed`, part of `SuiteArgs`, and on its own for binaries that draw
+/// random data without them.
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct SeedArgs {
+    /// Seed of all random data, to replay a run [default: TEST_SEED or the clock]
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+impl SeedArgs {
+    /// Fix the seed of the run and log it (see `rng::init`).
+    pub fn init(&self) -> Result<u64, String> {
+        rng::init(self.seed)
+    }
+}
+
+/// Flags handled inside `harness_docker`; see the module named in each.
+#[derive(Debug, Clone, Default, Args)]
+pub struct HarnessArgs {
+    /// Bring up this compose profile first (`profile`)
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Run against a private copy of the profile (`isolated`)
+    #[arg(long)]
+    pub isolated: bool,
+    /// Rerun across the image tags of a matrix file (`matrix`)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+    pub matrix: Option<Option<String>>,
+    /// Restart the services in a random order first (`startup`)
+    #[arg(long)]
+    pub startup_race: bool,
+    /// Leave containers the run started alive (`teardown`)
+    #[arg(long)]
+    pub keep_alive: bool,
+    /// Sample container resource usage per case (`stats`)
+    #[arg(long)]
+    pub stats: bool,
+    /// Fail if VSS memory grows faster than this, in MiB per hour (`memory`)
+    #[arg(long, value_name = "MIB_PER_HOUR")]
+    pub max_memory_slope: Option<f64>,
+}
+
+impl HarnessArgs {
+    /// The first of the flags given that acts on the local containers.
+    pub fn docker_flag(&self) -> Option<&'static str> {
+        let flags = [
+            (self.profile.is_some(), "--profile"),
+            (self.isolated, "--isolated"),
+            (self.matrix.is_some(), "--matrix"),
+            (self.startup_race, "--startup-race"),
+            (self.stats, "--stats"),
+            (self.max_memory_slope.is_some(), "--max-memory-slope"),
+        ];
+        flags
+            .into_iter()
+            .find(|(given, _)| *given)
+            .map(|(_, flag)| flag)
+    }
+}
+
+/// `pattern` as a glob over the whole of `name` if it has `*` or `?`, as a
+/// substring otherwise.
+fn matches(pattern: &str, name: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob(&pattern, &name)
+}
+
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob(rest, &name[1..]),
+    }
+}
@@ -0,0 +1,118 @@
+//! Flakiness across repeated runs
+//!
+//! `--repeat <n>` runs a suite's cases n times over, and the reporter groups
+//! what came back by case name. A case that both passed and failed is flaky:
+//! the stack, not the change under test, decided its outcome. Timings are
+//! kept per iteration, so a case whose duration swings widely shows up even
+//! while it still passes; such cases tend to turn flaky once a timeout is hit.
+
+use std::time::Duration;
+
+/// Every run of one case.
+#[derive(Debug, Clone)]
+pub struct CaseRuns {
+    pub name: String,
+    pub passed: usize,
+    pub failed: usize,
+    /// Per iteration, in order.
+    pub durations: Vec<Duration>,
+}
+
+impl CaseRuns {
+    pub fn flaky(&self) -> bool {
+        self.passed > 0 && self.failed > 0
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.durations.len() {
+            0 => Duration::ZERO,
+            n => self.durations.iter().sum::<Duration>() / n as u32,
+        }
+    }
+
+    /// Population standard deviation of the durations.
+    pub fn stddev(&self) -> Duration {
+        if self.durations.is_empty() {
+            return Duration::ZERO;
+        }
+        let mean = self.mean().as_secs_f64();
+        let variance = self
+            .durations
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / self.durations.len() as f64;
+        Duration::from_secs_f64(variance.sqrt())
+    }
+
+    pub fn min(&self) -> Duration {
+        self.durations.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.durations.iter().max().copied().unwrap_or_default()
+    }
+}
+
+/// `outcomes` (name, passed, duration) grouped by name, in the order names
+/// first appear.
+pub fn group(outcomes: &[(String, bool, Duration)]) -> Vec<CaseRuns> {
+    let mut runs: Vec<CaseRuns> = Vec::new();
+    for (name, passed, duration) in outcomes {
+        let i = match runs.iter().position(|r| &r.name == name) {
+            Some(i) => i,
+            None => {
+                runs.push(CaseRuns {
+                    name: name.clone(),
+                    passed: 0,
+                    failed: 0,
+                    durations: Vec::new(),
+                });
+                runs.len() - 1
+            }
+        };
+        let case = &mut runs[i];
+        if *passed {
+            case.passed += 1;
+        } else {
+            case.failed += 1;
+        }
+        case.durations.push(*duration);
+    }
+    runs
+}
+
+/// A table of `runs` for people, flaky cases marked, then the flaky names.
+pub fn render(runs: &[CaseRuns], iterations: usize) -> String {
+    let width = runs.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    let mut out = format!("Flakiness over {} iterations:\n", iterations);
+    for case in runs {
+        out.push_str(&format!(
+            "  {:<width$}  {:>3}/{:<3} passed  {:>9.1} ms ± {:>7.1} ms  ({:.1}-{:.1} ms){}\n",
+            case.name,
+            case.passed,
+            case.passed + case.failed,
+            millis(case.mean()),
+            millis(case.stddev()),
+            millis(case.min()),
+            millis(case.max()),
+            if case.flaky() { "  FLAKY" } else { "" },
+            width = width,
+        ));
+    }
+    let flaky: Vec<&str> = runs
+        .iter()
+        .filter(|r| r.flaky())
+        .map(|r| r.name.as_str())
+        .collect();
+    if flaky.is_empty() {
+        out.push_str("No flaky cases\n");
+    } else {
+        out.push_str(&format!("Flaky: {}\n", flaky.join(", ")));
+    }
+    out
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,180 @@
+//! Latency percentiles per endpoint
+//!
+//! Every HTTP call that goes over the network through `cassette::execute`,
+//! which `send` and the clients built on `cassette::send` end up in, is
+//! recorded in an HDR histogram of its endpoint (method and URL path, as
+//! `POST /vss/getObject`). The reporter prints p50, p90, p99 and p999 of each
+//! with the summary, so runs against two server versions can be compared by
+//! their tails rather than by total duration. Replayed calls are not
+//! recorded: they take no time the server spent.
+//!
+//! Histograms keep microseconds with three significant digits, so a
+//! percentile is within 0.1% of the exact one whatever the number of calls.
+//! `Latencies` is also what the load generator keeps per operation, and what
+//! a soak run keeps per time window.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use serde_json::{json, Map, Value};
+
+/// The percentiles reported, by name.
+pub const PERCENTILES: [(&str, f64); 4] =
+    [("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("p999", 99.9)];
+// An hour; slower calls are recorded as taking that long
+const MAX_MICROS: u64 = 3_600_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+static RECORDED: Mutex<BTreeMap<String, Latencies>> = Mutex::new(BTreeMap::new());
+
+/// A histogram of durations.
+#[derive(Debug, Clone)]
+pub struct Latencies {
+    histogram: Histogram<u64>,
+}
+
+impl Default for Latencies {
+    fn default() -> Self {
+        Self {
+            histogram: Histogram::new_with_bounds(1, MAX_MICROS, SIGNIFICANT_DIGITS)
+                .expect("valid histogram bounds"),
+        }
+    }
+}
+
+impl Latencies {
+    pub fn record(&mut self, duration: Duration) {
+        let micros = (duration.as_micros() as u64).clamp(1, MAX_MICROS);
+        self.histogram.saturating_record(micros);
+    }
+
+    pub fn add(&mut self, other: &Latencies) {
+        // Same bounds on both sides, so nothing is out of range
+        let _ = self.histogram.add(&other.histogram);
+    }
+
+    /// The calls recorded here but not yet in `earlier`, an earlier copy of
+    /// the same histogram.
+    pub fn since(&self, earlier: &Latencies) -> Latencies {
+        let mut recent = self.clone();
+        // Every count in `earlier` is also here, so nothing goes below zero
+        let _ = recent.histogram.subtract(&earlier.histogram);
+        recent
+    }
+
+    pub fn count(&self) -> u64 {
+        self.histogram.len()
+    }
+
+    /// The duration `percentile` percent of the calls took at most, in ms.
+    pub fn percentile_ms(&self, percentile: f64) -> f64 {
+        self.histogram.value_at_percentile(percentile) as f64 / 1000.0
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.histogram.max() as f64 / 1000.0
+    }
+
+    /// Count, percentiles and maximum as a JSON object, in ms.
+    pub fn to_json(&self) -> Value {
+        let mut entry = Map::new();
+        entry.insert("count".to_string(), json!(self.count()));
+        for (name, percentile) in PERCENTILES {
+            entry.insert(
+                format!("{}_ms", name),
+                json!(self.percentile_ms(percentile)),
+            );
+        }
+        entry.insert("max_ms".to_string(), json!(self.max_ms()));
+        Value::Object(entry)
+    }
+}
+
+/// How calls are grouped: method and URL path.
+pub fn endpoint(method: &str, url: &reqwest::Url) -> String {
+    format!("{} {}", method, url.path())
+}
+
+/// Count a call to `endpoint` that took `duration`.
+pub fn record(endpoint: &str, duration: Duration) {
+    RECORDED
+        .lock()
+        .unwrap()
+        .entry(endpoint.to_string())
+        .or_default()
+        .record(duration);
+}
+
+/// The histograms of every endpoint called so far.
+pub fn recorded() -> BTreeMap<String, Latencies> {
+    RECORDED.lock().unwrap().clone()
+}
+
+/// A table of `rows` for people: calls, percentiles and maximum in ms, under
+/// a first column headed `heading`.
+pub fn render<'a>(
+    heading: &str,
+    rows: impl IntoIterator<Item = (&'a str, &'a Latencies)>,
+) -> String {
+    let rows: Vec<_> = rows.into_iter().collect();
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain([heading.len()])
+        .max()
+        .unwrap_or(0);
+    let mut out = format!("  {:<width$}  {:>8}", heading, "calls", width = width);
+    for (name, _) in PERCENTILES {
+        out.push_str(&format!("  {:>9}", name));
+    }
+    out.push_str(&format!("  {:>9}\n", "max"));
+    for (name, latencies) in rows {
+        out.push_str(&format!(
+            "  {:<width$}  {:>8}",
+            name,
+            latencies.count(),
+            width = width
+        ));
+        for (_, percentile) in PERCENTILES {
+            out.push_str(&format!("  {:>9.1}", latencies.percentile_ms(percentile)));
+        }
+        out.push_str(&format!("  {:>9.1}\n", latencies.max_ms()));
+    }
+    out
+}
+
+/// The same table in Markdown.
+pub fn render_markdown(latencies: &BTreeMap<String, Latencies>) -> String {
+    let names: Vec<&str> = PERCENTILES.iter().map(|(name, _)| *name).collect();
+    let mut out = format!(
+        "| Endpoint | Calls | {} | max |\n|---|---:|{}---:|\n",
+        names.join(" | "),
+        "---:|".repeat(names.len())
+    );
+    for (name, latencies) in latencies {
+        let percentiles: Vec<String> = PERCENTILES
+            .iter()
+            .map(|(_, percentile)| format!("{:.1} ms", latencies.percentile_ms(*percentile)))
+            .collect();
+        out.push_str(&format!(
+            "| `{}` | {} | {} | {:.1} ms |\n",
+            name,
+            latencies.count(),
+            percentiles.join(" | "),
+            latencies.max_ms()
+        ));
+    }
+    out
+}
+
+/// Every endpoint's `Latencies::to_json`, by endpoint.
+pub fn to_json(latencies: &BTreeMap<String, Latencies>) -> Value {
+    Value::Object(
+        latencies
+            .iter()
+            .map(|(name, latencies)| (name.clone(), latencies.to_json()))
+            .collect(),
+    )
+}
@@ -0,0 +1,145 @@
+//! Running and reporting the cases of a test suite
+//!
+//! Shared by the test binaries so each holds only its scenarios. `case` turns
+//! checks into `TestCase`s and runs them with timing, result lines and counts,
+//! `runner` runs cases concurrently after the `setup` steps they need, `flaky`
+//! finds cases that both pass and fail across repeated runs, `soak` follows
+//! them as they loop for hours, `report` prints and records results in the
+//! chosen format, `markdown` summarizes them for PR comments, `annotate` marks
+//! failures for GitHub Actions, `progress` shows a live status line on a
+//! terminal, `log` sets up diagnostics, `redact` keeps credentials out of
+//! verbose output, `cassette` records and replays HTTP traffic, `snapshot`
+//! compares responses with golden files, `assert` diffs expected and actual
+//! values, `shard` splits suites across CI jobs, `perf` gates on latency
+//! baselines, `latency` keeps percentiles per endpoint, `rng` seeds all random
+//! data, `metrics` pushes run metrics to Prometheus, `otel` exports traces of
+//! the run and `cli` declares the flags every suite takes.
+
+pub mod annotate;
+pub mod assert;
+pub mod case;
+pub mod cassette;
+pub mod cli;
+pub mod flaky;
+pub mod latency;
+pub mod log;
+pub mod markdown;
+pub mod metrics;
+pub mod otel;
+pub mod perf;
+pub mod progress;
+pub mod redact;
+pub mod report;
+pub mod rng;
+pub mod runner;
+pub mod setup;
+pub mod shard;
+pub mod snapshot;
+pub mod soak;
+
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Request, RequestBuilder, Response};
+use tracing::Instrument;
+
+use crate::report::{Exchange, Reporter};
+
+/// HTTP client that gives up on a request after `timeout` instead of waiting forever.
+pub fn http_client(timeout: Duration) -> Result<Client, String> {
+    Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {:?}", e))
+}
+
+/// Send `request` as an HTTP call of the current case: with a fresh request id
+/// in its `X-Request-Id` header, inside an `http` span, and recorded as an
+/// `Exchange` whatever comes back. In traced runs it also carries a
+/// `traceparent` header for a new span of the case. Verbose runs also record the headers and
+/// bodies, which means reading the response body here and handing on a copy.
+pub async fn send(report: &Reporter, request: RequestBuilder) -> reqwest::Result<Response> {
+    let request_id = log::request_id();
+    let trace = report.trace_context().map(|case| case.child());
+    let mut request = request.header(log::REQUEST_ID_HEADER, &request_id);
+    if let Some(context) = &trace {
+        request = request.header(otel::TRACEPARENT_HEADER, context.traceparent());
+    }
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().to_string();
+    let url = request.url().to_string();
+    let span = log::http_span(&method, &url, &request_id);
+    let request_dump = report.verbose().then(|| dump_request(&request));
+    async {
+        let start = Instant::now();
+        let (response, response_dump) = match cassette::execute(&client, request).await {
+            Ok(resp) if request_dump.is_some() => match buffered(resp).await {
+                Ok((resp, dump)) => (Ok(resp), Some(dump)),
+                Err(e) => (Err(e), None),
+            },
+            response => (response, None),
+        };
+        let duration = start.elapsed();
+        let status = response.as_ref().ok().map(|resp| resp.status().as_u16());
+        match &response {
+            Ok(_) => tracing::debug!(status, elapsed_ms = duration.as_millis() as u64, "response"),
+            Err(e) => tracing::debug!(
+                elapsed_ms = duration.as_millis() as u64,
+                "no response: {}",
+                e
+            ),
+        }
+        report.exchange(Exchange {
+            method,
+            url,
+            status,
+            duration,
+            request: request_dump,
+            response: response_dump,
+            span: trace,
+        });
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+/// `response` with its body read into memory, and a redacted dump of its
+/// headers and body. The copy keeps status, version, headers and body; its
+/// `url()` is a placeholder.
+async fn buffered(response: Response) -> reqwest::Result<(Response, String)> {
+    let status = response.status();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    let dump = dump(&headers, &body);
+    let mut copy = http::Response::new(body);
+    *copy.status_mut() = status;
+    *copy.version_mut() = version;
+    *copy.headers_mut() = headers;
+    Ok((Response::from(copy), dump))
+}
+
+fn dump_request(request: &Request) -> String {
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default();
+    dump(request.headers(), body)
+}
+
+/// Headers one per line, then a blank line and the body if there is one.
+fn dump(headers: &HeaderMap, body: &[u8]) -> String {
+    let mut lines: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| {
+            redact::header(name.as_str(), &String::from_utf8_lossy(value.as_bytes()))
+        })
+        .collect();
+    if !body.is_empty() {
+        lines.push(String::new());
+        lines.push(redact::body(body));
+    }
+    lines.join("\n")
+}
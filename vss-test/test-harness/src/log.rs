@@ -0,0 +1,97 @@
+//! Diagnostics through `tracing`
+//!
+//! What the harness does on the way (bringing up profiles, healing faults,
+//! tearing down) is logged as `tracing` events on stderr, never mixed into
+//! the results on stdout. Each case runs in a `case` span and each HTTP call
+//! in an `http` span carrying the request id it sent as `X-Request-Id`, so
+//! server logs can be matched to the call. `RUST_LOG` filters as usual
+//! (default `info`); `LogFormat::Json` writes one JSON object per line, with
+//! the spans, for log ingestion.
+//!
+//! Text is colored only on a terminal, and never once `disable_color` was
+//! called or `NO_COLOR` is set (see no-color.org); `color` tells the reporter
+//! the same for stdout.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use clap::ValueEnum;
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+// Read when a binary does not take --log-format
+pub const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+pub const DEFAULT_FILTER: &str = "info";
+pub const NO_COLOR_ENV: &str = "NO_COLOR";
+
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(1);
+static COLOR_DISABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// One readable line per event
+    #[default]
+    Text,
+    /// One JSON object per event, with its spans
+    Json,
+}
+
+impl LogFormat {
+    /// `json` from LOG_FORMAT, text otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var(LOG_FORMAT_ENV).as_deref() {
+            Ok("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Send this process's events to stderr in `format`, filtered by RUST_LOG.
+/// Only the first call has an effect.
+pub fn init(format: LogFormat) {
+    init_filtered(format, DEFAULT_FILTER);
+}
+
+/// `init` with `default_filter` in place of `info` when RUST_LOG is unset.
+pub fn init_filtered(format: LogFormat, default_filter: &str) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(color(&std::io::stderr()))
+        .with_writer(std::io::stderr);
+    let _ = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+}
+
+/// Never color output from now on; call before `init`.
+pub fn disable_color() {
+    COLOR_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether to color what is written to `stream`.
+pub fn color(stream: &impl IsTerminal) -> bool {
+    let no_color = std::env::var_os(NO_COLOR_ENV).is_some_and(|v| !v.is_empty());
+    !COLOR_DISABLED.load(Ordering::Relaxed) && !no_color && stream.is_terminal()
+}
+
+/// An id unique within this run, for one HTTP request.
+pub fn request_id() -> String {
+    format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT_REQUEST.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// The span an HTTP call to `url` runs in.
+pub fn http_span(method: &str, url: &str, request_id: &str) -> Span {
+    tracing::info_span!("http", method, url, request_id)
+}
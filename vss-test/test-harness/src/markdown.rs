@@ -0,0 +1,167 @@
+//! A summary of the run for a GitHub PR comment
+//!
+//! `--format markdown` prints nothing while cases run (diagnostics go to
+//! stderr, as with json) and one Markdown document at the end, meant to be
+//! posted as is by the CI job:
+//!
+//! - a table with the suite's counts and duration
+//! - each failure as a collapsible `<details>` block holding its error and
+//!   the container logs attached to it, so the comment stays short
+//! - with `--baseline <report>`, each case's duration next to its duration
+//!   in an earlier `--format json` report (say, of the main branch), and the
+//!   change in percent
+//! - with `--repeat`, the flakiness table of the cases
+//!
+//! GitHub renders Markdown inside `<details>` only after a blank line, and a
+//! code block ends at the first fence as long as its own, so logs are fenced
+//! with more backticks than they contain.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::Value;
+
+/// One finished case, as the summary shows it.
+pub struct CaseResult<'a> {
+    pub name: &'a str,
+    pub passed: bool,
+    pub duration: Duration,
+    /// The detail of a pass or the error of a failure.
+    pub message: &'a str,
+    pub logs: &'a str,
+}
+
+/// Mean duration in ms of every case in the `--format json` report at `path`,
+/// by name.
+pub fn load_baseline(path: &Path) -> Result<BTreeMap<String, f64>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read baseline {}: {:?}", path.display(), e))?;
+    let mut runs: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(line).map_err(|e| {
+            format!(
+                "Baseline {}:{} is not a JSON record: {}",
+                path.display(),
+                number + 1,
+                e
+            )
+        })?;
+        if record["type"] != "test" {
+            continue;
+        }
+        if let (Some(name), Some(duration)) =
+            (record["name"].as_str(), record["duration_ms"].as_f64())
+        {
+            runs.entry(name.to_string()).or_default().push(duration);
+        }
+    }
+    Ok(runs
+        .into_iter()
+        .map(|(name, durations)| {
+            let mean = durations.iter().sum::<f64>() / durations.len() as f64;
+            (name, mean)
+        })
+        .collect())
+}
+
+/// The summary of suite `suite`, whose `cases` ran in `elapsed`; `flakiness`
+/// is the table `flaky::render` made of repeated runs.
+pub fn render(
+    suite: &str,
+    cases: &[CaseResult],
+    passed: usize,
+    failed: usize,
+    elapsed: Duration,
+    baseline: Option<&BTreeMap<String, f64>>,
+    flakiness: Option<&str>,
+) -> String {
+    let verdict = if failed == 0 { "passed" } else { "failed" };
+    let mut out = format!("## {} {}\n\n", cell(suite), verdict);
+    out.push_str("| Suite | Passed | Failed | Duration |\n");
+    out.push_str("|:--|--:|--:|--:|\n");
+    out.push_str(&format!(
+        "| {} | {} | {} | {:.1} s |\n",
+        cell(suite),
+        passed,
+        failed,
+        elapsed.as_secs_f64()
+    ));
+
+    let failures: Vec<&CaseResult> = cases.iter().filter(|case| !case.passed).collect();
+    if !failures.is_empty() {
+        out.push_str("\n### Failures\n\n");
+        for case in failures {
+            out.push_str(&format!(
+                "<details>\n<summary><code>{}</code> failed after {:.1} ms</summary>\n\n",
+                html(case.name),
+                millis(case.duration)
+            ));
+            out.push_str(&fenced(case.message));
+            if !case.logs.trim().is_empty() {
+                out.push_str("\nLogs:\n\n");
+                out.push_str(&fenced(case.logs));
+            }
+            out.push_str("\n</details>\n");
+        }
+    }
+
+    if let Some(baseline) = baseline {
+        out.push_str("\n### Timings against the baseline\n\n");
+        out.push_str("| Case | Duration | Baseline | Change |\n");
+        out.push_str("|:--|--:|--:|--:|\n");
+        for case in cases {
+            let now = millis(case.duration);
+            let (before, change) = match baseline.get(case.name) {
+                Some(&before) if before > 0.0 => (
+                    format!("{:.1} ms", before),
+                    format!("{:+.1}%", (now - before) / before * 100.0),
+                ),
+                Some(&before) => (format!("{:.1} ms", before), "-".to_string()),
+                None => ("-".to_string(), "new".to_string()),
+            };
+            out.push_str(&format!(
+                "| {} | {:.1} ms | {} | {} |\n",
+                cell(case.name),
+                now,
+                before,
+                change
+            ));
+        }
+    }
+
+    if let Some(flakiness) = flakiness {
+        out.push_str("\n### Flakiness\n\n");
+        out.push_str(&fenced(flakiness));
+    }
+    out
+}
+
+/// `text` in a code block fenced with more backticks than it holds in a row.
+fn fenced(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}\n{}\n{}\n", fence, text.trim_end(), fence)
+}
+
+/// `text` safe inside a table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
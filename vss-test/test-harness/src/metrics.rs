@@ -0,0 +1,220 @@
+//! Run metrics for Prometheus
+//!
+//! With `--pushgateway <url>` (or `PUSHGATEWAY_URL`) the reporter pushes the
+//! run to a Prometheus pushgateway once the summary is printed, grouped under
+//! the binary's name as `job`, so dashboards can follow the test environment
+//! over time. Each push replaces the job's previous one. The run has:
+//!
+//!   harness_case_duration_seconds{case}           how long each case took
+//!   harness_case_passed{case}                     1 if it passed, 0 if not
+//!   harness_request_duration_seconds{method,path,status}
+//!                                                 histogram of HTTP latencies
+//!   harness_run_cases{result}                     passed and failed counts
+//!   harness_run_duration_seconds                  the whole run
+//!   harness_run_last_timestamp_seconds            when the run finished
+//!
+//! Request latencies cover the calls made through `send`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+use crate::http_client;
+use crate::report::Exchange;
+
+pub const PUSHGATEWAY_ENV: &str = "PUSHGATEWAY_URL";
+// Upper bounds of the latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct Latencies {
+    // Observations at or below each of LATENCY_BUCKETS
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+/// The metrics of one run, in Prometheus text format once rendered.
+#[derive(Default)]
+pub struct Metrics {
+    cases: Vec<(String, bool, Duration)>,
+    // By method, path and status
+    requests: BTreeMap<(String, String, String), Latencies>,
+    run: Option<(usize, usize, Duration)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn case(&mut self, name: &str, passed: bool, duration: Duration) {
+        self.cases.push((name.to_string(), passed, duration));
+    }
+
+    pub fn request(&mut self, exchange: &Exchange) {
+        let path = reqwest::Url::parse(&exchange.url)
+            .map(|url| url.path().to_string())
+            .unwrap_or_else(|_| exchange.url.clone());
+        let status = exchange
+            .status
+            .map_or("none".to_string(), |status| status.to_string());
+        let latencies = self
+            .requests
+            .entry((exchange.method.clone(), path, status))
+            .or_default();
+        let seconds = exchange.duration.as_secs_f64();
+        for (bucket, bound) in latencies.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        latencies.sum += seconds;
+        latencies.count += 1;
+    }
+
+    pub fn run(&mut self, passed: usize, failed: usize, elapsed: Duration) {
+        self.run = Some((passed, failed, elapsed));
+    }
+
+    /// The text exposition format the pushgateway accepts.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "harness_case_duration_seconds",
+            "gauge",
+            "How long the case took.",
+        );
+        for (name, _, duration) in &self.cases {
+            let _ = writeln!(
+                out,
+                "harness_case_duration_seconds{{case=\"{}\"}} {}",
+                label(name),
+                duration.as_secs_f64()
+            );
+        }
+        header(
+            &mut out,
+            "harness_case_passed",
+            "gauge",
+            "1 if the case passed, 0 if it failed.",
+        );
+        for (name, passed, _) in &self.cases {
+            let _ = writeln!(
+                out,
+                "harness_case_passed{{case=\"{}\"}} {}",
+                label(name),
+                u8::from(*passed)
+            );
+        }
+        header(
+            &mut out,
+            "harness_request_duration_seconds",
+            "histogram",
+            "Latency of the HTTP requests cases made.",
+        );
+        for ((method, path, status), latencies) in &self.requests {
+            let labels = format!(
+                "method=\"{}\",path=\"{}\",status=\"{}\"",
+                label(method),
+                label(path),
+                label(status)
+            );
+            for (count, bound) in latencies.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "harness_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "harness_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, latencies.count
+            );
+            let _ = writeln!(
+                out,
+                "harness_request_duration_seconds_sum{{{}}} {}",
+                labels, latencies.sum
+            );
+            let _ = writeln!(
+                out,
+                "harness_request_duration_seconds_count{{{}}} {}",
+                labels, latencies.count
+            );
+        }
+        if let Some((passed, failed, elapsed)) = self.run {
+            header(
+                &mut out,
+                "harness_run_cases",
+                "gauge",
+                "Cases of the run by result.",
+            );
+            let _ = writeln!(out, "harness_run_cases{{result=\"passed\"}} {}", passed);
+            let _ = writeln!(out, "harness_run_cases{{result=\"failed\"}} {}", failed);
+            header(
+                &mut out,
+                "harness_run_duration_seconds",
+                "gauge",
+                "How long the whole run took.",
+            );
+            let _ = writeln!(
+                out,
+                "harness_run_duration_seconds {}",
+                elapsed.as_secs_f64()
+            );
+            let finished = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            header(
+                &mut out,
+                "harness_run_last_timestamp_seconds",
+                "gauge",
+                "Unix time the run finished.",
+            );
+            let _ = writeln!(out, "harness_run_last_timestamp_seconds {}", finished);
+        }
+        out
+    }
+}
+
+/// Replace the metrics of `job` on the pushgateway at `gateway` with `metrics`.
+pub async fn push(gateway: &str, job: &str, metrics: &Metrics) -> Result<(), String> {
+    let url = format!("{}/metrics/job/{}", gateway.trim_end_matches('/'), job);
+    let resp = http_client(PUSH_TIMEOUT)?
+        .put(&url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(metrics.render())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to push metrics to {}: {:?}", url, e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!(
+            "Pushgateway {} answered {}: {}",
+            url,
+            status,
+            body.trim()
+        ));
+    }
+    Ok(())
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// `value` escaped for a label value.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
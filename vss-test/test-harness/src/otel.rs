@@ -0,0 +1,168 @@
+//! Traces of the run for OpenTelemetry
+//!
+//! With `--otlp <url>` (or `OTEL_EXPORTER_OTLP_ENDPOINT`) each case becomes a
+//! trace: a span for the case, and a client span for each HTTP call it makes
+//! through `send`. The call carries its span in a W3C `traceparent` header;
+//! the servers in the compose file export no spans of their own, so a trace
+//! holds the harness side only. After the summary, the reporter exports the
+//! run's spans as OTLP/HTTP JSON to `<url>/v1/traces` and prints the trace
+//! id of every failing case, to look up in the collector's UI (the `tracing`
+//! compose profile runs Jaeger for this).
+//!
+//! Trace and span ids are random but do not come from `rng`: a replayed run
+//! must not reuse the traces of the run it replays.
+
+use std::time::{Duration, SystemTime};
+
+use serde_json::{json, Value};
+
+use crate::http_client;
+
+pub const OTLP_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+const SCOPE: &str = "test-harness";
+
+/// Where a span sits: its trace and its own id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+}
+
+impl SpanContext {
+    /// The first span of a new trace.
+    pub fn root() -> Self {
+        Self {
+            trace_id: rand::random(),
+            span_id: rand::random(),
+        }
+    }
+
+    /// A new span in the same trace.
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: rand::random(),
+        }
+    }
+
+    /// The `traceparent` header value of a sampled span.
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.trace_hex(), hex(&self.span_id))
+    }
+
+    pub fn trace_hex(&self) -> String {
+        hex(&self.trace_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal,
+    Client,
+}
+
+/// A finished span.
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub context: SpanContext,
+    pub parent: Option<[u8; 8]>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, Value)>,
+    /// Why the span failed, if it did.
+    pub error: Option<String>,
+}
+
+/// Send `spans` to the OTLP/HTTP collector at `endpoint` as spans of `service`.
+pub async fn export(endpoint: &str, service: &str, spans: &[Span]) -> Result<(), String> {
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let resp = http_client(EXPORT_TIMEOUT)?
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .body(request_body(service, spans).to_string())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to export traces to {}: {:?}", url, e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!(
+            "Collector {} answered {}: {}",
+            url,
+            status,
+            body.trim()
+        ));
+    }
+    Ok(())
+}
+
+/// An `ExportTraceServiceRequest` in the OTLP JSON encoding, where ids are
+/// hex and nanosecond times are strings.
+fn request_body(service: &str, spans: &[Span]) -> Value {
+    let spans: Vec<Value> = spans.iter().map(span_json).collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", &json!(service))],
+            },
+            "scopeSpans": [{
+                "scope": { "name": SCOPE },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+fn span_json(span: &Span) -> Value {
+    let mut value = json!({
+        "traceId": span.context.trace_hex(),
+        "spanId": hex(&span.context.span_id),
+        "name": span.name,
+        // SPAN_KIND_INTERNAL and SPAN_KIND_CLIENT
+        "kind": match span.kind {
+            SpanKind::Internal => 1,
+            SpanKind::Client => 3,
+        },
+        "startTimeUnixNano": unix_nanos(span.start).to_string(),
+        "endTimeUnixNano": unix_nanos(span.end).to_string(),
+        "attributes": span
+            .attributes
+            .iter()
+            .map(|(key, value)| attribute(key, value))
+            .collect::<Vec<_>>(),
+        // STATUS_CODE_OK and STATUS_CODE_ERROR
+        "status": match &span.error {
+            None => json!({ "code": 1 }),
+            Some(message) => json!({ "code": 2, "message": message }),
+        },
+    });
+    if let Some(parent) = &span.parent {
+        value["parentSpanId"] = Value::String(hex(parent));
+    }
+    value
+}
+
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_i64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
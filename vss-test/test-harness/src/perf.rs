@@ -0,0 +1,201 @@
+//! Latency baselines, for using the suite as a performance gate
+//!
+//! With `--perf-baseline <file>`, the reporter measures the run as it
+//! summarizes it: the median duration of every passing case, and the median
+//! latency of every endpoint (method and URL path) across the HTTP calls the
+//! cases made. `--update-perf-baseline` writes those medians to the file, to
+//! be checked in like a snapshot. Otherwise they are compared with the file,
+//! and each case or endpoint that got slower by more than the threshold
+//! (`--perf-threshold`, 20% by default) is reported as a failure of its own,
+//! or only warned about with `--perf-warn-only`.
+//!
+//! Medians rather than means, so one slow retry does not move the number,
+//! and a regression must also exceed a few milliseconds: a 2 ms call taking
+//! 3 ms is +50% and says nothing. Cases and endpoints missing on either side
+//! are left out. Repeating the run (`--repeat`) gives steadier medians.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Map, Value};
+
+pub const DEFAULT_THRESHOLD_PERCENT: f64 = 20.0;
+// Smaller slowdowns are noise, whatever their percentage
+const MIN_REGRESSION_MS: f64 = 5.0;
+
+/// How the run is measured against a baseline file.
+#[derive(Debug, Clone)]
+pub struct PerfGate {
+    pub path: PathBuf,
+    /// Write the file from this run instead of comparing with it.
+    pub update: bool,
+    pub threshold_percent: f64,
+    /// Warn about regressions instead of failing on them.
+    pub warn_only: bool,
+}
+
+/// Durations in ms measured in one run, by case and by endpoint.
+#[derive(Debug, Default)]
+pub struct Samples {
+    pub cases: BTreeMap<String, Vec<f64>>,
+    pub endpoints: BTreeMap<String, Vec<f64>>,
+}
+
+/// One median latency of a baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Latency {
+    pub median_ms: f64,
+    pub samples: usize,
+}
+
+/// Median latencies by case and by endpoint (`POST /vss/getObject`).
+#[derive(Debug, Default)]
+pub struct Baseline {
+    pub cases: BTreeMap<String, Latency>,
+    pub endpoints: BTreeMap<String, Latency>,
+}
+
+impl Baseline {
+    pub fn from_samples(samples: &Samples) -> Self {
+        Self {
+            cases: medians(&samples.cases),
+            endpoints: medians(&samples.endpoints),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Failed to read perf baseline {} (record it with --update-perf-baseline): {:?}",
+                path.display(),
+                e
+            )
+        })?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse perf baseline {}: {:?}", path.display(), e))?;
+        Ok(Self {
+            cases: latencies(&value["cases"]),
+            endpoints: latencies(&value["endpoints"]),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let value = json!({
+            "cases": latencies_json(&self.cases),
+            "endpoints": latencies_json(&self.endpoints),
+        });
+        std::fs::write(path, format!("{:#}\n", value))
+            .map_err(|e| format!("Failed to write perf baseline {}: {:?}", path.display(), e))
+    }
+}
+
+/// A case or endpoint slower than its baseline by more than the threshold.
+#[derive(Debug, Clone)]
+pub struct Regression {
+    /// `case` or `endpoint`.
+    pub kind: &'static str,
+    pub name: String,
+    pub baseline_ms: f64,
+    pub current_ms: f64,
+}
+
+impl Regression {
+    pub fn percent(&self) -> f64 {
+        (self.current_ms - self.baseline_ms) / self.baseline_ms * 100.0
+    }
+
+    pub fn describe(&self, threshold_percent: f64) -> String {
+        format!(
+            "{} {} took {:.1} ms at the median, {:.1} ms in the baseline ({:+.1}%, more than {}%)",
+            self.kind,
+            self.name,
+            self.current_ms,
+            self.baseline_ms,
+            self.percent(),
+            threshold_percent
+        )
+    }
+}
+
+/// What in `current` is slower than in `baseline` by more than
+/// `threshold_percent`, cases first.
+pub fn compare(baseline: &Baseline, current: &Baseline, threshold_percent: f64) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    let groups = [
+        ("case", &baseline.cases, &current.cases),
+        ("endpoint", &baseline.endpoints, &current.endpoints),
+    ];
+    for (kind, before, now) in groups {
+        for (name, latency) in now {
+            let Some(base) = before.get(name) else {
+                continue;
+            };
+            let slower_by = latency.median_ms - base.median_ms;
+            if base.median_ms > 0.0
+                && slower_by > MIN_REGRESSION_MS
+                && slower_by / base.median_ms * 100.0 > threshold_percent
+            {
+                regressions.push(Regression {
+                    kind,
+                    name: name.clone(),
+                    baseline_ms: base.median_ms,
+                    current_ms: latency.median_ms,
+                });
+            }
+        }
+    }
+    regressions
+}
+
+fn medians(samples: &BTreeMap<String, Vec<f64>>) -> BTreeMap<String, Latency> {
+    samples
+        .iter()
+        .filter(|(_, values)| !values.is_empty())
+        .map(|(name, values)| {
+            let latency = Latency {
+                median_ms: median(values),
+                samples: values.len(),
+            };
+            (name.clone(), latency)
+        })
+        .collect()
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+fn latencies(value: &Value) -> BTreeMap<String, Latency> {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, entry)| {
+            let latency = Latency {
+                median_ms: entry["median_ms"].as_f64()?,
+                samples: entry["samples"].as_u64().unwrap_or(1) as usize,
+            };
+            Some((name.clone(), latency))
+        })
+        .collect()
+}
+
+fn latencies_json(latencies: &BTreeMap<String, Latency>) -> Value {
+    let entries: Map<String, Value> = latencies
+        .iter()
+        .map(|(name, latency)| {
+            // Microsecond precision keeps the file diffable
+            let median_ms = (latency.median_ms * 1000.0).round() / 1000.0;
+            let entry = json!({ "median_ms": median_ms, "samples": latency.samples });
+            (name.clone(), entry)
+        })
+        .collect();
+    Value::Object(entries)
+}
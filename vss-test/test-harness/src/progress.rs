@@ -0,0 +1,60 @@
+//! A live status line for people watching a run
+//!
+//! On a terminal, a human run keeps a bar under the result lines while cases
+//! run: how many are done and how many are left, the time since they started
+//! and the names of the ones running now. Result lines go above it as cases
+//! finish. Output that is not a terminal (CI logs, pipes), the json and tap
+//! formats and `--no-progress` get the plain lines instead, since a bar
+//! redrawn in place only makes sense on a screen.
+
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+const TEMPLATE: &str = "{spinner} [{elapsed_precise}] [{bar:30}] {pos}/{len} {wide_msg}";
+const TICK: Duration = Duration::from_millis(100);
+
+pub struct Progress {
+    bar: ProgressBar,
+}
+
+impl Progress {
+    /// A bar on stdout for `total` cases.
+    pub fn new(total: usize) -> Self {
+        let bar = ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stdout());
+        if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+            bar.set_style(style.progress_chars("=> "));
+        }
+        bar.enable_steady_tick(TICK);
+        Self { bar }
+    }
+
+    /// Show `names` as the cases running now.
+    pub fn running(&self, names: &[String]) {
+        let left = self
+            .bar
+            .length()
+            .unwrap_or_default()
+            .saturating_sub(self.bar.position());
+        let message = match names {
+            [] => format!("{} left", left),
+            _ => format!("{} left, running {}", left, names.join(", ")),
+        };
+        self.bar.set_message(message);
+    }
+
+    /// Count one more case as done.
+    pub fn advance(&self) {
+        self.bar.inc(1);
+    }
+
+    /// Print `text` above the bar.
+    pub fn print(&self, text: &str) {
+        self.bar.suspend(|| print!("{}", text));
+    }
+
+    /// Remove the bar, leaving the result lines.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,61 @@
+//! Credentials kept out of verbose dumps
+//!
+//! Verbose runs print whole requests and responses, which end up in CI logs.
+//! Headers that carry credentials keep only their scheme (`Bearer <redacted>`),
+//! and anything shaped like a JWT (three base64url segments, the first
+//! starting `eyJ`) is replaced in bodies, whatever field it sits in. Bodies
+//! that are not text, such as VSS's protobuf, are shown as hex.
+
+const SENSITIVE_HEADERS: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "grpc-metadata-macaroon",
+];
+const REDACTED: &str = "<redacted>";
+const JWT_START: &str = "eyJ";
+
+/// `name: value` with the value redacted if the header carries credentials.
+pub fn header(name: &str, value: &str) -> String {
+    if !SENSITIVE_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+        return format!("{}: {}", name, text(value));
+    }
+    match value.split_once(' ') {
+        Some((scheme, _)) => format!("{}: {} {}", name, scheme, REDACTED),
+        None => format!("{}: {}", name, REDACTED),
+    }
+}
+
+/// `body` as redacted text, or as hex if it is not UTF-8 text.
+pub fn body(body: &[u8]) -> String {
+    match std::str::from_utf8(body) {
+        Ok(body) if !body.contains(|c: char| c.is_control() && !c.is_whitespace()) => text(body),
+        _ => {
+            let hex: String = body.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("({} bytes) {}", body.len(), hex)
+        }
+    }
+}
+
+/// `text` with every JWT in it replaced.
+pub fn text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(JWT_START) {
+        out.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(candidate.len());
+        let token = &candidate[..end];
+        if token.split('.').count() == 3 {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(token);
+        }
+        rest = &candidate[end..];
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,1104 @@
+//! How a test binary reports its cases
+//!
+//! Cases report through a `Reporter` instead of printing their result lines
+//! themselves. `Format::Human` prints the usual `name ... ok (duration) - detail`
+//! lines. `Format::Json` prints one JSON object per line on stdout instead: one
+//! per case, with its status, duration, detail or error, the HTTP exchanges it
+//! recorded and any logs attached to it, then a summary object. Everything
+//! else the binary says goes to stderr in that format, so stdout stays
+//! parseable. `Format::Tap` prints TAP version 13 for `prove` and other TAP
+//! consumers: an `ok`/`not ok` line per case with a YAML block of the same
+//! details, the plan at the end, and everything else as `#` comments.
+//! `Format::Markdown` keeps stdout for one Markdown summary at the end, for a
+//! PR comment, compared with a baseline report given to `with_baseline` (see
+//! `markdown`).
+//!
+//! Independently of the format, `with_html` makes the summary also write every
+//! case with its timings, HTTP exchanges and logs into one self-contained
+//! HTML file, for keeping as a CI artifact, and `with_pushgateway` lets
+//! `push_metrics` send the run's timings to Prometheus (see `metrics`).
+//! `with_otlp` traces each case and its HTTP calls, printing the trace id of
+//! failures and sending the spans to a collector in `export_traces` (see
+//! `otel`).
+//!
+//! `Verbosity::Quiet` leaves out passing cases and everything the binary says
+//! beyond errors, so a human run shows only failures and the summary.
+//! `Verbosity::Verbose` also prints each HTTP exchange a case recorded in
+//! full, as `send` captures them with credentials redacted. Human result
+//! lines are colored when `with_color` says so.
+//!
+//! `with_annotations` also writes failures and warnings as GitHub Actions
+//! workflow commands (see `annotate`).
+//!
+//! `with_perf` has `check_perf` compare the run's latencies with a baseline
+//! file, or record it (see `perf`). Whatever the options, the summary ends
+//! with the latency percentiles of every endpoint the run called (see
+//! `latency`).
+//!
+//! When the cases run more than once (`with_repeat`), the summary is preceded
+//! by a flakiness report of every case across the iterations (see `flaky`). A
+//! soak run prints its report of drift over time with `soak` (see `soak`).
+//!
+//! Cases running concurrently (see `runner`) each run in a slot of their own,
+//! which is how the reporter tells their calls apart. While more than one
+//! case may run, human result lines are printed whole once a case finishes.
+//! The same goes while `with_progress` keeps a status line under them (see
+//! `progress`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::annotate::{self, Level};
+use crate::flaky;
+use crate::latency::{self, Latencies};
+use crate::markdown::{self, CaseResult};
+use crate::metrics::{self, Metrics};
+use crate::otel::{self, SpanContext, SpanKind};
+use crate::perf::{self, Baseline, PerfGate, Samples};
+use crate::progress::Progress;
+use crate::rng;
+use crate::shard;
+use crate::soak::Soak;
+
+tokio::task_local! {
+    static SLOT: usize;
+}
+
+/// Run `case` in `slot`, so its reporter calls do not mix with other cases'.
+pub async fn in_slot<F: Future>(slot: usize, case: F) -> F::Output {
+    SLOT.scope(slot, case).await
+}
+
+fn current_slot() -> usize {
+    SLOT.try_with(|slot| *slot).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Result lines for people
+    #[default]
+    Human,
+    /// One JSON object per case and a summary, one per line
+    Json,
+    /// Test Anything Protocol, version 13
+    Tap,
+    /// A summary for a GitHub PR comment, at the end
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only failures, errors and the summary
+    Quiet,
+    #[default]
+    Normal,
+    /// Also the headers and bodies of every HTTP exchange
+    Verbose,
+}
+
+/// One HTTP request a case made and what came back.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub method: String,
+    pub url: String,
+    /// `None` if no response arrived.
+    pub status: Option<u16>,
+    pub duration: Duration,
+    /// Headers and body sent, redacted; only kept in verbose runs.
+    pub request: Option<String>,
+    /// Headers and body received, redacted; only kept in verbose runs.
+    pub response: Option<String>,
+    /// The span the request announced in `traceparent`, in traced runs.
+    pub span: Option<SpanContext>,
+}
+
+#[derive(Debug, Default)]
+struct Case {
+    name: String,
+    outcome: Option<(bool, Duration, String)>,
+    exchanges: Vec<Exchange>,
+    logs: String,
+    // The case's span and when it began, in traced runs
+    trace: Option<(SpanContext, SystemTime)>,
+}
+
+pub struct Reporter {
+    format: Format,
+    verbosity: Verbosity,
+    color: bool,
+    started: Instant,
+    // Open cases by slot
+    current: Mutex<HashMap<usize, Case>>,
+    concurrent: AtomicBool,
+    // Cases printed so far, in order; their count numbers TAP test points
+    reported: Mutex<Vec<Case>>,
+    html: Option<PathBuf>,
+    // Earlier JSON report the Markdown summary compares timings with
+    baseline: Option<PathBuf>,
+    perf: Option<PerfGate>,
+    annotations: bool,
+    pushgateway: Option<String>,
+    otlp: Option<String>,
+    // How many times the suite runs its cases
+    repeat: usize,
+    // Finished spans, exported after the summary
+    spans: Mutex<Vec<otel::Span>>,
+    // Whether human runs show a status line while cases run, and the line
+    show_progress: bool,
+    progress: Mutex<Option<Progress>>,
+}
+
+impl Reporter {
+    pub fn new(format: Format) -> Self {
+        if format == Format::Tap {
+            println!("TAP version 13");
+        }
+        Self {
+            format,
+            verbosity: Verbosity::Normal,
+            color: false,
+            started: Instant::now(),
+            current: Mutex::new(HashMap::new()),
+            concurrent: AtomicBool::new(false),
+            reported: Mutex::new(Vec::new()),
+            html: None,
+            baseline: None,
+            perf: None,
+            annotations: false,
+            pushgateway: None,
+            otlp: None,
+            repeat: 1,
+            spans: Mutex::new(Vec::new()),
+            show_progress: false,
+            progress: Mutex::new(None),
+        }
+    }
+
+    /// Also write an HTML report to `path` when the summary is printed.
+    pub fn with_html(mut self, path: &Path) -> Self {
+        self.html = Some(path.to_path_buf());
+        self
+    }
+
+    /// Compare case timings in the Markdown summary with the JSON report at
+    /// `path`.
+    pub fn with_baseline(mut self, path: &Path) -> Self {
+        self.baseline = Some(path.to_path_buf());
+        self
+    }
+
+    /// Annotate failures and warnings for GitHub Actions.
+    pub fn with_annotations(mut self, annotations: bool) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Measure the run against a latency baseline in `check_perf`.
+    pub fn with_perf(mut self, gate: PerfGate) -> Self {
+        self.perf = Some(gate);
+        self
+    }
+
+    /// Push the run's metrics to the Prometheus pushgateway at `url` in
+    /// `push_metrics`.
+    pub fn with_pushgateway(mut self, url: &str) -> Self {
+        self.pushgateway = Some(url.to_string());
+        self
+    }
+
+    /// Trace cases and their HTTP calls, for `export_traces` to send to the
+    /// OTLP/HTTP collector at `url`.
+    pub fn with_otlp(mut self, url: &str) -> Self {
+        self.otlp = Some(url.to_string());
+        self
+    }
+
+    /// The suite runs its cases `repeat` times; with more than one, the
+    /// summary reports flakiness.
+    pub fn with_repeat(mut self, repeat: usize) -> Self {
+        self.repeat = repeat.max(1);
+        self
+    }
+
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Keep a status line under human result lines while cases run.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.show_progress = progress;
+        self
+    }
+
+    /// Color `ok` and `FAILED` in human result lines.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Whether HTTP exchanges should be captured in full.
+    pub fn verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+
+    /// Say something outside any case's result line, unless running quietly.
+    pub fn say(&self, line: &str) {
+        if self.verbosity != Verbosity::Quiet {
+            self.error(line);
+        }
+    }
+
+    /// Say something that went wrong outside any case, even when running quietly.
+    pub fn error(&self, line: &str) {
+        match self.format {
+            Format::Human => self.print(&format!("{}\n", line)),
+            Format::Json | Format::Markdown => eprintln!("{}", line),
+            Format::Tap => {
+                for line in line.split('\n') {
+                    println!("{}", format!("# {}", line).trim_end());
+                }
+            }
+        }
+    }
+
+    /// Whether cases may run at the same time from now on.
+    pub fn set_concurrent(&self, concurrent: bool) {
+        self.concurrent.store(concurrent, Ordering::SeqCst);
+    }
+
+    /// Print a human result line in one go rather than starting it in `begin`.
+    fn whole_lines(&self) -> bool {
+        self.verbosity == Verbosity::Quiet
+            || self.concurrent.load(Ordering::SeqCst)
+            || self.progress.lock().unwrap().is_some()
+    }
+
+    /// Show the status line for a batch of `total` cases, if asked to.
+    pub fn start_progress(&self, total: usize) {
+        if self.show_progress && self.format == Format::Human && total > 0 {
+            let progress = Progress::new(total);
+            progress.running(&[]);
+            *self.progress.lock().unwrap() = Some(progress);
+        }
+    }
+
+    /// Remove the status line once the batch is done.
+    pub fn end_progress(&self) {
+        if let Some(progress) = self.progress.lock().unwrap().take() {
+            progress.finish();
+        }
+    }
+
+    /// Print `text` as `print!` would, above the status line if there is one.
+    fn print(&self, text: &str) {
+        match self.progress.lock().unwrap().as_ref() {
+            Some(progress) => progress.print(text),
+            None => print!("{}", text),
+        }
+    }
+
+    /// Show the cases running now on the status line.
+    fn update_progress(&self, finished: bool) {
+        let progress = self.progress.lock().unwrap();
+        let Some(progress) = progress.as_ref() else {
+            return;
+        };
+        if finished {
+            progress.advance();
+        }
+        let mut running: Vec<(usize, String)> = self
+            .current
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, case)| case.outcome.is_none())
+            .map(|(slot, case)| (*slot, case.name.clone()))
+            .collect();
+        running.sort();
+        let names: Vec<String> = running.into_iter().map(|(_, name)| name).collect();
+        progress.running(&names);
+    }
+
+    /// Announce iteration `iteration` (from 1) of a repeated run.
+    pub fn iteration(&self, iteration: usize) {
+        if self.repeat > 1 {
+            self.say(&format!("--- Iteration {}/{}", iteration, self.repeat));
+        }
+    }
+
+    /// Start case `name`; the previous one, if any, is complete now.
+    pub fn begin(&self, name: &str) {
+        self.flush(current_slot());
+        let name = name.to_string();
+        if self.format == Format::Human && !self.whole_lines() {
+            print!("{} ... ", name);
+        }
+        self.current.lock().unwrap().insert(
+            current_slot(),
+            Case {
+                name,
+                trace: self
+                    .otlp
+                    .is_some()
+                    .then(|| (SpanContext::root(), SystemTime::now())),
+                ..Default::default()
+            },
+        );
+        self.update_progress(false);
+    }
+
+    /// The span of the current case, in traced runs; HTTP calls of the case
+    /// are its children.
+    pub fn trace_context(&self) -> Option<SpanContext> {
+        let current = self.current.lock().unwrap();
+        current
+            .get(&current_slot())
+            .and_then(|case| case.trace)
+            .map(|(context, _)| context)
+    }
+
+    /// Record an HTTP exchange of the current case.
+    pub fn exchange(&self, exchange: Exchange) {
+        if let Some(case) = self.current.lock().unwrap().get_mut(&current_slot()) {
+            if let (Some((parent, _)), Some(context)) = (case.trace, exchange.span) {
+                self.spans
+                    .lock()
+                    .unwrap()
+                    .push(http_span(&exchange, context, parent));
+            }
+            case.exchanges.push(exchange);
+        }
+    }
+
+    /// The current case passed; returns `true` for the caller to pass on.
+    pub fn ok(&self, duration: Duration, detail: &str) -> bool {
+        if self.format == Format::Human && self.verbosity != Verbosity::Quiet {
+            let ok = self.paint(GREEN, "ok");
+            self.print(&format!(
+                "{}{} ({:?}) - {}\n",
+                self.line_start(),
+                ok,
+                duration,
+                detail
+            ));
+            self.print_exchanges();
+        }
+        self.finish(true, duration, detail);
+        true
+    }
+
+    /// The current case failed; returns `false` for the caller to pass on.
+    pub fn failed(&self, duration: Duration, error: &str) -> bool {
+        if self.format == Format::Human {
+            let failed = self.paint(RED, "FAILED");
+            self.print(&format!(
+                "{}{} ({:?}) - {}\n",
+                self.line_start(),
+                failed,
+                duration,
+                error
+            ));
+            if let Some(context) = self.trace_context() {
+                self.print(&format!("    trace {}\n", context.trace_hex()));
+            }
+            self.print_exchanges();
+        }
+        let name = self
+            .current
+            .lock()
+            .unwrap()
+            .get(&current_slot())
+            .map(|case| case.name.clone());
+        if let Some(name) = name {
+            self.annotate(Level::Error, &name, error);
+        }
+        self.finish(false, duration, error);
+        false
+    }
+
+    /// Write an annotation of case `name` for GitHub Actions, if asked to;
+    /// off stdout where it holds a report.
+    fn annotate(&self, level: Level, name: &str, message: &str) {
+        if !self.annotations {
+            return;
+        }
+        let line = annotate::annotation(level, &binary_name(), name, message);
+        match self.format {
+            Format::Human => self.print(&format!("{}\n", line)),
+            Format::Json | Format::Tap | Format::Markdown => eprintln!("{}", line),
+        }
+    }
+
+    /// Attach container logs to the case that just finished; quiet runs only
+    /// print them for a failure.
+    pub fn logs(&self, logs: &str) {
+        if self.format == Format::Human
+            && (self.verbosity != Verbosity::Quiet || !self.current_passed())
+        {
+            self.print(logs);
+        }
+        if let Some(case) = self.current.lock().unwrap().get_mut(&current_slot()) {
+            case.logs.push_str(logs);
+        }
+    }
+
+    /// Report the case in the current slot now instead of when the slot's
+    /// next case begins.
+    pub fn close(&self) {
+        self.flush(current_slot());
+    }
+
+    /// Compare the latencies of the cases reported so far with the perf
+    /// baseline, or record them; call before `summary`. Each regression is
+    /// reported as a failed case unless only warned about; returns how many
+    /// failed, to add to the suite's failures.
+    pub fn check_perf(&self) -> usize {
+        let Some(gate) = &self.perf else {
+            return 0;
+        };
+        let slots: Vec<usize> = self.current.lock().unwrap().keys().copied().collect();
+        for slot in slots {
+            self.flush(slot);
+        }
+        let current = Baseline::from_samples(&perf_samples(&self.reported.lock().unwrap()));
+        if gate.update {
+            match current.save(&gate.path) {
+                Ok(()) => self.say(&format!("Perf baseline written to {}", gate.path.display())),
+                Err(e) => self.error(&e),
+            }
+            return 0;
+        }
+        let regressions = match Baseline::load(&gate.path) {
+            Ok(baseline) => perf::compare(&baseline, &current, gate.threshold_percent),
+            Err(e) => {
+                self.begin("perf baseline");
+                self.failed(Duration::ZERO, &e);
+                self.close();
+                return 1;
+            }
+        };
+        if regressions.is_empty() {
+            self.say(&format!(
+                "No latency regressions beyond {}% against {}",
+                gate.threshold_percent,
+                gate.path.display()
+            ));
+            return 0;
+        }
+        for regression in &regressions {
+            let description = regression.describe(gate.threshold_percent);
+            if gate.warn_only {
+                self.error(&format!("Warning: {}", description));
+                let name = format!("perf {}", regression.name);
+                self.annotate(Level::Warning, &name, &description);
+            } else {
+                self.begin(&format!("perf {}", regression.name));
+                self.failed(Duration::ZERO, &description);
+                self.close();
+            }
+        }
+        if gate.warn_only {
+            0
+        } else {
+            regressions.len()
+        }
+    }
+
+    /// Names of the cases reported as failed so far, in order.
+    pub fn failed_cases(&self) -> Vec<String> {
+        let reported = self.reported.lock().unwrap();
+        reported
+            .iter()
+            .filter(|case| matches!(case.outcome, Some((false, _, _))))
+            .map(|case| case.name.clone())
+            .collect()
+    }
+
+    /// Close the last case and report the totals.
+    pub fn summary(&self, passed: usize, failed: usize) {
+        let slots: Vec<usize> = self.current.lock().unwrap().keys().copied().collect();
+        for slot in slots {
+            self.flush(slot);
+        }
+        if self.repeat > 1 {
+            self.print_flakiness();
+        }
+        let elapsed = self.started.elapsed();
+        let latencies = latency::recorded();
+        if let Some(path) = &self.html {
+            let page = html_page(&self.reported.lock().unwrap(), passed, failed, elapsed);
+            match std::fs::write(path, page) {
+                Ok(()) => self.say(&format!("HTML report written to {}", path.display())),
+                Err(e) => self.error(&format!("Failed to write {}: {:?}", path.display(), e)),
+            }
+        }
+        match self.format {
+            Format::Human => {
+                println!();
+                match shard::current() {
+                    Some(shard) => println!(
+                        "Results of shard {}: {} passed, {} failed",
+                        shard, passed, failed
+                    ),
+                    None => println!("Results: {} passed, {} failed", passed, failed),
+                }
+                if let Some(seed) = rng::used_seed().filter(|_| failed > 0) {
+                    println!("Replay with {} {}", rng::SEED_FLAG, seed);
+                }
+                if !latencies.is_empty() {
+                    println!();
+                    println!("Latency by endpoint (ms):");
+                    print!("{}", latency_table(&latencies));
+                }
+            }
+            Format::Json => println!(
+                "{}",
+                json!({
+                    "type": "summary",
+                    "passed": passed,
+                    "failed": failed,
+                    "duration_ms": millis(elapsed),
+                    "seed": rng::used_seed(),
+                    "shard": shard::current().map(|shard| shard.to_string()),
+                    "latency": latency::to_json(&latencies),
+                })
+            ),
+            Format::Tap => {
+                println!("1..{}", self.reported.lock().unwrap().len());
+                println!("# Results: {} passed, {} failed", passed, failed);
+                if let Some(seed) = rng::used_seed().filter(|_| failed > 0) {
+                    println!("# Replay with {} {}", rng::SEED_FLAG, seed);
+                }
+                if !latencies.is_empty() {
+                    println!("# Latency by endpoint (ms):");
+                    for line in latency_table(&latencies).lines() {
+                        println!("# {}", line);
+                    }
+                }
+            }
+            Format::Markdown => {
+                let mut out = self.markdown_summary(passed, failed, elapsed);
+                if !latencies.is_empty() {
+                    out.push_str("\n### Latency by endpoint\n\n");
+                    out.push_str(&latency::render_markdown(&latencies));
+                }
+                print!("{}", out);
+            }
+        }
+    }
+
+    /// The Markdown summary, with timings against the baseline if it loads.
+    fn markdown_summary(&self, passed: usize, failed: usize, elapsed: Duration) -> String {
+        let baseline =
+            self.baseline
+                .as_ref()
+                .and_then(|path| match markdown::load_baseline(path) {
+                    Ok(baseline) => Some(baseline),
+                    Err(e) => {
+                        self.error(&e);
+                        None
+                    }
+                });
+        let reported = self.reported.lock().unwrap();
+        let cases: Vec<CaseResult> = reported
+            .iter()
+            .filter_map(|case| {
+                let (passed, duration, message) = case.outcome.as_ref()?;
+                Some(CaseResult {
+                    name: &case.name,
+                    passed: *passed,
+                    duration: *duration,
+                    message,
+                    logs: &case.logs,
+                })
+            })
+            .collect();
+        let mut suite = binary_name();
+        if let Some(shard) = shard::current() {
+            suite.push_str(&format!(" (shard {})", shard));
+        }
+        let flakiness = (self.repeat > 1).then(|| self.flakiness_table(&reported));
+        let mut out = markdown::render(
+            &suite,
+            &cases,
+            passed,
+            failed,
+            elapsed,
+            baseline.as_ref(),
+            flakiness.as_deref(),
+        );
+        if let Some(seed) = rng::used_seed().filter(|_| failed > 0) {
+            out.push_str(&format!("\nReplay with `{} {}`.\n", rng::SEED_FLAG, seed));
+        }
+        out
+    }
+
+    /// Push the metrics of the run to the pushgateway, if there is one, after
+    /// `summary`. A failed push is only reported: the cases' results stand.
+    pub async fn push_metrics(&self, passed: usize, failed: usize) {
+        let Some(gateway) = &self.pushgateway else {
+            return;
+        };
+        let mut metrics = Metrics::new();
+        for case in self.reported.lock().unwrap().iter() {
+            let (ok, duration, _) = case.outcome.clone().unwrap_or_default();
+            metrics.case(&case.name, ok, duration);
+            for exchange in &case.exchanges {
+                metrics.request(exchange);
+            }
+        }
+        metrics.run(passed, failed, self.started.elapsed());
+        match metrics::push(gateway, &binary_name(), &metrics).await {
+            Ok(()) => self.say(&format!("Metrics pushed to {}", gateway)),
+            Err(e) => self.error(&e),
+        }
+    }
+
+    /// Send the spans of the run to the collector, if there is one, after
+    /// `summary`. Like the metrics, a failed export is only reported.
+    pub async fn export_traces(&self) {
+        let Some(endpoint) = &self.otlp else {
+            return;
+        };
+        let spans = std::mem::take(&mut *self.spans.lock().unwrap());
+        match otel::export(endpoint, &binary_name(), &spans).await {
+            Ok(()) => self.say(&format!("{} spans exported to {}", spans.len(), endpoint)),
+            Err(e) => self.error(&e),
+        }
+    }
+
+    /// Every case's runs across the iterations, in the chosen format.
+    fn print_flakiness(&self) {
+        let runs = case_runs(&self.reported.lock().unwrap());
+        for case in runs.iter().filter(|case| case.flaky()) {
+            let message = format!(
+                "Flaky: passed {} and failed {} of {} runs",
+                case.passed,
+                case.failed,
+                case.passed + case.failed
+            );
+            self.annotate(Level::Warning, &case.name, &message);
+        }
+        match self.format {
+            Format::Human => {
+                println!();
+                print!("{}", flaky::render(&runs, self.repeat));
+            }
+            Format::Json => {
+                for case in &runs {
+                    println!(
+                        "{}",
+                        json!({
+                            "type": "flakiness",
+                            "name": case.name,
+                            "passed": case.passed,
+                            "failed": case.failed,
+                            "flaky": case.flaky(),
+                            "durations_ms": case.durations.iter().map(|d| millis(*d)).collect::<Vec<_>>(),
+                            "mean_ms": millis(case.mean()),
+                            "stddev_ms": millis(case.stddev()),
+                        })
+                    );
+                }
+            }
+            Format::Tap => {
+                for line in flaky::render(&runs, self.repeat).lines() {
+                    println!("# {}", line);
+                }
+            }
+            // Part of the summary
+            Format::Markdown => {}
+        }
+    }
+
+    /// The soak report of a run that looped its cases, in the chosen format.
+    pub fn soak(&self, soak: &Soak) {
+        match self.format {
+            Format::Human => {
+                println!();
+                print!("{}", soak.render());
+            }
+            Format::Json => println!("{}", soak.to_json()),
+            Format::Tap => {
+                for line in soak.render().lines() {
+                    println!("# {}", line);
+                }
+            }
+            Format::Markdown => {
+                println!("### Soak\n\n```\n{}```\n", soak.render());
+            }
+        }
+    }
+
+    /// The flakiness table of `reported` for people.
+    fn flakiness_table(&self, reported: &[Case]) -> String {
+        flaky::render(&case_runs(reported), self.repeat)
+    }
+
+    /// `name ... ` of the current case if `begin` left it unprinted.
+    fn line_start(&self) -> String {
+        if !self.whole_lines() {
+            return String::new();
+        }
+        match self.current.lock().unwrap().get(&current_slot()) {
+            Some(case) => format!("{} ... ", case.name),
+            None => String::new(),
+        }
+    }
+
+    fn current_passed(&self) -> bool {
+        let current = self.current.lock().unwrap();
+        let outcome = current
+            .get(&current_slot())
+            .and_then(|case| case.outcome.as_ref());
+        matches!(outcome, Some((true, _, _)))
+    }
+
+    /// The exchanges of the current case in full, in verbose runs.
+    fn print_exchanges(&self) {
+        if !self.verbose() {
+            return;
+        }
+        let current = self.current.lock().unwrap();
+        let Some(case) = current.get(&current_slot()) else {
+            return;
+        };
+        let mut text = String::new();
+        for e in &case.exchanges {
+            text.push_str(&format!("    > {} {}\n", e.method, e.url));
+            for line in e.request.iter().flat_map(|r| r.lines()) {
+                text.push_str(&format!("{}\n", format!("    > {}", line).trim_end()));
+            }
+            match e.status {
+                Some(status) => text.push_str(&format!("    < {} ({:?})\n", status, e.duration)),
+                None => text.push_str(&format!("    < no response ({:?})\n", e.duration)),
+            }
+            for line in e.response.iter().flat_map(|r| r.lines()) {
+                text.push_str(&format!("{}\n", format!("    < {}", line).trim_end()));
+            }
+        }
+        drop(current);
+        self.print(&text);
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn finish(&self, passed: bool, duration: Duration, message: &str) {
+        self.record_outcome(passed, duration, message);
+        self.update_progress(true);
+    }
+
+    fn record_outcome(&self, passed: bool, duration: Duration, message: &str) {
+        if let Some(case) = self.current.lock().unwrap().get_mut(&current_slot()) {
+            case.outcome = Some((passed, duration, message.to_string()));
+            if let Some((context, start)) = case.trace {
+                self.spans.lock().unwrap().push(otel::Span {
+                    context,
+                    parent: None,
+                    name: case.name.clone(),
+                    kind: SpanKind::Internal,
+                    start,
+                    end: SystemTime::now(),
+                    attributes: Vec::new(),
+                    error: (!passed).then(|| message.to_string()),
+                });
+            }
+        }
+    }
+
+    /// Print the case in `slot` once it has an outcome, unless it was printed
+    /// as it finished; logs may still be attached until the slot's next case
+    /// begins, or its slot is closed.
+    fn flush(&self, slot: usize) {
+        let Some(case) = self.current.lock().unwrap().remove(&slot) else {
+            return;
+        };
+        if case.outcome.is_none() {
+            return;
+        }
+        let mut reported = self.reported.lock().unwrap();
+        match self.format {
+            Format::Human | Format::Markdown => {}
+            Format::Json => println!("{}", json_record(&case)),
+            Format::Tap => print!("{}", tap_point(reported.len() + 1, &case)),
+        }
+        reported.push(case);
+    }
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Durations of the passing cases in `reported`, and of every answered HTTP
+/// call they made by method and path, in ms.
+fn perf_samples(reported: &[Case]) -> Samples {
+    let mut samples = Samples::default();
+    for case in reported {
+        let Some((true, duration, _)) = &case.outcome else {
+            continue;
+        };
+        samples
+            .cases
+            .entry(case.name.clone())
+            .or_default()
+            .push(millis(*duration));
+        for e in case.exchanges.iter().filter(|e| e.status.is_some()) {
+            let path = reqwest::Url::parse(&e.url)
+                .map(|url| url.path().to_string())
+                .unwrap_or_else(|_| e.url.clone());
+            samples
+                .endpoints
+                .entry(format!("{} {}", e.method, path))
+                .or_default()
+                .push(millis(e.duration));
+        }
+    }
+    samples
+}
+
+fn latency_table(latencies: &BTreeMap<String, Latencies>) -> String {
+    latency::render(
+        "endpoint",
+        latencies
+            .iter()
+            .map(|(name, latencies)| (name.as_str(), latencies)),
+    )
+}
+
+/// The runs of every case in `reported`, grouped by name.
+fn case_runs(reported: &[Case]) -> Vec<flaky::CaseRuns> {
+    let outcomes: Vec<(String, bool, Duration)> = reported
+        .iter()
+        .filter_map(|case| {
+            let (passed, duration, _) = case.outcome.as_ref()?;
+            Some((case.name.clone(), *passed, *duration))
+        })
+        .collect();
+    flaky::group(&outcomes)
+}
+
+/// A finished case as a `"type": "test"` JSON object.
+fn json_record(case: &Case) -> Value {
+    let (passed, duration, message) = case.outcome.clone().unwrap_or_default();
+    let exchanges: Vec<Value> = case
+        .exchanges
+        .iter()
+        .map(|e| {
+            let mut exchange = json!({
+                "method": e.method,
+                "url": e.url,
+                "status": e.status,
+                "duration_ms": millis(e.duration),
+            });
+            if let Some(request) = &e.request {
+                exchange["request"] = Value::String(request.clone());
+            }
+            if let Some(response) = &e.response {
+                exchange["response"] = Value::String(response.clone());
+            }
+            exchange
+        })
+        .collect();
+    let mut record = json!({
+        "type": "test",
+        "name": case.name,
+        "status": if passed { "ok" } else { "failed" },
+        "duration_ms": millis(duration),
+        "requests": exchanges,
+    });
+    let key = if passed { "detail" } else { "error" };
+    record[key] = Value::String(message);
+    if let Some((context, _)) = case.trace {
+        record["trace_id"] = Value::String(context.trace_hex());
+    }
+    if !case.logs.is_empty() {
+        record["logs"] = Value::String(case.logs.clone());
+    }
+    record
+}
+
+/// A finished case as TAP test point `number` with a YAML diagnostic block.
+/// Strings are written as JSON strings, which YAML reads as quoted scalars.
+fn tap_point(number: usize, case: &Case) -> String {
+    let (passed, duration, message) = case.outcome.clone().unwrap_or_default();
+    // An unescaped `#` would start a directive such as SKIP
+    let name = case.name.replace('#', "\\#");
+    let mut point = format!(
+        "{} {} - {}\n  ---\n  duration_ms: {:.3}\n  {}: {}\n",
+        if passed { "ok" } else { "not ok" },
+        number,
+        name,
+        millis(duration),
+        if passed { "detail" } else { "error" },
+        Value::String(message)
+    );
+    if let Some((context, _)) = case.trace {
+        point.push_str(&format!("  trace_id: {}\n", context.trace_hex()));
+    }
+    if !case.exchanges.is_empty() {
+        point.push_str("  requests:\n");
+        for e in &case.exchanges {
+            point.push_str(&format!(
+                "    - method: {}\n      url: {}\n      status: {}\n      duration_ms: {:.3}\n",
+                Value::String(e.method.clone()),
+                Value::String(e.url.clone()),
+                e.status.map_or("null".to_string(), |s| s.to_string()),
+                millis(e.duration)
+            ));
+            if let Some(request) = &e.request {
+                point.push_str(&format!(
+                    "      request: {}\n",
+                    Value::String(request.clone())
+                ));
+            }
+            if let Some(response) = &e.response {
+                point.push_str(&format!(
+                    "      response: {}\n",
+                    Value::String(response.clone())
+                ));
+            }
+        }
+    }
+    if !case.logs.is_empty() {
+        point.push_str("  logs: |2\n");
+        for line in case.logs.lines() {
+            point.push_str(&format!("    {}\n", line));
+        }
+    }
+    point.push_str("  ...\n");
+    point
+}
+
+/// One page with a summary table and, per case, its outcome, the HTTP
+/// exchanges it made and the logs attached to it. Styles are inline, so the
+/// file works on its own.
+fn html_page(cases: &[Case], passed: usize, failed: usize, elapsed: Duration) -> String {
+    let mut rows = String::new();
+    let mut details = String::new();
+    for (i, case) in cases.iter().enumerate() {
+        let (ok, duration, message) = case.outcome.clone().unwrap_or_default();
+        let status = if ok { "ok" } else { "failed" };
+        rows.push_str(&format!(
+            "<tr class=\"{status}\"><td><a href=\"#case-{i}\">{}</a></td><td>{status}</td><td>{:.1} ms</td></tr>\n",
+            escape(&case.name),
+            millis(duration),
+        ));
+
+        details.push_str(&format!(
+            "<section id=\"case-{i}\" class=\"{status}\">\n<h2>{}</h2>\n<p>{status} in {:.1} ms: {}</p>\n",
+            escape(&case.name),
+            millis(duration),
+            escape(&message),
+        ));
+        if !case.exchanges.is_empty() {
+            details.push_str(
+                "<table>\n<tr><th>method</th><th>url</th><th>status</th><th>duration</th></tr>\n",
+            );
+            for e in &case.exchanges {
+                details.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1} ms</td></tr>\n",
+                    escape(&e.method),
+                    escape(&e.url),
+                    e.status
+                        .map_or("no response".to_string(), |s| s.to_string()),
+                    millis(e.duration),
+                ));
+            }
+            details.push_str("</table>\n");
+        }
+        if !case.logs.is_empty() {
+            details.push_str(&format!(
+                "<details><summary>Container logs</summary><pre>{}</pre></details>\n",
+                escape(&case.logs)
+            ));
+        }
+        details.push_str("</section>\n");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>{passed} passed, {failed} failed in {:.1} s</p>\n<table>\n<tr><th>case</th><th>status</th><th>duration</th></tr>\n{rows}</table>\n{details}</body>\n</html>\n",
+        elapsed.as_secs_f64(),
+        title = escape(&report_title()),
+    )
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+td, th { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+tr.ok td:nth-child(2), section.ok > p { color: #1a7f37; }
+tr.failed td:nth-child(2), section.failed > p { color: #cf222e; }
+pre { background: #f6f8fa; padding: 1em; overflow-x: auto; }
+";
+
+/// The binary's name, which titles its report.
+/// The client span of an HTTP call made as `context` within the case span `parent`.
+fn http_span(exchange: &Exchange, context: SpanContext, parent: SpanContext) -> otel::Span {
+    let path = reqwest::Url::parse(&exchange.url)
+        .map(|url| url.path().to_string())
+        .unwrap_or_else(|_| exchange.url.clone());
+    let end = SystemTime::now();
+    let mut attributes = vec![
+        ("http.request.method", json!(exchange.method)),
+        ("url.full", json!(exchange.url)),
+    ];
+    if let Some(status) = exchange.status {
+        attributes.push(("http.response.status_code", json!(status)));
+    }
+    let error = match exchange.status {
+        None => Some("no response".to_string()),
+        Some(status) if status >= 500 => Some(format!("status {}", status)),
+        Some(_) => None,
+    };
+    otel::Span {
+        context,
+        parent: Some(parent.span_id),
+        name: format!("{} {}", exchange.method, path),
+        kind: SpanKind::Client,
+        start: end - exchange.duration,
+        end,
+        attributes,
+        error,
+    }
+}
+
+fn report_title() -> String {
+    format!("{} report", binary_name())
+}
+
+/// The name of the running test binary, e.g. `vss_jwt_test`.
+pub fn binary_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "test".to_string())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,82 @@
+//! Seeded randomness, so a failing randomized run can be replayed
+//!
+//! Every random value the harness makes (subjects and pubkeys, payloads,
+//! shuffles, fuzz inputs) comes from `rng`, which derives a generator from the
+//! run's seed and what the values are for. Each purpose gets a stream of its
+//! own, so cases running concurrently or in another order still draw the same
+//! values. The seed is `--seed <n>` as the binary parsed it and passed to
+//! `init`, else `TEST_SEED`, else taken from the clock; `init` logs it at
+//! startup and failing summaries repeat it.
+//!
+//! Names that must differ between runs, such as VSS store ids, do not come
+//! from here: a replayed run would collide with the data of the first one.
+
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+pub const SEED_FLAG: &str = "--seed";
+pub const SEED_ENV: &str = "TEST_SEED";
+
+static SEED: OnceLock<u64> = OnceLock::new();
+
+/// The seed of this run, fixed by `init` or else on first use; an unparsable
+/// TEST_SEED panics, as no run could be reproduced from it.
+pub fn seed() -> u64 {
+    *SEED.get_or_init(|| chosen_seed(None).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+/// The seed if anything asked for it yet; summaries only mention a seed that
+/// shaped the run.
+pub fn used_seed() -> Option<u64> {
+    SEED.get().copied()
+}
+
+/// Fix the seed now, to `flag` (the parsed `--seed`) if given, and log it
+/// with how to replay the run.
+pub fn init(flag: Option<u64>) -> Result<u64, String> {
+    let seed = match SEED.get() {
+        Some(seed) => *seed,
+        None => {
+            let chosen = chosen_seed(flag)?;
+            *SEED.get_or_init(|| chosen)
+        }
+    };
+    tracing::info!(
+        seed,
+        "Random data is seeded; rerun with {} {} to replay",
+        SEED_FLAG,
+        seed
+    );
+    Ok(seed)
+}
+
+/// A generator for the values drawn for `purpose`, e.g. `identities:<name>`.
+pub fn rng(purpose: &str) -> StdRng {
+    StdRng::seed_from_u64(seed() ^ fnv1a(purpose))
+}
+
+/// `flag`, then TEST_SEED, then the clock.
+fn chosen_seed(flag: Option<u64>) -> Result<u64, String> {
+    if let Some(seed) = flag {
+        return Ok(seed);
+    }
+    if let Ok(value) = std::env::var(SEED_ENV) {
+        return value
+            .parse()
+            .map_err(|e| format!("Invalid {} {:?}: {}", SEED_ENV, value, e));
+    }
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default())
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same in every build.
+pub(crate) fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
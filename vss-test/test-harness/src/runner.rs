@@ -0,0 +1,177 @@
+//! Running a suite's cases concurrently
+//!
+//! A suite hands `run` its cases as futures resolving to pass or fail. Up to
+//! `jobs` of them run at once, each in its own reporter slot. A case marked
+//! `exclusive`, because it changes server state others depend on (recreating
+//! a container, skewing its clock), waits for the running ones to finish and
+//! runs alone. Cases start in the order given.
+//!
+//! Cases carry `Tag`s saying what they exercise and what they cost, which
+//! `--include` and `--exclude` select on (see `cli::Filter`), and may name
+//! the setup steps they need (see `setup`).
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use futures_util::stream::{self, StreamExt};
+use tokio::sync::RwLock;
+use tracing::Instrument;
+
+use crate::report::{in_slot, Reporter};
+use crate::setup::Setup;
+
+/// What a case exercises or what running it costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tag {
+    /// Issuing and checking tokens
+    Auth,
+    /// The VSS server and its store
+    Vss,
+    /// The LNURL servers
+    Lnurl,
+    /// Takes minutes rather than seconds
+    Slow,
+    /// Injects faults: shaped networks, skewed clocks, killed containers
+    Chaos,
+    /// Recreates or restarts services that others may be using
+    Destructive,
+    /// Looks inside or reshapes the local containers, so needs Docker
+    Docker,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.write_str(value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+pub type CaseRun<'a> = Pin<Box<dyn Future<Output = bool> + 'a>>;
+
+pub struct Case<'a> {
+    pub name: String,
+    pub exclusive: bool,
+    pub tags: Vec<Tag>,
+    /// Setup steps that must have succeeded before the case runs.
+    pub needs: Vec<String>,
+    run: CaseRun<'a>,
+}
+
+impl<'a> Case<'a> {
+    pub fn new(name: &str, run: impl Future<Output = bool> + 'a) -> Self {
+        Self {
+            name: name.to_string(),
+            exclusive: false,
+            tags: Vec::new(),
+            needs: Vec::new(),
+            run: Box::pin(run),
+        }
+    }
+
+    /// Run this case with no other case alongside.
+    pub fn exclusive(mut self) -> Self {
+        self.exclusive = true;
+        self
+    }
+
+    /// Add `tags` to the case's tags.
+    pub fn tagged(mut self, tags: &[Tag]) -> Self {
+        self.tags.extend_from_slice(tags);
+        self
+    }
+
+    /// Run the case only once the setup steps `steps` have succeeded.
+    pub fn needs(mut self, steps: &[&str]) -> Self {
+        self.needs.extend(steps.iter().map(|step| step.to_string()));
+        self
+    }
+
+    /// Run the case inside `wrap`, which gets it to await, e.g. to act before
+    /// it starts or after it is reported.
+    pub fn around<F>(mut self, wrap: impl FnOnce(CaseRun<'a>) -> F) -> Self
+    where
+        F: Future<Output = bool> + 'a,
+    {
+        self.run = Box::pin(wrap(self.run));
+        self
+    }
+}
+
+/// Run `cases`, at most `jobs` at a time, and return how many passed and failed.
+pub async fn run(report: &Reporter, jobs: usize, cases: Vec<Case<'_>>) -> (usize, usize) {
+    run_with_setup(report, jobs, Setup::new(), cases).await
+}
+
+/// Run the steps of `setup` that `cases` need, then `cases` as `run` does. A
+/// case needing a step that failed fails without running.
+pub async fn run_with_setup(
+    report: &Reporter,
+    jobs: usize,
+    setup: Setup<'_>,
+    cases: Vec<Case<'_>>,
+) -> (usize, usize) {
+    let mut needed: Vec<String> = Vec::new();
+    for step in cases.iter().flat_map(|case| &case.needs) {
+        if !needed.contains(step) {
+            needed.push(step.clone());
+        }
+    }
+    let failures = match setup.run(report, &needed).await {
+        Ok(failures) => failures,
+        Err(e) => {
+            report.error(&e);
+            return (0, cases.len());
+        }
+    };
+    let failures = &failures;
+    let jobs = jobs.max(1);
+    report.set_concurrent(jobs > 1);
+    report.start_progress(cases.len());
+    // Shared by regular cases, taken whole by exclusive ones; waiters are
+    // served in order, so an exclusive case is not starved
+    let lane = RwLock::new(());
+    let lane = &lane;
+    let outcomes: Vec<bool> = stream::iter(cases.into_iter().enumerate())
+        .map(|(slot, case)| async move {
+            let span = tracing::info_span!("case", test = %case.name);
+            let blocked = case.needs.iter().find_map(|step| failures.get(step));
+            let run: CaseRun<'_> = match blocked {
+                Some(reason) => Box::pin(blocked_case(report, case.name.clone(), reason)),
+                None => case.run,
+            };
+            let run = in_slot(slot, run_closed(report, run)).instrument(span);
+            let passed = if case.exclusive {
+                let _alone = lane.write().await;
+                run.await
+            } else {
+                let _shared = lane.read().await;
+                run.await
+            };
+            passed
+        })
+        .buffer_unordered(jobs)
+        .collect()
+        .await;
+    report.end_progress();
+    report.set_concurrent(false);
+    let passed = outcomes.iter().filter(|passed| **passed).count();
+    (passed, outcomes.len() - passed)
+}
+
+/// Report case `name` failed because a setup step it needs did not succeed.
+async fn blocked_case(report: &Reporter, name: String, reason: &str) -> bool {
+    report.begin(&name);
+    report.failed(Duration::ZERO, &format!("Not run: {}", reason))
+}
+
+/// Run a case and report it right away, since its slot is not reused.
+async fn run_closed(report: &Reporter, run: CaseRun<'_>) -> bool {
+    let passed = run.await;
+    report.close();
+    passed
+}
@@ -0,0 +1,155 @@
+//! Named setup steps that cases depend on
+//!
+//! Some cases only make sense once something else is in place: a store is
+//! seeded, a channel is open. Rather than relying on the order cases are
+//! listed in, a suite declares those steps in a `Setup`, each with the steps
+//! it comes after, and a case names the steps it needs (`Case::needs`).
+//! Before any case starts, `runner::run_with_setup` runs the steps the
+//! selected cases need, and the steps those need, each exactly once and in
+//! dependency order; steps no selected case needs are not run at all.
+//!
+//! A step that fails is reported once. Steps after it are not run, and the
+//! cases needing either fail right away with the reason instead of each
+//! tripping over the missing state. A dependency on an undeclared step, or a
+//! cycle, is a mistake in the suite and fails the run before anything starts.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+
+use crate::report::Reporter;
+
+pub type StepFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + 'a>>;
+
+struct Step<'a> {
+    name: String,
+    after: Vec<String>,
+    run: StepFuture<'a>,
+}
+
+/// The setup steps of a suite.
+#[derive(Default)]
+pub struct Setup<'a> {
+    steps: Vec<Step<'a>>,
+}
+
+impl<'a> Setup<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare step `name`, run after the steps in `after`.
+    pub fn step(
+        mut self,
+        name: &str,
+        after: &[&str],
+        run: impl Future<Output = Result<(), String>> + 'a,
+    ) -> Self {
+        self.steps.push(Step {
+            name: name.to_string(),
+            after: after.iter().map(|step| step.to_string()).collect(),
+            run: Box::pin(run),
+        });
+        self
+    }
+
+    /// The steps `needed` takes, dependencies first; among steps free to go
+    /// in either order, the one declared first goes first.
+    pub fn order(&self, needed: &[String]) -> Result<Vec<String>, String> {
+        let index: HashMap<&str, usize> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| (step.name.as_str(), i))
+            .collect();
+        let lookup = |name: &str, wanted_by: &str| {
+            index.get(name).copied().ok_or_else(|| {
+                format!(
+                    "{} needs setup step '{}', which is not declared",
+                    wanted_by, name
+                )
+            })
+        };
+        // Everything `needed` takes, directly or through other steps
+        let mut taken = HashSet::new();
+        let mut pending: Vec<usize> = Vec::new();
+        for name in needed {
+            pending.push(lookup(name, "A case")?);
+        }
+        while let Some(i) = pending.pop() {
+            if taken.insert(i) {
+                for dependency in &self.steps[i].after {
+                    let wanted_by = format!("Setup step '{}'", self.steps[i].name);
+                    pending.push(lookup(dependency, &wanted_by)?);
+                }
+            }
+        }
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        while done.len() < taken.len() {
+            let next = (0..self.steps.len()).find(|i| {
+                taken.contains(i)
+                    && !done.contains(i)
+                    && self.steps[*i]
+                        .after
+                        .iter()
+                        .all(|d| done.contains(&index[d.as_str()]))
+            });
+            match next {
+                Some(i) => {
+                    done.insert(i);
+                    order.push(self.steps[i].name.clone());
+                }
+                None => {
+                    let mut stuck: Vec<&str> = (0..self.steps.len())
+                        .filter(|i| taken.contains(i) && !done.contains(i))
+                        .map(|i| self.steps[i].name.as_str())
+                        .collect();
+                    stuck.sort_unstable();
+                    return Err(format!(
+                        "Setup steps depend on each other in a cycle: {}",
+                        stuck.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Run the steps `needed` takes, in order, and return why each step that
+    /// did not succeed failed, by name.
+    pub async fn run(
+        self,
+        report: &Reporter,
+        needed: &[String],
+    ) -> Result<HashMap<String, String>, String> {
+        let order = self.order(needed)?;
+        let mut steps: HashMap<String, Step<'a>> = self
+            .steps
+            .into_iter()
+            .map(|step| (step.name.clone(), step))
+            .collect();
+        let mut failures: HashMap<String, String> = HashMap::new();
+        for name in order {
+            let step = steps.remove(&name).expect("ordered steps are declared");
+            if let Some(dependency) = step.after.iter().find(|d| failures.contains_key(*d)) {
+                failures.insert(name, format!("setup step '{}' failed", dependency));
+                continue;
+            }
+            let start = Instant::now();
+            match step.run.await {
+                Ok(()) => report.say(&format!(
+                    "Setup {} done ({:.1}s)",
+                    name,
+                    start.elapsed().as_secs_f64()
+                )),
+                Err(e) => {
+                    report.error(&format!("Setup {} failed: {}", name, e));
+                    failures.insert(name.clone(), format!("setup step '{}' failed: {}", name, e));
+                }
+            }
+        }
+        Ok(failures)
+    }
+}
@@ -0,0 +1,267 @@
+//! Splitting a suite across parallel CI jobs
+//!
+//! `--shard K/N` keeps the selected cases whose name hashes to shard K of N,
+//! on top of every other filter. The hash is FNV-1a of the name, so a case
+//! lands in the same shard on every machine and in every build, and adding a
+//! case moves no other. Each job writes its own report with `--format json`;
+//! its summary record names the shard, and `harness_report merge` combines
+//! the reports of all N jobs into one, failing when a shard is missing or
+//! reported twice, so a job that died without reporting cannot pass as green.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde_json::{json, Value};
+
+use crate::rng::fnv1a;
+
+pub const SHARD_FLAG: &str = "--shard";
+
+/// Shard `index` of `count`, counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    /// Whether the case `name` belongs to this shard.
+    pub fn holds(&self, name: &str) -> bool {
+        fnv1a(name) % self.count as u64 == (self.index - 1) as u64
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || format!("expected K/N with 1 <= K <= N, got {:?}", text);
+        let (index, count) = text.split_once('/').ok_or_else(invalid)?;
+        let index: u32 = index.trim().parse().map_err(|_| invalid())?;
+        let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(invalid());
+        }
+        Ok(Self { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+static CURRENT: OnceLock<Shard> = OnceLock::new();
+
+/// Note the shard this run is, the `--shard` `cli::Filter` parsed, for
+/// reports; selecting cases goes through the filter itself.
+pub fn init(shard: Option<Shard>) {
+    if let Some(shard) = shard {
+        let _ = CURRENT.set(shard);
+    }
+}
+
+/// The shard `init` noted, if any.
+pub fn current() -> Option<Shard> {
+    CURRENT.get().copied()
+}
+
+/// The combined records of one suite's shard reports.
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub records: Vec<Value>,
+    pub passed: u64,
+    pub failed: u64,
+}
+
+/// Combine JSON reports (`--format json` output), given as file name and
+/// text, into one. Test and flakiness records are kept in the order given,
+/// followed by one summary for all shards: counts add up, the duration is the
+/// longest shard's (they ran side by side) and the seeds are listed per shard.
+pub fn merge(reports: &[(String, String)]) -> Result<Merged, String> {
+    let mut merged = Merged::default();
+    let mut shards: Vec<(Shard, &str)> = Vec::new();
+    let mut duration_ms: f64 = 0.0;
+    let mut seeds = Vec::new();
+    for (file, text) in reports {
+        let mut summary = None;
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Value = serde_json::from_str(line)
+                .map_err(|e| format!("{}:{}: not a JSON record: {}", file, number + 1, e))?;
+            match record["type"].as_str() {
+                Some("summary") => summary = Some(record),
+                Some(_) => merged.records.push(record),
+                None => return Err(format!("{}:{}: record has no type", file, number + 1)),
+            }
+        }
+        let summary = summary
+            .ok_or_else(|| format!("{} has no summary record; did its shard finish?", file))?;
+        let shard: Shard = summary["shard"]
+            .as_str()
+            .ok_or_else(|| format!("{} is not the report of a {} run", file, SHARD_FLAG))?
+            .parse()
+            .map_err(|e| format!("{} names an invalid shard: {}", file, e))?;
+        if let Some((_, other)) = shards.iter().find(|(s, _)| s.index == shard.index) {
+            return Err(format!(
+                "Shard {} is reported by both {} and {}",
+                shard, other, file
+            ));
+        }
+        if let Some((first, other)) = shards.first().filter(|(s, _)| s.count != shard.count) {
+            return Err(format!(
+                "{} splits the suite into {} shards, {} into {}",
+                other, first.count, file, shard.count
+            ));
+        }
+        shards.push((shard, file));
+        merged.passed += summary["passed"].as_u64().unwrap_or_default();
+        merged.failed += summary["failed"].as_u64().unwrap_or_default();
+        duration_ms = duration_ms.max(summary["duration_ms"].as_f64().unwrap_or_default());
+        seeds.push(json!({ "shard": shard.to_string(), "seed": summary["seed"] }));
+    }
+    let Some(count) = shards.first().map(|(s, _)| s.count) else {
+        return Err("No reports to merge".to_string());
+    };
+    let missing: Vec<String> = (1..=count)
+        .filter(|index| !shards.iter().any(|(s, _)| s.index == *index))
+        .map(|index| format!("{}/{}", index, count))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("No report for shard {}", missing.join(", ")));
+    }
+    merged.records.push(json!({
+        "type": "summary",
+        "passed": merged.passed,
+        "failed": merged.failed,
+        "duration_ms": duration_ms,
+        "shards": count,
+        "seeds": seeds,
+    }));
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(shard: &str, passed: u64, failed: u64, duration_ms: f64) -> String {
+        [
+            json!({ "type": "test", "name": format!("test_in_{}", shard), "ok": failed == 0 }),
+            json!({
+                "type": "summary",
+                "passed": passed,
+                "failed": failed,
+                "duration_ms": duration_ms,
+                "seed": 7,
+                "shard": shard,
+            }),
+        ]
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+
+    fn reports(shards: &[(&str, String)]) -> Vec<(String, String)> {
+        shards
+            .iter()
+            .map(|(file, text)| (file.to_string(), text.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_first_and_last_shard() {
+        assert_eq!("1/1".parse(), Ok(Shard { index: 1, count: 1 }));
+        assert_eq!("4/4".parse(), Ok(Shard { index: 4, count: 4 }));
+        assert_eq!(" 2 / 3 ".parse(), Ok(Shard { index: 2, count: 3 }));
+        assert_eq!(Shard { index: 2, count: 3 }.to_string(), "2/3");
+    }
+
+    #[test]
+    fn rejects_shards_out_of_range_or_malformed() {
+        for text in [
+            "0/4", "5/4", "1/0", "0/0", "-1/4", "1-4", "1/4/2", "a/b", "/4", "1/", "",
+        ] {
+            let e = text.parse::<Shard>().unwrap_err();
+            assert!(e.contains("expected K/N"), "{:?}: {}", text, e);
+        }
+    }
+
+    #[test]
+    fn every_name_is_in_exactly_one_shard() {
+        let count = 3;
+        for i in 0..100 {
+            let name = format!("test_case_{}", i);
+            let holding = (1..=count)
+                .filter(|index| {
+                    Shard {
+                        index: *index,
+                        count,
+                    }
+                    .holds(&name)
+                })
+                .count();
+            assert_eq!(holding, 1, "{}", name);
+        }
+    }
+
+    #[test]
+    fn merges_every_shard_into_one_summary() {
+        let merged = merge(&reports(&[
+            ("b.json", report("2/2", 3, 1, 900.0)),
+            ("a.json", report("1/2", 4, 0, 1200.0)),
+        ]))
+        .unwrap();
+        assert_eq!((merged.passed, merged.failed), (7, 1));
+        let names: Vec<&str> = merged
+            .records
+            .iter()
+            .filter_map(|r| r["name"].as_str())
+            .collect();
+        assert_eq!(names, ["test_in_2/2", "test_in_1/2"]);
+        let summary = merged.records.last().unwrap();
+        assert_eq!(summary["type"], "summary");
+        assert_eq!(summary["shards"], 2);
+        assert_eq!(summary["duration_ms"], 1200.0);
+        assert_eq!(summary["seeds"][1], json!({ "shard": "1/2", "seed": 7 }));
+    }
+
+    #[test]
+    fn rejects_a_shard_reported_twice() {
+        let e = merge(&reports(&[
+            ("a.json", report("1/2", 4, 0, 1.0)),
+            ("b.json", report("2/2", 3, 0, 1.0)),
+            ("c.json", report("1/2", 4, 0, 1.0)),
+        ]))
+        .unwrap_err();
+        assert_eq!(e, "Shard 1/2 is reported by both a.json and c.json");
+    }
+
+    #[test]
+    fn rejects_missing_or_mismatched_shards() {
+        let e = merge(&reports(&[("a.json", report("1/3", 1, 0, 1.0))])).unwrap_err();
+        assert_eq!(e, "No report for shard 2/3, 3/3");
+        let e = merge(&reports(&[
+            ("a.json", report("1/2", 1, 0, 1.0)),
+            ("b.json", report("2/3", 1, 0, 1.0)),
+        ]))
+        .unwrap_err();
+        assert!(e.contains("a.json splits the suite into 2 shards"), "{}", e);
+        assert!(merge(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_reports_without_a_sharded_summary() {
+        let unfinished = r#"{"type":"test","name":"x"}"#.to_string();
+        let e = merge(&reports(&[("a.json", unfinished)])).unwrap_err();
+        assert!(e.contains("has no summary record"), "{}", e);
+        let unsharded = r#"{"type":"summary","passed":1,"failed":0}"#.to_string();
+        let e = merge(&reports(&[("a.json", unsharded)])).unwrap_err();
+        assert!(e.contains("not the report of a --shard run"), "{}", e);
+    }
+}
@@ -0,0 +1,150 @@
+//! Golden-file snapshots of server responses
+//!
+//! A case serializes what a server answered (an error body, a listing) and
+//! `Snapshots::check` compares it with the checked-in file of the same name
+//! under `snapshots/`. A difference fails the case with a line diff, so a
+//! change in the VSS or lnurl contract shows up in review instead of in the
+//! app. `--update-snapshots` rewrites the files with what the servers say now.
+//! A snapshot that does not exist yet is recorded and the case passes, except
+//! when `CI` is set: there it fails, since nothing would keep the new file.
+//!
+//! Values that change from run to run, such as generated ids, are replaced
+//! with `scrub` before comparing, and `shape` reduces a JSON body to its
+//! fields and types for responses whose values are not stable at all.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+pub const SNAPSHOT_DIR: &str = "snapshots";
+const SNAPSHOT_EXTENSION: &str = "snap";
+const CI_ENV: &str = "CI";
+
+pub struct Snapshots {
+    dir: PathBuf,
+    update: bool,
+}
+
+impl Snapshots {
+    /// Snapshots stored in `dir`; with `update`, mismatches are rewritten
+    /// instead of failing.
+    pub fn new(dir: &Path, update: bool) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            update,
+        }
+    }
+
+    /// Compare `actual` with the snapshot `name`; the `Ok` value says whether
+    /// it matched or was written.
+    pub fn check(&self, name: &str, actual: &str) -> Result<String, String> {
+        let path = self.path(name);
+        let expected = match fs::read_to_string(&path) {
+            Ok(expected) => Some(expected),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("Failed to read {}: {:?}", path.display(), e)),
+        };
+        match expected {
+            Some(expected) if expected == actual => Ok(format!("matches {}", path.display())),
+            Some(_) if self.update => self.write(&path, actual, "updated"),
+            Some(expected) => Err(format!(
+                "Response differs from {} (rerun with --update-snapshots to accept it):\n{}",
+                path.display(),
+                diff(&expected, actual)
+            )),
+            None if self.update || std::env::var_os(CI_ENV).is_none() => {
+                self.write(&path, actual, "recorded")
+            }
+            None => Err(format!(
+                "No snapshot {}; record it with --update-snapshots and commit it",
+                path.display()
+            )),
+        }
+    }
+
+    /// `check` of `actual` as pretty-printed JSON with sorted keys.
+    pub fn check_json(&self, name: &str, actual: &Value) -> Result<String, String> {
+        let text = serde_json::to_string_pretty(actual)
+            .map_err(|e| format!("Failed to serialize snapshot {}: {:?}", name, e))?;
+        self.check(name, &format!("{}\n", text))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", name, SNAPSHOT_EXTENSION))
+    }
+
+    fn write(&self, path: &Path, actual: &str, done: &str) -> Result<String, String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {:?}", self.dir.display(), e))?;
+        fs::write(path, actual)
+            .map_err(|e| format!("Failed to write {}: {:?}", path.display(), e))?;
+        Ok(format!("{} {}", done, path.display()))
+    }
+}
+
+/// `text` with each `(value, placeholder)` pair's value replaced by its
+/// placeholder, longest values first so one never eats part of another.
+pub fn scrub(text: &str, replacements: &[(&str, &str)]) -> String {
+    let mut replacements = replacements.to_vec();
+    replacements.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+    replacements
+        .iter()
+        .filter(|(value, _)| !value.is_empty())
+        .fold(text.to_string(), |text, (value, placeholder)| {
+            text.replace(value, placeholder)
+        })
+}
+
+/// `value` with every scalar replaced by the name of its type; arrays keep
+/// only their first element, so their length does not matter either.
+pub fn shape(value: &Value) -> Value {
+    match value {
+        Value::Null => Value::String("null".to_string()),
+        Value::Bool(_) => Value::String("bool".to_string()),
+        Value::Number(_) => Value::String("number".to_string()),
+        Value::String(_) => Value::String("string".to_string()),
+        Value::Array(items) => Value::Array(items.first().map(shape).into_iter().collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), shape(value)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+/// The lines of `expected` and `actual` as a unified-style diff, `-` for
+/// lines only in `expected` (the snapshot) and `+` for lines only in
+/// `actual` (the response).
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,217 @@
+//! Soak runs: the suite's cases looped for hours
+//!
+//! `--soak <duration>` runs a suite's cases over and over, one iteration after
+//! the other at the suite's usual concurrency, until the duration is up.
+//! Leaks, tokens reaching their expiry and exhausted connection pools do not
+//! show in a run of a minute; they show as failures or latencies that creep
+//! up as the hours go by. So the run is cut into equal time windows and each
+//! window keeps the iterations that started in it: how many cases passed and
+//! failed, and the latencies of the HTTP calls they made (see `latency`).
+//! The soak report then sets the windows side by side and gives the drift of
+//! p50 and p99, and of failures per iteration, from the first window to the
+//! last.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::latency::{self, Latencies};
+
+// Time windows the run is cut into
+const WINDOWS: u32 = 12;
+
+/// What the iterations that started in one time window did.
+#[derive(Debug, Clone, Default)]
+pub struct Window {
+    pub iterations: usize,
+    pub passed: usize,
+    pub failed: usize,
+    /// Every HTTP call of the window, whatever the endpoint.
+    pub latency: Latencies,
+}
+
+impl Window {
+    fn failures_per_iteration(&self) -> f64 {
+        if self.iterations == 0 {
+            0.0
+        } else {
+            self.failed as f64 / self.iterations as f64
+        }
+    }
+}
+
+/// A soak run in progress.
+#[derive(Debug)]
+pub struct Soak {
+    duration: Duration,
+    started: Instant,
+    iteration: usize,
+    // Where the current iteration started, and the calls recorded by then
+    iteration_start: Option<(Duration, BTreeMap<String, Latencies>)>,
+    windows: Vec<Window>,
+}
+
+impl Soak {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            started: Instant::now(),
+            iteration: 0,
+            iteration_start: None,
+            windows: vec![Window::default(); WINDOWS as usize],
+        }
+    }
+
+    /// Whether another iteration should start.
+    pub fn running(&self) -> bool {
+        self.started.elapsed() < self.duration
+    }
+
+    /// Start an iteration; returns its number, from 1.
+    pub fn begin(&mut self) -> usize {
+        self.iteration += 1;
+        self.iteration_start = Some((self.started.elapsed(), latency::recorded()));
+        self.iteration
+    }
+
+    /// How far into the run it is, as `1h 2m of 4h`.
+    pub fn progress(&self) -> String {
+        format!(
+            "{} of {}",
+            whole_seconds(self.started.elapsed()),
+            whole_seconds(self.duration)
+        )
+    }
+
+    /// End the iteration `begin` started, in which `passed` cases passed and
+    /// `failed` failed.
+    pub fn end(&mut self, passed: usize, failed: usize) {
+        let Some((start, before)) = self.iteration_start.take() else {
+            return;
+        };
+        let window_length = self.duration / WINDOWS;
+        let index = if window_length.is_zero() {
+            0
+        } else {
+            ((start.as_secs_f64() / window_length.as_secs_f64()) as usize).min(WINDOWS as usize - 1)
+        };
+        let window = &mut self.windows[index];
+        window.iterations += 1;
+        window.passed += passed;
+        window.failed += failed;
+        for (endpoint, latencies) in latency::recorded() {
+            let earlier = before.get(&endpoint).cloned().unwrap_or_default();
+            window.latency.add(&latencies.since(&earlier));
+        }
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iteration
+    }
+
+    /// The windows that saw an iteration start, with their offset into the run.
+    fn used_windows(&self) -> Vec<(Duration, &Window)> {
+        let window_length = self.duration / WINDOWS;
+        self.windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.iterations > 0)
+            .map(|(index, window)| (window_length * index as u32, window))
+            .collect()
+    }
+
+    /// Change from the first window to the last, in percent of the first:
+    /// p50, p99 and failures per iteration. `None` where the first is zero or
+    /// there is only one window.
+    pub fn drift(&self) -> (Option<f64>, Option<f64>, Option<f64>) {
+        let windows = self.used_windows();
+        let (Some((_, first)), Some((_, last))) = (windows.first(), windows.last()) else {
+            return (None, None, None);
+        };
+        if windows.len() < 2 {
+            return (None, None, None);
+        }
+        let change =
+            |before: f64, after: f64| (before > 0.0).then(|| (after - before) / before * 100.0);
+        (
+            change(
+                first.latency.percentile_ms(50.0),
+                last.latency.percentile_ms(50.0),
+            ),
+            change(
+                first.latency.percentile_ms(99.0),
+                last.latency.percentile_ms(99.0),
+            ),
+            change(
+                first.failures_per_iteration(),
+                last.failures_per_iteration(),
+            ),
+        )
+    }
+
+    /// The windows side by side and the drift, for people.
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "Soak over {} in {} iterations:\n  {:>10}  {:>10}  {:>7}  {:>7}  {:>9}  {:>9}\n",
+            whole_seconds(self.started.elapsed()),
+            self.iteration,
+            "from",
+            "iterations",
+            "passed",
+            "failed",
+            "p50 ms",
+            "p99 ms"
+        );
+        for (offset, window) in self.used_windows() {
+            out.push_str(&format!(
+                "  {:>10}  {:>10}  {:>7}  {:>7}  {:>9.1}  {:>9.1}\n",
+                whole_seconds(offset).to_string(),
+                window.iterations,
+                window.passed,
+                window.failed,
+                window.latency.percentile_ms(50.0),
+                window.latency.percentile_ms(99.0)
+            ));
+        }
+        let (p50, p99, failures) = self.drift();
+        let percent =
+            |drift: Option<f64>| drift.map_or("n/a".to_string(), |drift| format!("{:+.1}%", drift));
+        out.push_str(&format!(
+            "Drift from the first window to the last: p50 {}, p99 {}, failures per iteration {}\n",
+            percent(p50),
+            percent(p99),
+            percent(failures)
+        ));
+        out
+    }
+
+    /// The windows and the drift as a `"type": "soak"` JSON object.
+    pub fn to_json(&self) -> Value {
+        let windows: Vec<Value> = self
+            .used_windows()
+            .into_iter()
+            .map(|(offset, window)| {
+                json!({
+                    "from_s": offset.as_secs(),
+                    "iterations": window.iterations,
+                    "passed": window.passed,
+                    "failed": window.failed,
+                    "latency": window.latency.to_json(),
+                })
+            })
+            .collect();
+        let (p50, p99, failures) = self.drift();
+        json!({
+            "type": "soak",
+            "duration_s": self.started.elapsed().as_secs(),
+            "iterations": self.iteration,
+            "windows": windows,
+            "drift_percent": { "p50": p50, "p99": p99, "failures_per_iteration": failures },
+        })
+    }
+}
+
+fn whole_seconds(duration: Duration) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::from_secs(duration.as_secs()))
+}